@@ -0,0 +1,62 @@
+//! Records Unix modes and symlinks under `templates/` for the embedded copy
+//! of the templates: `include_dir!` only keeps file contents, so
+//! `template::extract_embedded` restores both from the generated tables.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+fn main() {
+    println!("cargo:rerun-if-changed=templates");
+    let mut modes = Vec::new();
+    let mut links = Vec::new();
+    let root = Path::new("templates");
+    if root.is_dir() {
+        visit(root, root, &mut modes, &mut links);
+    }
+
+    let mut out = String::from("/// Executable files under `templates/` and their mode.\n");
+    out.push_str("pub static EMBEDDED_MODES: &[(&str, u32)] = &[\n");
+    for (path, mode) in &modes {
+        out.push_str(&format!("    ({:?}, 0o{:o}),\n", path, mode));
+    }
+    out.push_str("];\n\n/// Symlinks under `templates/` and their target.\n");
+    out.push_str("pub static EMBEDDED_SYMLINKS: &[(&str, &str)] = &[\n");
+    for (path, target) in &links {
+        out.push_str(&format!("    ({:?}, {:?}),\n", path, target));
+    }
+    out.push_str("];\n");
+
+    let dest = Path::new(&env::var("OUT_DIR").expect("OUT_DIR")).join("embedded_meta.rs");
+    fs::write(dest, out).expect("writing embedded_meta.rs");
+}
+
+fn visit(
+    root: &Path,
+    dir: &Path,
+    modes: &mut Vec<(String, u32)>,
+    links: &mut Vec<(String, String)>,
+) {
+    let mut entries: Vec<_> = fs::read_dir(dir).expect("reading templates").flatten().collect();
+    entries.sort_by_key(|e| e.path());
+    for entry in entries {
+        let path = entry.path();
+        let rel = path.strip_prefix(root).unwrap().to_string_lossy().replace('\\', "/");
+        let meta = fs::symlink_metadata(&path).expect("reading template metadata");
+        if meta.file_type().is_symlink() {
+            let target = fs::read_link(&path).expect("reading template symlink");
+            links.push((rel, target.to_string_lossy().replace('\\', "/")));
+        } else if meta.is_dir() {
+            visit(root, &path, modes, links);
+        } else {
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let mode = meta.permissions().mode() & 0o7777;
+                if mode & 0o111 != 0 {
+                    modes.push((rel, mode));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,262 @@
+//! Pack a generated project into a `.tar.gz`, `.tar` or `.zip` archive
+//! (`cosmos generate --output-archive`).
+//!
+//! Entries are sorted, keep their Unix mode (and symlinks their target), and
+//! are stamped with `SOURCE_DATE_EPOCH` when it is set, so the same input
+//! gives the same archive.
+
+use std::fs;
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::error::CosmosError;
+
+/// Archive layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    TarGz,
+    Tar,
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// `tar.gz` (or `tgz`), `tar` or `zip`.
+    pub fn from_name(name: &str) -> Result<ArchiveFormat> {
+        match name.trim_start_matches('.') {
+            "tar.gz" | "tgz" => Ok(ArchiveFormat::TarGz),
+            "tar" => Ok(ArchiveFormat::Tar),
+            "zip" => Ok(ArchiveFormat::Zip),
+            other => Err(CosmosError::Usage(format!(
+                "unknown archive format '{}' (expected tar.gz, tar or zip)",
+                other
+            ))
+            .into()),
+        }
+    }
+
+    /// Canonical name, as accepted by [`ArchiveFormat::from_name`].
+    pub fn name(self) -> &'static str {
+        match self {
+            ArchiveFormat::TarGz => "tar.gz",
+            ArchiveFormat::Tar => "tar",
+            ArchiveFormat::Zip => "zip",
+        }
+    }
+
+    /// Format implied by the file name (`out.tar.gz`, `out.tgz`, `out.tar`,
+    /// `out.zip`).
+    pub fn from_path(path: &Path) -> Option<ArchiveFormat> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        [("tar.gz", ArchiveFormat::TarGz), ("tgz", ArchiveFormat::TarGz)]
+            .into_iter()
+            .chain([("tar", ArchiveFormat::Tar), ("zip", ArchiveFormat::Zip)])
+            .find(|(ext, _)| name.ends_with(&format!(".{}", ext)))
+            .map(|(_, f)| f)
+    }
+}
+
+/// One file or symlink of the packed directory.
+struct Entry {
+    rel: String,
+    path: PathBuf,
+    mode: u32,
+    link: Option<PathBuf>,
+}
+
+fn entries(dir: &Path) -> Result<Vec<Entry>> {
+    let mut out = Vec::new();
+    for e in walkdir::WalkDir::new(dir).sort_by_file_name() {
+        let e = e?;
+        if !(e.file_type().is_file() || e.path_is_symlink()) {
+            continue;
+        }
+        let rel = e.path().strip_prefix(dir)?;
+        let rel = rel.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>();
+        let meta = e.path().symlink_metadata()?;
+        let link =
+            if meta.file_type().is_symlink() { Some(fs::read_link(e.path())?) } else { None };
+        out.push(Entry {
+            rel: rel.join("/"),
+            path: e.path().to_path_buf(),
+            mode: mode(&meta),
+            link,
+        });
+    }
+    Ok(out)
+}
+
+#[cfg(unix)]
+fn mode(meta: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    meta.permissions().mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+fn mode(meta: &fs::Metadata) -> u32 {
+    if meta.file_type().is_symlink() {
+        0o777
+    } else {
+        0o644
+    }
+}
+
+/// Write every file under `dir` to `out` as a `format` archive; returns the
+/// number of entries.
+pub fn write_archive(dir: &Path, format: ArchiveFormat, out: &mut dyn Write) -> Result<usize> {
+    let entries = entries(dir)?;
+    let mtime = crate::helpers::now().max(0) as u64;
+    match format {
+        ArchiveFormat::TarGz => {
+            let mut gz = GzEncoder::new(out, Compression::default());
+            write_tar(&entries, mtime, &mut gz)?;
+            gz.finish()?;
+        }
+        ArchiveFormat::Tar => write_tar(&entries, mtime, out)?,
+        ArchiveFormat::Zip => {
+            // the zip writer needs to seek back to patch the headers
+            let mut buf = Cursor::new(Vec::new());
+            write_zip(&entries, mtime, &mut buf)?;
+            out.write_all(buf.get_ref())?;
+        }
+    }
+    Ok(entries.len())
+}
+
+/// Write `bytes` to `path` through a temporary file next to it, so a failed
+/// run leaves no partial archive behind.
+pub fn save(path: &Path, bytes: &[u8]) -> Result<()> {
+    let parent = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    let mut tmp = tempfile::NamedTempFile::new_in(parent)
+        .with_context(|| format!("creating a temporary file in {:?}", parent))?;
+    tmp.write_all(bytes)?;
+    tmp.persist(path).with_context(|| format!("writing {:?}", path))?;
+    Ok(())
+}
+
+fn write_tar(entries: &[Entry], mtime: u64, out: &mut dyn Write) -> Result<()> {
+    let mut tar = tar::Builder::new(out);
+    for e in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_mode(e.mode);
+        header.set_mtime(mtime);
+        header.set_uid(0);
+        header.set_gid(0);
+        match &e.link {
+            Some(target) => {
+                header.set_entry_type(tar::EntryType::Symlink);
+                header.set_size(0);
+                tar.append_link(&mut header, &e.rel, target)?;
+            }
+            None => {
+                let bytes = fs::read(&e.path).with_context(|| format!("reading {:?}", e.path))?;
+                header.set_entry_type(tar::EntryType::Regular);
+                header.set_size(bytes.len() as u64);
+                tar.append_data(&mut header, &e.rel, bytes.as_slice())?;
+            }
+        }
+    }
+    tar.into_inner()?;
+    Ok(())
+}
+
+fn write_zip(entries: &[Entry], mtime: u64, out: &mut Cursor<Vec<u8>>) -> Result<()> {
+    let mut zip = zip::ZipWriter::new(out);
+    let options = zip::write::FileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .last_modified_time(zip_time(mtime));
+    for e in entries {
+        let options = options.unix_permissions(e.mode);
+        match &e.link {
+            Some(target) => zip.add_symlink(&e.rel, target.to_string_lossy(), options)?,
+            None => {
+                zip.start_file(&e.rel, options)?;
+                zip.write_all(
+                    &fs::read(&e.path).with_context(|| format!("reading {:?}", e.path))?,
+                )?;
+            }
+        }
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+/// Zip timestamps are local date and time from 1980 on; use UTC.
+fn zip_time(secs: u64) -> zip::DateTime {
+    let secs = secs as i64;
+    let (y, m, d) = crate::helpers::civil_from_days(secs.div_euclid(86_400));
+    let t = secs.rem_euclid(86_400);
+    zip::DateTime::from_date_and_time(
+        y.clamp(1980, 2107) as u16,
+        m as u8,
+        d as u8,
+        (t / 3600) as u8,
+        (t / 60 % 60) as u8,
+        (t % 60) as u8,
+    )
+    .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn archives_keep_modes_and_links() {
+        let td = tempfile::tempdir().unwrap();
+        fs::create_dir_all(td.path().join("scripts")).unwrap();
+        fs::write(td.path().join("README.md"), "hi\n").unwrap();
+        fs::write(td.path().join("scripts/run.sh"), "#!/bin/sh\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let script = td.path().join("scripts/run.sh");
+            fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+            std::os::unix::fs::symlink("README.md", td.path().join("LINK.md")).unwrap();
+        }
+
+        let mut tgz = Vec::new();
+        let n = write_archive(td.path(), ArchiveFormat::TarGz, &mut tgz).unwrap();
+        let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(tgz.as_slice()));
+        let mut seen = Vec::new();
+        for e in tar.entries().unwrap() {
+            let e = e.unwrap();
+            let path = e.path().unwrap().to_string_lossy().into_owned();
+            seen.push((path, e.header().mode().unwrap(), e.header().entry_type().is_symlink()));
+        }
+        assert_eq!(seen.len(), n);
+        assert!(seen.contains(&("README.md".to_string(), 0o644, false)), "{:?}", seen);
+        #[cfg(unix)]
+        {
+            assert!(seen.contains(&("scripts/run.sh".to_string(), 0o755, false)), "{:?}", seen);
+            assert!(seen.iter().any(|(p, _, link)| p == "LINK.md" && *link));
+        }
+
+        let mut zip_bytes = Vec::new();
+        write_archive(td.path(), ArchiveFormat::Zip, &mut zip_bytes).unwrap();
+        let mut zip = zip::ZipArchive::new(Cursor::new(zip_bytes)).unwrap();
+        let mut readme = String::new();
+        zip.by_name("README.md").unwrap().read_to_string(&mut readme).unwrap();
+        assert_eq!(readme, "hi\n");
+        #[cfg(unix)]
+        assert_eq!(zip.by_name("scripts/run.sh").unwrap().unix_mode().unwrap() & 0o777, 0o755);
+    }
+
+    #[test]
+    fn format_from_file_name() {
+        let f = |p: &str| ArchiveFormat::from_path(Path::new(p));
+        assert_eq!(f("out.tar.gz"), Some(ArchiveFormat::TarGz));
+        assert_eq!(f("OUT.TGZ"), Some(ArchiveFormat::TarGz));
+        assert_eq!(f("out.tar"), Some(ArchiveFormat::Tar));
+        assert_eq!(f("out.zip"), Some(ArchiveFormat::Zip));
+        assert_eq!(f("out.gz"), None);
+        assert!(ArchiveFormat::from_name("rar").is_err());
+    }
+}
@@ -1,14 +1,31 @@
-use std::collections::HashMap;
-use std::fs;
+use std::collections::{BTreeMap, HashSet};
+use std::io::{stdin, stdout, Write};
 use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
-use glob::glob;
-use include_dir::{include_dir, Dir};
-use tempfile::TempDir;
-
-use serde::Deserialize;
+use clap::{Parser, Subcommand, ValueEnum};
+use rust_repo_template::archive::{self, ArchiveFormat};
+use rust_repo_template::component;
+use rust_repo_template::error::{self, CosmosError, ErrorKind};
+use rust_repo_template::exclude;
+use rust_repo_template::generate::{
+    self, FileChoice, Generator, Prepared, Prompt, Selection, SyncOptions,
+};
+use rust_repo_template::git;
+use rust_repo_template::plan::{self, NewTask, Plan, Status, TaskUpdate, TransitionOptions};
+use rust_repo_template::project;
+use rust_repo_template::repo;
+use rust_repo_template::template::{
+    self, CategoryFilter, FileDiff, RenderedFile, Renderer, SyncPlan, Template, TemplateInfo,
+    TemplateManifest, TemplateSource, VariablePrompt,
+};
+use rust_repo_template::transaction::{self, Commit, Transaction};
+use rust_repo_template::update::{self, TemplateLock};
+use rust_repo_template::validate::{self, Issue};
+use rust_repo_template::vars;
+use rust_repo_template::verify::{self, CheckoutOptions, VerifyOptions, VerifyStep};
+use serde_json::{json, Value};
 
 /// Simple project validator & generator (cosmos)
 #[derive(Parser)]
@@ -16,17 +33,40 @@ use serde::Deserialize;
 #[command(version)]
 #[command(about = "Manage and validate project templates", long_about = None)]
 struct Cli {
+    /// Output format: human text, one JSON document, or newline-delimited JSON records
+    #[arg(long, global = true, value_enum, default_value_t = Format::Text)]
+    format: Format,
+    /// Repository root (default: nearest ancestor with project.toml, plan/ or .git)
+    #[arg(long, global = true, value_name = "DIR", env = "COSMOS_REPO")]
+    repo: Option<PathBuf>,
     #[command(subcommand)]
     command: Commands,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Text,
+    Json,
+    Ndjson,
+}
+
+impl Format {
+    fn is_text(self) -> bool {
+        self == Format::Text
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Generate files from a template
     Generate {
-        /// Category to generate (all|basis|docs|ci|tests|examples|scripts|plan)
-        #[arg(short, long, default_value_t = String::from("all"))]
-        category: String,
+        /// Categories to generate (all|basis|docs|ci|tests|examples|scripts|plan); repeat or
+        /// comma-separate to select several
+        #[arg(short, long, value_delimiter = ',', default_value = "all")]
+        category: Vec<String>,
+        /// Categories to leave out (wins over --category)
+        #[arg(long, value_delimiter = ',')]
+        exclude_category: Vec<String>,
         /// Destination directory (default: ./out)
         #[arg(short, long, value_name = "DIR", default_value = "out")]
         out_dir: PathBuf,
@@ -45,7 +85,8 @@ enum Commands {
         /// Template name (default: default)
         #[arg(long, default_value = "default")]
         template: String,
-        /// Alternative template directory to use instead of repository 'templates/<name>'
+        /// Template folder to use instead of looking up '<name>' (COSMOS_TEMPLATE_PATH roots,
+        /// then repository 'templates/', then embedded templates)
         #[arg(long, value_name = "DIR")]
         template_dir: Option<PathBuf>,
         /// Project name to use for templating (used for {{project-name}})
@@ -54,9 +95,109 @@ enum Commands {
         /// Additional template variables in key=value form; may be repeated
         #[arg(long = "var", value_parser = parse_key_val, num_args=0..)]
         vars: Vec<(String, String)>,
-        /// After generation, run verification steps (fmt/clippy/test)
+        /// Read template variables from a TOML, JSON or YAML file; may be repeated.
+        /// Precedence: --var > COSMOS_VAR_<NAME> > --vars-file > existing project (template lock, Cargo.toml, project.toml)
+        #[arg(long, value_name = "FILE")]
+        vars_file: Vec<PathBuf>,
+        /// After generation, run the verification steps: `[verify]` in the project's
+        /// project.toml or the template manifest, else fmt/clippy/test
         #[arg(long)]
         verify: bool,
+        /// Run the verification steps with CARGO_NET_OFFLINE=true (no network access)
+        #[arg(long, requires = "verify")]
+        offline: bool,
+        /// Fail on template errors (unknown variables, bad syntax) before writing anything
+        #[arg(long)]
+        strict: bool,
+        /// Show unified diffs of the rendered output against existing destination files
+        #[arg(long)]
+        diff: bool,
+        /// Show a per-file summary of added/removed lines
+        #[arg(long)]
+        diff_stat: bool,
+        /// Ask per differing or extra file (keep/overwrite/diff/side-by-side/merge) instead of
+        /// one confirmation; answers are remembered in .cosmos/template.lock
+        #[arg(short, long, conflicts_with = "yes")]
+        interactive: bool,
+        /// Undo an earlier --apply: restore the files backed up in .cosmos/backup/<ID>
+        /// (`latest` for the most recent) and remove the files it added
+        #[arg(long, value_name = "ID")]
+        rollback: Option<String>,
+        /// Pack the generated project into FILE (.tar.gz, .tgz, .tar or .zip) instead of
+        /// writing --out-dir; `-` streams the archive to stdout
+        #[arg(
+            long,
+            value_name = "FILE",
+            conflicts_with_all = ["out_dir", "interactive", "rollback", "diff", "diff_stat"]
+        )]
+        output_archive: Option<PathBuf>,
+        /// Archive format (tar.gz|tar|zip) when FILE does not tell (default for `-`: tar.gz)
+        #[arg(long, value_name = "FORMAT", value_parser = parse_archive_format, requires = "output_archive")]
+        archive_format: Option<ArchiveFormat>,
+        /// After a successful --apply (and --verify), `git init` the destination and install
+        /// its hooks (scripts/install-git-hooks.sh or .githooks)
+        #[arg(long, conflicts_with = "output_archive")]
+        git_init: bool,
+        /// Initial branch for --git-init
+        #[arg(long, value_name = "NAME", default_value = "main")]
+        git_branch: String,
+        /// Commit all generated files with this message, as the rendered `author`
+        /// (implies --git-init)
+        #[arg(long, value_name = "MSG", conflicts_with = "output_archive")]
+        git_commit: Option<String>,
+        /// Apply even when the destination has uncommitted git changes
+        #[arg(long)]
+        allow_dirty: bool,
+    },
+
+    /// Re-render the template a project was generated from and merge the changes in
+    Update {
+        /// Project directory containing .cosmos/template.lock
+        #[arg(short, long, value_name = "DIR", default_value = ".")]
+        out_dir: PathBuf,
+        /// Template folder to use instead of the one recorded in the lock
+        #[arg(long, value_name = "DIR")]
+        template_dir: Option<PathBuf>,
+        /// Override recorded template variables in key=value form; may be repeated
+        #[arg(long = "var", value_parser = parse_key_val, num_args=0..)]
+        vars: Vec<(String, String)>,
+        /// Actually write the merged files (default: dry-run)
+        #[arg(long)]
+        apply: bool,
+        /// Leave conflicting files untouched and write the template change to <file>.rej
+        #[arg(long)]
+        reject: bool,
+    },
+
+    /// Add a component (binary, test, workflow, plan hook, docs page, ...) to an existing
+    /// project; without COMPONENT, list the available components
+    Add {
+        /// Component name (`components/<name>/` in the template roots)
+        component: Option<String>,
+        /// Project directory
+        #[arg(short, long, value_name = "DIR", default_value = ".")]
+        out_dir: PathBuf,
+        /// Component folder to use instead of looking up <COMPONENT>
+        #[arg(long, value_name = "DIR")]
+        template_dir: Option<PathBuf>,
+        /// Component variables in key=value form; may be repeated
+        #[arg(long = "var", value_parser = parse_key_val, num_args=0..)]
+        vars: Vec<(String, String)>,
+        /// Actually write the files (default: dry-run)
+        #[arg(long)]
+        apply: bool,
+        /// Overwrite files the component creates when they already exist
+        #[arg(long)]
+        force: bool,
+        /// Show unified diffs of the new and patched files
+        #[arg(long)]
+        diff: bool,
+    },
+
+    /// List, inspect, export and check templates
+    Template {
+        #[command(subcommand)]
+        sub: TemplateCmd,
     },
 
     /// Validate repository / template
@@ -69,6 +210,24 @@ enum Commands {
         fix: bool,
     },
 
+    /// Run the verification gate (fmt/clippy/test, the project.toml `[clippy]` allow list
+    /// and MSRV, or its `[verify]` steps) on the repository
+    Verify {
+        /// Only run these steps (comma-separated or repeated), e.g. `--only fmt,clippy`
+        #[arg(long, value_delimiter = ',', value_name = "STEPS")]
+        only: Vec<String>,
+        /// Skip the steps none of whose files changed since REV (committed, staged,
+        /// modified or untracked changes)
+        #[arg(long, value_name = "REV")]
+        changed_since: Option<String>,
+        /// Run the steps with CARGO_NET_OFFLINE=true (no network access)
+        #[arg(long)]
+        offline: bool,
+        /// Also write verify.json and a JUnit verify.xml into DIR
+        #[arg(long, value_name = "DIR")]
+        report_dir: Option<PathBuf>,
+    },
+
     /// Third-party AI / LLM related commands
     Ai {
         #[command(subcommand)]
@@ -91,6 +250,44 @@ enum Commands {
     },
 }
 
+#[derive(Subcommand)]
+enum TemplateCmd {
+    /// List available templates, where they come from and which copy is used
+    List {},
+    /// Show a template's manifest (categories, variables) and file tree
+    Show {
+        /// Template name
+        #[arg(default_value = "default")]
+        name: String,
+        /// Template folder to use instead of looking up <NAME>
+        #[arg(long, value_name = "DIR")]
+        template_dir: Option<PathBuf>,
+    },
+    /// Copy a template (e.g. the embedded one) into DIR for customization; the manifest
+    /// is written to DIR.toml
+    Export {
+        /// Template name
+        name: String,
+        /// Destination folder
+        dir: PathBuf,
+        /// Export into an existing, non-empty folder
+        #[arg(long, default_value_t = false)]
+        force: bool,
+    },
+    /// Render a template in strict mode with sample variables and validate the result
+    Check {
+        /// Template name
+        #[arg(default_value = "default")]
+        name: String,
+        /// Template folder to use instead of looking up <NAME>
+        #[arg(long, value_name = "DIR")]
+        template_dir: Option<PathBuf>,
+        /// Variables in key=value form instead of the samples; may be repeated
+        #[arg(long = "var", value_parser = parse_key_val, num_args=0..)]
+        vars: Vec<(String, String)>,
+    },
+}
+
 #[derive(Subcommand)]
 enum ProjectCmd {
     /// Emit GitHub Actions outputs parsed from project.toml (writes to $GITHUB_OUTPUT if set)
@@ -260,2504 +457,1960 @@ enum HooksCmd {
         name: Option<String>,
     },
 }
-
-#[derive(serde::Serialize)]
-struct AiValidationReport {
-    tool: &'static str,
-    kind: &'static str,
-    task_id: String,
-    from_status: String,
-    to_status: String,
-    ok: bool,
-    summary: String,
-    suggestions: Vec<String>,
+fn parse_key_val(s: &str) -> Result<(String, String), String> {
+    let mut parts = s.splitn(2, '=');
+    match (parts.next(), parts.next()) {
+        (Some(k), Some(v)) => Ok((k.to_string(), v.to_string())),
+        _ => Err(format!("invalid key=value: '{}'", s)),
+    }
 }
 
-fn task_root_dir(repo_root: &Path, id: &str) -> PathBuf {
-    let tasks = repo_root.join("plan/tasks").join(id);
-    if tasks.exists() {
-        return tasks;
-    }
-    repo_root.join("plan/archive").join(id)
+fn parse_archive_format(s: &str) -> Result<ArchiveFormat, String> {
+    ArchiveFormat::from_name(s).map_err(|e| e.to_string())
 }
 
-fn get_task_context(repo_root: &Path, id: &str) -> Result<(String, String)> {
-    let plan = read_todo(repo_root)?;
-    for t in plan.task {
-        if t.id == id {
-            return Ok((t.status.unwrap_or_default(), t.task_file.unwrap_or_default()));
+/// Print a structured document: the whole `doc` for `json`, one compact line
+/// per record for `ndjson`. Text output is printed by the callers.
+fn emit(format: Format, doc: &Value, records: &[Value]) {
+    match format {
+        Format::Text => {}
+        Format::Json => {
+            println!("{}", serde_json::to_string_pretty(doc).unwrap_or_default());
+        }
+        Format::Ndjson => {
+            for r in records {
+                println!("{}", r);
+            }
         }
     }
-    anyhow::bail!("plan: task '{}' not found", id)
 }
 
-fn write_ai_validation_report(
-    repo_root: &Path,
-    id: &str,
-    from_status: &str,
-    to_status: &str,
-    report: &AiValidationReport,
-) -> Result<PathBuf> {
-    let root = task_root_dir(repo_root, id);
-    let reports_dir = root.join("reports");
-    fs::create_dir_all(&reports_dir)?;
-    let path = reports_dir.join("ai_validation.json");
-    fs::write(&path, serde_json::to_string_pretty(report)?)?;
-    // Also keep a per-transition copy for audit/debugging.
-    let per = reports_dir.join(format!("ai_validation_{}_to_{}.json", from_status, to_status));
-    let _ = fs::write(&per, serde_json::to_string_pretty(report)?);
-    Ok(path)
+/// Emit a document that is its own single ndjson record.
+fn emit_doc(format: Format, doc: Value) {
+    emit(format, &doc, std::slice::from_ref(&doc));
 }
 
-fn ai_validate_transition(
-    repo_root: &Path,
-    id: &str,
-    from_status: &str,
-    to_status: &str,
-) -> Result<AiValidationReport> {
-    // Default behavior: if LLM isn't enabled/available, we don't block.
-    #[cfg(feature = "llm")]
-    {
-        let res = rust_repo_template::llm::evaluate_with_llm(repo_root);
-        match res {
-            Ok(()) => Ok(AiValidationReport {
-                tool: "cosmos",
-                kind: "plan_transition",
-                task_id: id.to_string(),
-                from_status: from_status.to_string(),
-                to_status: to_status.to_string(),
-                ok: true,
-                summary: "LLM validation completed (stub)".to_string(),
-                suggestions: vec![
-                    "Review the generated .cosmos_llm_report.txt for details".to_string()
-                ],
-            }),
-            Err(e) => Ok(AiValidationReport {
-                tool: "cosmos",
-                kind: "plan_transition",
-                task_id: id.to_string(),
-                from_status: from_status.to_string(),
-                to_status: to_status.to_string(),
-                ok: true,
-                summary: format!("LLM validation unavailable/non-fatal: {}", e),
-                suggestions: vec![
-                    "Enable and configure an LLM provider to enforce checks".to_string()
-                ],
-            }),
-        }
-    }
-    #[cfg(not(feature = "llm"))]
-    {
-        let _ = repo_root;
-        Ok(AiValidationReport {
-            tool: "cosmos",
-            kind: "plan_transition",
-            task_id: id.to_string(),
-            from_status: from_status.to_string(),
-            to_status: to_status.to_string(),
-            ok: true,
-            summary: "LLM support not enabled in this build".to_string(),
-            suggestions: vec![
-                "Rebuild with `--features llm` to enable third-party AI validation".to_string()
-            ],
-        })
-    }
+/// Validation issues as ndjson records tagged with their severity.
+fn issue_records(errors: &[Issue], warnings: &[Issue]) -> Vec<Value> {
+    let tagged = |issues: &[Issue], severity: &str| -> Vec<Value> {
+        issues
+            .iter()
+            .map(|i| {
+                let mut v = json!(i);
+                v["severity"] = json!(severity);
+                v
+            })
+            .collect()
+    };
+    let mut out = tagged(errors, "error");
+    out.extend(tagged(warnings, "warning"));
+    out
 }
 
-#[allow(dead_code)]
-#[derive(Debug, Deserialize)]
-struct TemplateManifest {
-    name: Option<String>,
-    categories: HashMap<String, Vec<String>>,
+/// Prompts need a terminal conversation; structured output requires `--yes`.
+fn require_interactive(format: Format) -> Result<()> {
+    if format.is_text() {
+        return Ok(());
+    }
+    Err(CosmosError::Usage(
+        "confirmation required: pass --yes when using --format json/ndjson".to_string(),
+    )
+    .into())
 }
 
-fn load_manifest(repo_root: &Path, template_name: &str) -> Result<Option<TemplateManifest>> {
-    let path = repo_root.join("templates").join(format!("{}.toml", template_name));
-    if path.exists() {
-        let s =
-            fs::read_to_string(&path).with_context(|| format!("reading manifest {:?}", path))?;
-        let m: TemplateManifest = toml::from_str(&s).context("parsing manifest")?;
-        Ok(Some(m))
-    } else {
-        Ok(None)
-    }
+/// Ask a y/N question on stdin.
+fn confirm(prompt: &str) -> bool {
+    print!("{}", prompt);
+    stdout().flush().ok();
+    let mut input = String::new();
+    let _ = stdin().read_line(&mut input);
+    matches!(input.trim().to_lowercase().as_str(), "y" | "yes")
 }
 
-fn expand_patterns(repo_root: &Path, patterns: &[String]) -> Result<Vec<PathBuf>> {
-    let mut matches = Vec::new();
-    for pat in patterns {
-        // Patterns are relative to repo root
-        let full = repo_root.join(pat);
-        let pattern = full.to_string_lossy().to_string();
-        for p in (glob(&pattern).context("globbing pattern")?).flatten() {
-            // skip .git and target directories
-            if p.components().any(|c| c.as_os_str() == ".git" || c.as_os_str() == "target") {
+/// Ask for a template variable on stdin until a valid value is entered.
+fn prompt_variable(p: &VariablePrompt) -> Result<String> {
+    let mut question = match &p.spec.description {
+        Some(d) => format!("{} ({})", p.name, d),
+        None => p.name.to_string(),
+    };
+    if !p.spec.choices.is_empty() {
+        question.push_str(&format!(" [{}]", p.spec.choices.join("/")));
+    }
+    if let Some(d) = &p.default {
+        question.push_str(&format!(" [default: {}]", d));
+    }
+    loop {
+        print!("{}: ", question);
+        stdout().flush().ok();
+        let mut input = String::new();
+        if stdin().read_line(&mut input)? == 0 {
+            return Err(
+                CosmosError::Usage(format!("no value given for variable '{}'", p.name)).into()
+            );
+        }
+        let value = match (input.trim(), &p.default) {
+            ("", Some(d)) => d.clone(),
+            ("", None) => {
+                println!("A value is required.");
                 continue;
             }
-            matches.push(p);
+            (v, _) => v.to_string(),
+        };
+        match p.spec.parse(p.name, &value) {
+            Ok(_) => return Ok(value),
+            Err(e) => println!("{:#}", e),
         }
     }
-    matches.sort();
-    matches.dedup();
-    Ok(matches)
 }
 
-fn copy_paths_to(repo_root: &Path, paths: &[PathBuf], dest: &Path, force: bool) -> Result<()> {
-    for p in paths {
-        let rel = p.strip_prefix(repo_root).unwrap_or(p);
-        let destpath = dest.join(rel);
-        if let Some(parent) = destpath.parent() {
-            fs::create_dir_all(parent)?;
-        }
-        if p.is_dir() {
-            // create dir marker
-            fs::create_dir_all(&destpath)?;
-        } else {
-            if destpath.exists() && !force {
-                println!("Skipping existing file: {}", destpath.display());
-                continue;
-            }
-            fs::copy(p, &destpath).with_context(|| format!("copy {:?} to {:?}", p, destpath))?;
-        }
-    }
-    Ok(())
+/// Sync flags shared by both generate modes, plus what to report.
+#[derive(Clone, Copy)]
+struct SyncOpts {
+    sync: SyncOptions,
+    format: Format,
+    diff: bool,
+    diff_stat: bool,
 }
 
-fn validate_repo(repo_root: &Path, _level: &str, fix: bool) -> Result<(Vec<String>, Vec<String>)> {
-    let mut errors = Vec::new();
-    let mut warnings = Vec::new();
+/// Answers the questions of [`generate::Prompt`] on the terminal.
+struct Terminal {
+    format: Format,
+}
 
-    // required basis files
-    for b in &["Cargo.toml", "README.md", "LICENSE", "CONTRIBUTING.md"] {
-        if !repo_root.join(b).exists() {
-            errors.push(format!("Missing required file: {}", b));
+impl Prompt for Terminal {
+    fn say(&mut self, line: &str) {
+        if self.format.is_text() {
+            println!("{}", line);
         }
     }
 
-    // docs
-    if !repo_root.join("docs").exists() {
-        warnings.push("docs/ missing".to_string());
-    }
-
-    // workflows
-    let wf = repo_root.join(".github/workflows");
-    if !wf.exists() {
-        warnings.push(".github/workflows missing".to_string());
-    } else {
-        let mut found = false;
-        for ent in
-            wf.read_dir().with_context(|| format!("reading workflows dir {:?}", wf))?.flatten()
-        {
-            let p = ent.path();
-            if p.extension()
-                .and_then(|s| s.to_str())
-                .map(|s| s == "yml" || s == "yaml")
-                .unwrap_or(false)
-            {
-                found = true;
-                break;
+    fn confirm(&mut self, plan: &SyncPlan) -> Result<bool> {
+        require_interactive(self.format)?;
+        if !confirm("Apply these fixes? [y/N]: ") {
+            println!("Aborted by user; no changes applied.");
+            return Ok(false);
+        }
+        if plan.extra.is_empty() {
+            if !plan.protected.is_empty() {
+                println!("No deletions to perform (all extra items are protected). Continuing.");
             }
+            return Ok(true);
         }
-        if !found {
-            warnings.push("No workflow yml files found under .github/workflows".to_string());
+        print!(
+            "This operation will DELETE {} files. Type DELETE to confirm deletions: ",
+            plan.extra.len()
+        );
+        stdout().flush().ok();
+        let mut dconfirm = String::new();
+        let _ = stdin().read_line(&mut dconfirm);
+        if dconfirm.trim() != "DELETE" {
+            println!("Deletion confirmation failed; aborting without changes.");
+            return Ok(false);
         }
-    }
-
-    // scripts
-    if !repo_root.join("scripts").exists() {
-        warnings.push("scripts/ missing".to_string());
-    } else if !repo_root.join("scripts/validate_plan.py").exists() {
-        warnings.push("scripts/validate_plan.py missing".to_string());
-    }
-
-    // plan checks
-    let plan_issues = validate_plan(repo_root)?;
-    for p in plan_issues.clone() {
-        errors.push(p);
-    }
-
-    // project.toml consistency checks (blocking on errors)
-    if repo_root.join("project.toml").exists() {
-        let report =
-            validate_project_manifest(repo_root, false).context("validating project.toml")?;
-        errors.extend(report.errors);
-        warnings.extend(report.warnings);
-    }
-
-    // AI heuristics
-    let ai_w = check_ai_heuristics(repo_root)?;
-    for w in ai_w {
-        warnings.push(w);
-    }
-
-    // attempt auto-fixes if requested and we found issues
-    if fix {
-        let mut applied = Vec::new();
-        // Only attempt fixes when there are errors or missing files
-        if !errors.is_empty() || !warnings.is_empty() {
-            let fixes = auto_fix_repo(repo_root)?;
-            for f in fixes {
-                applied.push(f);
-            }
-            // Re-run validations to update lists
-            let (new_errors, new_warnings) = validate_repo(repo_root, _level, false)?;
-            errors = new_errors;
-            warnings = new_warnings;
-            if !applied.is_empty() {
-                println!("Applied fixes:");
-                for f in applied {
-                    println!(" - {}", f);
+        Ok(true)
+    }
+
+    fn differing(
+        &mut self,
+        rel: &Path,
+        out: &RenderedFile,
+        dest: &Path,
+    ) -> Result<Option<FileChoice>> {
+        require_interactive(self.format)?;
+        let prompt = format!(
+            "{} differs from the template: [k]eep, [o]verwrite, [d]iff, [n]ew copy ({}), [m]erge in $EDITOR, [q]uit? ",
+            rel.display(),
+            update::SIDE_BY_SIDE_SUFFIX
+        );
+        loop {
+            match ask_choice(&prompt, "kodnmq")? {
+                'k' => return Ok(Some(FileChoice::Keep)),
+                'o' => return Ok(Some(FileChoice::Overwrite)),
+                'n' => return Ok(Some(FileChoice::SideBySide)),
+                'd' => {
+                    for d in template::diff_rendered(&[(rel.to_path_buf(), out.clone())], dest)? {
+                        print!("{}", colorize_diff(&d.diff));
+                    }
+                }
+                'm' => match out {
+                    RenderedFile::Contents(new) if !template::is_binary(new) => {
+                        return Ok(Some(FileChoice::Merge))
+                    }
+                    _ => println!("{} cannot be merged as text", rel.display()),
+                },
+                _ => {
+                    println!("Aborted by user; no changes applied.");
+                    return Ok(None);
                 }
             }
         }
     }
 
-    Ok((errors, warnings))
-}
-
-#[allow(dead_code)]
-#[derive(Debug, Deserialize, serde::Serialize)]
-struct PlanTodo {
-    meta: Option<toml::Value>,
-    #[serde(default)]
-    task: Vec<PlanTask>,
+    fn delete_extra(&mut self, rel: &Path) -> Result<Option<bool>> {
+        require_interactive(self.format)?;
+        let prompt =
+            format!("{} is not part of the template: [k]eep, [d]elete, [q]uit? ", rel.display());
+        match ask_choice(&prompt, "kdq")? {
+            'k' => Ok(Some(false)),
+            'd' => Ok(Some(true)),
+            _ => {
+                println!("Aborted by user; no changes applied.");
+                Ok(None)
+            }
+        }
+    }
 }
 
-#[allow(dead_code)]
-#[derive(Debug, Deserialize, serde::Serialize, Clone)]
-struct PlanTask {
-    id: String,
-    kind: Option<String>, // "bug" or "feature"
-    title: Option<String>,
-    status: Option<String>, // pending_review|queued|working|testing|under_acceptance|finished
-    assignee: Option<String>,
-    task_file: Option<String>,
+/// Color diff lines when printing to a terminal (unless `NO_COLOR` is set).
+fn colorize_diff(diff: &str) -> String {
+    if !atty::is(atty::Stream::Stdout) || std::env::var_os("NO_COLOR").is_some() {
+        return diff.to_string();
+    }
+    diff.lines()
+        .map(|l| {
+            let color = if l.starts_with("+++") || l.starts_with("---") {
+                "1"
+            } else if l.starts_with('+') {
+                "32"
+            } else if l.starts_with('-') {
+                "31"
+            } else if l.starts_with("@@") {
+                "36"
+            } else {
+                return format!("{}\n", l);
+            };
+            format!("\x1b[{}m{}\x1b[0m\n", color, l)
+        })
+        .collect()
 }
 
-// Helpers to read/write todo.toml and manage next_id
-fn read_todo(repo_root: &Path) -> Result<PlanTodo> {
-    let todo = repo_root.join("plan/todo.toml");
-    if !todo.exists() {
-        return Ok(PlanTodo { meta: None, task: Vec::new() });
+/// `git diff --stat` style summary.
+fn print_diff_stat(diffs: &[FileDiff]) {
+    let width = diffs.iter().map(|d| d.path.to_string_lossy().len()).max().unwrap_or(0);
+    let most = diffs.iter().map(|d| d.insertions + d.deletions).max().unwrap_or(0);
+    let digits = most.to_string().len();
+    for d in diffs {
+        let path = d.path.to_string_lossy();
+        if d.binary {
+            println!(" {:width$} | Bin", path, width = width);
+            continue;
+        }
+        // scale the +/- bar to at most 40 columns
+        let total = d.insertions + d.deletions;
+        let scale = |n: usize| if most > 40 { (n * 40 + most - 1) / most } else { n };
+        println!(
+            " {:width$} | {:>digits$} {}{}",
+            path,
+            total,
+            "+".repeat(scale(d.insertions)),
+            "-".repeat(scale(d.deletions)),
+            width = width,
+            digits = digits
+        );
     }
-    let s = fs::read_to_string(&todo).context("reading plan/todo.toml")?;
-    let plan: PlanTodo = toml::from_str(&s).context("parsing plan/todo.toml")?;
-    Ok(plan)
+    let (ins, del) = diffs.iter().fold((0, 0), |(i, d), f| (i + f.insertions, d + f.deletions));
+    println!(
+        " {} file{} changed, {} insertion{}(+), {} deletion{}(-)",
+        diffs.len(),
+        if diffs.len() == 1 { "" } else { "s" },
+        ins,
+        if ins == 1 { "" } else { "s" },
+        del,
+        if del == 1 { "" } else { "s" }
+    );
 }
 
-fn write_todo(repo_root: &Path, plan: &PlanTodo) -> Result<()> {
-    let plan_dir = repo_root.join("plan");
-    fs::create_dir_all(&plan_dir)?;
-    let s = toml::to_string_pretty(plan).context("serializing todo.toml")?;
-    fs::write(plan_dir.join("todo.toml"), s).context("writing plan/todo.toml")?;
+/// Print `--diff` / `--diff-stat` output in text mode and add the diffs to
+/// the structured document.
+fn report_diffs(
+    rendered: &[(PathBuf, template::RenderedFile)],
+    dest: &Path,
+    opts: &SyncOpts,
+    doc: &mut Value,
+) -> Result<()> {
+    if !opts.diff && !opts.diff_stat {
+        return Ok(());
+    }
+    let diffs = template::diff_rendered(rendered, dest)?;
+    if opts.format.is_text() {
+        if diffs.is_empty() {
+            println!("No differences against {}", dest.display());
+        }
+        if opts.diff {
+            for d in &diffs {
+                print!("{}", colorize_diff(&d.diff));
+            }
+        }
+        if opts.diff_stat && !diffs.is_empty() {
+            print_diff_stat(&diffs);
+        }
+    }
+    doc["diffs"] = json!(diffs);
     Ok(())
 }
 
-fn read_next_id(repo_root: &Path) -> Result<u32> {
-    let p = repo_root.join("plan/next_id.txt");
-    if p.exists() {
-        let s = fs::read_to_string(p)?.trim().to_string();
-        if let Ok(n) = s.parse::<u32>() {
-            return Ok(n);
-        }
-    }
-    // fallback: compute max existing id + 1
-    let mut max = 0u32;
-    let plan = read_todo(repo_root)?;
-    for t in plan.task {
-        if let Ok(n) = t.id.parse::<u32>() {
-            if n > max {
-                max = n;
+fn print_sync_plan(plan: &SyncPlan, header: &str) {
+    println!("{}", header);
+    if !plan.missing.is_empty() {
+        println!("  Missing files to add ({}):", plan.missing.len());
+        for m in &plan.missing {
+            println!("   + {}", m.display());
+        }
+    }
+    if !plan.modified.is_empty() {
+        println!("  Files that will be overwritten ({}):", plan.modified.len());
+        for m in &plan.modified {
+            println!("   ~ {}", m.display());
+        }
+    }
+    if !plan.skipped_overwrite.is_empty() {
+        println!(
+            "  Skipped overwriting existing files (use --force to override): {}",
+            plan.skipped_overwrite.len()
+        );
+        for s in &plan.skipped_overwrite {
+            println!("    (skipped) {}", s.display());
+        }
+    }
+    if !plan.extra.is_empty() {
+        println!("  Extra files to delete ({}):", plan.extra.len());
+        for e in &plan.extra {
+            println!("   - {}", e.display());
+        }
+    }
+    if !plan.protected.is_empty() {
+        println!("  Kept {} protected files:", plan.protected.len());
+        for p in &plan.protected {
+            match plan.rules.get(p) {
+                Some(rule) => println!("    (protected) {} [{}]", p.display(), rule),
+                None => println!("    (protected) {}", p.display()),
             }
         }
     }
-    Ok(max + 1)
 }
 
-fn write_next_id(repo_root: &Path, n: u32) -> Result<()> {
-    let plan_dir = repo_root.join("plan");
-    fs::create_dir_all(&plan_dir)?;
-    fs::write(plan_dir.join("next_id.txt"), format!("{:04}\n", n))?;
+/// `generate --rollback`: undo the commit recorded as backup `id`.
+fn rollback_generate(dest: &Path, id: &str, format: Format) -> Result<()> {
+    let commit = transaction::rollback(dest, id)?;
+    if format.is_text() {
+        for p in commit.replaced.iter().chain(&commit.added) {
+            println!(" - restored {}", p.display());
+        }
+        for p in &commit.deleted {
+            println!(" - removed {}", p.display());
+        }
+        println!("Rolled back {} in {}", id, dest.display());
+        print_backup(&commit);
+    }
+    emit_doc(
+        format,
+        json!({
+            "rollback": id,
+            "dest": dest,
+            "restored": commit.replaced.iter().chain(&commit.added).collect::<Vec<_>>(),
+            "removed": commit.deleted,
+            "backup": commit.backup,
+        }),
+    );
     Ok(())
 }
 
-fn validate_plan(repo_root: &Path) -> Result<Vec<String>> {
-    let mut issues = Vec::new();
-    let plan_dir = repo_root.join("plan");
-    let todo = plan_dir.join("todo.toml");
-    if !todo.exists() {
-        issues.push("plan/todo.toml missing".to_string());
-        return Ok(issues);
+/// Tell where replaced and deleted files were backed up.
+fn print_backup(commit: &Commit) {
+    let (Some(id), n) = (&commit.backup, commit.backed_up()) else {
+        return;
+    };
+    if n > 0 {
+        println!(
+            "Backed up {} replaced/deleted file(s) to {}/{} (undo with `cosmos generate --rollback {}`)",
+            n,
+            transaction::BACKUP_DIR,
+            id,
+            id
+        );
     }
-    let s = fs::read_to_string(&todo).context("reading plan/todo.toml")?;
-    let plan: PlanTodo = toml::from_str(&s).context("parsing plan/todo.toml")?;
+}
 
-    if plan.task.is_empty() {
-        issues.push("plan: no tasks found in todo.toml".to_string());
+/// Read a one-letter answer from `options` on stdin.
+fn ask_choice(prompt: &str, options: &str) -> Result<char> {
+    loop {
+        print!("{}", prompt);
+        stdout().flush().ok();
+        let mut input = String::new();
+        if stdin().read_line(&mut input)? == 0 {
+            return Err(CosmosError::Usage("no answer on stdin".to_string()).into());
+        }
+        match input.trim().to_lowercase().chars().next() {
+            Some(c) if options.contains(c) => return Ok(c),
+            _ => {
+                println!("Please answer one of: {}", options)
+            }
+        }
     }
+}
 
-    let allowed_status =
-        ["pending_review", "queued", "working", "testing", "under_acceptance", "finished"];
-
-    for t in &plan.task {
-        if t.id.trim().is_empty() {
-            issues.push(format!("plan: task with empty id: {:?}", t.title));
-            continue;
+/// `cosmos verify` (see [`verify::verify_checkout`]): prints a summary table.
+fn verify_checkout(root: &Path, opts: &CheckoutOptions, format: Format) -> Result<()> {
+    let text = format.is_text();
+    let say = |line: &str| {
+        if text {
+            println!("{}", line);
         }
-        if let Some(kind) = &t.kind {
-            if kind != "bug" && kind != "feature" {
-                issues.push(format!(
-                    "plan: task {} has invalid kind '{}', must be 'bug' or 'feature'",
-                    t.id, kind
-                ));
+    };
+    let run = verify::verify_checkout(root, opts, &say)?;
+    let steps = &run.steps;
+    if text {
+        print_verify_table(steps);
+        for s in steps.iter().filter(|s| !s.ok) {
+            println!("\n{} ({}):", s.name, s.command);
+            for line in s.failed_output() {
+                println!("{}", line);
             }
         }
-        if let Some(st) = &t.status {
-            if !allowed_status.contains(&st.as_str()) {
-                issues.push(format!("plan: task {} has invalid status '{}'", t.id, st));
-            }
+        let count = |f: fn(&VerifyStep) -> bool| steps.iter().filter(|s| f(s)).count();
+        println!(
+            "\n{} passed, {} failed, {} allowed to fail, {} skipped",
+            count(|s| s.ok && !s.skipped),
+            count(VerifyStep::blocks),
+            count(|s| !s.ok && s.allow_failure),
+            count(|s| s.skipped)
+        );
+        for r in &run.reports {
+            println!("Wrote {}", r.display());
         }
-        if let Some(tf) = &t.task_file {
-            let p = plan_dir.join(tf);
-            if !p.exists() {
-                issues.push(format!("plan: referenced task_file '{}' not found", tf));
-            } else {
-                // if task is finished, ensure it is in archive/
-                if let Some(st) = &t.status {
-                    if st == "finished" && !tf.starts_with("archive/") {
-                        issues.push(format!(
-                            "plan: task {} marked finished but task_file '{}' is not in archive/",
-                            t.id, tf
-                        ));
-                    }
-                }
-            }
+    }
+    let doc = json!({
+        "dir": root,
+        "source": run.source,
+        "changed_since": opts.changed_since,
+        "changed": run.changed,
+        "passed": verify::passed(steps),
+        "steps": steps,
+        "reports": run.reports,
+    });
+    let records: Vec<Value> = steps
+        .iter()
+        .map(|s| {
+            let mut r = json!(s);
+            r["type"] = json!("step");
+            r
+        })
+        .collect();
+    emit(format, &doc, &records);
+    verify::check(steps)
+}
+
+/// `STEP  STATUS  TIME  COMMAND` table of a verification run.
+fn print_verify_table(steps: &[VerifyStep]) {
+    let width = steps.iter().map(|s| s.name.len()).chain([4]).max().unwrap_or(0);
+    let status = steps.iter().map(|s| s.status().len()).chain([6]).max().unwrap_or(0);
+    println!("{:width$}  {:status$}  {:>7}  COMMAND", "STEP", "STATUS", "TIME");
+    for s in steps {
+        let time = if s.skipped {
+            "-".to_string()
         } else {
-            issues.push(format!("plan: task {} missing task_file", t.id));
-        }
+            format!("{:.1}s", s.duration_ms as f64 / 1000.0)
+        };
+        println!("{:width$}  {:status$}  {:>7}  {}", s.name, s.status(), time, s.command);
     }
+}
 
-    let tasks = repo_root.join("plan/tasks");
-    if !tasks.exists() {
-        issues.push("plan/tasks/ missing".to_string());
+/// ` [ci, docs]` suffix for file listings.
+fn category_suffix(cats: &[String]) -> String {
+    if cats.is_empty() {
+        String::new()
+    } else {
+        format!(" [{}]", cats.join(", "))
     }
-    Ok(issues)
 }
 
-use std::process::Command;
+/// Emit the structured `generate` result: file plan (with each file's
+/// categories) plus verification steps.
+fn emit_generate(
+    format: Format,
+    mut doc: Value,
+    applied: bool,
+    plan: &SyncPlan,
+    steps: &[VerifyStep],
+    sel: &Selection,
+) {
+    let entries: Vec<Value> = plan
+        .entries()
+        .iter()
+        .map(|e| {
+            let mut entry = json!({
+                "path": e.path,
+                "action": e.action,
+                "categories": sel.categories_of(&e.path),
+            });
+            if let Some(rule) = &e.rule {
+                entry["rule"] = json!(rule);
+            }
+            entry
+        })
+        .collect();
+    doc["applied"] = json!(applied);
+    doc["categories"] = json!(sel.filter.include);
+    doc["exclude_categories"] = json!(sel.filter.exclude);
+    doc["files"] = json!(entries);
+    doc["verification"] = json!(steps);
+    let mut records: Vec<Value> = entries
+        .into_iter()
+        .map(|mut e| {
+            e["type"] = json!("file");
+            e
+        })
+        .collect();
+    records.extend(
+        steps.iter().map(
+            |s| json!({"type": "verification", "name": s.name, "ok": s.ok, "output": s.output}),
+        ),
+    );
+    emit(format, &doc, &records);
+}
 
-fn check_ai_heuristics(repo_root: &Path) -> Result<Vec<String>> {
-    let mut warnings = Vec::new();
-    if !repo_root.join(".github/copilot-instructions.md").exists()
-        && !repo_root.join(".github/ai").exists()
-    {
-        warnings.push("No AI guidelines or .github/copilot-instructions.md found".to_string());
-    }
-    let readme = fs::read_to_string(repo_root.join("README.md")).unwrap_or_default();
-    if !readme.to_lowercase().contains("ai") {
-        warnings.push("README doesn't mention AI collaboration guidance".to_string());
+/// `0.2.0 (1a2b3c4d)`: template version and short content hash.
+fn template_label(version: Option<&str>, hash: &str) -> String {
+    let short: String = hash.trim_start_matches("sha256:").chars().take(8).collect();
+    match version {
+        Some(v) => format!("{} ({})", v, short),
+        None => short,
     }
-    Ok(warnings)
 }
 
-fn append_history(
-    repo_root: &Path,
-    id: &str,
-    message: Option<&str>,
-    author: Option<&str>,
-) -> Result<()> {
-    // prefer tasks dir, fallback to archive
-    let mut history_dir = repo_root.join("plan/tasks").join(id).join("history");
-    if !history_dir.exists() {
-        history_dir = repo_root.join("plan/archive").join(id).join("history");
-    }
-    fs::create_dir_all(&history_dir)?;
-    let ts = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
-    let filename = format!("{}.md", ts);
-    let path = history_dir.join(&filename);
-    let mut contents = String::new();
-    contents.push_str(&format!("time: {}\n", ts));
-    if let Some(a) = author {
-        contents.push_str(&format!("author: {}\n", a));
-    }
-    contents.push_str("---\n");
-    if let Some(m) = message {
-        contents.push_str(m);
-    }
-    fs::write(&path, contents)?;
-    Ok(())
+/// Steps after a successful `generate --apply`.
+struct AfterApply {
+    /// `--verify` (and `--offline`)
+    verify: Option<VerifyOptions>,
+    git: Option<git::Setup>,
 }
 
-/// Run a user-provided hook script if present. Scripts are looked up under
-/// scripts/plan-hooks/<hook_name>.py or scripts/plan-hooks/<hook_name>/*.py
-/// Scripts are Python files and receive context via environment variables:
-/// - PLAN_TASK_ID, PLAN_REPO_ROOT, PLAN_CURRENT_STATUS, PLAN_NEW_STATUS, PLAN_TASK_FILE
-fn run_user_hook(
-    repo_root: &Path,
-    hook_name: &str,
-    id: &str,
-    new_status: Option<&str>,
-    ai_validation_path: Option<&Path>,
+/// [`git::setup`] with its text report; returns the JSON summary.
+fn setup_git(
+    dest: &Path,
+    setup: &git::Setup,
+    vars: &serde_json::Map<String, Value>,
+    format: Format,
+) -> Result<Value> {
+    let report = git::setup(dest, setup, vars)?;
+    if format.is_text() {
+        if report.initialized {
+            println!(
+                "Initialized a git repository in {} (branch {})",
+                dest.display(),
+                setup.branch
+            );
+        } else {
+            println!("{} is already a git repository", dest.display());
+        }
+        if let Some(h) = &report.hooks {
+            println!("Installed git hooks: {}", h);
+        }
+        if let (Some(id), Some(author)) = (&report.commit, &report.author) {
+            println!(
+                "Committed the generated files as {} <{}>: {}",
+                author.name,
+                author.email,
+                &id[..id.len().min(8)]
+            );
+        }
+    }
+    Ok(json!(report))
+}
+
+/// `generate` into a folder: print the plan, and with `apply` write it (see
+/// [`Generator::apply`]), then verify the project and set up git.
+fn generate_from_template(
+    gen: &Generator,
+    dest: &Path,
+    apply: bool,
+    opts: &SyncOpts,
+    after: &AfterApply,
 ) -> Result<()> {
-    let hooks_dir = repo_root.join("scripts/plan-hooks");
-    let single = hooks_dir.join(format!("{}.py", hook_name));
-    let dir = hooks_dir.join(hook_name);
-    let mut scripts: Vec<std::path::PathBuf> = Vec::new();
-    if single.exists() {
-        scripts.push(single);
-    }
-    if dir.exists() && dir.is_dir() {
-        let mut entries: Vec<_> = fs::read_dir(&dir)?.filter_map(|e| e.ok()).collect();
-        entries.sort_by_key(|e| e.path());
-        for e in entries {
-            let p = e.path();
-            if p.is_file() && p.extension().and_then(|s| s.to_str()) == Some("py") {
-                scripts.push(p);
-            }
+    let (tpl, sel) = (gen.tpl, gen.sel);
+    let text = opts.format.is_text();
+    let prepared = gen.prepare()?;
+    let mut doc = json!({
+        "mode": "template",
+        "template": tpl.name,
+        "source": tpl.source,
+        "template_path": (tpl.source != TemplateSource::Embedded).then_some(&tpl.root),
+        "dest": dest,
+        "variables": gen.renderer.context(),
+        "excluded": prepared.excluded,
+        "ignored": prepared.ignored,
+        "members": sel.members.iter().map(|m| json!({"template": m.template.name, "path": m.path})).collect::<Vec<_>>(),
+    });
+    if prepared.is_empty() {
+        if text {
+            println!("Template '{}' has no files", tpl.name);
+        }
+        emit_generate(opts.format, doc, false, &SyncPlan::default(), &[], sel);
+        return Ok(());
+    }
+
+    let plan = gen.plan(&prepared, dest, opts.sync.force)?;
+    if text {
+        for line in matched_lines(tpl, &prepared, sel) {
+            println!("{}", line);
         }
     }
-    if scripts.is_empty() {
+    gen.check_strict(&prepared)?;
+    report_diffs(&prepared.rendered, dest, opts, &mut doc)?;
+
+    if !apply {
+        if text {
+            println!("Dry run (no files written). Use --apply to write files.");
+        }
+        emit_generate(opts.format, doc, false, &plan, &[], sel);
         return Ok(());
     }
-    // gather task info
-    let (cur_status, tf) = get_task_context(repo_root, id)?;
-    for s in scripts {
-        let mut cmd = Command::new("python3");
-        cmd.arg(s.as_os_str());
-        cmd.env("PLAN_TASK_ID", id);
-        cmd.env("PLAN_REPO_ROOT", repo_root);
-        cmd.env("PLAN_CURRENT_STATUS", &cur_status);
-        cmd.env("PLAN_NEW_STATUS", new_status.unwrap_or(""));
-        cmd.env("PLAN_TASK_FILE", &tf);
-        if let Some(p) = ai_validation_path {
-            cmd.env("PLAN_AI_VALIDATION_PATH", p);
-        }
-
-        // Also provide a JSON context to stdin for scripts that want structured input.
-        let ctx = serde_json::json!({
-            "task_id": id,
-            "repo_root": repo_root,
-            "current_status": cur_status,
-            "new_status": new_status.unwrap_or(""),
-            "task_file": tf,
-            "ai_validation_path": ai_validation_path.map(|p| p.to_string_lossy().to_string()).unwrap_or_default(),
-        });
-        cmd.stdin(std::process::Stdio::piped());
-        cmd.stdout(std::process::Stdio::piped());
-        cmd.stderr(std::process::Stdio::piped());
-
-        let mut child = cmd.spawn().context("spawning hook script")?;
-        if let Some(mut stdin) = child.stdin.take() {
-            use std::io::Write;
-            let _ = stdin.write_all(serde_json::to_string(&ctx)?.as_bytes());
-        }
-        let output = child.wait_with_output().context("running hook script")?;
-        if !output.status.success() {
-            let mut msg = String::new();
-            if !output.stdout.is_empty() {
-                msg.push_str(&String::from_utf8_lossy(&output.stdout));
-            }
-            if !output.stderr.is_empty() {
-                if !msg.is_empty() {
-                    msg.push('\n');
-                }
-                msg.push_str(&String::from_utf8_lossy(&output.stderr));
-            }
-            anyhow::bail!("hook '{}' failed for {}: {}", hook_name, id, msg.trim());
+
+    if text && !opts.sync.interactive && dest.exists() && plan.has_changes() {
+        print_sync_plan(&plan, "Detected inconsistencies between template and destination:");
+    }
+    let mut terminal = Terminal { format: opts.format };
+    let Some(applied) = gen.apply(&prepared, &plan, dest, &opts.sync, &mut terminal)? else {
+        return Ok(());
+    };
+    if text {
+        for s in &applied.skipped {
+            println!("Skipping existing file (use --force to overwrite): {}", s.display());
+        }
+        if applied.workspace_updated {
+            println!("Added the new workspace members to the existing Cargo.toml");
+        }
+        println!("Template files written to {}", dest.display());
+        print_backup(&applied.commit);
+        if applied.flattened {
+            println!("Detected nested template entries, flattening into destination");
         }
     }
-    Ok(())
-}
+    doc["backup"] = json!(applied.commit.backup);
 
-fn set_task_status(
-    repo_root: &Path,
-    id: &str,
-    expected: Option<&str>,
-    new_status: &str,
-) -> Result<()> {
-    let mut plan = read_todo(repo_root)?;
-    let mut found = false;
-    for t in plan.task.iter_mut() {
-        if t.id == id {
-            let cur = t.status.clone().unwrap_or_default();
-            if let Some(exp) = expected {
-                if cur != exp {
-                    anyhow::bail!("plan: cannot change status for {}: expected '{}' but current status is '{}'", id, exp, cur);
-                }
-            }
-            // handle finished -> move to archive
-            if new_status == "finished" {
-                let src = repo_root.join("plan/tasks").join(id);
-                let dst = repo_root.join("plan/archive").join(id);
-                if src.exists() {
-                    fs::create_dir_all(dst.parent().unwrap())?;
-                    fs::rename(&src, &dst).or_else(|_| {
-                        // fallback copy
-                        fs::create_dir_all(&dst)?;
-                        for entry in walkdir::WalkDir::new(&src).into_iter().filter_map(|e| e.ok())
-                        {
-                            let p = entry.path();
-                            if p.is_file() {
-                                if let Ok(rel) = p.strip_prefix(&src) {
-                                    let target = dst.join(rel);
-                                    if let Some(parent) = target.parent() {
-                                        fs::create_dir_all(parent)?;
-                                    }
-                                    fs::copy(p, &target)?;
-                                }
-                            }
-                        }
-                        fs::remove_dir_all(&src)
-                    })?;
-                    // update task_file path to archive
-                    if let Some(_tf) = &t.task_file {
-                        t.task_file = Some(format!("archive/{}/{}", id, "task.md"));
-                    }
-                }
-            }
-            // handle reopen: bring archive back to tasks
-            if new_status == "pending_review" {
-                // if currently finished, the task_file should start with archive/
-                if let Some(tf) = &t.task_file {
-                    if tf.starts_with("archive/") {
-                        let src = repo_root.join("plan/archive").join(id);
-                        let dst = repo_root.join("plan/tasks").join(id);
-                        if src.exists() {
-                            fs::create_dir_all(dst.parent().unwrap())?;
-                            fs::rename(&src, &dst).or_else(|_| {
-                                fs::create_dir_all(&dst)?;
-                                for entry in
-                                    walkdir::WalkDir::new(&src).into_iter().filter_map(|e| e.ok())
-                                {
-                                    let p = entry.path();
-                                    if p.is_file() {
-                                        if let Ok(rel) = p.strip_prefix(&src) {
-                                            let target = dst.join(rel);
-                                            if let Some(parent) = target.parent() {
-                                                fs::create_dir_all(parent)?;
-                                            }
-                                            fs::copy(p, &target)?;
-                                        }
-                                    }
-                                }
-                                fs::remove_dir_all(&src)
-                            })?;
-                            // update task_file path to tasks/
-                            if let Some(_tf) = &t.task_file {
-                                t.task_file = Some(format!("tasks/{}/{}", id, "task.md"));
-                            }
-                        }
-                    }
-                }
+    // Optional verification: run the configured steps in the generated project
+    let mut steps = Vec::new();
+    if let Some(verify_opts) = &after.verify {
+        let say = |line: &str| {
+            if text {
+                println!("{}", line);
             }
-            t.status = Some(new_status.to_string());
-            found = true;
-            break;
+        };
+        steps = verify::verify_project(dest, sel.verify.as_ref(), verify_opts, &say)?;
+        let reports = verify::write_reports(dest, &steps)?;
+        if text {
+            let names: Vec<String> = reports.iter().map(|p| p.display().to_string()).collect();
+            println!("Wrote the verification report to {}", names.join(" and "));
         }
+        doc["verification_reports"] = json!(reports);
     }
-    if !found {
-        anyhow::bail!("plan: task '{}' not found", id);
+    // only a project that passed verification becomes a repository
+    if let (true, Some(setup)) = (verify::passed(&steps), &after.git) {
+        doc["git"] = setup_git(dest, setup, gen.renderer.context(), opts.format)?;
     }
-    write_todo(repo_root, &plan)?;
-    Ok(())
+    emit_generate(opts.format, doc, true, &plan, &steps, sel);
+    verify::check(&steps)
 }
 
-fn default_check_review_accept(repo_root: &Path, id: &str) -> Result<()> {
-    // ensure task.md contains some acceptance criteria
-    let task_file = repo_root.join("plan/tasks").join(id).join("task.md");
-    if !task_file.exists() {
-        anyhow::bail!("review check failed: task file not found: {}", task_file.display());
+/// `Template '<name>' matched N files (<folder>):` and one line per selected
+/// (or skipped) file.
+fn matched_lines(tpl: &Template, prepared: &Prepared, sel: &Selection) -> Vec<String> {
+    let count = prepared.rendered.len();
+    let from = match tpl.source {
+        TemplateSource::Embedded => "embedded".to_string(),
+        _ => tpl.root.display().to_string(),
+    };
+    let mut lines = vec![format!("Template '{}' matched {} files ({}):", tpl.name, count, from)];
+    for f in &prepared.files {
+        lines.push(format!(" - {}{}", f.rel.display(), category_suffix(&f.categories)));
+    }
+    for m in &sel.members {
+        for f in &m.files {
+            let rel = m.dest_rel(f);
+            lines.push(format!(
+                " - {}{}",
+                rel.display(),
+                category_suffix(&sel.categories_of(&rel))
+            ));
+        }
     }
-    let content = fs::read_to_string(&task_file).unwrap_or_default();
-    let has_acceptance = content.to_lowercase().contains("acceptance")
-        || content.to_lowercase().contains("acceptance criteria")
-        || content.to_lowercase().contains("tests")
-        || content.len() > 100;
-    if !has_acceptance {
-        anyhow::bail!("review check failed: task appears to be missing acceptance criteria or tests in task.md");
+    for e in &prepared.excluded {
+        lines.push(format!(" - {} (skipped: {})", e.path.display(), e.when));
     }
-    Ok(())
+    for i in &prepared.ignored {
+        lines.push(format!(" - {} (ignored: {})", i.path.display(), i.rule));
+    }
+    lines
 }
 
-fn default_check_start(repo_root: &Path, id: &str) -> Result<()> {
-    // allow start if the task already shows queued status, or if history contains acceptance
-    let plan = read_todo(repo_root)?;
-    for t in plan.task.iter() {
-        if t.id == id {
-            if let Some(st) = &t.status {
-                if st == "queued" {
-                    return Ok(());
-                }
+/// `--output-archive` destination.
+struct ArchiveTarget {
+    /// Archive file; `None` streams to stdout
+    path: Option<PathBuf>,
+    format: ArchiveFormat,
+}
+
+impl ArchiveTarget {
+    fn new(path: PathBuf, format: Option<ArchiveFormat>, output: Format) -> Result<ArchiveTarget> {
+        if path.as_os_str() == "-" {
+            if !output.is_text() {
+                return Err(CosmosError::Usage(
+                    "--output-archive - writes the archive to stdout; it cannot be combined with --format json/ndjson".to_string(),
+                )
+                .into());
             }
-            break;
+            return Ok(ArchiveTarget {
+                path: None,
+                format: format.unwrap_or(ArchiveFormat::TarGz),
+            });
         }
-    }
-    let hist_dir = repo_root.join("plan/tasks").join(id).join("history");
-    if hist_dir.exists() {
-        for e in fs::read_dir(&hist_dir)?.filter_map(|e| e.ok()) {
-            let body = fs::read_to_string(e.path()).unwrap_or_default().to_lowercase();
-            if body.contains("accept") || body.contains("queued") || body.contains("lgtm") {
-                return Ok(());
-            }
+        let format = format.or_else(|| ArchiveFormat::from_path(&path)).ok_or_else(|| {
+            CosmosError::Usage(format!(
+                "cannot tell the archive format of {:?}: use .tar.gz, .tgz, .tar or .zip, or --archive-format",
+                path
+            ))
+        })?;
+        Ok(ArchiveTarget { path: Some(path), format })
+    }
+
+    fn label(&self) -> String {
+        match &self.path {
+            Some(p) => p.display().to_string(),
+            None => "stdout".to_string(),
         }
     }
-    anyhow::bail!("start check failed: no acceptance found in history and task is not queued");
 }
 
-fn default_check_test(repo_root: &Path, id: &str) -> Result<()> {
-    // ensure there is some test plan or presence of tests/ directory
-    let task_file = repo_root.join("plan/tasks").join(id).join("task.md");
-    let content = fs::read_to_string(&task_file).unwrap_or_default();
-    if content.to_lowercase().contains("test") || repo_has_tests(repo_root) {
+/// `generate --output-archive`: the same selection, rendering and strict
+/// check as [`generate_from_template`], packed by [`Generator::archive`]
+/// instead of written. Human output goes to stderr when the archive is
+/// streamed to stdout.
+fn archive_from_template(
+    gen: &Generator,
+    target: &ArchiveTarget,
+    apply: bool,
+    opts: &SyncOpts,
+    after: &AfterApply,
+) -> Result<()> {
+    let (tpl, sel) = (gen.tpl, gen.sel);
+    let text = opts.format.is_text();
+    let say = |line: &str| match (text, &target.path) {
+        (false, _) => {}
+        (true, Some(_)) => println!("{}", line),
+        (true, None) => eprintln!("{}", line),
+    };
+    let prepared = gen.prepare()?;
+    let doc = json!({
+        "mode": "template",
+        "template": tpl.name,
+        "source": tpl.source,
+        "template_path": (tpl.source != TemplateSource::Embedded).then_some(&tpl.root),
+        "archive": target.path,
+        "archive_format": target.format.name(),
+        "variables": gen.renderer.context(),
+        "excluded": prepared.excluded,
+        "ignored": prepared.ignored,
+        "members": sel.members.iter().map(|m| json!({"template": m.template.name, "path": m.path})).collect::<Vec<_>>(),
+    });
+    if prepared.is_empty() {
+        say(&format!("Template '{}' has no files", tpl.name));
+        emit_generate(opts.format, doc, false, &SyncPlan::default(), &[], sel);
         return Ok(());
     }
-    anyhow::bail!("test check failed: no test plan or tests detected for task");
-}
 
-fn default_check_accept(repo_root: &Path, id: &str) -> Result<()> {
-    // check for presence of reports under plan/tasks/<id>/reports or history mentioning 'tests passed' or report
-    let reports_dir = repo_root.join("plan/tasks").join(id).join("reports");
-    if reports_dir.exists() && reports_dir.read_dir()?.next().is_some() {
+    let plan = gen.archive_plan(&prepared)?;
+    for line in matched_lines(tpl, &prepared, sel) {
+        say(&line);
+    }
+    let kept = generate::bookkeeping(&prepared.rendered).len();
+    say(&format!(
+        "Also packs {} and {} snapshot files under {}/ (used by `cosmos update`)",
+        update::LOCK_FILE,
+        kept - 1,
+        update::BASE_DIR
+    ));
+    gen.check_strict(&prepared)?;
+
+    if !apply {
+        say(&format!(
+            "Dry run (no archive written). Use --apply to pack {} files into {}.",
+            plan.missing.len(),
+            target.label()
+        ));
+        emit_generate(opts.format, doc, false, &plan, &[], sel);
         return Ok(());
     }
-    let hist_dir = repo_root.join("plan/tasks").join(id).join("history");
-    if hist_dir.exists() {
-        for e in fs::read_dir(&hist_dir)?.filter_map(|e| e.ok()) {
-            let body = fs::read_to_string(e.path()).unwrap_or_default().to_lowercase();
-            if body.contains("test") || body.contains("report") || body.contains("passed") {
-                return Ok(());
-            }
+
+    let packed = gen.archive(&prepared, target.format, after.verify.as_ref(), &say)?;
+    match &target.path {
+        Some(path) => archive::save(path, &packed.bytes)?,
+        None => {
+            let mut out = stdout().lock();
+            out.write_all(&packed.bytes)?;
+            out.flush()?;
         }
     }
-    anyhow::bail!("accept check failed: no test reports or evidence found for task");
+    say(&format!(
+        "Packed {} files into {} ({})",
+        packed.count,
+        target.label(),
+        target.format.name()
+    ));
+    emit_generate(opts.format, doc, true, &plan, &packed.steps, sel);
+    Ok(())
 }
 
-fn default_check_finish(repo_root: &Path, id: &str) -> Result<()> {
-    // ensure acceptance artifacts exist (reports or explicit acceptance note)
-    let reports_dir = repo_root.join("plan/tasks").join(id).join("reports");
-    if reports_dir.exists() && reports_dir.read_dir()?.next().is_some() {
+fn generate_from_patterns(
+    repo_root: &Path,
+    dest: &Path,
+    apply: bool,
+    opts: &SyncOpts,
+    sel: &Selection,
+    git: Option<&git::Setup>,
+) -> Result<()> {
+    let text = opts.format.is_text();
+    let pats = sel.filter.patterns(&sel.categories);
+    let mut doc = json!({
+        "mode": "patterns",
+        "patterns": pats,
+        "dest": dest,
+    });
+    let mut matches = template::expand_patterns(repo_root, &pats)?;
+    matches.retain(|p| sel.selects(p.strip_prefix(repo_root).unwrap_or(p)));
+    if matches.is_empty() {
+        if text {
+            println!(
+                "No files matched for category '{}', patterns: {:?}",
+                sel.filter.include.join(","),
+                pats
+            );
+        }
+        emit_generate(opts.format, doc, false, &SyncPlan::default(), &[], sel);
         return Ok(());
     }
-    let hist_dir = repo_root.join("plan/tasks").join(id).join("history");
-    if hist_dir.exists() {
-        for e in fs::read_dir(&hist_dir)?.filter_map(|e| e.ok()) {
-            let body = fs::read_to_string(e.path()).unwrap_or_default().to_lowercase();
-            if body.contains("accept")
-                || body.contains("acceptance")
-                || body.contains("acceptance report")
-            {
-                return Ok(());
-            }
+
+    let files = template::raw_outputs(&template::pattern_files(repo_root, &matches))?;
+    let plan = template::plan_sync_rendered(&files, dest, opts.sync.force)?;
+    if text {
+        println!("Matched {} paths", matches.len());
+        for p in &matches {
+            let rel = p.strip_prefix(repo_root).unwrap_or(p);
+            let cats = if p.is_file() { sel.categories_of(rel) } else { Vec::new() };
+            println!(" - {}{}", rel.display(), category_suffix(&cats));
         }
     }
-    anyhow::bail!("finish check failed: no acceptance report or evidence found for task");
-}
+    report_diffs(&files, dest, opts, &mut doc)?;
 
-fn repo_has_tests(repo_root: &Path) -> bool {
-    let tests_dir = repo_root.join("tests");
-    if tests_dir.exists() {
-        return true;
+    if !apply {
+        if text {
+            println!("Dry run (no files written). Use --apply to write files.");
+        }
+        emit_generate(opts.format, doc, false, &plan, &[], sel);
+        return Ok(());
     }
-    false
-}
 
-fn auto_fix_repo(repo_root: &Path) -> Result<Vec<String>> {
-    let mut fixes = Vec::new();
-
-    // 1) Ensure CONTRIBUTING.md exists: copy from docs/contributing.md if present
-    if !repo_root.join("CONTRIBUTING.md").exists() {
-        let candidates = ["docs/contributing.md", "CONTRIBUTING.md", ".github/CONTRIBUTING.md"];
-        for c in candidates {
-            let p = repo_root.join(c);
-            if p.exists() {
-                fs::copy(&p, repo_root.join("CONTRIBUTING.md"))?;
-                fixes.push(format!("Copied {} -> CONTRIBUTING.md", c));
-                break;
-            }
+    if text && dest.exists() && plan.has_changes() {
+        print_sync_plan(
+            &plan,
+            "Detected inconsistencies between template patterns and destination:",
+        );
+    }
+    let mut terminal = Terminal { format: opts.format };
+    let Some((skipped, commit)) =
+        generate::apply_patterns(repo_root, &matches, &plan, dest, &opts.sync, &mut terminal)?
+    else {
+        return Ok(());
+    };
+    if text {
+        for s in &skipped {
+            println!("Skipping existing file: {}", s.display());
         }
+        println!("Files written to {}", dest.display());
+        print_backup(&commit);
+    }
+    doc["backup"] = json!(commit.backup);
+    if let Some(setup) = git {
+        doc["git"] = setup_git(dest, setup, &serde_json::Map::new(), opts.format)?;
     }
+    emit_generate(opts.format, doc, true, &plan, &[], sel);
+    Ok(())
+}
 
-    // 2) Fix plan todo entries
-    let mut plan = read_todo(repo_root)?;
-    let mut changed = false;
-    for t in plan.task.iter_mut() {
-        // normalize status
-        if let Some(st) = &t.status {
-            if st == "open" {
-                t.status = Some("pending_review".to_string());
-                fixes.push(format!("Updated status for {}: open -> pending_review", t.id));
-                changed = true;
-            } else if st == "done" {
-                t.status = Some("finished".to_string());
-                fixes.push(format!("Updated status for {}: done -> finished", t.id));
-                changed = true;
-            }
+/// One line per template: name, source, version, description, folder.
+fn print_templates(templates: &[TemplateInfo], none: &str) {
+    if templates.is_empty() {
+        println!("{}", none);
+    }
+    let width = templates.iter().map(|t| t.name.len()).max().unwrap_or(0);
+    for t in templates {
+        let source = json!(t.source);
+        let mut line = format!(
+            "{:width$}  {:12}  {:8}  {}",
+            t.name,
+            source.as_str().unwrap_or_default(),
+            t.version.as_deref().unwrap_or("-"),
+            t.description.as_deref().unwrap_or(""),
+            width = width
+        );
+        if let Some(p) = &t.path {
+            line.push_str(&format!(" [{}]", p.display()));
         }
-
-        // ensure task_file points to existing file
-        if let Some(tf) = t.task_file.clone() {
-            let p = repo_root.join("plan").join(&tf);
-            if !p.exists() {
-                // try to normalize patterns like tasks/0001-template.md -> tasks/0001/task.md
-                if tf.contains("-template") {
-                    // try to extract id from tf or use t.id
-                    let id = t.id.clone();
-                    let new_tf = format!("tasks/{}/task.md", id);
-                    let newp = repo_root.join("plan").join(&new_tf);
-                    if newp.exists() {
-                        t.task_file = Some(new_tf.clone());
-                        fixes.push(format!("Fixed task_file for {}: {} -> {}", t.id, tf, new_tf));
-                        changed = true;
-                        continue;
-                    }
-                    // try to copy from template if available
-                    let templ = repo_root
-                        .join("templates")
-                        .join("default")
-                        .join("plan")
-                        .join("tasks")
-                        .join(&id)
-                        .join("task.md");
-                    if templ.exists() {
-                        fs::create_dir_all(newp.parent().unwrap())?;
-                        fs::copy(&templ, &newp)?;
-                        t.task_file = Some(new_tf.clone());
-                        fixes.push(format!(
-                            "Copied template task {} to {}",
-                            templ.display(),
-                            newp.display()
-                        ));
-                        changed = true;
-                        continue;
-                    }
-                }
-
-                // try tasks/<id>/task.md
-                let new_tf = format!("tasks/{}/task.md", t.id);
-                let newp = repo_root.join("plan").join(&new_tf);
-                if newp.exists() {
-                    t.task_file = Some(new_tf.clone());
-                    fixes.push(format!("Fixed task_file for {}: {} -> {}", t.id, tf, new_tf));
-                    changed = true;
-                    continue;
-                }
-
-                // as last resort, create placeholder
-                fs::create_dir_all(newp.parent().unwrap())?;
-                fs::write(&newp, format!("title: \"Task {}\"\n\nAutomatically created.", t.id))?;
-                t.task_file = Some(new_tf.clone());
-                fixes.push(format!("Created placeholder task file for {}", t.id));
-                changed = true;
-            }
-        } else {
-            // missing task_file: create one under tasks/{id}/task.md
-            let new_tf = format!("tasks/{}/task.md", t.id);
-            let newp = repo_root.join("plan").join(&new_tf);
-            if !newp.exists() {
-                fs::create_dir_all(newp.parent().unwrap())?;
-                fs::write(&newp, format!("title: \"Task {}\"\n\nAutomatically created.", t.id))?;
-                fixes.push(format!("Created task file {}", new_tf));
-            } else {
-                fixes.push(format!("Found existing task file {}", new_tf));
-            }
-            t.task_file = Some(new_tf);
-            changed = true;
-        }
-
-        // if finished, ensure task_file in archive
-        if let Some(st) = &t.status {
-            if st == "finished" {
-                if let Some(tf) = t.task_file.clone() {
-                    if !tf.starts_with("archive/") {
-                        let src = repo_root.join("plan").join(&tf);
-                        if src.exists() {
-                            let dst_dir = repo_root.join("plan").join("archive").join(&t.id);
-                            fs::create_dir_all(&dst_dir)?;
-                            let dst = dst_dir.join("task.md");
-                            fs::rename(&src, &dst).or_else(|_| {
-                                fs::copy(&src, &dst)?;
-                                fs::remove_file(&src)
-                            })?;
-                            t.task_file = Some(format!("archive/{}/task.md", t.id));
-                            fixes.push(format!("Moved {} to archive/{}", tf, t.id));
-                            changed = true;
-                        }
-                    }
-                }
-            }
+        if !t.active {
+            line.push_str(" (shadowed)");
         }
+        println!("{}", line.trim_end());
     }
-
-    if changed {
-        write_todo(repo_root, &plan)?;
-    }
-
-    Ok(fixes)
 }
 
-fn run_cmd_in_dir(cmd: &str, args: &[&str], dir: &Path) -> Result<(bool, String)> {
-    let output =
-        Command::new(cmd).args(args).current_dir(dir).output().context("running command")?;
-    let success = output.status.success();
-    let mut out = String::new();
-    out.push_str(&String::from_utf8_lossy(&output.stdout));
-    out.push_str(&String::from_utf8_lossy(&output.stderr));
-    Ok((success, out))
-}
-
-fn run_verification(dest: &Path) -> Result<bool> {
-    let mut all_ok = true;
-    println!("Verification summary:");
-
-    // 1) cargo fmt --all -- --check
-    let (ok, out) = run_cmd_in_dir("cargo", &["fmt", "--all", "--", "--check"], dest)?;
-    println!(" - cargo fmt: {}", if ok { "OK" } else { "FAILED" });
-    if !ok {
-        println!("   {}", &out);
-        all_ok = false;
-    }
-
-    // 2) cargo clippy --all-targets --all-features -- -D warnings
-    let (ok, out) = run_cmd_in_dir(
-        "cargo",
-        &["clippy", "--all-targets", "--all-features", "--", "-D", "warnings"],
-        dest,
-    )?;
-    println!(" - cargo clippy: {}", if ok { "OK" } else { "FAILED" });
-    if !ok {
-        println!("   {}", &out);
-        all_ok = false;
-    }
-
-    // 3) cargo test --all --quiet
-    let (ok, out) = run_cmd_in_dir("cargo", &["test", "--all", "--quiet"], dest)?;
-    println!(" - cargo test: {}", if ok { "OK" } else { "FAILED" });
-    if !ok {
-        println!("   {}", &out);
-        all_ok = false;
-    }
-
-    Ok(all_ok)
-}
-
-fn parse_key_val(s: &str) -> Result<(String, String), String> {
-    let mut parts = s.splitn(2, '=');
-    match (parts.next(), parts.next()) {
-        (Some(k), Some(v)) => Ok((k.to_string(), v.to_string())),
-        _ => Err(format!("invalid key=value: '{}'", s)),
+/// `cosmos add`: render component `tpl` into the project at `dest` and
+/// apply its manifest patches (see [`component::plan_add`]; dry-run unless
+/// `apply`).
+fn add_component(
+    tpl: &Template,
+    renderer: &Renderer,
+    manifest: &TemplateManifest,
+    dest: &Path,
+    opts: &SyncOpts,
+    apply: bool,
+) -> Result<()> {
+    let text = opts.format.is_text();
+    let add = component::plan_add(tpl, renderer, manifest, dest, opts.sync.force)?;
+    if text {
+        println!(
+            "Component '{}' for {}:",
+            tpl.name.trim_start_matches("components/"),
+            dest.display()
+        );
+        for a in &add.files {
+            println!(" - {} {}", a.action, a.path.display());
+        }
+        for o in &add.outcomes {
+            let note = if o.changed { "" } else { " (already present)" };
+            println!(" - patch {}: {}{}", o.file.display(), o.description, note);
+        }
     }
-}
-
-fn emit_project_gha_outputs(repo_root: &Path) -> Result<()> {
-    use std::io::Write;
-
-    let path = repo_root.join("project.toml");
-    if !path.exists() {
-        anyhow::bail!("project.toml not found at {}", path.display());
-    }
-
-    let s = fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
-    let data: toml::Value = toml::from_str(&s).context("parsing project.toml")?;
-
-    let project = data.get("project").and_then(|v| v.as_table());
-    let ci = data.get("ci").and_then(|v| v.as_table());
-    let artifact = data.get("artifact").and_then(|v| v.as_table());
-    let docker = data.get("docker").and_then(|v| v.as_table());
-
-    let project_type = project
-        .and_then(|t| t.get("type"))
-        .and_then(|v| v.as_str())
-        .unwrap_or("library")
-        .to_string();
-
-    let run_build = ci.and_then(|t| t.get("run_build")).and_then(|v| v.as_bool()).unwrap_or(true);
-    let run_tests = ci.and_then(|t| t.get("run_tests")).and_then(|v| v.as_bool()).unwrap_or(true);
-    let run_security =
-        ci.and_then(|t| t.get("run_security")).and_then(|v| v.as_bool()).unwrap_or(true);
-    let run_docs = ci.and_then(|t| t.get("run_docs")).and_then(|v| v.as_bool()).unwrap_or(true);
-
-    let quick_gate_precommit = ci
-        .and_then(|t| t.get("quick_gate"))
-        .and_then(|v| v.as_array())
-        .map(|arr| arr.iter().any(|x| x.as_str() == Some("pre-commit")))
-        .unwrap_or(false);
-
-    let outputs = artifact
-        .and_then(|t| t.get("outputs"))
-        .and_then(|v| v.as_array())
-        .map(|arr| arr.iter().filter_map(|x| x.as_str().map(|s| s.to_string())).collect::<Vec<_>>())
-        .unwrap_or_default();
-
-    let outputs_list = outputs.join(",");
-    let outputs_contains_docker = outputs.iter().any(|s| s == "docker");
-
-    let docker_enabled =
-        docker.and_then(|t| t.get("enabled")).and_then(|v| v.as_bool()).unwrap_or(false);
-    let docker_image =
-        docker.and_then(|t| t.get("image")).and_then(|v| v.as_str()).unwrap_or("").to_string();
-
-    let project_name =
-        project.and_then(|t| t.get("name")).and_then(|v| v.as_str()).unwrap_or("").to_string();
-    let project_version =
-        project.and_then(|t| t.get("version")).and_then(|v| v.as_str()).unwrap_or("").to_string();
-
-    let b = |v: bool| if v { "true" } else { "false" };
-    let outputs_kv: [(String, String); 12] = [
-        ("project_type".to_string(), project_type),
-        ("run_build".to_string(), b(run_build).to_string()),
-        ("run_tests".to_string(), b(run_tests).to_string()),
-        ("run_security".to_string(), b(run_security).to_string()),
-        ("run_docs".to_string(), b(run_docs).to_string()),
-        ("quick_gate_precommit".to_string(), b(quick_gate_precommit).to_string()),
-        ("outputs_list".to_string(), outputs_list),
-        ("outputs_contains_docker".to_string(), b(outputs_contains_docker).to_string()),
-        ("docker_enabled".to_string(), b(docker_enabled).to_string()),
-        ("docker_image".to_string(), docker_image),
-        ("project_name".to_string(), project_name),
-        ("project_version".to_string(), project_version),
-    ];
-
-    if let Ok(gh_out) = std::env::var("GITHUB_OUTPUT") {
-        let mut f = fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&gh_out)
-            .with_context(|| format!("opening {}", gh_out))?;
-        for (k, v) in outputs_kv {
-            writeln!(f, "{}={}", k, v).context("writing GITHUB_OUTPUT")?;
+    let mut doc = json!({
+        "component": tpl.name.trim_start_matches("components/"),
+        "dest": dest,
+        "variables": renderer.context(),
+        "files": add.files,
+        "patches": add.outcomes,
+    });
+    let diff_opts = SyncOpts { diff_stat: false, ..*opts };
+    report_diffs(&add.changes, dest, &diff_opts, &mut doc)?;
+
+    if add.changes.is_empty() {
+        if text {
+            println!("Nothing to add: the component is already present.");
         }
-    } else {
-        for (k, v) in outputs_kv {
-            println!("{}={}", k, v);
+        doc["applied"] = json!(apply);
+        emit_doc(opts.format, doc);
+        return Ok(());
+    }
+    if !apply {
+        if text {
+            println!("Dry run (no files written). Use --apply to add the component.");
         }
+        doc["applied"] = json!(false);
+        emit_doc(opts.format, doc);
+        return Ok(());
     }
-
+    let commit = add.apply(tpl, renderer, dest)?;
+    if text {
+        println!("Component added to {}", dest.display());
+        print_backup(&commit);
+    }
+    doc["applied"] = json!(true);
+    doc["backup"] = json!(commit.backup);
+    emit_doc(opts.format, doc);
     Ok(())
 }
 
-#[derive(Debug, Default)]
-struct ProjectValidationReport {
-    errors: Vec<String>,
-    warnings: Vec<String>,
+/// Locate a template by name (or folder), failing when it does not exist.
+fn require_template(repo_root: &Path, name: &str, template_dir: Option<&Path>) -> Result<Template> {
+    Template::locate(repo_root, name, template_dir, &template::template_search_path())?.ok_or_else(
+        || {
+            CosmosError::NotFound(format!(
+                "template '{}' not found (see `cosmos template list`)",
+                name
+            ))
+            .into()
+        },
+    )
 }
 
-impl ProjectValidationReport {
-    fn has_blocking_issues(&self, strict: bool) -> bool {
-        !self.errors.is_empty() || (strict && !self.warnings.is_empty())
+/// Print sorted relative paths as an indented tree.
+fn print_tree(paths: &[PathBuf]) {
+    let mut printed: HashSet<PathBuf> = HashSet::new();
+    for p in paths {
+        let mut dir = PathBuf::new();
+        let parts: Vec<_> = p.components().collect();
+        for (depth, c) in parts.iter().enumerate() {
+            dir.push(c);
+            if printed.insert(dir.clone()) {
+                let is_dir = depth + 1 < parts.len();
+                let name = c.as_os_str().to_string_lossy();
+                println!("  {}{}{}", "  ".repeat(depth), name, if is_dir { "/" } else { "" });
+            }
+        }
     }
 }
 
-fn is_template_placeholder(s: &str) -> bool {
-    // Heuristic: treat handlebars-style placeholders as template values.
-    // This repo is itself a template, so we must not fail drift checks on placeholders.
-    s.contains("{{") && s.contains("}}")
-}
-
-fn is_concrete_value(s: &str) -> bool {
-    let trimmed = s.trim();
-    !trimmed.is_empty() && !is_template_placeholder(trimmed)
-}
-
-fn read_toml_value(path: &Path) -> Result<toml::Value> {
-    let s = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
-    toml::from_str(&s).with_context(|| format!("parsing {}", path.display()))
-}
-
-fn collect_project_validation_issues(
-    repo_root: &Path,
-    project_toml: &toml::Value,
-    cargo_toml: &toml::Value,
-) -> ProjectValidationReport {
-    let mut report = ProjectValidationReport::default();
-
-    let cosmos = project_toml.get("cosmos").and_then(|v| v.as_table());
-    let schema_version = cosmos.and_then(|t| t.get("schema_version")).and_then(|v| v.as_integer());
-    if schema_version != Some(1) {
-        report.errors.push("project.toml: expected [cosmos].schema_version = 1".to_string());
-    }
-
-    let project = project_toml.get("project").and_then(|v| v.as_table());
-    let build = project_toml.get("build").and_then(|v| v.as_table());
-    let artifact = project_toml.get("artifact").and_then(|v| v.as_table());
-    let docker = project_toml.get("docker").and_then(|v| v.as_table());
-
-    let project_name = project.and_then(|t| t.get("name")).and_then(|v| v.as_str()).unwrap_or("");
-    let project_version =
-        project.and_then(|t| t.get("version")).and_then(|v| v.as_str()).unwrap_or("");
-
-    let cargo_pkg = cargo_toml.get("package").and_then(|v| v.as_table());
-    let cargo_name = cargo_pkg.and_then(|t| t.get("name")).and_then(|v| v.as_str()).unwrap_or("");
-    let cargo_version =
-        cargo_pkg.and_then(|t| t.get("version")).and_then(|v| v.as_str()).unwrap_or("");
-
-    // Drift checks: enable only when the manifest looks like a concrete repo (not a template).
-    // We key off project.name because template repos commonly use handlebars placeholders there.
-    let drift_checks_enabled = is_concrete_value(project_name);
-
-    if drift_checks_enabled && !cargo_name.is_empty() && project_name != cargo_name {
-        report.errors.push(format!(
-            "project.toml: [project].name '{}' does not match Cargo.toml [package].name '{}'",
-            project_name, cargo_name
-        ));
-    }
-    if drift_checks_enabled
-        && is_concrete_value(project_version)
-        && !cargo_version.is_empty()
-        && project_version != cargo_version
-    {
-        report.errors.push(format!(
-            "project.toml: [project].version '{}' does not match Cargo.toml [package].version '{}'",
-            project_version, cargo_version
-        ));
-    }
-
-    let outputs: Vec<String> = artifact
-        .and_then(|t| t.get("outputs"))
-        .and_then(|v| v.as_array())
-        .map(|arr| arr.iter().filter_map(|x| x.as_str().map(|s| s.to_string())).collect::<Vec<_>>())
-        .unwrap_or_default();
-
-    let allowed_outputs = ["docker", "binary", "crate", "wasm", "deb", "rpm"];
-    for o in &outputs {
-        if !allowed_outputs.contains(&o.as_str()) {
-            report
-                .errors
-                .push(format!("project.toml: [artifact].outputs contains unknown value '{}'", o));
+fn run_template_cmd(sub: TemplateCmd, repo_root: &Path, format: Format) -> Result<()> {
+    let text = format.is_text();
+    match sub {
+        TemplateCmd::List {} => {
+            let templates = template::list_templates(repo_root, &template::template_search_path())?;
+            if text {
+                print_templates(&templates, "No templates found");
+            }
+            let records: Vec<Value> = templates.iter().map(|t| json!(t)).collect();
+            emit(format, &json!({ "templates": templates }), &records);
         }
-    }
 
-    let outputs_contains_docker = outputs.iter().any(|s| s == "docker");
-    let outputs_contains_binary = outputs.iter().any(|s| s == "binary");
-
-    let docker_enabled =
-        docker.and_then(|t| t.get("enabled")).and_then(|v| v.as_bool()).unwrap_or(false);
-    let docker_image = docker.and_then(|t| t.get("image")).and_then(|v| v.as_str()).unwrap_or("");
+        TemplateCmd::Show { name, template_dir } => {
+            let tpl = require_template(repo_root, &name, template_dir.as_deref())?;
+            let manifest = tpl.manifest()?.unwrap_or_default();
+            let mut files = tpl.files();
+            files.sort_by(|a, b| a.rel.cmp(&b.rel));
+            let partials: Vec<String> = tpl.partial_files().into_iter().map(|(n, _)| n).collect();
+            let mut chain = Vec::new();
+            let mut parent = tpl.parent.as_deref();
+            while let Some(p) = parent {
+                chain.push(p.name.clone());
+                parent = p.parent.as_deref();
+            }
+            let mut categories: Vec<_> = manifest.categories.iter().collect();
+            categories.sort();
+            if text {
+                println!(
+                    "Template '{}' ({})",
+                    tpl.name,
+                    json!(tpl.source).as_str().unwrap_or_default()
+                );
+                if tpl.source != TemplateSource::Embedded {
+                    println!("  path: {}", tpl.root.display());
+                }
+                if let Some(v) = &manifest.version {
+                    println!("  version: {}", v);
+                }
+                if let Some(d) = &manifest.description {
+                    println!("  description: {}", d);
+                }
+                if !chain.is_empty() {
+                    println!("  extends: {}", chain.join(" -> "));
+                }
+                println!("Categories:");
+                for (cat, globs) in &categories {
+                    println!("  {}: {}", cat, globs.join(", "));
+                }
+                println!("Variables:");
+                if manifest.variables.is_empty() {
+                    println!("  (none)");
+                }
+                for (var, spec) in &manifest.variables {
+                    let kind = json!(spec.kind);
+                    let mut line = format!("  {} ({})", var, kind.as_str().unwrap_or_default());
+                    match &spec.default {
+                        Some(d) => line.push_str(&format!(" = {}", d)),
+                        None => line.push_str(" [required]"),
+                    }
+                    if !spec.choices.is_empty() {
+                        line.push_str(&format!(" one of {}", spec.choices.join("/")));
+                    }
+                    if let Some(d) = &spec.description {
+                        line.push_str(&format!(" - {}", d));
+                    }
+                    println!("{}", line);
+                }
+                if !partials.is_empty() {
+                    println!("Partials: {}", partials.join(", "));
+                }
+                println!("Files ({}):", files.len());
+                print_tree(&files.iter().map(|f| f.rel.clone()).collect::<Vec<_>>());
+            }
+            let file_docs: Vec<Value> = files
+                .iter()
+                .map(|f| {
+                    json!({
+                        "path": f.rel,
+                        "categories": template::file_categories(&manifest.categories, &f.rel),
+                    })
+                })
+                .collect();
+            let doc = json!({
+                "name": tpl.name,
+                "source": tpl.source,
+                "path": (tpl.source != TemplateSource::Embedded).then_some(&tpl.root),
+                "version": manifest.version,
+                "description": manifest.description,
+                "extends": chain,
+                "categories": manifest.categories,
+                "variables": manifest.variables,
+                "raw": manifest.raw,
+                "conditional": manifest.conditional,
+                "partials": partials,
+                "files": file_docs,
+            });
+            emit_doc(format, doc);
+        }
 
-    if docker_enabled {
-        if !outputs_contains_docker {
-            report.errors.push(
-                "project.toml: [docker].enabled=true requires [artifact].outputs to include 'docker'"
-                    .to_string(),
+        TemplateCmd::Export { name, dir, force } => {
+            let tpl = require_template(repo_root, &name, None)?;
+            let non_empty = dir.read_dir().map(|mut d| d.next().is_some()).unwrap_or(false);
+            if non_empty && !force {
+                return Err(CosmosError::AlreadyExists(format!(
+                    "{} is not empty (pass --force to export into it)",
+                    dir.display()
+                ))
+                .into());
+            }
+            let copied = tpl.export(&dir)?;
+            if text {
+                println!(
+                    "Exported template '{}' ({}) to {} ({} files)",
+                    tpl.name,
+                    json!(tpl.source).as_str().unwrap_or_default(),
+                    dir.display(),
+                    copied.len()
+                );
+                if let Some(p) = &tpl.parent {
+                    println!("Note: inherited files stay with the parent template '{}'", p.name);
+                }
+                println!("Use it with: cosmos generate --template-dir {}", dir.display());
+            }
+            emit_doc(
+                format,
+                json!({"template": tpl.name, "source": tpl.source, "dir": dir, "files": copied}),
             );
         }
-        if docker_image.trim().is_empty() {
-            report.errors.push(
-                "project.toml: [docker].image must be non-empty when docker is enabled".to_string(),
-            );
-        } else if drift_checks_enabled && is_template_placeholder(docker_image) {
-            report.warnings.push(
-                "project.toml: [docker].image appears to be a template placeholder".to_string(),
-            );
+
+        TemplateCmd::Check { name, template_dir, vars } => {
+            let tpl = require_template(repo_root, &name, template_dir.as_deref())?;
+            let (mut sel, variables) = Selection::new(
+                tpl.manifest()?,
+                CategoryFilter::new(vec!["all".into()], Vec::new()),
+            )?;
+            let mut ctx = template::build_context(None, vars);
+            template::resolve_variables(
+                &variables,
+                &mut ctx,
+                Some(&mut |p: &VariablePrompt| Ok(template::sample_value(p))),
+            )?;
+            let renderer = Renderer::new(ctx).partials(&tpl.partials()?)?.strict(true);
+            sel.resolve_members(&tpl, &renderer, repo_root)?;
+            let (files, _) = Generator::new(&tpl, &renderer, &sel).select()?;
+            let mut issues = template::check_render(&files, &renderer)?;
+            for m in &sel.members {
+                issues.extend(template::check_render(&m.files, &m.renderer)?);
+            }
+            if issues.is_empty() {
+                let td = tempfile::tempdir().context("creating temp dir for template check")?;
+                template::write_files(&tpl, &files, &renderer, td.path(), true)?;
+                for m in &sel.members {
+                    let dir = td.path().join(&m.path);
+                    template::write_files(&m.template, &m.files, &m.renderer, &dir, true)?;
+                }
+                issues = template::check_output(td.path())?;
+            }
+            let file_count = files.len() + sel.members.iter().map(|m| m.files.len()).sum::<usize>();
+            if text {
+                println!("Checked template '{}' ({} files) with:", tpl.name, file_count);
+                for (k, v) in renderer.context() {
+                    println!("  {} = {}", k, v);
+                }
+                if issues.is_empty() {
+                    println!("Template check passed");
+                } else {
+                    println!("Problems:");
+                    for i in &issues {
+                        println!(" - {}", i);
+                    }
+                }
+            }
+            let records: Vec<Value> = issues.iter().map(|i| json!(i)).collect();
+            let doc = json!({
+                "template": tpl.name,
+                "variables": renderer.context(),
+                "files": file_count,
+                "issues": issues,
+            });
+            emit(format, &doc, &records);
+            if !issues.is_empty() {
+                return Err(CosmosError::Validation(format!(
+                    "template '{}' has {} problem(s)",
+                    tpl.name,
+                    issues.len()
+                ))
+                .into());
+            }
         }
-    } else if outputs_contains_docker {
-        report.warnings.push(
-            "project.toml: [artifact].outputs contains 'docker' but [docker].enabled is false"
-                .to_string(),
-        );
     }
+    Ok(())
+}
 
-    if outputs_contains_binary {
-        let build_bins: Vec<String> = build
-            .and_then(|t| t.get("bins"))
-            .and_then(|v| v.as_array())
-            .map(|arr| {
-                arr.iter().filter_map(|x| x.as_str().map(|s| s.to_string())).collect::<Vec<_>>()
-            })
-            .unwrap_or_default();
-
-        let cargo_bins_defined = cargo_toml
-            .get("bin")
-            .and_then(|v| v.as_array())
-            .map(|a| !a.is_empty())
-            .unwrap_or(false);
-        let default_main_exists = repo_root.join("src/main.rs").exists();
-
-        if build_bins.is_empty() && !cargo_bins_defined && !default_main_exists {
-            report.errors.push(
-                "project.toml: [artifact].outputs includes 'binary' but no binaries were found (set [build].bins, define [[bin]] in Cargo.toml, or provide src/main.rs)".to_string(),
-            );
+/// Run a plan transition; warnings go to stderr in text mode and into the
+/// document otherwise.
+fn run_transition(
+    plan: &mut Plan,
+    id: &str,
+    to: Status,
+    opts: &TransitionOptions,
+    format: Format,
+) -> Result<()> {
+    let outcome = plan.transition_with(id, to, opts)?;
+    if format.is_text() {
+        for w in &outcome.warnings {
+            eprintln!("{}", w);
         }
     }
-
-    report
+    emit_doc(
+        format,
+        json!({
+            "task": id,
+            "status": to,
+            "ai_report": outcome.ai_report,
+            "warnings": outcome.warnings,
+        }),
+    );
+    Ok(())
 }
 
-fn validate_project_manifest(repo_root: &Path, strict: bool) -> Result<ProjectValidationReport> {
-    let project_path = repo_root.join("project.toml");
-    let cargo_path = repo_root.join("Cargo.toml");
-
-    if !project_path.exists() {
-        anyhow::bail!("project.toml not found at {}", project_path.display());
-    }
-    if !cargo_path.exists() {
-        anyhow::bail!("Cargo.toml not found at {}", cargo_path.display());
+fn print_issues(title: &str, issues: &[Issue]) {
+    if !issues.is_empty() {
+        println!("\n{}:", title);
+        for i in issues {
+            println!(" - {}", i);
+        }
     }
+}
 
-    let project_toml = read_toml_value(&project_path)?;
-    let cargo_toml = read_toml_value(&cargo_path)?;
+/// Report a failure and map it to its stable exit code (see
+/// `rust_repo_template::error`): `error[<kind>]: <message>` plus hint in text
+/// mode, a JSON object on stderr otherwise.
+fn report_error(e: &anyhow::Error, format: Format) -> ExitCode {
+    let kind = error::error_kind(e);
+    let hint = error::error_hint(e);
+    if format.is_text() {
+        eprintln!("error[{}]: {:#}", kind, e);
+        if let Some(hint) = hint {
+            eprintln!("{}", hint);
+        }
+    } else {
+        let doc = json!({
+            "error": {
+                "kind": kind,
+                "exit_code": kind.exit_code(),
+                "message": format!("{:#}", e),
+                "hint": hint,
+            }
+        });
+        eprintln!("{}", doc);
+    }
+    ExitCode::from(kind.exit_code())
+}
 
-    let report = collect_project_validation_issues(repo_root, &project_toml, &cargo_toml);
-    if report.has_blocking_issues(strict) {
-        // return report to caller; command decides exit code
+fn main() -> ExitCode {
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(e) if e.use_stderr() => {
+            let _ = e.print();
+            return ExitCode::from(ErrorKind::Usage.exit_code());
+        }
+        Err(e) => e.exit(),
+    };
+    let format = cli.format;
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => report_error(&e, format),
     }
-    Ok(report)
 }
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
-    // Assume repo root is current dir
-    let repo_root = std::env::current_dir().context("current dir")?;
+fn run(cli: Cli) -> Result<()> {
+    let format = cli.format;
+    let text = format.is_text();
+    let cwd = std::env::current_dir().context("current dir")?;
+    let repo_root = repo::resolve_root(cli.repo.as_deref(), &cwd)?;
 
     match cli.command {
         Commands::Generate {
             category,
+            exclude_category,
             out_dir,
             apply,
             yes,
             allow_delete,
             force,
             template,
-            template_dir: _template_dir,
+            template_dir,
             project_name,
             vars,
+            vars_file,
             verify,
+            offline,
+            strict,
+            diff,
+            diff_stat,
+            interactive,
+            rollback,
+            output_archive,
+            archive_format,
+            git_init,
+            git_branch,
+            git_commit,
+            allow_dirty,
         } => {
-            let manifest = load_manifest(&repo_root, &template)?;
-            let categories = if let Some(m) = manifest {
-                m.categories
-            } else {
-                // fallback to reasonable defaults for this repo template
-                let mut map = HashMap::new();
-                map.insert(
-                    "basis".to_string(),
-                    vec![
-                        "Cargo.toml".into(),
-                        "README.md".into(),
-                        "LICENSE".into(),
-                        "CONTRIBUTING.md".into(),
-                    ],
-                );
-                map.insert("docs".to_string(), vec!["docs/**".into()]);
-                map.insert("ci".to_string(), vec![".github/**".into()]);
-                map.insert("tests".to_string(), vec!["tests/**".into()]);
-                map.insert("examples".to_string(), vec!["examples/**".into()]);
-                map.insert("scripts".to_string(), vec!["scripts/**".into()]);
-                map.insert("plan".to_string(), vec!["plan/**".into()]);
-                map
-            };
-
-            let mut pats = Vec::new();
-            if category == "all" {
-                for v in categories.values() {
-                    pats.extend(v.clone());
-                }
-            } else if let Some(v) = categories.get(&category) {
-                pats.extend(v.clone());
-            } else {
-                eprintln!("Unknown category '{}'", category);
-                std::process::exit(2);
+            if let Some(id) = rollback {
+                return rollback_generate(&out_dir, &id, format);
             }
-
-            // If there is a folder templates/<name>/, use it as a template source
-            // Resolve template source: prefer repo templates/, then embedded templates, then exe-relative templates/
-            let mut _maybe_tempdir: Option<TempDir> = None;
-            let template_dir = repo_root.join("templates").join(&template);
-            let template_source = if template_dir.exists() && template_dir.is_dir() {
-                template_dir
-            } else {
-                static EMBEDDED_TEMPLATES: Dir = include_dir!("templates");
-                if let Some(d) = EMBEDDED_TEMPLATES.get_dir(&template) {
-                    // extract embedded template into tempdir
-                    let td =
-                        tempfile::tempdir().context("creating temp dir for embedded template")?;
-                    let td_path = td.path().to_path_buf();
-                    fn write_dir(
-                        d: &Dir,
-                        base: &std::path::Path,
-                        template: &str,
-                    ) -> anyhow::Result<()> {
-                        for file in d.files() {
-                            let rel = file.path();
-                            // remove a leading template dir segment if present (e.g., 'default/...')
-                            let rel = if rel
-                                .components()
-                                .next()
-                                .map(|c| c.as_os_str() == std::ffi::OsStr::new(template))
-                                .unwrap_or(false)
-                            {
-                                rel.strip_prefix(template).unwrap()
-                            } else {
-                                rel
-                            };
-                            let dest = base.join(rel);
-                            if let Some(parent) = dest.parent() {
-                                std::fs::create_dir_all(parent)?;
-                            }
-                            std::fs::write(&dest, file.contents())?;
-                        }
-                        for sd in d.dirs() {
-                            write_dir(sd, base, template)?;
-                        }
-                        Ok(())
-                    }
-                    write_dir(d, &td_path, &template)
-                        .context("extracting embedded template files")?;
-                    _maybe_tempdir = Some(td);
-                    td_path
-                } else {
-                    // try executable-relative templates/ (useful for installed binaries)
-                    match std::env::current_exe() {
-                        Ok(exe) => {
-                            if let Some(parent) = exe.parent() {
-                                let exe_t = parent.join("templates").join(&template);
-                                if exe_t.exists() && exe_t.is_dir() {
-                                    exe_t
-                                } else {
-                                    template_dir
-                                }
-                            } else {
-                                template_dir
-                            }
-                        }
-                        Err(_) => template_dir,
-                    }
-                }
+            if apply && output_archive.is_none() && !allow_dirty {
+                git::ensure_clean(&out_dir)?;
+            }
+            let archive = output_archive
+                .map(|path| ArchiveTarget::new(path, archive_format, format))
+                .transpose()?;
+            let tpl = Template::locate(
+                &repo_root,
+                &template,
+                template_dir.as_deref(),
+                &template::template_search_path(),
+            )?;
+            let manifest = match &tpl {
+                Some(t) => t.manifest()?,
+                None => template::load_manifest(&repo_root, &template)?,
             };
-            if template_source.exists() && template_source.is_dir() {
-                // collect files inside template_source
-                let mut src_files = Vec::new();
-                for entry in
-                    walkdir::WalkDir::new(&template_source).into_iter().filter_map(|e| e.ok())
-                {
-                    let p = entry.path().to_path_buf();
-                    if p.is_file() {
-                        // compute relative path inside template
-                        let rel = p.strip_prefix(&template_source).unwrap().to_path_buf();
-                        src_files.push((p, rel));
-                    }
-                }
-                // Normalize rels to drop an accidental leading template component (e.g., 'default/...')
-                for item in src_files.iter_mut() {
-                    let rel = &mut item.1;
-                    let s = rel.to_string_lossy();
-                    if s.starts_with(&format!("{}/", template)) {
-                        *rel = PathBuf::from(&s[template.len() + 1..]);
-                    }
-                }
-                if src_files.is_empty() {
-                    println!("Template '{}' has no files", template);
-                    return Ok(());
-                }
-
-                // Build template context
-                let mut hb_ctx = serde_json::Map::new();
-                if let Some(n) = project_name {
-                    hb_ctx.insert("project-name".to_string(), serde_json::Value::String(n));
-                }
-                for (k, v) in vars {
-                    hb_ctx.insert(k, serde_json::Value::String(v));
-                }
-                let hb = handlebars::Handlebars::new();
-
-                println!("Template '{}' matched {} files:", template, src_files.len());
-                for (_, rel) in &src_files {
-                    let dr = rel.to_string_lossy();
-                    let display_rel: &std::path::Path = if dr.starts_with(&format!("{}/", template))
-                    {
-                        // strip leading template dir if present
-                        let s = &dr[template.len() + 1..];
-                        std::path::Path::new(s)
-                    } else {
-                        rel.as_path()
+            let (mut sel, variables) =
+                Selection::new(manifest, CategoryFilter::new(category, exclude_category))?;
+            let sync = SyncOptions { yes, allow_delete, force, interactive };
+            let opts = SyncOpts { sync, format, diff, diff_stat };
+            let git = (git_init || git_commit.is_some())
+                .then_some(git::Setup { branch: git_branch, commit: git_commit });
+            let verify = verify.then_some(VerifyOptions { offline, ..VerifyOptions::default() });
+
+            // If there is a template folder (repo, embedded or exe-relative), render it;
+            // otherwise fall back to pattern-based copy using manifest/categories
+            match tpl {
+                Some(tpl) => {
+                    // an archive starts from scratch: no existing project to read
+                    let mut ctx = match archive {
+                        Some(_) => serde_json::Map::new(),
+                        None => vars::from_project(&out_dir),
                     };
-                    println!(" - {}", display_rel.display());
-                }
-
-                if apply {
-                    let dest = out_dir.clone();
-
-                    // Build desired rel paths from template
-                    let mut desired: Vec<PathBuf> = Vec::new();
-                    for (_s, rel) in &src_files {
-                        desired.push(rel.clone());
-                    }
-
-                    // If destination exists, compute diffs and prompt for fixes
-                    if dest.exists() {
-                        // gather current files under dest
-                        let mut current: Vec<PathBuf> = Vec::new();
-                        for entry in walkdir::WalkDir::new(&dest).into_iter().filter_map(|e| e.ok())
-                        {
-                            let p = entry.path();
-                            if p.is_file() {
-                                if let Ok(rel) = p.strip_prefix(&dest) {
-                                    current.push(rel.to_path_buf());
-                                }
-                            }
-                        }
-
-                        use std::collections::HashSet;
-                        let desired_set: HashSet<_> = desired.iter().cloned().collect();
-                        let current_set: HashSet<_> = current.iter().cloned().collect();
-
-                        let mut missing: Vec<PathBuf> =
-                            desired_set.difference(&current_set).cloned().collect();
-                        missing.sort();
-                        let mut extra: Vec<PathBuf> =
-                            current_set.difference(&desired_set).cloned().collect();
-                        extra.sort();
-
-                        // modified: intersection where contents differ
-                        let mut modified: Vec<PathBuf> = Vec::new();
-                        let mut skipped_overwrite: Vec<PathBuf> = Vec::new();
-                        for common in desired_set.intersection(&current_set) {
-                            // find source path for this rel
-                            let src_path = src_files
-                                .iter()
-                                .find(|(_s, r)| r == common)
-                                .map(|(s, _)| s.clone());
-                            if let Some(s_path) = src_path {
-                                let dest_path = dest.join(common);
-                                if let (Ok(sb), Ok(db)) = (fs::read(&s_path), fs::read(&dest_path))
-                                {
-                                    if sb != db {
-                                        if !force {
-                                            skipped_overwrite.push(common.clone());
-                                        } else {
-                                            modified.push(common.clone());
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        modified.sort();
-                        skipped_overwrite.sort();
-
-                        if !missing.is_empty() || !extra.is_empty() || !modified.is_empty() {
-                            println!("Detected inconsistencies between template and destination:");
-                            if !missing.is_empty() {
-                                println!("  Missing files to add ({}):", missing.len());
-                                for m in &missing {
-                                    println!("   + {}", m.display());
-                                }
-                            }
-                            if !modified.is_empty() {
-                                println!("  Files that will be overwritten ({}):", modified.len());
-                                for m in &modified {
-                                    println!("   ~ {}", m.display());
-                                }
-                            }
-                            if !skipped_overwrite.is_empty() {
-                                println!("  Skipped overwriting existing files (use --force to override): {}", skipped_overwrite.len());
-                                for s in &skipped_overwrite {
-                                    println!("    (skipped) {}", s.display());
-                                }
-                            }
-
-                            // Helper: protect common VCS or third-party directories from deletion
-                            fn is_protected_path(p: &Path) -> bool {
-                                // Protect common VCS and lockfiles
-                                if let Some(name) = p.file_name().and_then(|s| s.to_str()) {
-                                    if name == "Cargo.lock" {
-                                        return true;
-                                    }
-                                }
-                                if let Some(s) = p.to_str() {
-                                    let s = s.replace('\\', "/");
-                                    return s == ".git"
-                                        || s.starts_with(".git/")
-                                        || s == "node_modules"
-                                        || s.starts_with("node_modules/")
-                                        || s == "target"
-                                        || s.starts_with("target/")
-                                        || s == ".venv"
-                                        || s.starts_with(".venv/");
-                                }
-                                false
-                            }
-
-                            // split extras into visible ones (we may delete) and protected ones (skip)
-                            let mut extra_protected: Vec<PathBuf> =
-                                extra.iter().filter(|p| is_protected_path(p)).cloned().collect();
-                            extra_protected.sort();
-                            let mut extra_visible: Vec<PathBuf> =
-                                extra.iter().filter(|p| !is_protected_path(p)).cloned().collect();
-                            extra_visible.sort();
-
-                            if !extra_visible.is_empty() {
-                                println!("  Extra files to delete ({}):", extra_visible.len());
-                                for e in &extra_visible {
-                                    println!("   - {}", e.display());
-                                }
-                            }
-                            if !extra_protected.is_empty() {
-                                println!("  Skipped {} protected files/dirs (e.g. .git, node_modules, target):", extra_protected.len());
-                                for p in &extra_protected {
-                                    println!("    (protected) {}", p.display());
-                                }
-                            }
-
-                            // Auto-apply if --yes set; otherwise prompt
-                            if yes {
-                                if !extra_visible.is_empty() && !allow_delete {
-                                    eprintln!("Detected deletions but --allow-delete not set; aborting. Use --allow-delete with --yes to permit deletions (protected paths will still be skipped).");
-                                    std::process::exit(2);
-                                }
-                                if !extra_visible.is_empty() {
-                                    for e in &extra_visible {
-                                        let p = dest.join(e);
-                                        if p.exists() {
-                                            fs::remove_file(&p).with_context(|| {
-                                                format!("deleting extra file {:?}", p)
-                                            })?;
-                                        }
-                                    }
-                                }
-                            } else {
-                                use std::io::{stdin, stdout, Write};
-                                print!("Apply these fixes? [y/N]: ");
-                                stdout().flush().ok();
-                                let mut input = String::new();
-                                let _ = stdin().read_line(&mut input);
-                                if !matches!(input.trim().to_lowercase().as_str(), "y" | "yes") {
-                                    println!("Aborted by user; no changes applied.");
-                                    std::process::exit(0);
-                                }
-
-                                if !extra_visible.is_empty() {
-                                    print!("This operation will DELETE {} files. Type DELETE to confirm deletions: ", extra_visible.len());
-                                    stdout().flush().ok();
-                                    let mut dconfirm = String::new();
-                                    let _ = stdin().read_line(&mut dconfirm);
-                                    if dconfirm.trim() != "DELETE" {
-                                        println!("Deletion confirmation failed; aborting without changes.");
-                                        std::process::exit(0);
-                                    }
-
-                                    // perform deletions of visible/non-protected extras
-                                    for e in &extra_visible {
-                                        let p = dest.join(e);
-                                        if p.exists() {
-                                            fs::remove_file(&p).with_context(|| {
-                                                format!("deleting extra file {:?}", p)
-                                            })?;
-                                        }
-                                    }
-                                } else {
-                                    println!("No deletions to perform (all extra items are protected). Continuing.");
-                                }
-                            }
-                            // continue to write/overwrite missing and modified files
-                        }
+                    for file in &vars_file {
+                        ctx.extend(vars::from_file(file)?);
                     }
-
-                    // write files (add/overwrite)
-                    for (src, rel) in &src_files {
-                        // render destination path
-                        let mut rel_str = rel.to_string_lossy().to_string();
-                        if rel_str.starts_with(&format!("{}/", template)) {
-                            rel_str = rel_str[template.len() + 1..].to_string();
-                        }
-                        let mut dest_rel = match hb.render_template(&rel_str, &hb_ctx) {
-                            Ok(s) => PathBuf::from(s),
-                            Err(_) => rel.clone(),
-                        };
-                        // ensure dest_rel does not unintentionally contain a leading template directory segment
-                        if dest_rel
-                            .components()
-                            .next()
-                            .map(|c| c.as_os_str() == std::ffi::OsStr::new(&template))
-                            .unwrap_or(false)
-                        {
-                            let mut iter = dest_rel.iter();
-                            iter.next(); // skip first
-                            let new_rel: PathBuf = iter.collect();
-                            dest_rel = new_rel;
-                        }
-                        let destpath = dest.join(dest_rel);
-                        if let Some(parent) = destpath.parent() {
-                            fs::create_dir_all(parent)?;
-                        }
-
-                        // If target exists and is protected (Cargo.toml/project.toml) and --force not set, skip overwrite
-                        if destpath.exists() && !force {
-                            println!(
-                                "Skipping existing file (use --force to overwrite): {}",
-                                destpath.display()
-                            );
-                            continue;
-                        }
-
-                        // Read source and render template variables if present
-                        let sbytes = fs::read(src)
-                            .with_context(|| format!("reading template file {:?}", src))?;
-                        let stext = String::from_utf8_lossy(&sbytes).to_string();
-                        let rendered = match hb.render_template(&stext, &hb_ctx) {
-                            Ok(s) => s,
-                            Err(_) => stext,
-                        };
-                        fs::write(&destpath, rendered)
-                            .with_context(|| format!("writing file {:?}", destpath))?;
-                    }
-
-                    println!("Template files written to {}", dest.display());
-
-                    // If files were accidentally written under a leading template subdir (e.g., ./default/*),
-                    // move them up to the destination root so generated projects are placed at `dest`.
-                    // Detect any files whose relative path starts with the template name and move them up.
-                    let mut found_nested = false;
-                    for entry in walkdir::WalkDir::new(&dest).into_iter().filter_map(|e| e.ok()) {
-                        if entry.path().is_file() {
-                            if let Ok(rel) = entry.path().strip_prefix(&dest) {
-                                let s = rel.to_string_lossy();
-                                if s.starts_with(&format!("{}/", template)) {
-                                    found_nested = true;
-                                    break;
-                                }
-                            }
-                        }
+                    let declared: Vec<&str> = std::iter::once("project-name")
+                        .chain(variables.keys().map(String::as_str))
+                        .collect();
+                    ctx.extend(vars::from_env(std::env::vars(), &declared));
+                    ctx.extend(template::build_context(project_name, vars));
+                    if text && !yes && atty::is(atty::Stream::Stdin) {
+                        template::resolve_variables(
+                            &variables,
+                            &mut ctx,
+                            Some(&mut prompt_variable),
+                        )?;
+                    } else {
+                        template::resolve_variables(&variables, &mut ctx, None)?;
                     }
-                    if found_nested {
-                        println!("Detected nested template entries, flattening into destination");
-                        for entry in walkdir::WalkDir::new(&dest).into_iter().filter_map(|e| e.ok())
-                        {
-                            let p = entry.path().to_path_buf();
-                            if p.is_file() {
-                                if let Ok(rel) = p.strip_prefix(&dest) {
-                                    let s = rel.to_string_lossy();
-                                    if s.starts_with(&format!("{}/", template)) {
-                                        let trimmed = &s[template.len() + 1..];
-                                        let target = dest.join(trimmed);
-                                        if let Some(parent) = target.parent() {
-                                            fs::create_dir_all(parent)?;
-                                        }
-                                        fs::rename(&p, &target).or_else(|_| {
-                                            // fallback to copy+remove
-                                            fs::copy(&p, &target).and_then(|_| fs::remove_file(&p))
-                                        })?;
-                                    }
-                                }
-                            }
+                    let renderer = Renderer::new(ctx).partials(&tpl.partials()?)?.strict(strict);
+                    sel.resolve_members(&tpl, &renderer, &repo_root)?;
+                    let gen = Generator::new(&tpl, &renderer, &sel);
+                    match &archive {
+                        Some(target) => {
+                            let after = AfterApply { verify, git: None };
+                            archive_from_template(&gen, target, apply, &opts, &after)?
                         }
-                        // try to remove the now-empty template dir
-                        let _ = fs::remove_dir_all(dest.join(&template));
-                    }
-
-                    // Optional verification step: run fmt/clippy/test in the generated project
-                    if verify {
-                        println!(
-                            "Running verification checks (fmt/clippy/test) in {}",
-                            dest.display()
-                        );
-                        match run_verification(&dest) {
-                            Ok(true) => println!("Verification checks passed"),
-                            Ok(false) => {
-                                eprintln!("Verification checks failed");
-                                std::process::exit(4);
-                            }
-                            Err(e) => {
-                                eprintln!("Verification error: {}", e);
-                                std::process::exit(5);
-                            }
+                        None => {
+                            let after = AfterApply { verify, git };
+                            generate_from_template(&gen, &out_dir, apply, &opts, &after)?
                         }
                     }
-                } else {
-                    println!("Dry run (no files written). Use --apply to write files.");
                 }
-
-                return Ok(());
+                None if archive.is_some() => {
+                    return Err(CosmosError::Usage(format!(
+                        "--output-archive needs a template folder; '{}' has none",
+                        template
+                    ))
+                    .into());
+                }
+                None => {
+                    generate_from_patterns(&repo_root, &out_dir, apply, &opts, &sel, git.as_ref())?;
+                }
             }
+        }
 
-            // fallback to pattern-based copy using manifest/categories
-            let matches = expand_patterns(&repo_root, &pats)?;
-            if matches.is_empty() {
-                println!("No files matched for category '{}', patterns: {:?}", category, pats);
+        Commands::Add { component, out_dir, template_dir, vars, apply, force, diff } => {
+            let search_path = template::template_search_path();
+            let name = component.or_else(|| {
+                template_dir
+                    .as_deref()
+                    .and_then(Path::file_name)
+                    .map(|n| n.to_string_lossy().into())
+            });
+            let Some(name) = name else {
+                let components = template::list_components(&repo_root, &search_path)?;
+                if text {
+                    print_templates(&components, "No components found");
+                }
+                let records: Vec<Value> = components.iter().map(|t| json!(t)).collect();
+                emit(format, &json!({ "components": components }), &records);
                 return Ok(());
+            };
+            let tpl = match &template_dir {
+                Some(dir) => Template::locate(&repo_root, &name, Some(dir), &search_path)?,
+                None => component::locate(&repo_root, &name, &search_path)?,
             }
-
-            println!("Matched {} paths", matches.len());
-            for p in &matches {
-                println!(" - {}", p.strip_prefix(&repo_root).unwrap_or(p).display());
+            .ok_or_else(|| {
+                CosmosError::NotFound(format!(
+                    "component '{}' not found (run `cosmos add` to list components)",
+                    name
+                ))
+            })?;
+            let manifest = tpl.manifest()?.unwrap_or_default();
+            let mut ctx = template::build_context(None, vars);
+            if text && atty::is(atty::Stream::Stdin) {
+                template::resolve_variables(
+                    &manifest.variables,
+                    &mut ctx,
+                    Some(&mut prompt_variable),
+                )?;
+            } else {
+                template::resolve_variables(&manifest.variables, &mut ctx, None)?;
             }
+            let renderer = Renderer::new(ctx).partials(&tpl.partials()?)?;
+            let sync = SyncOptions { force, ..SyncOptions::default() };
+            let opts = SyncOpts { sync, format, diff, diff_stat: false };
+            add_component(&tpl, &renderer, &manifest, &out_dir, &opts, apply)?;
+        }
 
-            if apply {
-                let dest = out_dir;
-
-                // desired relative paths from matches
-                let mut desired: Vec<PathBuf> = Vec::new();
-                for p in &matches {
-                    if let Ok(rel) = p.strip_prefix(&repo_root) {
-                        desired.push(rel.to_path_buf());
-                    }
+        Commands::Update { out_dir, template_dir, vars, apply, reject } => {
+            let lock = TemplateLock::read(&out_dir)?.ok_or_else(|| {
+                CosmosError::NotFound(format!(
+                    "{} not found in {}; generate the project with `cosmos generate --apply` first",
+                    update::LOCK_FILE,
+                    out_dir.display()
+                ))
+            })?;
+            let locked_dir = match lock.template.source {
+                TemplateSource::Dir => lock.template.path.clone(),
+                _ => None,
+            };
+            let tpl = Template::locate(
+                &repo_root,
+                &lock.template.name,
+                template_dir.as_deref().or(locked_dir.as_deref()),
+                &template::template_search_path(),
+            )?
+            .ok_or_else(|| {
+                CosmosError::NotFound(format!(
+                    "template '{}' not found (pass --template-dir)",
+                    lock.template.name
+                ))
+            })?;
+            let filter =
+                CategoryFilter::new(lock.categories.clone(), lock.exclude_categories.clone());
+            let (mut sel, variables) = Selection::new(tpl.manifest()?, filter)?;
+            let mut ctx = lock.context();
+            ctx.extend(template::build_context(None, vars));
+            template::resolve_variables(&variables, &mut ctx, None)?;
+            let renderer = Renderer::new(ctx).partials(&tpl.partials()?)?;
+            sel.resolve_members(&tpl, &renderer, &repo_root)?;
+            let gen = Generator::new(&tpl, &renderer, &sel);
+            let to_hash = tpl.content_hash()?;
+            let rendered = gen.prepare()?.rendered;
+            let mut plan = update::plan_update(&out_dir, &rendered, reject)?;
+            plan.protect(&exclude::Rules::destination(&out_dir, &sel.protected)?);
+            plan.resolve(&lock.resolutions);
+
+            if text {
+                if to_hash == lock.template.hash && plan.entries.is_empty() {
+                    println!("Project is up to date with template '{}'", tpl.name);
+                } else {
+                    println!(
+                        "Updating from template '{}' ({} -> {}):",
+                        tpl.name,
+                        template_label(lock.template.version.as_deref(), &lock.template.hash),
+                        template_label(sel.version.as_deref(), &to_hash)
+                    );
                 }
-
-                if dest.exists() {
-                    // gather current files under dest
-                    let mut current: Vec<PathBuf> = Vec::new();
-                    for entry in walkdir::WalkDir::new(&dest).into_iter().filter_map(|e| e.ok()) {
-                        let p = entry.path();
-                        if p.is_file() {
-                            if let Ok(rel) = p.strip_prefix(&dest) {
-                                current.push(rel.to_path_buf());
-                            }
-                        }
-                    }
-
-                    use std::collections::HashSet;
-                    let desired_set: HashSet<_> = desired.iter().cloned().collect();
-                    let current_set: HashSet<_> = current.iter().cloned().collect();
-
-                    let mut missing: Vec<PathBuf> =
-                        desired_set.difference(&current_set).cloned().collect();
-                    missing.sort();
-                    let mut extra: Vec<PathBuf> =
-                        current_set.difference(&desired_set).cloned().collect();
-                    extra.sort();
-
-                    // modified detection
-                    let mut modified: Vec<PathBuf> = Vec::new();
-                    for common in desired_set.intersection(&current_set) {
-                        let src_path = repo_root.join(common);
-                        let dest_path = dest.join(common);
-                        if let (Ok(sb), Ok(db)) = (fs::read(&src_path), fs::read(&dest_path)) {
-                            if sb != db {
-                                modified.push(common.clone());
-                            }
-                        }
-                    }
-                    modified.sort();
-
-                    if !missing.is_empty() || !extra.is_empty() || !modified.is_empty() {
-                        println!(
-                            "Detected inconsistencies between template patterns and destination:"
-                        );
-                        if !missing.is_empty() {
-                            println!("  Missing files to add ({}):", missing.len());
-                            for m in &missing {
-                                println!("   + {}", m.display());
-                            }
-                        }
-                        if !modified.is_empty() {
-                            println!("  Files that will be overwritten ({}):", modified.len());
-                            for m in &modified {
-                                println!("   ~ {}", m.display());
-                            }
+                for e in &plan.entries {
+                    let remembered = if e.resolution.is_some() { ", remembered" } else { "" };
+                    match (&e.reject, &e.rule) {
+                        (Some(r), _) => println!(
+                            " - {}: {} (see {}{})",
+                            e.path.display(),
+                            e.action,
+                            r.display(),
+                            remembered
+                        ),
+                        (None, None) if e.resolution.is_some() => {
+                            println!(" - {}: {} (remembered)", e.path.display(), e.action)
                         }
-                        if !extra.is_empty() {
-                            println!("  Extra files to delete ({}):", extra.len());
-                            for e in &extra {
-                                println!("   - {}", e.display());
-                            }
-                        }
-
-                        // If --yes was provided, we'll auto-apply; otherwise prompt
-                        if yes {
-                            // auto-confirm mode
-                            if !extra.is_empty() && !allow_delete {
-                                eprintln!("Detected deletions but --allow-delete not set; aborting. Use --allow-delete with --yes to permit deletions.");
-                                std::process::exit(2);
-                            }
-                            if !extra.is_empty() {
-                                // perform deletions
-                                for e in &extra {
-                                    let p = dest.join(e);
-                                    if p.exists() {
-                                        fs::remove_file(&p).with_context(|| {
-                                            format!("deleting extra file {:?}", p)
-                                        })?;
-                                    }
-                                }
-                            }
-                        } else {
-                            use std::io::{stdin, stdout, Write};
-                            print!("Apply these fixes? [y/N]: ");
-                            stdout().flush().ok();
-                            let mut input = String::new();
-                            let _ = stdin().read_line(&mut input);
-                            if !matches!(input.trim().to_lowercase().as_str(), "y" | "yes") {
-                                println!("Aborted by user; no changes applied.");
-                                std::process::exit(0);
-                            }
-                            if !extra.is_empty() {
-                                print!("This operation will DELETE {} files. Type DELETE to confirm deletions: ", extra.len());
-                                stdout().flush().ok();
-                                let mut dconfirm = String::new();
-                                let _ = stdin().read_line(&mut dconfirm);
-                                if dconfirm.trim() != "DELETE" {
-                                    println!(
-                                        "Deletion confirmation failed; aborting without changes."
-                                    );
-                                    std::process::exit(0);
-                                }
-                            }
-
-                            // perform deletions
-                            for e in &extra {
-                                let p = dest.join(e);
-                                if p.exists() {
-                                    fs::remove_file(&p)
-                                        .with_context(|| format!("deleting extra file {:?}", p))?;
-                                }
-                            }
+                        (None, Some(rule)) => {
+                            println!(" - {}: {} [{}]", e.path.display(), e.action, rule)
                         }
+                        (None, None) => println!(" - {}: {}", e.path.display(), e.action),
                     }
                 }
-
-                // now copy matched paths
-                copy_paths_to(&repo_root, &matches, &dest, force)?;
-                println!("Files written to {}", dest.display());
-            } else {
-                println!("Dry run (no files written). Use --apply to write files.");
+                if !apply {
+                    println!("Dry run (no files written). Use --apply to update the project.");
+                }
+            }
+            let mut backup = None;
+            if apply {
+                let mut tx = Transaction::begin(&out_dir)?;
+                update::apply_update(&mut tx, &plan)?;
+                let mut new = gen.lock(&out_dir, BTreeMap::new())?;
+                update::stage_generation(&mut tx, &mut new, &rendered)?;
+                let commit = tx.commit()?;
+                if text {
+                    print_backup(&commit);
+                }
+                backup = commit.backup;
+            }
+            let doc = json!({
+                "template": tpl.name,
+                "from_version": lock.template.version,
+                "to_version": sel.version,
+                "from_hash": lock.template.hash,
+                "to_hash": to_hash,
+                "applied": apply,
+                "backup": backup,
+                "files": plan.entries,
+            });
+            let records: Vec<Value> = plan
+                .entries
+                .iter()
+                .map(|e| {
+                    let mut r = json!(e);
+                    r["type"] = json!("file");
+                    r
+                })
+                .collect();
+            emit(format, &doc, &records);
+            if apply && plan.conflicts() > 0 {
+                return Err(CosmosError::Conflict(format!(
+                    "{} file(s) with conflicts; resolve them and remove the markers or .rej files",
+                    plan.conflicts()
+                ))
+                .into());
             }
         }
 
+        Commands::Template { sub } => run_template_cmd(sub, &repo_root, format)?,
+
         Commands::Validate { level, fix } => {
-            println!("Running {} validation...", level);
-            let (errors, warnings) = validate_repo(&repo_root, &level, fix)?;
-            let has_errors = !errors.is_empty();
-            println!("\nValidation summary: {} errors, {} warnings", errors.len(), warnings.len());
-            if !errors.is_empty() {
-                println!("\nErrors:");
-                for e in &errors {
-                    println!(" - {}", e);
-                }
+            if text {
+                println!("Running {} validation...", level);
             }
-            if !warnings.is_empty() {
-                println!("\nWarnings:");
-                for w in &warnings {
-                    println!(" - {}", w);
+            let report = validate::validate_repo(&repo_root, &level, fix)?;
+            if text {
+                if !report.fixes.is_empty() {
+                    println!("Applied fixes:");
+                    for f in &report.fixes {
+                        println!(" - {}", f);
+                    }
                 }
+                println!(
+                    "\nValidation summary: {} errors, {} warnings",
+                    report.errors.len(),
+                    report.warnings.len()
+                );
+                print_issues("Errors", &report.errors);
+                print_issues("Warnings", &report.warnings);
             }
+            let mut doc = json!(report);
+            doc["level"] = json!(level);
+            emit(format, &doc, &issue_records(&report.errors, &report.warnings));
 
             // Treat errors as a failing validation in automation.
             // Warnings are informational and do not fail the command.
-            if has_errors {
-                std::process::exit(2);
+            if !report.errors.is_empty() {
+                return Err(CosmosError::Validation(format!(
+                    "validation found {} errors",
+                    report.errors.len()
+                ))
+                .into());
             }
         }
 
+        Commands::Verify { only, changed_since, offline, report_dir } => {
+            let opts = CheckoutOptions { only, changed_since, offline, report_dir };
+            verify_checkout(&repo_root, &opts, format)?;
+        }
+
         Commands::Ai { sub } => match sub {
             AiCmd::Doctor {} => {
-                println!("AI/LLM configuration:");
-                #[cfg(feature = "llm")]
-                println!(" - Build feature: llm (enabled)");
-                #[cfg(not(feature = "llm"))]
-                println!(" - Build feature: llm (disabled; rebuild with --features llm)");
+                let llm_enabled = cfg!(feature = "llm");
                 let provider = std::env::var("LLM_PROVIDER").ok().unwrap_or_default();
-                if provider.is_empty() {
-                    println!(" - LLM_PROVIDER: (not set)");
-                    println!("   Hint: set LLM_PROVIDER=stub for the built-in stub provider");
-                } else {
-                    println!(" - LLM_PROVIDER: {}", provider);
-                }
-                println!("\nPlan integration:");
-                println!(" - Use 'cosmos plan --ai-validate <transition>' to run AI validation before user hooks");
-                println!(" - Hooks can read PLAN_AI_VALIDATION_PATH (JSON report) if present");
+                if text {
+                    println!("AI/LLM configuration:");
+                    if llm_enabled {
+                        println!(" - Build feature: llm (enabled)");
+                    } else {
+                        println!(" - Build feature: llm (disabled; rebuild with --features llm)");
+                    }
+                    if provider.is_empty() {
+                        println!(" - LLM_PROVIDER: (not set)");
+                        println!("   Hint: set LLM_PROVIDER=stub for the built-in stub provider");
+                    } else {
+                        println!(" - LLM_PROVIDER: {}", provider);
+                    }
+                    println!("\nPlan integration:");
+                    println!(" - Use 'cosmos plan --ai-validate <transition>' to run AI validation before user hooks");
+                    println!(" - Hooks can read PLAN_AI_VALIDATION_PATH (JSON report) if present");
+                }
+                emit_doc(
+                    format,
+                    json!({
+                        "llm_feature": llm_enabled,
+                        "provider": if provider.is_empty() { None } else { Some(provider) },
+                    }),
+                );
             }
             AiCmd::Eval {} => {
                 #[cfg(feature = "llm")]
                 {
-                    match rust_repo_template::llm::evaluate_with_llm(&repo_root) {
-                        Ok(()) => println!("AI/LLM evaluation completed"),
-                        Err(e) => {
-                            eprintln!("AI/LLM evaluation error: {}", e);
-                            std::process::exit(3);
-                        }
+                    rust_repo_template::llm::evaluate_with_llm(&repo_root).map_err(|e| {
+                        CosmosError::AiUnavailable(format!("AI/LLM evaluation error: {}", e))
+                    })?;
+                    if text {
+                        println!("AI/LLM evaluation completed");
                     }
+                    emit_doc(format, json!({"completed": true}));
                 }
                 #[cfg(not(feature = "llm"))]
                 {
-                    eprintln!("AI/LLM evaluation requested but not enabled in this build. Build with '--features llm' to enable.");
-                    std::process::exit(3);
+                    return Err(CosmosError::AiUnavailable("AI/LLM evaluation requested but not enabled in this build. Build with '--features llm' to enable.".to_string()).into());
                 }
             }
         },
 
         Commands::Plan { ai_validate, sub } => match sub {
             PlanCmd::List {} => {
-                let todo = repo_root.join("plan/todo.toml");
-                if !todo.exists() {
-                    println!("plan/todo.toml not found");
-                } else {
-                    let s = fs::read_to_string(&todo).context("reading plan/todo.toml")?;
-                    let plan: PlanTodo = toml::from_str(&s).context("parsing plan/todo.toml")?;
-                    println!("Tasks ({}):", plan.task.len());
-                    for t in plan.task {
-                        println!(
-                            " - {}: {} [{}]",
-                            t.id,
-                            t.title.unwrap_or_default(),
-                            t.status.unwrap_or_default()
-                        );
+                let plan = Plan::open(&repo_root)?;
+                let found = plan.todo_path().exists();
+                if text {
+                    if !found {
+                        println!("plan/todo.toml not found");
+                    } else {
+                        println!("Tasks ({}):", plan.tasks().len());
+                        for t in plan.tasks() {
+                            println!(
+                                " - {}: {} [{}]",
+                                t.id,
+                                t.title.as_deref().unwrap_or_default(),
+                                t.status.as_deref().unwrap_or_default()
+                            );
+                        }
                     }
                 }
+                let records: Vec<Value> = plan.tasks().iter().map(|t| json!(t)).collect();
+                emit(format, &json!({"todo_found": found, "tasks": plan.tasks()}), &records);
             }
             PlanCmd::Validate { task: _, fix } => {
-                println!("Running plan validation...");
-                let issues = validate_plan(&repo_root)?;
+                if text {
+                    println!("Running plan validation...");
+                }
+                let issues = plan::validate_plan(&repo_root)?;
                 if issues.is_empty() {
-                    println!("Plan validation OK");
-                } else {
+                    if text {
+                        println!("Plan validation OK");
+                    }
+                    emit(format, &json!({"issues": issues, "fixes": []}), &[]);
+                    return Ok(());
+                }
+                if text {
                     println!("Plan validation found {} issues:", issues.len());
                     for i in issues.iter() {
                         println!(" - {}", i);
                     }
-                    if fix {
-                        println!("Attempting to auto-fix plan issues...");
-                        let fixes = auto_fix_repo(&repo_root)?;
-                        for f in fixes.iter() {
-                            println!(" - fixed: {}", f);
-                        }
-                        // re-run validation
-                        let issues2 = validate_plan(&repo_root)?;
-                        if issues2.is_empty() {
-                            println!("Plan validation OK after fixes");
-                        } else {
-                            println!("Plan still has {} issues after fixes:", issues2.len());
-                            for i in issues2 {
-                                println!(" - {}", i);
-                            }
-                            std::process::exit(2);
+                }
+                if !fix {
+                    emit(
+                        format,
+                        &json!({"issues": issues, "fixes": []}),
+                        &issue_records(&issues, &[]),
+                    );
+                    return Err(CosmosError::Validation(format!(
+                        "plan validation found {} issues",
+                        issues.len()
+                    ))
+                    .into());
+                }
+                if text {
+                    println!("Attempting to auto-fix plan issues...");
+                }
+                let fixes = validate::auto_fix_repo(&repo_root)?;
+                if text {
+                    for f in &fixes {
+                        println!(" - fixed: {}", f);
+                    }
+                }
+                // re-run validation
+                let issues2 = plan::validate_plan(&repo_root)?;
+                emit(
+                    format,
+                    &json!({"issues": issues2, "fixes": fixes}),
+                    &issue_records(&issues2, &[]),
+                );
+                if issues2.is_empty() {
+                    if text {
+                        println!("Plan validation OK after fixes");
+                    }
+                } else {
+                    if text {
+                        println!("Plan still has {} issues after fixes:", issues2.len());
+                        for i in &issues2 {
+                            println!(" - {}", i);
                         }
-                    } else {
-                        std::process::exit(2);
                     }
+                    return Err(CosmosError::Validation(format!(
+                        "plan still has {} issues after fixes",
+                        issues2.len()
+                    ))
+                    .into());
                 }
             }
             PlanCmd::Create { kind, title, content, assignee } => {
-                let mut plan = read_todo(&repo_root)?;
-                let next = read_next_id(&repo_root)?;
-                let id = format!("{:04}", next);
-                let task_dir = repo_root.join("plan/tasks").join(&id);
-                fs::create_dir_all(&task_dir)?;
-                let tf_path = task_dir.join("task.md");
-                let body = content.unwrap_or_else(|| format!("# {}\n", title));
-                fs::write(&tf_path, body)?;
-                // create history dir
-                let history_dir = task_dir.join("history");
-                fs::create_dir_all(&history_dir)?;
-                let task_file = format!("tasks/{}/task.md", id);
-                let t = PlanTask {
-                    id: id.clone(),
-                    kind: Some(kind.clone()),
-                    title: Some(title.clone()),
-                    status: Some("pending_review".to_string()),
-                    assignee,
-                    task_file: Some(task_file),
-                };
-                plan.task.push(t);
-                write_todo(&repo_root, &plan)?;
-                write_next_id(&repo_root, next + 1)?;
-                println!("Created task {}", id);
+                let mut plan = Plan::open(&repo_root)?;
+                let id = plan.create(NewTask { kind, title, content, assignee })?;
+                if text {
+                    println!("Created task {}", id);
+                }
+                emit_doc(format, json!({"created": plan.task(&id)}));
             }
             PlanCmd::Update { id, title, assignee, content } => {
-                let mut plan = read_todo(&repo_root)?;
-                let mut found = false;
-                for t in plan.task.iter_mut() {
-                    if t.id == id {
-                        if let Some(tt) = title.clone() {
-                            t.title = Some(tt);
-                        }
-                        if let Some(a) = assignee.clone() {
-                            t.assignee = Some(a);
-                        }
-                        if let Some(c) = content.clone() {
-                            // write into task file whether under tasks or archive
-                            if let Some(tf) = &t.task_file {
-                                let p = repo_root.join("plan").join(tf);
-                                fs::write(&p, c)?;
-                            }
-                        }
-                        found = true;
-                        break;
-                    }
-                }
-                if !found {
-                    eprintln!("plan: task '{}' not found", id);
-                    std::process::exit(2);
+                let mut plan = Plan::open(&repo_root)?;
+                plan.update(&id, TaskUpdate { title, assignee, content })?;
+                if text {
+                    println!("Updated task {}", id);
                 }
-                write_todo(&repo_root, &plan)?;
-                println!("Updated task {}", id);
+                emit_doc(format, json!({"updated": plan.task(&id)}));
             }
             PlanCmd::Hooks { sub } => match sub {
                 HooksCmd::Add { name } => {
-                    let hooks_dir = repo_root.join("scripts/plan-hooks");
-                    fs::create_dir_all(&hooks_dir)?;
-                    let path = hooks_dir.join(format!("{}.py", name));
-                    if path.exists() {
-                        eprintln!("hook '{}' already exists at {}", name, path.display());
-                        std::process::exit(2);
+                    let path = plan::add_hook(&repo_root, &name)?;
+                    if text {
+                        println!("Created hook {} -> {}", name, path.display());
                     }
-                    let template = r#"#!/usr/bin/env python3
-"""Plan hook template.
-Provide a unified entrypoint: `def run(ctx: dict) -> dict`.
-When executed as a script it reads a JSON `ctx` from stdin and prints a JSON `res` to stdout.
-Exiting with nonzero indicates failure and will block the transition.
-"""
-import json
-import sys
-
-
-def run(ctx: dict) -> dict:
-    # implement checks here, e.g. ensure reports exist
-    task_id = ctx.get('task_id')
-    repo_root = ctx.get('repo_root')
-    # return {'ok': True, 'message': 'ok'} or {'ok': False, 'message': 'explain'}
-    return {'ok': True, 'message': 'ok'}
-
-
-if __name__ == "__main__":
-    try:
-        data = json.load(sys.stdin)
-    except Exception:
-        data = {}
-    res = run(data)
-    print(json.dumps(res))
-"#;
-                    fs::write(&path, template)?;
-                    // make executable
-                    #[cfg(unix)]
-                    {
-                        use std::os::unix::fs::PermissionsExt;
-                        let mut perm = fs::metadata(&path)?.permissions();
-                        perm.set_mode(0o755);
-                        fs::set_permissions(&path, perm)?;
-                    }
-                    println!("Created hook {} -> {}", name, path.display());
+                    emit_doc(format, json!({"hook": name, "path": path}));
                 }
                 HooksCmd::List {} => {
-                    let hooks_dir = repo_root.join("scripts/plan-hooks");
-                    if !hooks_dir.exists() {
-                        println!("No hooks directory found: {}", hooks_dir.display());
-                    } else {
-                        println!("Hooks:");
-                        let mut names: Vec<String> = Vec::new();
-                        for entry in fs::read_dir(&hooks_dir)?.filter_map(|e| e.ok()) {
-                            let p = entry.path();
-                            if p.is_file() && p.extension().and_then(|s| s.to_str()) == Some("py") {
-                                if let Some(n) = p.file_stem().and_then(|s| s.to_str()) {
-                                    names.push(n.to_string());
+                    let hooks = plan::list_hooks(&repo_root)?;
+                    if text {
+                        match &hooks {
+                            None => println!(
+                                "No hooks directory found: {}",
+                                plan::hooks_dir(&repo_root).display()
+                            ),
+                            Some(names) => {
+                                println!("Hooks:");
+                                for n in names {
+                                    println!(" - {}", n);
                                 }
                             }
                         }
-                        names.sort();
-                        for n in names {
-                            println!(" - {}", n);
-                        }
                     }
+                    let names = hooks.unwrap_or_default();
+                    let records: Vec<Value> = names.iter().map(|n| json!({"hook": n})).collect();
+                    emit(format, &json!({"hooks": names}), &records);
                 }
                 HooksCmd::Check { name } => {
-                    let hooks_dir = repo_root.join("scripts/plan-hooks");
-                    let mut candidates: Vec<PathBuf> = Vec::new();
-                    if let Some(nm) = name.clone() {
-                        candidates.push(hooks_dir.join(format!("{}.py", nm)));
-                    } else if hooks_dir.exists() {
-                        for entry in fs::read_dir(&hooks_dir)?.filter_map(|e| e.ok()) {
-                            let p = entry.path();
-                            if p.is_file() && p.extension().and_then(|s| s.to_str()) == Some("py") {
-                                candidates.push(p);
-                            }
-                        }
-                    }
-                    if candidates.is_empty() {
+                    let candidates = plan::hook_candidates(&repo_root, name.as_deref())?;
+                    if candidates.is_empty() && text {
                         println!("No hooks to check");
                     }
+                    let mut checked = Vec::new();
                     for c in candidates {
-                        if !c.exists() {
-                            eprintln!("hook not found: {}", c.display());
-                            std::process::exit(2);
+                        plan::check_hook(&c)?;
+                        if text {
+                            println!("OK: {}", c.display());
                         }
-                        // syntax check
-                        let st =
-                            Command::new("python3").arg("-m").arg("py_compile").arg(&c).output();
-                        match st {
-                            Ok(o) => {
-                                if !o.status.success() {
-                                    let mut msg = String::new();
-                                    msg.push_str(&String::from_utf8_lossy(&o.stdout));
-                                    msg.push_str(&String::from_utf8_lossy(&o.stderr));
-                                    eprintln!("syntax error in {}: {}", c.display(), msg.trim());
-                                    std::process::exit(2);
-                                }
-                            }
-                            Err(e) => {
-                                eprintln!("failed running python: {}", e);
-                                std::process::exit(2);
-                            }
-                        }
-                        // entrypoint check: module must define callable 'run'
-                        let check_code = format!(
-                            "import importlib.util,sys\nspec=importlib.util.spec_from_file_location('m','{}');m=importlib.util.module_from_spec(spec);spec.loader.exec_module(m);print(hasattr(m,'run') and callable(m.run))",
-                            c.display()
-                        );
-                        let out = Command::new("python3").arg("-c").arg(&check_code).output();
-                        match out {
-                            Ok(o) => {
-                                let s = String::from_utf8_lossy(&o.stdout).trim().to_string();
-                                if s != "True" {
-                                    eprintln!(
-                                        "entrypoint check failed for {}: missing callable 'run'",
-                                        c.display()
-                                    );
-                                    std::process::exit(2);
-                                }
-                            }
-                            Err(e) => {
-                                eprintln!("failed running python: {}", e);
-                                std::process::exit(2);
-                            }
-                        }
-                        println!("OK: {}", c.display());
+                        checked.push(json!({"path": c, "ok": true}));
                     }
+                    emit(format, &json!({"checked": checked}), &checked);
                 }
             },
             PlanCmd::Review { id, decision, message, author } => {
+                let mut plan = Plan::open(&repo_root)?;
+                let opts = TransitionOptions { message, author, ai_validate };
                 match decision.as_str() {
                     "accept" => {
-                        // default pre-check: ensure task has acceptance criteria
-                        if let Err(e) = default_check_review_accept(&repo_root, &id) {
-                            eprintln!("{}", e);
-                            eprintln!("Hint: Add 'Acceptance criteria' to task.md or provide a custom hook at scripts/plan-hooks/pre_review_accept.py");
-                            std::process::exit(2);
+                        run_transition(&mut plan, &id, Status::Queued, &opts, format)?;
+                        if text {
+                            println!("Task {} accepted and queued", id);
                         }
-                        let mut ai_path: Option<PathBuf> = None;
-                        if ai_validate {
-                            let (cur, _) = match get_task_context(&repo_root, &id) {
-                                Ok(v) => v,
-                                Err(e) => {
-                                    eprintln!("{}", e);
-                                    std::process::exit(2);
-                                }
-                            };
-                            match ai_validate_transition(&repo_root, &id, &cur, "queued") {
-                                Ok(report) => match write_ai_validation_report(
-                                    &repo_root, &id, &cur, "queued", &report,
-                                ) {
-                                    Ok(p) => ai_path = Some(p),
-                                    Err(e) => {
-                                        eprintln!(
-                                            "AI validation report write error (non-fatal): {}",
-                                            e
-                                        );
-                                    }
-                                },
-                                Err(e) => {
-                                    eprintln!("AI validation error (non-fatal): {}", e);
-                                }
+                    }
+                    "reject" => {
+                        let outcome = plan.reject(&id, &opts)?;
+                        if text {
+                            for w in &outcome.warnings {
+                                eprintln!("{}", w);
                             }
+                            println!(
+                                "Task {} review recorded as rejected (still pending_review)",
+                                id
+                            );
                         }
-                        // run user pre-review hook if present
-                        if let Err(e) = run_user_hook(
-                            &repo_root,
-                            "pre_review_accept",
-                            &id,
-                            Some("queued"),
-                            ai_path.as_deref(),
-                        ) {
-                            eprintln!("{}", e);
-                            std::process::exit(2);
-                        }
-                        if let Err(e) =
-                            set_task_status(&repo_root, &id, Some("pending_review"), "queued")
-                        {
-                            eprintln!("{}", e);
-                            eprintln!("Hint: Use 'cosmos plan review --id {} --decision accept' only when task is 'pending_review'", id);
-                            std::process::exit(2);
-                        }
-                        append_history(&repo_root, &id, message.as_deref(), author.as_deref())?;
-                        // run post-review hooks
-                        let _ = run_user_hook(
-                            &repo_root,
-                            "post_review_accept",
-                            &id,
-                            Some("queued"),
-                            ai_path.as_deref(),
+                        emit_doc(
+                            format,
+                            json!({
+                                "task": id,
+                                "status": Status::PendingReview,
+                                "rejected": true,
+                                "warnings": outcome.warnings,
+                            }),
                         );
-                        println!("Task {} accepted and queued", id);
-                    }
-                    "reject" => {
-                        append_history(&repo_root, &id, message.as_deref(), author.as_deref())?;
-                        // run user hook for rejection
-                        let _ = run_user_hook(&repo_root, "post_review_reject", &id, None, None);
-                        println!("Task {} review recorded as rejected (still pending_review)", id);
                     }
                     other => {
-                        eprintln!("plan: unknown decision '{}'. Use 'accept' or 'reject'", other);
-                        std::process::exit(2);
+                        return Err(CosmosError::Usage(format!(
+                            "plan: unknown decision '{}'. Use 'accept' or 'reject'",
+                            other
+                        ))
+                        .into());
                     }
                 }
             }
             PlanCmd::Start { id, message, author } => {
-                // default pre-check: ensure review accepted exists in history
-                if let Err(e) = default_check_start(&repo_root, &id) {
-                    eprintln!("{}", e);
-                    eprintln!("Hint: Ensure the task was accepted during review (use 'cosmos plan review --id <id> --decision accept') or provide a custom hook at scripts/plan-hooks/pre_start.py");
-                    std::process::exit(2);
-                }
-                let mut ai_path: Option<PathBuf> = None;
-                if ai_validate {
-                    let (cur, _) = match get_task_context(&repo_root, &id) {
-                        Ok(v) => v,
-                        Err(e) => {
-                            eprintln!("{}", e);
-                            std::process::exit(2);
-                        }
-                    };
-                    match ai_validate_transition(&repo_root, &id, &cur, "working") {
-                        Ok(report) => {
-                            if let Ok(p) = write_ai_validation_report(
-                                &repo_root, &id, &cur, "working", &report,
-                            ) {
-                                ai_path = Some(p);
-                            }
-                        }
-                        Err(e) => eprintln!("AI validation error (non-fatal): {}", e),
-                    }
-                }
-                if let Err(e) =
-                    run_user_hook(&repo_root, "pre_start", &id, Some("working"), ai_path.as_deref())
-                {
-                    eprintln!("{}", e);
-                    std::process::exit(2);
-                }
-                if let Err(e) = set_task_status(&repo_root, &id, Some("queued"), "working") {
-                    eprintln!("{}", e);
-                    eprintln!("Hint: task must be 'queued' to start. Consider 'cosmos plan review --id {} --decision accept' first.", id);
-                    std::process::exit(2);
+                let mut plan = Plan::open(&repo_root)?;
+                let opts = TransitionOptions { message, author, ai_validate };
+                run_transition(&mut plan, &id, Status::Working, &opts, format)?;
+                if text {
+                    println!("Task {} started (working)", id);
                 }
-                append_history(&repo_root, &id, message.as_deref(), author.as_deref())?;
-                let _ = run_user_hook(
-                    &repo_root,
-                    "post_start",
-                    &id,
-                    Some("working"),
-                    ai_path.as_deref(),
-                );
-                println!("Task {} started (working)", id);
             }
             PlanCmd::Test { id, message, author } => {
-                if let Err(e) = default_check_test(&repo_root, &id) {
-                    eprintln!("{}", e);
-                    eprintln!("Hint: Ensure tests or test plan are present, or provide a custom hook at scripts/plan-hooks/pre_test.py");
-                    std::process::exit(2);
-                }
-                let mut ai_path: Option<PathBuf> = None;
-                if ai_validate {
-                    let (cur, _) = match get_task_context(&repo_root, &id) {
-                        Ok(v) => v,
-                        Err(e) => {
-                            eprintln!("{}", e);
-                            std::process::exit(2);
-                        }
-                    };
-                    match ai_validate_transition(&repo_root, &id, &cur, "testing") {
-                        Ok(report) => {
-                            if let Ok(p) = write_ai_validation_report(
-                                &repo_root, &id, &cur, "testing", &report,
-                            ) {
-                                ai_path = Some(p);
-                            }
-                        }
-                        Err(e) => eprintln!("AI validation error (non-fatal): {}", e),
-                    }
-                }
-                if let Err(e) =
-                    run_user_hook(&repo_root, "pre_test", &id, Some("testing"), ai_path.as_deref())
-                {
-                    eprintln!("{}", e);
-                    std::process::exit(2);
+                let mut plan = Plan::open(&repo_root)?;
+                let opts = TransitionOptions { message, author, ai_validate };
+                run_transition(&mut plan, &id, Status::Testing, &opts, format)?;
+                if text {
+                    println!("Task {} moved to testing", id);
                 }
-                if let Err(e) = set_task_status(&repo_root, &id, Some("working"), "testing") {
-                    eprintln!("{}", e);
-                    eprintln!(
-                        "Hint: task must be 'working' to run tests (use 'cosmos plan start')."
-                    );
-                    std::process::exit(2);
-                }
-                append_history(&repo_root, &id, message.as_deref(), author.as_deref())?;
-                let _ = run_user_hook(
-                    &repo_root,
-                    "post_test",
-                    &id,
-                    Some("testing"),
-                    ai_path.as_deref(),
-                );
-                println!("Task {} moved to testing", id);
             }
             PlanCmd::Accept { id, message, author } => {
-                if let Err(e) = default_check_accept(&repo_root, &id) {
-                    eprintln!("{}", e);
-                    eprintln!("Hint: Ensure tests and reports are present or provide a custom hook at scripts/plan-hooks/pre_accept.py");
-                    std::process::exit(2);
+                let mut plan = Plan::open(&repo_root)?;
+                let opts = TransitionOptions { message, author, ai_validate };
+                run_transition(&mut plan, &id, Status::UnderAcceptance, &opts, format)?;
+                if text {
+                    println!("Task {} marked under_acceptance", id);
                 }
-                let mut ai_path: Option<PathBuf> = None;
-                if ai_validate {
-                    let (cur, _) = match get_task_context(&repo_root, &id) {
-                        Ok(v) => v,
-                        Err(e) => {
-                            eprintln!("{}", e);
-                            std::process::exit(2);
-                        }
-                    };
-                    match ai_validate_transition(&repo_root, &id, &cur, "under_acceptance") {
-                        Ok(report) => {
-                            if let Ok(p) = write_ai_validation_report(
-                                &repo_root,
-                                &id,
-                                &cur,
-                                "under_acceptance",
-                                &report,
-                            ) {
-                                ai_path = Some(p);
-                            }
-                        }
-                        Err(e) => eprintln!("AI validation error (non-fatal): {}", e),
-                    }
-                }
-                if let Err(e) = run_user_hook(
-                    &repo_root,
-                    "pre_accept",
-                    &id,
-                    Some("under_acceptance"),
-                    ai_path.as_deref(),
-                ) {
-                    eprintln!("{}", e);
-                    std::process::exit(2);
-                }
-                if let Err(e) =
-                    set_task_status(&repo_root, &id, Some("testing"), "under_acceptance")
-                {
-                    eprintln!("{}", e);
-                    eprintln!("Hint: task must be 'testing' before acceptance.");
-                    std::process::exit(2);
-                }
-                append_history(&repo_root, &id, message.as_deref(), author.as_deref())?;
-                let _ = run_user_hook(
-                    &repo_root,
-                    "post_accept",
-                    &id,
-                    Some("under_acceptance"),
-                    ai_path.as_deref(),
-                );
-                println!("Task {} marked under_acceptance", id);
             }
             PlanCmd::Finish { id, message, author } => {
-                if let Err(e) = default_check_finish(&repo_root, &id) {
-                    eprintln!("{}", e);
-                    eprintln!("Hint: Ensure acceptance report is present or provide a custom hook at scripts/plan-hooks/pre_finish.py");
-                    std::process::exit(2);
+                let mut plan = Plan::open(&repo_root)?;
+                let opts = TransitionOptions { message, author, ai_validate };
+                run_transition(&mut plan, &id, Status::Finished, &opts, format)?;
+                if text {
+                    println!("Task {} finished and archived", id);
                 }
-                let mut ai_path: Option<PathBuf> = None;
-                if ai_validate {
-                    let (cur, _) = match get_task_context(&repo_root, &id) {
-                        Ok(v) => v,
-                        Err(e) => {
-                            eprintln!("{}", e);
-                            std::process::exit(2);
-                        }
-                    };
-                    match ai_validate_transition(&repo_root, &id, &cur, "finished") {
-                        Ok(report) => {
-                            if let Ok(p) = write_ai_validation_report(
-                                &repo_root, &id, &cur, "finished", &report,
-                            ) {
-                                ai_path = Some(p);
-                            }
-                        }
-                        Err(e) => eprintln!("AI validation error (non-fatal): {}", e),
-                    }
-                }
-                if let Err(e) = run_user_hook(
-                    &repo_root,
-                    "pre_finish",
-                    &id,
-                    Some("finished"),
-                    ai_path.as_deref(),
-                ) {
-                    eprintln!("{}", e);
-                    std::process::exit(2);
-                }
-                if let Err(e) =
-                    set_task_status(&repo_root, &id, Some("under_acceptance"), "finished")
-                {
-                    eprintln!("{}", e);
-                    eprintln!("Hint: task must be 'under_acceptance' before finishing.");
-                    std::process::exit(2);
-                }
-                append_history(&repo_root, &id, message.as_deref(), author.as_deref())?;
-                let _ = run_user_hook(
-                    &repo_root,
-                    "post_finish",
-                    &id,
-                    Some("finished"),
-                    ai_path.as_deref(),
-                );
-                println!("Task {} finished and archived", id);
             }
             PlanCmd::Reopen { id, message, author } => {
-                if ai_validate {
-                    let (cur, _) = match get_task_context(&repo_root, &id) {
-                        Ok(v) => v,
-                        Err(e) => {
-                            eprintln!("{}", e);
-                            std::process::exit(2);
-                        }
-                    };
-                    if let Ok(report) =
-                        ai_validate_transition(&repo_root, &id, &cur, "pending_review")
-                    {
-                        let _ = write_ai_validation_report(
-                            &repo_root,
-                            &id,
-                            &cur,
-                            "pending_review",
-                            &report,
-                        );
-                    }
-                }
-                if let Err(e) = set_task_status(&repo_root, &id, Some("finished"), "pending_review")
-                {
-                    eprintln!("{}", e);
-                    eprintln!("Hint: only 'finished' tasks can be reopened.");
-                    std::process::exit(2);
+                let mut plan = Plan::open(&repo_root)?;
+                let opts = TransitionOptions { message, author, ai_validate };
+                run_transition(&mut plan, &id, Status::PendingReview, &opts, format)?;
+                if text {
+                    println!("Task {} reopened to pending_review", id);
                 }
-                append_history(&repo_root, &id, message.as_deref(), author.as_deref())?;
-                println!("Task {} reopened to pending_review", id);
             }
             PlanCmd::Delete { id, yes } => {
-                let mut plan = read_todo(&repo_root)?;
-                let idx = plan.task.iter().position(|t| t.id == id);
-                if idx.is_none() {
-                    eprintln!("plan: task '{}' not found", id);
-                    std::process::exit(2);
+                let mut plan = Plan::open(&repo_root)?;
+                if plan.task(&id).is_none() {
+                    return Err(CosmosError::TaskNotFound { id }.into());
                 }
-                let idx = idx.unwrap();
                 if !yes {
-                    use std::io::{stdin, stdout, Write};
-                    print!("Delete task {} (y/N)? ", id);
-                    stdout().flush().ok();
-                    let mut input = String::new();
-                    let _ = stdin().read_line(&mut input);
-                    if !matches!(input.trim().to_lowercase().as_str(), "y" | "yes") {
+                    require_interactive(format)?;
+                    if !confirm(&format!("Delete task {} (y/N)? ", id)) {
                         println!("Aborted");
-                        std::process::exit(0);
+                        return Ok(());
                     }
                 }
-                // remove task dir
-                let dir = repo_root.join("plan/tasks").join(&id);
-                if dir.exists() {
-                    fs::remove_dir_all(&dir)
-                        .with_context(|| format!("deleting task dir {:?}", dir))?;
-                }
-                // also remove archive dir if present
-                let adir = repo_root.join("plan/archive").join(&id);
-                if adir.exists() {
-                    fs::remove_dir_all(&adir)
-                        .with_context(|| format!("deleting archive dir {:?}", adir))?;
+                plan.delete(&id)?;
+                if text {
+                    println!("Deleted task {}", id);
                 }
-                plan.task.remove(idx);
-                write_todo(&repo_root, &plan)?;
-                println!("Deleted task {}", id);
+                emit_doc(format, json!({"deleted": id}));
             }
             PlanCmd::Log { id, message, author } => {
-                let plan = read_todo(&repo_root)?;
-                let t = plan.task.iter().find(|x| x.id == id);
-                if t.is_none() {
-                    eprintln!("plan: task '{}' not found", id);
-                    std::process::exit(2);
-                }
-                // prefer tasks dir, fallback to archive
-                let mut history_dir = repo_root.join("plan/tasks").join(&id).join("history");
-                if !history_dir.exists() {
-                    history_dir = repo_root.join("plan/archive").join(&id).join("history");
-                }
-                fs::create_dir_all(&history_dir)?;
-                let ts = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs();
-                let filename = format!("{}.md", ts);
-                let path = history_dir.join(&filename);
-                let mut contents = String::new();
-                contents.push_str(&format!("time: {}\n", ts));
-                if let Some(a) = author {
-                    contents.push_str(&format!("author: {}\n", a));
+                let plan = Plan::open(&repo_root)?;
+                let path = plan.log(&id, &message, author.as_deref())?;
+                if text {
+                    println!("Logged event to {}", path.display());
                 }
-                contents.push_str("---\n");
-                contents.push_str(&message);
-                fs::write(&path, contents)?;
-                println!("Logged event to {}", path.display());
+                emit_doc(format, json!({"task": id, "history": path}));
             }
             PlanCmd::Show { id } => {
-                let plan = read_todo(&repo_root)?;
-                let t = plan.task.into_iter().find(|x| x.id == id);
-                if t.is_none() {
-                    eprintln!("plan: task '{}' not found", id);
-                    std::process::exit(2);
-                }
-                let t = t.unwrap();
-                println!("Task {}", t.id);
-                println!(" Kind: {}", t.kind.clone().unwrap_or_default());
-                println!(" Title: {}", t.title.unwrap_or_default());
-                println!(" Status: {}", t.status.unwrap_or_default());
-                println!(" Assignee: {}", t.assignee.unwrap_or_default());
-                println!(" Task file: {}", t.task_file.clone().unwrap_or_default());
-                // show main task content
-                if let Some(tf) = &t.task_file {
-                    let p = repo_root.join("plan").join(tf);
-                    if p.exists() {
-                        if let Ok(s) = fs::read_to_string(&p) {
-                            println!("---");
-                            println!("{}", s);
-                        }
+                let plan = Plan::open(&repo_root)?;
+                let t =
+                    plan.task(&id).ok_or_else(|| CosmosError::TaskNotFound { id: id.clone() })?;
+                let content = plan.task_content(&id);
+                let history = plan.history(&id)?;
+                if text {
+                    println!("Task {}", t.id);
+                    println!(" Kind: {}", t.kind.as_deref().unwrap_or_default());
+                    println!(" Title: {}", t.title.as_deref().unwrap_or_default());
+                    println!(" Status: {}", t.status.as_deref().unwrap_or_default());
+                    println!(" Assignee: {}", t.assignee.as_deref().unwrap_or_default());
+                    println!(" Task file: {}", t.task_file.as_deref().unwrap_or_default());
+                    // show main task content
+                    if let Some(s) = &content {
+                        println!("---");
+                        println!("{}", s);
                     }
-                }
-                // show history entries
-                let mut history_dir = repo_root.join("plan/tasks").join(&t.id).join("history");
-                if !history_dir.exists() {
-                    history_dir = repo_root.join("plan/archive").join(&t.id).join("history");
-                }
-                if history_dir.exists() {
-                    println!("History:");
-                    let mut entries: Vec<_> =
-                        std::fs::read_dir(&history_dir)?.filter_map(|e| e.ok()).collect();
-                    entries.sort_by_key(|e| e.path());
-                    for e in entries {
-                        if let Ok(s) = fs::read_to_string(e.path()) {
+                    // show history entries
+                    if !history.is_empty() {
+                        println!("History:");
+                        for s in &history {
                             println!("---");
                             println!("{}", s);
                         }
                     }
                 }
+                emit_doc(format, json!({"task": t, "content": content, "history": history}));
             }
         },
 
         Commands::Project { sub } => match sub {
             ProjectCmd::GhaOutputs {} => {
-                emit_project_gha_outputs(&repo_root)?;
-            }
-            ProjectCmd::Validate { strict } => {
-                let report = validate_project_manifest(&repo_root, strict)?;
-                println!(
-                    "Project validation summary: {} errors, {} warnings",
-                    report.errors.len(),
-                    report.warnings.len()
-                );
-                if !report.errors.is_empty() {
-                    println!("\nErrors:");
-                    for e in &report.errors {
-                        println!(" - {}", e);
+                let outputs = project::gha_outputs(&repo_root)?;
+                if let Ok(gh_out) = std::env::var("GITHUB_OUTPUT") {
+                    let mut f = std::fs::OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(&gh_out)
+                        .with_context(|| format!("opening {}", gh_out))?;
+                    for (k, v) in &outputs {
+                        writeln!(f, "{}={}", k, v).context("writing GITHUB_OUTPUT")?;
                     }
-                }
-                if !report.warnings.is_empty() {
-                    println!("\nWarnings:");
-                    for w in &report.warnings {
-                        println!(" - {}", w);
+                } else if text {
+                    for (k, v) in &outputs {
+                        println!("{}={}", k, v);
                     }
                 }
+                let map: serde_json::Map<String, Value> =
+                    outputs.into_iter().map(|(k, v)| (k, json!(v))).collect();
+                emit_doc(format, Value::Object(map));
+            }
+            ProjectCmd::Validate { strict } => {
+                let report = project::validate_project_manifest(&repo_root)?;
+                if text {
+                    println!(
+                        "Project validation summary: {} errors, {} warnings",
+                        report.errors.len(),
+                        report.warnings.len()
+                    );
+                    print_issues("Errors", &report.errors);
+                    print_issues("Warnings", &report.warnings);
+                }
+                let mut doc = json!(report);
+                doc["strict"] = json!(strict);
+                emit(format, &doc, &issue_records(&report.errors, &report.warnings));
 
                 if report.has_blocking_issues(strict) {
-                    std::process::exit(2);
+                    return Err(CosmosError::Validation(
+                        "project validation found blocking issues".to_string(),
+                    )
+                    .into());
                 }
             }
         },
@@ -2765,118 +2418,3 @@ if __name__ == "__main__":
 
     Ok(())
 }
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::tempdir;
-
-    #[test]
-    fn it_works() {
-        assert_eq!(2 + 2, 4);
-    }
-
-    #[test]
-    fn test_auto_fix_repo_creates_contributing_and_fixes_plan() -> Result<()> {
-        let td = tempdir()?;
-        let root = td.path();
-
-        // create docs/contributing.md
-        fs::create_dir_all(root.join("docs"))?;
-        fs::write(root.join("docs").join("contributing.md"), "contrib")?;
-
-        // create plan dir and task file exists
-        fs::create_dir_all(root.join("plan").join("tasks").join("0001"))?;
-        fs::write(root.join("plan").join("tasks").join("0001").join("task.md"), "title: x")?;
-
-        // write todo.toml with open status and wrong task_file
-        let todo = r#"[meta]
-owner = "maintainers"
-created = "2025-12-18T12:00:00Z"
-
-[[task]]
-id = "0001"
-title = "Template init"
-status = "open"
-assignee = "maintainer"
-labels = ["template"]
-task_file = "tasks/0001-template.md"
-"#;
-        fs::create_dir_all(root.join("plan"))?;
-        fs::write(root.join("plan").join("todo.toml"), todo)?;
-
-        let _fixes = auto_fix_repo(root)?;
-        assert!(root.join("CONTRIBUTING.md").exists());
-        let plan = read_todo(root)?;
-        assert_eq!(plan.task[0].status.as_deref(), Some("pending_review"));
-        assert_eq!(plan.task[0].task_file.as_deref(), Some("tasks/0001/task.md"));
-        Ok(())
-    }
-
-    #[test]
-    fn project_validate_collects_drift_errors_for_concrete_values() {
-        let td = tempdir().unwrap();
-        let root = td.path();
-
-        let project_toml: toml::Value = toml::from_str(
-            r#"[cosmos]
-schema_version = 1
-
-[project]
-name = "myapp"
-version = "1.0.0"
-
-[artifact]
-outputs = ["binary"]
-"#,
-        )
-        .unwrap();
-        let cargo_toml: toml::Value = toml::from_str(
-            r#"[package]
-name = "other"
-version = "2.0.0"
-edition = "2021"
-"#,
-        )
-        .unwrap();
-
-        let report = collect_project_validation_issues(root, &project_toml, &cargo_toml);
-        assert!(!report.errors.is_empty());
-        assert!(report.errors.iter().any(|e| e.contains("[project].name")));
-        assert!(report.errors.iter().any(|e| e.contains("[project].version")));
-    }
-
-    #[test]
-    fn project_validate_skips_drift_for_placeholders() {
-        let td = tempdir().unwrap();
-        let root = td.path();
-
-        let project_toml: toml::Value = toml::from_str(
-            r#"[cosmos]
-schema_version = 1
-
-[project]
-name = "{{project-name}}"
-version = "{{version}}"
-
-[artifact]
-outputs = ["binary"]
-"#,
-        )
-        .unwrap();
-        let cargo_toml: toml::Value = toml::from_str(
-            r#"[package]
-name = "rust-repo-template"
-version = "0.2.0"
-edition = "2021"
-"#,
-        )
-        .unwrap();
-
-        let report = collect_project_validation_issues(root, &project_toml, &cargo_toml);
-        assert!(report
-            .errors
-            .iter()
-            .all(|e| !e.contains("[project].name") && !e.contains("[project].version")));
-    }
-}
@@ -0,0 +1,572 @@
+//! Component templates for `cosmos add`: small templates that add one
+//! building block (a binary, a test, a workflow, a docs page, ...) to an
+//! existing project.
+//!
+//! A component is a template folder under `components/<name>/` in any
+//! template root (`COSMOS_TEMPLATE_PATH` roots, repository `templates/`, the
+//! embedded copy, exe-relative `templates/`) with an optional
+//! `components/<name>.toml` manifest. Besides creating its files, a component
+//! may patch structured files of the project:
+//!
+//! ```toml
+//! [[patch]]                  # append a table to `[[bin]]`
+//! file = "Cargo.toml"
+//! append = "bin"
+//! value = { name = "{{name}}", path = "src/bin/{{name}}.rs" }
+//!
+//! [[patch]]                  # set a key, creating tables as needed
+//! file = "Cargo.toml"
+//! set = "dependencies.clap"
+//! value = "4"
+//!
+//! [[patch]]                  # add a page to the mkdocs nav; `/` nests sections
+//! file = "mkdocs.yml"
+//! nav = "Guides/{{title}}"
+//! value = "guides/{{name}}.md"
+//!
+//! [[patch]]                  # append text unless it is already there
+//! file = ".gitignore"
+//! text = "/data/\n"
+//! ```
+//!
+//! `file`, `nav`, `text` and every string in `value` are rendered with the
+//! component's variables; `when` makes a patch conditional (see
+//! [`eval_condition`]). Patches are idempotent: an entry that is already
+//! present is left alone.
+//!
+//! [`plan_add`] renders a component and its patches for a project;
+//! [`AddPlan::apply`] writes them in one transaction.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use toml_edit::{ArrayOfTables, Document, Item, Table, TableLike};
+
+use crate::error::CosmosError;
+use crate::template::{
+    self, eval_condition, RenderedFile, Renderer, Template, TemplateFile, TemplateManifest,
+};
+use crate::transaction::{Commit, Transaction};
+
+/// Folder (inside a template root) holding the component templates.
+pub const COMPONENTS_DIR: &str = "components";
+
+/// `[[patch]]` entry of a component manifest; exactly one of `append`,
+/// `set`, `nav` and `text` is given.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PatchSpec {
+    /// File to patch, relative to the project
+    pub file: String,
+    /// Dotted TOML key of an array of tables (or array) to append `value` to
+    #[serde(default)]
+    pub append: Option<String>,
+    /// Dotted TOML key to set to `value`
+    #[serde(default)]
+    pub set: Option<String>,
+    /// mkdocs nav entry (`Section/Title`) pointing at `value`
+    #[serde(default)]
+    pub nav: Option<String>,
+    /// Text appended to the file
+    #[serde(default)]
+    pub text: Option<String>,
+    #[serde(default)]
+    pub value: Option<toml::Value>,
+    /// Only apply when this condition holds
+    #[serde(default)]
+    pub when: Option<String>,
+}
+
+/// What one patch did to its file.
+#[derive(Debug, Clone, Serialize)]
+pub struct PatchOutcome {
+    pub file: PathBuf,
+    pub description: String,
+    /// `false` when the entry was already present
+    pub changed: bool,
+}
+
+/// Locate component `name` like a template (see [`Template::locate`]).
+pub fn locate(repo_root: &Path, name: &str, search_path: &[PathBuf]) -> Result<Option<Template>> {
+    Template::locate(repo_root, &format!("{}/{}", COMPONENTS_DIR, name), None, search_path)
+}
+
+/// Project-relative paths with their new contents.
+pub type PatchedFiles = Vec<(PathBuf, RenderedFile)>;
+
+/// What `cosmos add` does with one component file: `create`, `overwrite`
+/// or `unchanged`.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileAction {
+    pub path: PathBuf,
+    pub action: &'static str,
+}
+
+/// Adding a component to a project (see [`plan_add`]).
+pub struct AddPlan {
+    pub files: Vec<FileAction>,
+    pub outcomes: Vec<PatchOutcome>,
+    /// New contents of every file that is created or patched
+    pub changes: PatchedFiles,
+    create: Vec<TemplateFile>,
+    patched: PatchedFiles,
+}
+
+/// Render component `tpl` for the project in `dest` and apply its manifest
+/// patches in memory. Existing files that differ are an error unless
+/// `force`; unchanged ones are left alone.
+pub fn plan_add(
+    tpl: &Template,
+    renderer: &Renderer,
+    manifest: &TemplateManifest,
+    dest: &Path,
+    force: bool,
+) -> Result<AddPlan> {
+    let mut files = tpl.files();
+    template::mark_raw(&mut files, &manifest.raw);
+    let (files, _) =
+        template::filter_conditional(files, &manifest.conditional, renderer.context())?;
+    let rendered = template::render_all(tpl, &files, renderer)?;
+
+    let mut actions = Vec::new();
+    let mut create = Vec::new();
+    let mut created = Vec::new();
+    for (f, (rel, out)) in files.iter().zip(&rendered) {
+        let path = dest.join(rel);
+        let action = if path.symlink_metadata().is_err() {
+            "create"
+        } else if !template::differs(out, &path) {
+            "unchanged"
+        } else if force {
+            "overwrite"
+        } else {
+            return Err(CosmosError::AlreadyExists(format!(
+                "{} already exists in {} (use --force to overwrite)",
+                rel.display(),
+                dest.display()
+            ))
+            .into());
+        };
+        actions.push(FileAction { path: rel.clone(), action });
+        if action != "unchanged" {
+            create.push(f.clone());
+            created.push((rel.clone(), out.clone()));
+        }
+    }
+    let (patched, outcomes) = apply_patches(&manifest.patch, renderer, dest, &created)?;
+    let mut changes = created;
+    for (rel, out) in &patched {
+        match changes.iter_mut().find(|(r, _)| r == rel) {
+            Some(c) => c.1 = out.clone(),
+            None => changes.push((rel.clone(), out.clone())),
+        }
+    }
+    Ok(AddPlan { files: actions, outcomes, changes, create, patched })
+}
+
+impl AddPlan {
+    /// Write the component files and patched files into `dest` in one
+    /// transaction.
+    pub fn apply(&self, tpl: &Template, renderer: &Renderer, dest: &Path) -> Result<Commit> {
+        let tx = Transaction::begin(dest)?;
+        template::stage_files(tpl, &self.create, renderer, &tx, true)?;
+        for (rel, out) in &self.patched {
+            if let RenderedFile::Contents(bytes) = out {
+                let p = tx.root().join(rel);
+                if let Some(parent) = p.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(&p, bytes).with_context(|| format!("writing {:?}", p))?;
+            }
+        }
+        tx.commit()
+    }
+}
+
+/// Apply `patches` to the project in `dest`. Files the component creates
+/// (`created`) are patched in their rendered form. Returns the new contents
+/// of every file that changed, and what each patch did.
+pub fn apply_patches(
+    patches: &[PatchSpec],
+    renderer: &Renderer,
+    dest: &Path,
+    created: &[(PathBuf, RenderedFile)],
+) -> Result<(PatchedFiles, Vec<PatchOutcome>)> {
+    let mut original: BTreeMap<PathBuf, Option<String>> = BTreeMap::new();
+    let mut current: BTreeMap<PathBuf, Option<String>> = BTreeMap::new();
+    let mut outcomes = Vec::new();
+    for spec in patches {
+        if let Some(cond) = &spec.when {
+            if !eval_condition(cond, renderer.context())? {
+                continue;
+            }
+        }
+        let rel = PathBuf::from(renderer.render_text(&spec.file));
+        if !current.contains_key(&rel) {
+            let text = match created.iter().find(|(p, _)| *p == rel) {
+                Some((_, RenderedFile::Contents(bytes))) => {
+                    Some(String::from_utf8_lossy(bytes).into_owned())
+                }
+                Some((_, RenderedFile::Symlink(_))) => bail!("cannot patch symlink {:?}", rel),
+                None => match std::fs::read_to_string(dest.join(&rel)) {
+                    Ok(s) => Some(s),
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                    Err(e) => return Err(e).with_context(|| format!("reading {:?}", rel)),
+                },
+            };
+            original.insert(rel.clone(), text.clone());
+            current.insert(rel.clone(), text);
+        }
+        let text = current.get_mut(&rel).expect("loaded above");
+        let (description, patched) = patch_one(spec, renderer, text.as_deref())
+            .with_context(|| format!("patching {}", rel.display()))?;
+        outcomes.push(PatchOutcome { file: rel, description, changed: patched.is_some() });
+        if patched.is_some() {
+            *text = patched;
+        }
+    }
+    let changed = current
+        .into_iter()
+        .filter(|(rel, text)| original.get(rel) != Some(text))
+        .filter_map(|(rel, text)| text.map(|t| (rel, RenderedFile::Contents(t.into_bytes()))))
+        .collect();
+    Ok((changed, outcomes))
+}
+
+/// Apply one patch to `current` (`None`: the file does not exist). Returns
+/// a description and the new text, or `None` when nothing changes.
+fn patch_one(
+    spec: &PatchSpec,
+    renderer: &Renderer,
+    current: Option<&str>,
+) -> Result<(String, Option<String>)> {
+    let ops = [&spec.append, &spec.set, &spec.nav, &spec.text];
+    if ops.iter().filter(|o| o.is_some()).count() != 1 {
+        return Err(CosmosError::Validation(format!(
+            "patch of '{}' needs exactly one of append, set, nav or text",
+            spec.file
+        ))
+        .into());
+    }
+    let value = || {
+        spec.value.as_ref().map(|v| render_value(v, renderer)).ok_or_else(|| {
+            CosmosError::Validation(format!("patch of '{}' needs a value", spec.file))
+        })
+    };
+    let existing = |what: &str| {
+        current.ok_or_else(|| {
+            anyhow!(CosmosError::NotFound(format!("{} not found (needed to {})", spec.file, what)))
+        })
+    };
+
+    if let Some(key) = &spec.append {
+        let what = format!("append to {}", key);
+        let patched = toml_append(existing(&what)?, key, &value()?)?;
+        return Ok((what, patched));
+    }
+    if let Some(key) = &spec.set {
+        let what = format!("set {}", key);
+        let patched = toml_set(existing(&what)?, key, &value()?)?;
+        return Ok((what, patched));
+    }
+    if let Some(nav) = &spec.nav {
+        let nav = renderer.render_text(nav);
+        let target = match value()? {
+            toml::Value::String(s) => s,
+            other => other.to_string(),
+        };
+        let what = format!("add nav entry {}", nav);
+        let path: Vec<String> = nav.split('/').map(|s| s.trim().to_string()).collect();
+        return Ok((what.clone(), nav_insert(existing(&what)?, &path, &target)));
+    }
+    let text = renderer.render_text(spec.text.as_deref().unwrap_or_default());
+    let current = current.unwrap_or_default();
+    if !text.trim().is_empty() && current.contains(text.trim_end()) {
+        return Ok(("append text".to_string(), None));
+    }
+    let mut out = current.to_string();
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+    out.push_str(&text);
+    if !out.ends_with('\n') {
+        out.push('\n');
+    }
+    Ok(("append text".to_string(), Some(out)))
+}
+
+fn render_value(v: &toml::Value, renderer: &Renderer) -> toml::Value {
+    match v {
+        toml::Value::String(s) => toml::Value::String(renderer.render_text(s)),
+        toml::Value::Array(a) => {
+            toml::Value::Array(a.iter().map(|v| render_value(v, renderer)).collect())
+        }
+        toml::Value::Table(t) => toml::Value::Table(
+            t.iter().map(|(k, v)| (k.clone(), render_value(v, renderer))).collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// The table holding the last segment of dotted `key`, creating missing
+/// parents as implicit tables.
+fn parent_table<'a>(
+    doc: &'a mut Document,
+    key: &'a str,
+) -> Result<(&'a mut dyn TableLike, &'a str)> {
+    let mut parts: Vec<&str> = key.split('.').map(str::trim).collect();
+    let last = parts.pop().filter(|k| !k.is_empty()).ok_or_else(|| anyhow!("empty key"))?;
+    let mut table: &mut dyn TableLike = doc.as_table_mut();
+    for part in parts {
+        let item = table.entry(part).or_insert_with(|| {
+            let mut t = Table::new();
+            t.set_implicit(true);
+            Item::Table(t)
+        });
+        table = item.as_table_like_mut().ok_or_else(|| anyhow!("{} is not a table", part))?;
+    }
+    Ok((table, last))
+}
+
+/// `value` as a TOML item (tables as `[table]`s).
+fn to_item(value: &toml::Value) -> Result<Item> {
+    let mut wrapper = toml::value::Table::new();
+    wrapper.insert("v".to_string(), value.clone());
+    let doc: Document = toml::to_string(&wrapper)?.parse()?;
+    Ok(doc["v"].clone())
+}
+
+/// A TOML item as a plain value, for comparisons.
+fn plain(item: &Item) -> Option<toml::Value> {
+    let mut doc = Document::new();
+    doc.as_table_mut().insert("v", item.clone());
+    toml::from_str::<toml::Value>(&doc.to_string()).ok()?.get("v").cloned()
+}
+
+fn toml_set(current: &str, key: &str, value: &toml::Value) -> Result<Option<String>> {
+    let mut doc: Document = current.parse().context("parsing TOML")?;
+    let (table, last) = parent_table(&mut doc, key)?;
+    if table.get(last).and_then(plain).as_ref() == Some(value) {
+        return Ok(None);
+    }
+    let item = match to_item(value)? {
+        Item::Table(t) => Item::Value(t.into_inline_table().into()),
+        other => other,
+    };
+    table.insert(last, item);
+    Ok(Some(doc.to_string()))
+}
+
+/// Highest position of any table in `table`, so new tables can go last.
+fn max_position(table: &Table) -> usize {
+    let mut max = table.position().unwrap_or(0);
+    for (_, item) in table.iter() {
+        match item {
+            Item::Table(t) => max = max.max(max_position(t)),
+            Item::ArrayOfTables(aot) => {
+                max = aot.iter().map(max_position).fold(max, usize::max);
+            }
+            _ => {}
+        }
+    }
+    max
+}
+
+/// `value` as a table placed after the last table of the document.
+fn new_table(value: &toml::Value, key: &str, position: usize) -> Result<Table> {
+    let Item::Table(mut t) = to_item(value)? else {
+        bail!("{}: the value must be a table", key);
+    };
+    t.set_position(position);
+    t.decor_mut().set_prefix("\n");
+    Ok(t)
+}
+
+fn toml_append(current: &str, key: &str, value: &toml::Value) -> Result<Option<String>> {
+    let mut doc: Document = current.parse().context("parsing TOML")?;
+    let position = max_position(doc.as_table()) + 1;
+    let (table, last) = parent_table(&mut doc, key)?;
+    let same_name = |t: &toml::Value| match (t.get("name"), value.get("name")) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
+    };
+    match table.get_mut(last) {
+        None => {
+            let mut aot = ArrayOfTables::new();
+            aot.push(new_table(value, key, position)?);
+            table.insert(last, Item::ArrayOfTables(aot));
+        }
+        Some(Item::ArrayOfTables(aot)) => {
+            let present = aot
+                .iter()
+                .filter_map(|t| plain(&Item::Table(t.clone())))
+                .any(|t| t == *value || same_name(&t));
+            if present {
+                return Ok(None);
+            }
+            aot.push(new_table(value, key, position)?);
+        }
+        Some(Item::Value(toml_edit::Value::Array(array))) => {
+            let item = to_item(value)?;
+            let Some(new) = item.as_value() else {
+                bail!("{} is an array: its value must not be a table", key);
+            };
+            if array.iter().any(|v| plain(&Item::Value(v.clone())).as_ref() == Some(value)) {
+                return Ok(None);
+            }
+            array.push(new.clone());
+        }
+        Some(_) => bail!("{} is neither an array nor an array of tables", key),
+    }
+    Ok(Some(doc.to_string()))
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// `(title, is_section)` of a `- Title: target` / `- Section:` nav line.
+fn nav_item(line: &str) -> Option<(String, bool)> {
+    let rest = line.trim_start().strip_prefix("- ")?;
+    let (title, target) = rest.split_once(':')?;
+    let title = title.trim().trim_matches(|c| c == '"' || c == '\'').to_string();
+    Some((title, target.trim().is_empty()))
+}
+
+/// Insert `path` (sections then title) pointing at `target` into the `nav:`
+/// list of an mkdocs.yml. Returns `None` when the title already exists.
+fn nav_insert(text: &str, path: &[String], target: &str) -> Option<String> {
+    let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+    let (start, end) = match lines.iter().position(|l| l.trim_end() == "nav:") {
+        Some(i) => {
+            let mut end = i + 1;
+            while end < lines.len() {
+                let l = &lines[end];
+                if !(l.trim().is_empty() || l.starts_with(' ') || l.starts_with('-')) {
+                    break;
+                }
+                end += 1;
+            }
+            (i + 1, end)
+        }
+        None => {
+            lines.push("nav:".to_string());
+            (lines.len(), lines.len())
+        }
+    };
+    if !nav_insert_at(&mut lines, start, end, 2, path, target) {
+        return None;
+    }
+    let mut out = lines.join("\n");
+    out.push('\n');
+    Some(out)
+}
+
+fn nav_insert_at(
+    lines: &mut Vec<String>,
+    start: usize,
+    end: usize,
+    indent: usize,
+    path: &[String],
+    target: &str,
+) -> bool {
+    let Some((segment, rest)) = path.split_first() else {
+        return false;
+    };
+    let is_item = |l: &String| nav_item(l).is_some();
+    let level = (start..end).filter(|&i| is_item(&lines[i])).map(|i| indent_of(&lines[i])).min();
+    let level = level.unwrap_or(indent);
+    let items: Vec<usize> =
+        (start..end).filter(|&i| is_item(&lines[i]) && indent_of(&lines[i]) == level).collect();
+    let found = items.iter().position(|&i| {
+        nav_item(&lines[i])
+            .is_some_and(|(title, section)| title == *segment && (rest.is_empty() || section))
+    });
+    match (found, rest.is_empty()) {
+        (Some(_), true) => false,
+        (Some(pos), false) => {
+            let i = items[pos];
+            let child_end = items.get(pos + 1).copied().unwrap_or(end);
+            nav_insert_at(lines, i + 1, child_end, level + 4, rest, target)
+        }
+        (None, leaf) => {
+            let at =
+                (start..end).rev().find(|&i| !lines[i].trim().is_empty()).map_or(start, |i| i + 1);
+            let pad = " ".repeat(level);
+            if leaf {
+                lines.insert(at, format!("{}- {}: {}", pad, segment, target));
+                true
+            } else {
+                lines.insert(at, format!("{}- {}:", pad, segment));
+                nav_insert_at(lines, at + 1, at + 1, level + 4, rest, target)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toml_patches_are_idempotent() {
+        let cargo = "[package]\nname = \"demo\"\n\n[dependencies]\nserde = \"1\"\n";
+        let bin: toml::Value =
+            toml::from_str("name = \"tool\"\npath = \"src/bin/tool.rs\"").unwrap();
+        let once = toml_append(cargo, "bin", &bin).unwrap().unwrap();
+        assert!(
+            once.contains("[[bin]]\nname = \"tool\"\npath = \"src/bin/tool.rs\"\n"),
+            "{}",
+            once
+        );
+        assert!(toml_append(&once, "bin", &bin).unwrap().is_none());
+
+        let other: toml::Value = toml::from_str("name = \"other\"").unwrap();
+        let twice = toml_append(&once, "bin", &other).unwrap().unwrap();
+        assert!(
+            twice.ends_with("path = \"src/bin/tool.rs\"\n\n[[bin]]\nname = \"other\"\n"),
+            "{}",
+            twice
+        );
+
+        let dep = toml::Value::String("4".into());
+        let set = toml_set(&once, "dependencies.clap", &dep).unwrap().unwrap();
+        assert!(set.contains("serde = \"1\"\nclap = \"4\"\n"), "{}", set);
+        assert!(toml_set(&set, "dependencies.clap", &dep).unwrap().is_none());
+
+        let ws = "[workspace]\nmembers = [\"a\"]\n";
+        let member = toml::Value::String("b".into());
+        assert_eq!(
+            toml_append(ws, "workspace.members", &member).unwrap().unwrap(),
+            "[workspace]\nmembers = [\"a\", \"b\"]\n"
+        );
+    }
+
+    #[test]
+    fn nav_entries_nest_under_sections() {
+        let yml = "site_name: x\nnav:\n  - Home: index.md\n  - Guides:\n      - Intro: guides/intro.md\ntheme:\n  name: material\n";
+        let path = |s: &str| s.split('/').map(str::to_string).collect::<Vec<_>>();
+        let out = nav_insert(yml, &path("Guides/Deploy"), "guides/deploy.md").unwrap();
+        assert!(
+            out.contains(
+                "      - Intro: guides/intro.md\n      - Deploy: guides/deploy.md\ntheme:"
+            ),
+            "{}",
+            out
+        );
+        assert!(nav_insert(&out, &path("Guides/Deploy"), "guides/deploy.md").is_none());
+
+        let out = nav_insert(yml, &path("Reference/API"), "api.md").unwrap();
+        assert!(
+            out.contains(
+                "      - Intro: guides/intro.md\n  - Reference:\n      - API: api.md\ntheme:"
+            ),
+            "{}",
+            out
+        );
+        let out = nav_insert("site_name: x\n", &path("Home"), "index.md").unwrap();
+        assert_eq!(out, "site_name: x\nnav:\n  - Home: index.md\n");
+    }
+}
@@ -0,0 +1,277 @@
+//! Typed errors and the stable exit-code contract of the `cosmos` CLI.
+//!
+//! Library functions keep returning [`anyhow::Result`]; failures that callers
+//! need to tell apart are raised as [`CosmosError`] and can be recovered with
+//! [`error_kind`] (or `err.downcast_ref::<CosmosError>()`).
+//!
+//! | code | kind                  | meaning                                               |
+//! |------|-----------------------|-------------------------------------------------------|
+//! | 0    | -                     | success (also: user declined a prompt)                |
+//! | 1    | `internal`            | unexpected error                                      |
+//! | 2    | `validation_failed`   | validation reported blocking issues                   |
+//! | 3    | `ai_unavailable`      | AI/LLM support not enabled or provider failed         |
+//! | 4    | `verification_failed` | post-generation fmt/clippy/test failed                |
+//! | 5    | `verification_error`  | verification could not be run                         |
+//! | 6    | `usage`               | invalid arguments or argument combination             |
+//! | 7    | `not_found`           | file, template, hook or manifest not found            |
+//! | 8    | `task_not_found`      | plan task id does not exist                           |
+//! | 9    | `invalid_transition`  | task is not in the status the transition starts from  |
+//! | 10   | `check_failed`        | built-in transition pre-check rejected the change     |
+//! | 11   | `hook_rejected`       | a plan hook script exited non-zero                    |
+//! | 12   | `already_exists`      | the thing to create already exists                    |
+//! | 13   | `io`                  | I/O failure                                           |
+//! | 14   | `parse`               | malformed TOML/JSON/YAML input                        |
+//! | 15   | `render_failed`       | template is malformed or strict rendering failed      |
+//! | 16   | `conflict`            | `cosmos update` left merge conflicts to resolve       |
+//! | 17   | `dirty_worktree`      | the destination has uncommitted git changes           |
+//!
+//! Before this contract, code 2 covered most plan and usage failures as well
+//! as validation; it now means only `validation_failed`, and the others moved
+//! to 6 and 8-12 (see the changelog for the full mapping). Codes 3-5 are
+//! unchanged.
+
+use std::fmt;
+
+use serde::Serialize;
+
+/// Machine-readable error kind with a stable exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    Internal,
+    ValidationFailed,
+    AiUnavailable,
+    VerificationFailed,
+    VerificationError,
+    Usage,
+    NotFound,
+    TaskNotFound,
+    InvalidTransition,
+    CheckFailed,
+    HookRejected,
+    AlreadyExists,
+    Io,
+    Parse,
+    RenderFailed,
+    Conflict,
+    DirtyWorktree,
+}
+
+impl ErrorKind {
+    /// Process exit code for this kind.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            ErrorKind::Internal => 1,
+            ErrorKind::ValidationFailed => 2,
+            ErrorKind::AiUnavailable => 3,
+            ErrorKind::VerificationFailed => 4,
+            ErrorKind::VerificationError => 5,
+            ErrorKind::Usage => 6,
+            ErrorKind::NotFound => 7,
+            ErrorKind::TaskNotFound => 8,
+            ErrorKind::InvalidTransition => 9,
+            ErrorKind::CheckFailed => 10,
+            ErrorKind::HookRejected => 11,
+            ErrorKind::AlreadyExists => 12,
+            ErrorKind::Io => 13,
+            ErrorKind::Parse => 14,
+            ErrorKind::RenderFailed => 15,
+            ErrorKind::Conflict => 16,
+            ErrorKind::DirtyWorktree => 17,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorKind::Internal => "internal",
+            ErrorKind::ValidationFailed => "validation_failed",
+            ErrorKind::AiUnavailable => "ai_unavailable",
+            ErrorKind::VerificationFailed => "verification_failed",
+            ErrorKind::VerificationError => "verification_error",
+            ErrorKind::Usage => "usage",
+            ErrorKind::NotFound => "not_found",
+            ErrorKind::TaskNotFound => "task_not_found",
+            ErrorKind::InvalidTransition => "invalid_transition",
+            ErrorKind::CheckFailed => "check_failed",
+            ErrorKind::HookRejected => "hook_rejected",
+            ErrorKind::AlreadyExists => "already_exists",
+            ErrorKind::Io => "io",
+            ErrorKind::Parse => "parse",
+            ErrorKind::RenderFailed => "render_failed",
+            ErrorKind::Conflict => "conflict",
+            ErrorKind::DirtyWorktree => "dirty_worktree",
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Errors callers may want to react to programmatically.
+#[derive(Debug)]
+pub enum CosmosError {
+    /// Invalid argument value or combination
+    Usage(String),
+    /// A file, template, hook or manifest does not exist
+    NotFound(String),
+    /// A plan task id does not exist
+    TaskNotFound { id: String },
+    /// The task is not in the status the transition starts from
+    InvalidTransition { id: String, expected: String, actual: String, hint: Option<String> },
+    /// A built-in transition pre-check rejected the change
+    CheckFailed { id: String, message: String, hint: Option<String> },
+    /// A plan hook script exited non-zero
+    HookRejected { hook: String, id: String, output: String },
+    /// The thing to create already exists
+    AlreadyExists(String),
+    /// Validation reported blocking issues
+    Validation(String),
+    /// Post-generation verification steps failed
+    VerificationFailed(String),
+    /// Verification could not be run
+    VerificationError(String),
+    /// AI/LLM support is not enabled or the provider failed
+    AiUnavailable(String),
+    /// The template is malformed (bad `when` condition, `extends` cycle) or
+    /// strict rendering reported errors
+    Render(String),
+    /// A template update left merge conflicts
+    Conflict(String),
+    /// The destination has uncommitted changes (see `--allow-dirty`)
+    DirtyWorktree(String),
+}
+
+impl CosmosError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            CosmosError::Usage(_) => ErrorKind::Usage,
+            CosmosError::NotFound(_) => ErrorKind::NotFound,
+            CosmosError::TaskNotFound { .. } => ErrorKind::TaskNotFound,
+            CosmosError::InvalidTransition { .. } => ErrorKind::InvalidTransition,
+            CosmosError::CheckFailed { .. } => ErrorKind::CheckFailed,
+            CosmosError::HookRejected { .. } => ErrorKind::HookRejected,
+            CosmosError::AlreadyExists(_) => ErrorKind::AlreadyExists,
+            CosmosError::Validation(_) => ErrorKind::ValidationFailed,
+            CosmosError::VerificationFailed(_) => ErrorKind::VerificationFailed,
+            CosmosError::VerificationError(_) => ErrorKind::VerificationError,
+            CosmosError::AiUnavailable(_) => ErrorKind::AiUnavailable,
+            CosmosError::Render(_) => ErrorKind::RenderFailed,
+            CosmosError::Conflict(_) => ErrorKind::Conflict,
+            CosmosError::DirtyWorktree(_) => ErrorKind::DirtyWorktree,
+        }
+    }
+
+    /// Human hint explaining how to get past the error, if any.
+    pub fn hint(&self) -> Option<&str> {
+        match self {
+            CosmosError::InvalidTransition { hint, .. } | CosmosError::CheckFailed { hint, .. } => {
+                hint.as_deref()
+            }
+            CosmosError::DirtyWorktree(_) => {
+                Some("commit or stash the changes first, or pass --allow-dirty")
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for CosmosError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CosmosError::Usage(m)
+            | CosmosError::NotFound(m)
+            | CosmosError::AlreadyExists(m)
+            | CosmosError::Validation(m)
+            | CosmosError::VerificationFailed(m)
+            | CosmosError::VerificationError(m)
+            | CosmosError::AiUnavailable(m)
+            | CosmosError::Render(m)
+            | CosmosError::Conflict(m)
+            | CosmosError::DirtyWorktree(m) => f.write_str(m),
+            CosmosError::TaskNotFound { id } => write!(f, "plan: task '{}' not found", id),
+            CosmosError::InvalidTransition { id, expected, actual, .. } => write!(
+                f,
+                "plan: cannot change status for {}: expected '{}' but current status is '{}'",
+                id, expected, actual
+            ),
+            CosmosError::CheckFailed { message, .. } => f.write_str(message),
+            CosmosError::HookRejected { hook, id, output } => {
+                write!(f, "hook '{}' failed for {}: {}", hook, id, output)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CosmosError {}
+
+/// Classify any error returned by this crate: a [`CosmosError`] anywhere in
+/// the chain wins, then I/O and parse failures, otherwise `internal`.
+pub fn error_kind(err: &anyhow::Error) -> ErrorKind {
+    if let Some(e) = err.chain().find_map(|c| c.downcast_ref::<CosmosError>()) {
+        return e.kind();
+    }
+    for cause in err.chain() {
+        if cause.is::<std::io::Error>() {
+            return ErrorKind::Io;
+        }
+        if cause.is::<toml::de::Error>()
+            || cause.is::<serde_json::Error>()
+            || cause.is::<serde_yaml::Error>()
+        {
+            return ErrorKind::Parse;
+        }
+    }
+    ErrorKind::Internal
+}
+
+/// Hint attached to a [`CosmosError`] in the chain, if any.
+pub fn error_hint(err: &anyhow::Error) -> Option<&str> {
+    err.chain().find_map(|c| c.downcast_ref::<CosmosError>()).and_then(CosmosError::hint)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn exit_codes_are_unique() {
+        let kinds = [
+            ErrorKind::Internal,
+            ErrorKind::ValidationFailed,
+            ErrorKind::AiUnavailable,
+            ErrorKind::VerificationFailed,
+            ErrorKind::VerificationError,
+            ErrorKind::Usage,
+            ErrorKind::NotFound,
+            ErrorKind::TaskNotFound,
+            ErrorKind::InvalidTransition,
+            ErrorKind::CheckFailed,
+            ErrorKind::HookRejected,
+            ErrorKind::AlreadyExists,
+            ErrorKind::Io,
+            ErrorKind::Parse,
+            ErrorKind::RenderFailed,
+            ErrorKind::Conflict,
+            ErrorKind::DirtyWorktree,
+        ];
+        let mut codes: Vec<u8> = kinds.iter().map(|k| k.exit_code()).collect();
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), kinds.len());
+    }
+
+    #[test]
+    fn error_kind_looks_through_context() {
+        let err = anyhow::Error::new(CosmosError::TaskNotFound { id: "0007".into() })
+            .context("running transition");
+        assert_eq!(error_kind(&err), ErrorKind::TaskNotFound);
+
+        let io: anyhow::Result<String> =
+            std::fs::read_to_string("/nonexistent/cosmos").context("reading file");
+        assert_eq!(error_kind(&io.unwrap_err()), ErrorKind::Io);
+    }
+}
@@ -0,0 +1,161 @@
+//! Exclusion rules in gitignore syntax: `.cosmosignore` files and the
+//! manifest's `protected = [...]` list.
+//!
+//! - A template's `.cosmosignore` lists template files that are never
+//!   generated (see [`crate::template::Template::ignored`]).
+//! - A destination's `.cosmosignore`, the manifest `protected` globs and the
+//!   built-in [`BUILTIN_PROTECTED`] list name destination files that are
+//!   never deleted or overwritten (see [`Rules::destination`]).
+//!
+//! Every match reports the rule behind it, e.g. `.cosmosignore:3: *.local`.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use serde::Serialize;
+
+/// Name of the ignore file in templates and destinations.
+pub const IGNORE_FILE: &str = ".cosmosignore";
+
+/// Destination paths that are always protected: VCS data, third-party
+/// folders, lockfiles and the destination's own ignore file.
+pub const BUILTIN_PROTECTED: &[&str] =
+    &["/.git", "/node_modules", "/target", "/.venv", "Cargo.lock", "/.cosmosignore"];
+
+/// The rule a path matched: where it is written and the pattern.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Rule {
+    /// `.cosmosignore:3`, `protected` (manifest) or `built-in`
+    pub source: String,
+    pub pattern: String,
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.source, self.pattern)
+    }
+}
+
+/// One set of patterns and where they come from.
+struct RuleSet {
+    /// `.cosmosignore`, `protected` or `built-in`
+    label: String,
+    /// Lines are numbered in the rule source (ignore files)
+    numbered: bool,
+    lines: Vec<String>,
+    matcher: Gitignore,
+}
+
+/// Ordered sets of gitignore patterns; the first set with a match wins, and
+/// a `!pattern` in a set only re-includes paths for that set.
+#[derive(Default)]
+pub struct Rules {
+    sets: Vec<RuleSet>,
+}
+
+impl Rules {
+    /// Rules protecting destination files: `dest/.cosmosignore`, then the
+    /// manifest's `protected` globs, then [`BUILTIN_PROTECTED`].
+    pub fn destination(dest: &Path, protected: &[String]) -> Result<Rules> {
+        let mut rules = Rules::default();
+        rules.add_file(&dest.join(IGNORE_FILE))?;
+        rules.add_patterns("protected", protected)?;
+        rules.add_patterns("built-in", BUILTIN_PROTECTED)?;
+        Ok(rules)
+    }
+
+    /// Add the patterns of an ignore file, if it exists.
+    pub fn add_file(&mut self, path: &Path) -> Result<()> {
+        let Ok(text) = std::fs::read_to_string(path) else {
+            return Ok(());
+        };
+        let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        self.add_set(name, true, text.lines()).with_context(|| format!("parsing {:?}", path))
+    }
+
+    /// Add a list of patterns (one per entry) under `label`.
+    pub fn add_patterns<S: AsRef<str>>(&mut self, label: &str, patterns: &[S]) -> Result<()> {
+        self.add_set(label.to_string(), false, patterns.iter().map(AsRef::as_ref))
+            .with_context(|| format!("parsing the `{}` patterns", label))
+    }
+
+    fn add_set<'a>(
+        &mut self,
+        label: String,
+        numbered: bool,
+        lines: impl Iterator<Item = &'a str>,
+    ) -> Result<()> {
+        let lines: Vec<String> = lines.map(str::to_string).collect();
+        if lines.iter().all(|l| l.trim().is_empty() || l.starts_with('#')) {
+            return Ok(());
+        }
+        let mut builder = GitignoreBuilder::new("");
+        for line in &lines {
+            builder.add_line(None, line)?;
+        }
+        self.sets.push(RuleSet { label, numbered, lines, matcher: builder.build()? });
+        Ok(())
+    }
+
+    /// Whether there are no rules at all.
+    pub fn is_empty(&self) -> bool {
+        self.sets.is_empty()
+    }
+
+    /// The rule matching `rel` (relative to the rules' root) or one of its
+    /// parent folders.
+    pub fn matched(&self, rel: &Path, is_dir: bool) -> Option<Rule> {
+        let rel = normalize(rel);
+        if rel.as_os_str().is_empty() {
+            return None;
+        }
+        for set in &self.sets {
+            let m = set.matcher.matched_path_or_any_parents(&rel, is_dir);
+            if m.is_none() || m.is_whitelist() {
+                continue;
+            }
+            let glob = m.inner()?;
+            let pattern = glob.original().to_string();
+            let source = match set.lines.iter().position(|l| l.trim_end() == pattern) {
+                Some(i) if set.numbered => format!("{}:{}", set.label, i + 1),
+                _ => set.label.clone(),
+            };
+            return Some(Rule { source, pattern });
+        }
+        None
+    }
+}
+
+/// `a/./b` as `a/b`; the matcher wants clean relative paths.
+fn normalize(rel: &Path) -> PathBuf {
+    rel.components().filter(|c| matches!(c, std::path::Component::Normal(_))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_report_the_matching_line() {
+        let td = tempfile::tempdir().unwrap();
+        std::fs::write(
+            td.path().join(IGNORE_FILE),
+            "# local files\n*.local\n\ndrafts/\n!keep.local\n",
+        )
+        .unwrap();
+        let rules = Rules::destination(td.path(), &["secrets/**".to_string()]).unwrap();
+        let rule = |p: &str| rules.matched(Path::new(p), false).map(|r| r.to_string());
+
+        assert_eq!(rule("config/app.local").as_deref(), Some(".cosmosignore:2: *.local"));
+        assert_eq!(rule("docs/drafts/intro.md").as_deref(), Some(".cosmosignore:4: drafts/"));
+        assert_eq!(rule("secrets/key.pem").as_deref(), Some("protected: secrets/**"));
+        assert_eq!(rule("target/debug/app").as_deref(), Some("built-in: /target"));
+        assert_eq!(rule("crates/core/Cargo.lock").as_deref(), Some("built-in: Cargo.lock"));
+        assert_eq!(rule(".cosmosignore").as_deref(), Some("built-in: /.cosmosignore"));
+        assert_eq!(rule("keep.local"), None);
+        assert_eq!(rule("src/target/mod.rs"), None);
+        assert_eq!(rule("README.md"), None);
+    }
+}
@@ -0,0 +1,739 @@
+//! `cosmos generate` as a library: select and render the template files
+//! ([`Selection`], [`Generator::prepare`]), plan the sync against the
+//! destination ([`Generator::plan`]) and apply it in one [`Transaction`]
+//! ([`Generator::apply`]), or pack it into an archive
+//! ([`Generator::archive`]).
+//!
+//! Questions (confirming the sync, per-file answers with `--interactive`)
+//! go through a [`Prompt`]; the `cosmos` binary answers them on the
+//! terminal and prints the plans and results it gets back.
+//!
+//! ```no_run
+//! use std::path::Path;
+//! use rust_repo_template::generate::{Generator, Prompt, Selection, SyncOptions};
+//! use rust_repo_template::template::{self, CategoryFilter, Renderer, Template};
+//!
+//! # fn run(prompt: &mut dyn Prompt) -> anyhow::Result<()> {
+//! let root = Path::new(".");
+//! let tpl = Template::locate(root, "default", None, &[])?.expect("template");
+//! let filter = CategoryFilter::new(vec!["all".into()], Vec::new());
+//! let (mut sel, variables) = Selection::new(tpl.manifest()?, filter)?;
+//! let mut ctx = template::build_context(Some("demo".into()), Vec::new());
+//! template::resolve_variables(&variables, &mut ctx, None)?;
+//! let renderer = Renderer::new(ctx).partials(&tpl.partials()?)?;
+//! sel.resolve_members(&tpl, &renderer, root)?;
+//!
+//! let generator = Generator::new(&tpl, &renderer, &sel);
+//! let prepared = generator.prepare()?;
+//! let plan = generator.plan(&prepared, Path::new("demo"), false)?;
+//! let opts = SyncOptions { yes: true, ..SyncOptions::default() };
+//! generator.apply(&prepared, &plan, Path::new("demo"), &opts, prompt)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::archive::{self, ArchiveFormat};
+use crate::error::CosmosError;
+use crate::exclude::Rules;
+use crate::template::{
+    self, CategoryFilter, ConditionalRule, ExcludedFile, IgnoredFile, RenderedFile, Renderer,
+    SyncPlan, Template, TemplateFile, TemplateManifest, TemplateSource, VariableSpec,
+};
+use crate::transaction::{Commit, Transaction};
+use crate::update::{self, Choice, LockedTemplate, Resolution, TemplateLock};
+use crate::verify::{self, VerifyConfig, VerifyOptions, VerifyStep};
+use crate::workspace::{self, Member, MemberSpec};
+
+/// Manifest file rules (categories, raw globs, conditions) and the
+/// `--category` / `--exclude-category` selection.
+pub struct Selection {
+    pub categories: HashMap<String, Vec<String>>,
+    pub filter: CategoryFilter,
+    pub raw: Vec<String>,
+    pub conditional: Vec<ConditionalRule>,
+    pub version: Option<String>,
+    pub member_specs: Vec<MemberSpec>,
+    /// Manifest `protected` globs (see [`Rules::destination`])
+    pub protected: Vec<String>,
+    /// Manifest `[verify]` steps (see [`verify::resolve_steps`])
+    pub verify: Option<VerifyConfig>,
+    /// Workspace members, once resolved against the variables (see [`Selection::resolve_members`])
+    pub members: Vec<Member>,
+}
+
+impl Selection {
+    /// Apply `filter` to the manifest (built-in categories without one);
+    /// also returns the declared variables.
+    pub fn new(
+        manifest: Option<TemplateManifest>,
+        filter: CategoryFilter,
+    ) -> Result<(Selection, BTreeMap<String, VariableSpec>)> {
+        let m = manifest.unwrap_or_else(|| TemplateManifest {
+            categories: template::default_categories(),
+            ..TemplateManifest::default()
+        });
+        filter.check(&m.categories)?;
+        let sel = Selection {
+            categories: m.categories,
+            filter,
+            raw: m.raw,
+            conditional: m.conditional,
+            version: m.version,
+            member_specs: m.members,
+            protected: m.protected,
+            verify: m.verify,
+            members: Vec::new(),
+        };
+        Ok((sel, m.variables))
+    }
+
+    /// Resolve the manifest's `[[members]]`, keeping the member files the
+    /// category selection includes (and the members with any).
+    pub fn resolve_members(
+        &mut self,
+        tpl: &Template,
+        renderer: &Renderer,
+        repo_root: &Path,
+    ) -> Result<()> {
+        let search_path = template::template_search_path();
+        let mut members =
+            workspace::resolve(&self.member_specs, tpl, renderer, repo_root, &search_path)?;
+        for m in &mut members {
+            let files: Vec<TemplateFile> =
+                m.files.iter().filter(|f| self.selects(&m.dest_rel(f))).cloned().collect();
+            m.files = files;
+        }
+        // members the selection leaves empty are not touched at all
+        members.retain(|m| !m.files.is_empty());
+        self.members = members;
+        Ok(())
+    }
+
+    /// Categories of the destination path `rel`.
+    pub fn categories_of(&self, rel: &Path) -> Vec<String> {
+        template::file_categories(&self.categories, rel)
+    }
+
+    /// Whether the category filter keeps `rel`.
+    pub fn selects(&self, rel: &Path) -> bool {
+        self.filter.selects(&self.categories_of(rel))
+    }
+}
+
+/// Sync flags of `cosmos generate`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SyncOptions {
+    /// Apply without asking (`--yes`)
+    pub yes: bool,
+    /// With `yes`, also delete files that are not part of the template
+    pub allow_delete: bool,
+    /// Overwrite existing files that differ
+    pub force: bool,
+    /// Ask about every differing and extra file (see [`Prompt::differing`])
+    pub interactive: bool,
+}
+
+/// What to do with a destination file that differs from the template output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileChoice {
+    Keep,
+    Overwrite,
+    /// Write the template output next to the file (see [`update::SIDE_BY_SIDE_SUFFIX`])
+    SideBySide,
+    /// Three-way merge in an editor (see [`merge_in_editor`]); text files only
+    Merge,
+}
+
+/// Questions asked while applying a generation. Answers of `None` quit
+/// without changing anything.
+pub trait Prompt {
+    /// Show a progress or warning line.
+    fn say(&mut self, line: &str);
+    /// Apply `plan`, deleting `plan.extra`? Only asked without `--yes`.
+    fn confirm(&mut self, plan: &SyncPlan) -> Result<bool>;
+    /// What to do with `rel`, whose template output `out` differs from the
+    /// file in `dest` (`--interactive`).
+    fn differing(
+        &mut self,
+        rel: &Path,
+        out: &RenderedFile,
+        dest: &Path,
+    ) -> Result<Option<FileChoice>>;
+    /// Delete `rel`, which is not part of the template (`--interactive`)?
+    fn delete_extra(&mut self, rel: &Path) -> Result<Option<bool>>;
+}
+
+/// Selected template files and their output.
+pub struct Prepared {
+    /// Selected template files (workspace member files not included)
+    pub files: Vec<TemplateFile>,
+    /// Files left out by a condition
+    pub excluded: Vec<ExcludedFile>,
+    /// Files left out by the template's `.cosmosignore`
+    pub ignored: Vec<IgnoredFile>,
+    /// Output of `files` and the members, by destination path
+    pub rendered: Vec<(PathBuf, RenderedFile)>,
+}
+
+impl Prepared {
+    /// Nothing selected: neither template files nor members.
+    pub fn is_empty(&self) -> bool {
+        self.rendered.is_empty()
+    }
+}
+
+/// A successful [`Generator::apply`].
+pub struct Applied {
+    /// Existing files left alone (no `--force`)
+    pub skipped: Vec<PathBuf>,
+    /// The project's existing `Cargo.toml` got the new workspace members
+    pub workspace_updated: bool,
+    /// Nested template folders were flattened into the destination
+    pub flattened: bool,
+    pub commit: Commit,
+}
+
+/// A generation packed by [`Generator::archive`].
+pub struct Packed {
+    pub bytes: Vec<u8>,
+    /// Number of archive entries
+    pub count: usize,
+    /// Verification run on the staged project
+    pub steps: Vec<VerifyStep>,
+}
+
+/// One template rendered with one set of variables and a [`Selection`].
+pub struct Generator<'a> {
+    pub tpl: &'a Template,
+    pub renderer: &'a Renderer,
+    pub sel: &'a Selection,
+}
+
+impl<'a> Generator<'a> {
+    pub fn new(tpl: &'a Template, renderer: &'a Renderer, sel: &'a Selection) -> Self {
+        Generator { tpl, renderer, sel }
+    }
+
+    /// Template files selected by category and condition, raw files marked.
+    pub fn select(&self) -> Result<(Vec<TemplateFile>, Vec<ExcludedFile>)> {
+        let mut files =
+            template::filter_by_category(self.tpl.files(), &self.sel.categories, &self.sel.filter);
+        template::mark_raw(&mut files, &self.sel.raw);
+        template::filter_conditional(files, &self.sel.conditional, self.renderer.context())
+    }
+
+    /// Select the files and render them with the workspace members (listed
+    /// in the root `Cargo.toml`). Nothing is rendered for an empty selection.
+    pub fn prepare(&self) -> Result<Prepared> {
+        let (files, excluded) = self.select()?;
+        let ignored = self.tpl.ignored()?;
+        let rendered = if files.is_empty() && self.sel.members.is_empty() {
+            Vec::new()
+        } else {
+            self.render(&files)?
+        };
+        Ok(Prepared { files, excluded, ignored, rendered })
+    }
+
+    fn render(&self, files: &[TemplateFile]) -> Result<Vec<(PathBuf, RenderedFile)>> {
+        let mut rendered = template::render_all(self.tpl, files, self.renderer)?;
+        if self.sel.members.is_empty() {
+            return Ok(rendered);
+        }
+        let paths: Vec<PathBuf> = self.sel.members.iter().map(|m| m.path.clone()).collect();
+        for (rel, out) in &mut rendered {
+            if let (true, RenderedFile::Contents(bytes)) =
+                (rel.as_path() == Path::new("Cargo.toml"), &*out)
+            {
+                let text = String::from_utf8_lossy(bytes).into_owned();
+                if let Some(new) = workspace::set_members(&text, &paths)? {
+                    *out = RenderedFile::Contents(new.into_bytes());
+                }
+            }
+        }
+        for m in &self.sel.members {
+            rendered.extend(m.render()?);
+        }
+        Ok(rendered)
+    }
+
+    /// With a strict renderer, fail on any render error of the template or
+    /// its members.
+    pub fn check_strict(&self, prepared: &Prepared) -> Result<()> {
+        if !self.renderer.is_strict() {
+            return Ok(());
+        }
+        let mut issues = template::check_render(&prepared.files, self.renderer)?;
+        for m in &self.sel.members {
+            issues.extend(template::check_render(&m.files, &m.renderer)?);
+        }
+        if issues.is_empty() {
+            return Ok(());
+        }
+        let lines: Vec<String> = issues.iter().map(|i| format!("  {}", i)).collect();
+        Err(CosmosError::Render(format!(
+            "{} template render error(s); nothing was written:\n{}",
+            issues.len(),
+            lines.join("\n")
+        ))
+        .into())
+    }
+
+    /// The sync plan against `dest`, keeping its protected files.
+    pub fn plan(&self, prepared: &Prepared, dest: &Path, force: bool) -> Result<SyncPlan> {
+        let protect = Rules::destination(dest, &self.sel.protected)?;
+        template::plan_sync_protected(&prepared.rendered, dest, force, &protect)
+    }
+
+    /// Write the generation into `dest` in one transaction, after asking
+    /// `prompt` as `opts` require. Returns `None` when the user declined.
+    pub fn apply(
+        &self,
+        prepared: &Prepared,
+        plan: &SyncPlan,
+        dest: &Path,
+        opts: &SyncOptions,
+        prompt: &mut dyn Prompt,
+    ) -> Result<Option<Applied>> {
+        let (tpl, renderer, sel) = (self.tpl, self.renderer, self.sel);
+        let rendered = &prepared.rendered;
+        let mut resolutions = BTreeMap::new();
+        let mut to_write = prepared.files.clone();
+        let mut only: Option<BTreeSet<PathBuf>> = None;
+        let mut force = opts.force;
+        let mut deletes = Vec::new();
+        let mut side_by_side = Vec::new();
+        let mut merged = Vec::new();
+        let needs_answers = plan.has_changes() || !plan.skipped_overwrite.is_empty();
+        if opts.interactive && dest.exists() && needs_answers {
+            let remembered = TemplateLock::read(dest)?.map(|l| l.resolutions).unwrap_or_default();
+            let Some(answers) = resolve_per_file(plan, rendered, dest, &remembered, prompt)? else {
+                return Ok(None);
+            };
+            side_by_side = answers.side_by_side;
+            merged = answers.merged;
+            deletes = answers.delete;
+            // new files plus the ones the user chose to overwrite
+            let chosen: BTreeSet<PathBuf> =
+                plan.missing.iter().chain(&answers.overwrite).cloned().collect();
+            to_write = prepared
+                .files
+                .iter()
+                .filter(|f| chosen.contains(&tpl.dest_rel(f, renderer)))
+                .cloned()
+                .collect();
+            only = Some(chosen);
+            force = true;
+            resolutions = answers.resolutions;
+        } else if dest.exists() && plan.has_changes() {
+            if !confirm_sync(plan, opts, prompt)? {
+                return Ok(None);
+            }
+            deletes = plan.extra.clone();
+        }
+        if only.is_none() && !plan.protected.is_empty() {
+            // protected files keep the project's copy, even with --force
+            let kept: BTreeSet<&PathBuf> = plan.protected.iter().collect();
+            to_write.retain(|f| !kept.contains(&tpl.dest_rel(f, renderer)));
+            let members = sel.members.iter().flat_map(|m| m.files.iter().map(|f| m.dest_rel(f)));
+            only = Some(members.filter(|rel| !kept.contains(rel)).collect());
+        }
+
+        // stage everything, then move it into place in one go
+        let protect = Rules::destination(dest, &sel.protected)?;
+        let mut tx = Transaction::begin(dest)?;
+        let mut report = template::stage_files(tpl, &to_write, renderer, &tx, force)?;
+        for m in &sel.members {
+            let r = m.stage(&tx, only.as_ref(), force)?;
+            report.written.extend(r.written);
+            report.skipped.extend(r.skipped);
+        }
+        let workspace_updated = protect.matched(Path::new("Cargo.toml"), false).is_none()
+            && stage_workspace_members(&tx, sel)?;
+        for rel in &side_by_side {
+            if let Some((_, RenderedFile::Contents(bytes))) =
+                rendered.iter().find(|(r, _)| r == rel)
+            {
+                let staged = tx.root().join(rel);
+                if let Some(parent) = staged.parent() {
+                    fs::create_dir_all(parent).with_context(|| format!("creating {:?}", parent))?;
+                }
+                let mut p = staged.into_os_string();
+                p.push(update::SIDE_BY_SIDE_SUFFIX);
+                fs::write(&p, bytes).with_context(|| format!("writing {:?}", p))?;
+            }
+        }
+        for (rel, bytes) in &merged {
+            let p = tx.root().join(rel);
+            if let Some(parent) = p.parent() {
+                fs::create_dir_all(parent).with_context(|| format!("creating {:?}", parent))?;
+            }
+            fs::write(&p, bytes).with_context(|| format!("writing {:?}", p))?;
+        }
+        for rel in deletes {
+            tx.delete(rel);
+        }
+        let mut lock = self.lock(dest, resolutions)?;
+        update::stage_generation(&mut tx, &mut lock, rendered)?;
+        let commit = tx.commit()?;
+        let flattened = template::flatten_nested(dest, &tpl.name)?;
+        Ok(Some(Applied { skipped: report.skipped, workspace_updated, flattened, commit }))
+    }
+
+    /// The plan of an archive: every file is added, the [`bookkeeping`]
+    /// files included, so it lists exactly the entries that are packed.
+    pub fn archive_plan(&self, prepared: &Prepared) -> Result<SyncPlan> {
+        let work = tempfile::Builder::new().prefix("cosmos-archive-").tempdir()?;
+        let dest = work.path().join(&self.tpl.name);
+        let mut plan = template::plan_sync_rendered(&prepared.rendered, &dest, false)?;
+        plan.missing.extend(bookkeeping(&prepared.rendered));
+        Ok(plan)
+    }
+
+    /// Stage the generation into an empty folder as [`Generator::apply`]
+    /// would, run the verification there when `verify` is set (progress
+    /// lines go to `say`) and pack it. A project that fails verification is
+    /// not packed.
+    pub fn archive(
+        &self,
+        prepared: &Prepared,
+        format: ArchiveFormat,
+        verify: Option<&VerifyOptions>,
+        say: &dyn Fn(&str),
+    ) -> Result<Packed> {
+        let (tpl, renderer, sel) = (self.tpl, self.renderer, self.sel);
+        let work = tempfile::Builder::new().prefix("cosmos-archive-").tempdir()?;
+        let dest = work.path().join(&tpl.name);
+        let mut tx = Transaction::begin(&dest)?;
+        template::stage_files(tpl, &prepared.files, renderer, &tx, true)?;
+        for m in &sel.members {
+            m.stage(&tx, None, true)?;
+        }
+        stage_workspace_members(&tx, sel)?;
+        let mut lock = self.lock(&dest, BTreeMap::new())?;
+        update::stage_generation(&mut tx, &mut lock, &prepared.rendered)?;
+        template::flatten_nested(tx.root(), &tpl.name)?;
+        let mut bytes = Vec::new();
+        let count = archive::write_archive(tx.root(), format, &mut bytes)?;
+
+        let steps = match verify {
+            Some(opts) => verify::verify_project(tx.root(), sel.verify.as_ref(), opts, say)?,
+            None => Vec::new(),
+        };
+        verify::check(&steps)?;
+        Ok(Packed { bytes, count, steps })
+    }
+
+    /// `.cosmos/template.lock` for generating into `dest`, keeping the
+    /// remembered answers of earlier interactive runs.
+    pub fn lock(
+        &self,
+        dest: &Path,
+        resolutions: BTreeMap<String, Resolution>,
+    ) -> Result<TemplateLock> {
+        let tpl = self.tpl;
+        let mut remembered = TemplateLock::read(dest)?.map(|l| l.resolutions).unwrap_or_default();
+        remembered.extend(resolutions);
+        let path = match tpl.source {
+            TemplateSource::Dir => {
+                Some(tpl.root.canonicalize().unwrap_or_else(|_| tpl.root.clone()))
+            }
+            _ => None,
+        };
+        let mut lock = TemplateLock {
+            template: LockedTemplate {
+                name: tpl.name.clone(),
+                source: tpl.source,
+                path,
+                version: self.sel.version.clone(),
+                hash: tpl.content_hash()?,
+            },
+            categories: self.sel.filter.include.clone(),
+            exclude_categories: self.sel.filter.exclude.clone(),
+            variables: BTreeMap::new(),
+            files: BTreeMap::new(),
+            resolutions: remembered,
+        };
+        lock.set_context(self.renderer.context());
+        Ok(lock)
+    }
+}
+
+/// Files written next to a generation of `rendered` for `cosmos update`:
+/// [`update::LOCK_FILE`] and the snapshot of every rendered file (symlinks
+/// excepted) under [`update::BASE_DIR`].
+pub fn bookkeeping(rendered: &[(PathBuf, RenderedFile)]) -> Vec<PathBuf> {
+    let snapshot = rendered.iter().filter_map(|(rel, out)| match out {
+        RenderedFile::Contents(_) => Some(Path::new(update::BASE_DIR).join(rel)),
+        RenderedFile::Symlink(_) => None,
+    });
+    std::iter::once(PathBuf::from(update::LOCK_FILE)).chain(snapshot).collect()
+}
+
+/// Copy the `matches` of the manifest patterns (paths under `repo_root`)
+/// into `dest` in one transaction, confirming changes of an existing
+/// destination first. Returns the existing files left alone and the commit,
+/// `None` when the user declined.
+pub fn apply_patterns(
+    repo_root: &Path,
+    matches: &[PathBuf],
+    plan: &SyncPlan,
+    dest: &Path,
+    opts: &SyncOptions,
+    prompt: &mut dyn Prompt,
+) -> Result<Option<(Vec<PathBuf>, Commit)>> {
+    if dest.exists() && plan.has_changes() && !confirm_sync(plan, opts, prompt)? {
+        return Ok(None);
+    }
+    let mut tx = Transaction::begin(dest)?;
+    let skipped = template::stage_paths(repo_root, matches, &tx, opts.force)?;
+    for rel in &plan.extra {
+        tx.delete(rel);
+    }
+    Ok(Some((skipped, tx.commit()?)))
+}
+
+/// Confirm the sync, including deleting extras: `--yes` confirms (deletions
+/// only with `--allow-delete`), otherwise `prompt` is asked.
+fn confirm_sync(plan: &SyncPlan, opts: &SyncOptions, prompt: &mut dyn Prompt) -> Result<bool> {
+    if !opts.yes {
+        return prompt.confirm(plan);
+    }
+    if !plan.extra.is_empty() && !opts.allow_delete {
+        return Err(CosmosError::Usage("Detected deletions but --allow-delete not set; aborting. Use --allow-delete with --yes to permit deletions (protected paths will still be skipped).".to_string()).into());
+    }
+    Ok(true)
+}
+
+/// List the workspace members in the root `Cargo.toml`: the one being
+/// written, or else the existing one. Returns whether an existing file that
+/// is otherwise kept was changed.
+fn stage_workspace_members(tx: &Transaction, sel: &Selection) -> Result<bool> {
+    let staged = tx.root().join("Cargo.toml");
+    let current = if staged.is_file() { staged.clone() } else { tx.dest().join("Cargo.toml") };
+    if sel.members.is_empty() || !current.is_file() {
+        return Ok(false);
+    }
+    let paths: Vec<PathBuf> = sel.members.iter().map(|m| m.path.clone()).collect();
+    let text = fs::read_to_string(&current).with_context(|| format!("reading {:?}", current))?;
+    let Some(new) = workspace::set_members(&text, &paths)? else {
+        return Ok(false);
+    };
+    fs::write(&staged, new).with_context(|| format!("writing {:?}", staged))?;
+    Ok(current != staged)
+}
+
+/// Answers collected by [`resolve_per_file`].
+#[derive(Default)]
+struct PerFileAnswers {
+    /// Differing files replaced with the template output
+    overwrite: HashSet<PathBuf>,
+    /// Differing files whose template output goes to `<file>.cosmos-new`
+    side_by_side: Vec<PathBuf>,
+    /// Extra files to delete
+    delete: Vec<PathBuf>,
+    /// Differing files replaced with the result of an editor merge
+    merged: Vec<(PathBuf, Vec<u8>)>,
+    /// Choices to remember in the lock
+    resolutions: BTreeMap<String, Resolution>,
+}
+
+fn rendered_hash(out: &RenderedFile) -> String {
+    match out {
+        RenderedFile::Contents(bytes) => update::sha256(bytes),
+        RenderedFile::Symlink(target) => update::sha256(target.to_string_lossy().as_bytes()),
+    }
+}
+
+/// Ask what to do with every differing and extra destination file, reusing
+/// `remembered` answers whose template output has not changed since.
+/// Returns `None` when the user quits.
+fn resolve_per_file(
+    plan: &SyncPlan,
+    rendered: &[(PathBuf, RenderedFile)],
+    dest: &Path,
+    remembered: &BTreeMap<String, Resolution>,
+    prompt: &mut dyn Prompt,
+) -> Result<Option<PerFileAnswers>> {
+    let mut answers = PerFileAnswers::default();
+    let mut differing: Vec<&PathBuf> =
+        plan.modified.iter().chain(&plan.skipped_overwrite).collect();
+    differing.sort();
+    for rel in differing {
+        let Some((_, out)) = rendered.iter().find(|(r, _)| r == rel) else {
+            continue;
+        };
+        let key = rel.to_string_lossy().replace('\\', "/");
+        let hash = rendered_hash(out);
+        let choice = match remembered.get(&key).and_then(|r| r.applies_to(Some(&hash))) {
+            Some(choice) => {
+                prompt.say(&format!(" - {}: {} (remembered)", rel.display(), choice));
+                choice
+            }
+            None => match prompt.differing(rel, out, dest)? {
+                None => return Ok(None),
+                Some(FileChoice::Keep) => Choice::Keep,
+                Some(FileChoice::Overwrite) => Choice::Overwrite,
+                Some(FileChoice::SideBySide) => Choice::SideBySide,
+                Some(FileChoice::Merge) => {
+                    let RenderedFile::Contents(new) = out else {
+                        return Err(CosmosError::Usage(format!(
+                            "{} cannot be merged as text",
+                            rel.display()
+                        ))
+                        .into());
+                    };
+                    let (edited, conflicts_left) = merge_in_editor(rel, new, dest)?;
+                    if conflicts_left {
+                        prompt.say(&format!("{} still contains conflict markers", rel.display()));
+                    }
+                    answers.merged.push((rel.clone(), edited));
+                    Choice::Keep
+                }
+            },
+        };
+        match choice {
+            Choice::Overwrite => {
+                answers.overwrite.insert(rel.clone());
+            }
+            Choice::SideBySide => answers.side_by_side.push(rel.clone()),
+            Choice::Keep | Choice::Delete => {}
+        }
+        answers.resolutions.insert(key, Resolution { choice, template_hash: Some(hash) });
+    }
+
+    for rel in &plan.extra {
+        let key = rel.to_string_lossy().replace('\\', "/");
+        if remembered.get(&key).and_then(|r| r.applies_to(None)) == Some(Choice::Keep) {
+            prompt.say(&format!(" - {}: keep (remembered)", rel.display()));
+            continue;
+        }
+        match prompt.delete_extra(rel)? {
+            None => return Ok(None),
+            Some(true) => answers.delete.push(rel.clone()),
+            Some(false) => {
+                answers
+                    .resolutions
+                    .insert(key, Resolution { choice: Choice::Keep, template_hash: None });
+            }
+        }
+    }
+    Ok(Some(answers))
+}
+
+/// Three-way merge the template output `new` with `dest/rel` (against the
+/// base snapshot when there is one) in a scratch file, open it in
+/// `$VISUAL`/`$EDITOR` and return the result, and whether conflict markers
+/// are left; the destination is untouched until the caller stages it.
+pub fn merge_in_editor(rel: &Path, new: &[u8], dest: &Path) -> Result<(Vec<u8>, bool)> {
+    let path = dest.join(rel);
+    let base = fs::read(dest.join(update::BASE_DIR).join(rel)).unwrap_or_default();
+    let current = fs::read(&path).with_context(|| format!("reading {:?}", path))?;
+    let (merged, conflict) = update::merge3(
+        &String::from_utf8_lossy(&base),
+        &String::from_utf8_lossy(&current),
+        &String::from_utf8_lossy(new),
+    );
+    // keep the file name so the editor picks the right syntax
+    let scratch = tempfile::tempdir().context("creating a scratch directory")?;
+    let file_name = rel.file_name().unwrap_or(rel.as_os_str());
+    let scratch_path = scratch.path().join(file_name);
+    fs::write(&scratch_path, merged).with_context(|| format!("writing {:?}", scratch_path))?;
+
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or("vi");
+    let status = std::process::Command::new(program)
+        .args(parts)
+        .arg(&scratch_path)
+        .status()
+        .with_context(|| format!("running editor '{}'", editor))?;
+    if !status.success() {
+        return Err(
+            CosmosError::Usage(format!("editor '{}' exited with {}", editor, status)).into()
+        );
+    }
+    let edited = fs::read(&scratch_path).with_context(|| format!("reading {:?}", scratch_path))?;
+    let conflicts_left = conflict && String::from_utf8_lossy(&edited).contains("<<<<<<< current");
+    Ok((edited, conflicts_left))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answers every question the same way and records what was asked.
+    struct Scripted {
+        choice: Option<FileChoice>,
+        delete: Option<bool>,
+        asked: Vec<PathBuf>,
+    }
+
+    impl Prompt for Scripted {
+        fn say(&mut self, _line: &str) {}
+
+        fn confirm(&mut self, _plan: &SyncPlan) -> Result<bool> {
+            Ok(self.choice.is_some())
+        }
+
+        fn differing(
+            &mut self,
+            rel: &Path,
+            _: &RenderedFile,
+            _: &Path,
+        ) -> Result<Option<FileChoice>> {
+            self.asked.push(rel.to_path_buf());
+            Ok(self.choice)
+        }
+
+        fn delete_extra(&mut self, rel: &Path) -> Result<Option<bool>> {
+            self.asked.push(rel.to_path_buf());
+            Ok(self.delete)
+        }
+    }
+
+    #[test]
+    fn interactive_apply_follows_the_prompt() {
+        let td = tempfile::tempdir().unwrap();
+        let tpl_dir = td.path().join("tpl");
+        fs::create_dir_all(&tpl_dir).unwrap();
+        fs::write(tpl_dir.join("a.txt"), "new\n").unwrap();
+        fs::write(tpl_dir.join("b.txt"), "b\n").unwrap();
+        let dest = td.path().join("out");
+        fs::create_dir_all(&dest).unwrap();
+        fs::write(dest.join("a.txt"), "old\n").unwrap();
+        fs::write(dest.join("extra.txt"), "x\n").unwrap();
+
+        let tpl = Template::locate(td.path(), "tpl", Some(&tpl_dir), &[]).unwrap().unwrap();
+        let (sel, _) =
+            Selection::new(None, CategoryFilter::new(vec!["all".into()], Vec::new())).unwrap();
+        let renderer = Renderer::new(serde_json::Map::new());
+        let gen = Generator::new(&tpl, &renderer, &sel);
+        let prepared = gen.prepare().unwrap();
+        let plan = gen.plan(&prepared, &dest, false).unwrap();
+        let opts = SyncOptions { interactive: true, ..SyncOptions::default() };
+
+        // quitting writes nothing
+        let mut quit = Scripted { choice: None, delete: None, asked: Vec::new() };
+        assert!(gen.apply(&prepared, &plan, &dest, &opts, &mut quit).unwrap().is_none());
+        assert_eq!(fs::read_to_string(dest.join("a.txt")).unwrap(), "old\n");
+        assert!(!dest.join("b.txt").exists());
+
+        let mut answers =
+            Scripted { choice: Some(FileChoice::Overwrite), delete: Some(true), asked: Vec::new() };
+        let applied = gen.apply(&prepared, &plan, &dest, &opts, &mut answers).unwrap().unwrap();
+        assert_eq!(answers.asked, [PathBuf::from("a.txt"), PathBuf::from("extra.txt")]);
+        assert_eq!(fs::read_to_string(dest.join("a.txt")).unwrap(), "new\n");
+        assert_eq!(fs::read_to_string(dest.join("b.txt")).unwrap(), "b\n");
+        assert!(!dest.join("extra.txt").exists());
+        assert_eq!(applied.commit.backed_up(), 2);
+    }
+}
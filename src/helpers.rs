@@ -0,0 +1,308 @@
+//! Handlebars helpers available to every template, in file contents and
+//! file paths alike.
+//!
+//! | Helper | Example | Output for `project-name = "my-cool app"` |
+//! | --- | --- | --- |
+//! | `snake_case` | `{{snake_case project-name}}` | `my_cool_app` |
+//! | `kebab_case` | `{{kebab_case project-name}}` | `my-cool-app` |
+//! | `pascal_case` | `{{pascal_case project-name}}` | `MyCoolApp` |
+//! | `camel_case` | `{{camel_case project-name}}` | `myCoolApp` |
+//! | `screaming_snake_case` | `{{screaming_snake_case project-name}}` | `MY_COOL_APP` |
+//! | `year` | `{{year}}` | `2026` |
+//! | `date` | `{{date}}`, `{{date "%d.%m.%Y"}}` | `2026-10-17`, `17.10.2026` |
+//! | `git-user-name` | `{{git-user-name}}` | `git config user.name` |
+//! | `env` | `{{env "USER"}}`, `{{env "CI" "false"}}` | variable value or fallback |
+//! | `license` | `{{license "MIT OR Apache-2.0"}}` | `SPDX-License-Identifier: MIT OR Apache-2.0` |
+//!
+//! The case helpers are also registered as `kebab-case`, `PascalCase` and
+//! `SCREAMING_SNAKE`, and `git-user-name` as `git_user_name`, so either
+//! naming style works.
+//!
+//! `year` and `date` use the current UTC date, or `SOURCE_DATE_EPOCH` when
+//! set (reproducible output); a template variable of the same name wins.
+//! In strict mode `env` without a fallback and `git-user-name` fail when
+//! there is no value instead of rendering an empty string, and `license`
+//! fails on an id outside [`SPDX_IDS`].
+
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use handlebars::{
+    handlebars_helper, Context, Handlebars, Helper, HelperResult, Output, RenderContext,
+    RenderError,
+};
+use heck::{ToKebabCase, ToLowerCamelCase, ToShoutySnakeCase, ToSnakeCase, ToUpperCamelCase};
+
+handlebars_helper!(snake_case: |s: str| s.to_snake_case());
+handlebars_helper!(kebab_case: |s: str| s.to_kebab_case());
+handlebars_helper!(pascal_case: |s: str| s.to_upper_camel_case());
+handlebars_helper!(camel_case: |s: str| s.to_lower_camel_case());
+handlebars_helper!(screaming_snake_case: |s: str| s.to_shouty_snake_case());
+
+/// Register all helpers on `hb`.
+pub fn register(hb: &mut Handlebars<'_>) {
+    hb.register_helper("snake_case", Box::new(snake_case));
+    hb.register_helper("kebab_case", Box::new(kebab_case));
+    hb.register_helper("pascal_case", Box::new(pascal_case));
+    hb.register_helper("camel_case", Box::new(camel_case));
+    hb.register_helper("screaming_snake_case", Box::new(screaming_snake_case));
+    hb.register_helper("year", Box::new(year));
+    hb.register_helper("date", Box::new(date));
+    hb.register_helper("git-user-name", Box::new(git_user_name));
+    hb.register_helper("env", Box::new(env));
+    hb.register_helper("license", Box::new(license));
+    // spellings used in template READMEs and by other generators
+    hb.register_helper("kebab-case", Box::new(kebab_case));
+    hb.register_helper("PascalCase", Box::new(pascal_case));
+    hb.register_helper("SCREAMING_SNAKE", Box::new(screaming_snake_case));
+    hb.register_helper("git_user_name", Box::new(git_user_name));
+}
+
+/// License ids `{{license}}` accepts (the common subset of the SPDX list).
+pub const SPDX_IDS: &[&str] = &[
+    "0BSD",
+    "AGPL-3.0-only",
+    "AGPL-3.0-or-later",
+    "Apache-2.0",
+    "BSD-2-Clause",
+    "BSD-3-Clause",
+    "BSL-1.0",
+    "CC0-1.0",
+    "GPL-2.0-only",
+    "GPL-2.0-or-later",
+    "GPL-3.0-only",
+    "GPL-3.0-or-later",
+    "ISC",
+    "LGPL-2.1-only",
+    "LGPL-2.1-or-later",
+    "LGPL-3.0-only",
+    "LGPL-3.0-or-later",
+    "LLVM-exception",
+    "MIT",
+    "MPL-2.0",
+    "Unlicense",
+    "Zlib",
+];
+
+/// `{{license "<SPDX expression>"}}`: the `SPDX-License-Identifier:` header
+/// line for source files, e.g. `// {{license license}}`. Without a parameter
+/// it renders the `license` variable, as before the helper existed.
+fn license(
+    h: &Helper,
+    r: &Handlebars,
+    ctx: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    // a bare `{{license}}` is the template variable of that name
+    if let Some(v) = variable(h, ctx) {
+        return Ok(out.write(&v)?);
+    }
+    let expr = h.param(0).and_then(|p| p.value().as_str()).ok_or_else(|| {
+        RenderError::new("license: expected an SPDX expression, e.g. {{license \"MIT\"}}")
+    })?;
+    let expr = expr.split_whitespace().collect::<Vec<_>>().join(" ");
+    let unknown = expr
+        .split(|c: char| c.is_whitespace() || c == '(' || c == ')')
+        .filter(|t| !t.is_empty() && !matches!(*t, "AND" | "OR" | "WITH"))
+        .find(|t| !SPDX_IDS.contains(t));
+    if let (Some(id), true) = (unknown, r.strict_mode()) {
+        return Err(RenderError::new(format!("license: unknown SPDX license id '{}'", id)));
+    }
+    out.write(&format!("SPDX-License-Identifier: {}", expr))?;
+    Ok(())
+}
+
+/// `{{year}}`, unless the template context has a `year` variable.
+fn year(
+    h: &Helper,
+    _: &Handlebars,
+    ctx: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    if let Some(v) = variable(h, ctx) {
+        return Ok(out.write(&v)?);
+    }
+    out.write(&today().0.to_string())?;
+    Ok(())
+}
+
+/// `{{date}}` / `{{date "<format>"}}` with `%Y`, `%m`, `%d` and `%%`.
+fn date(
+    h: &Helper,
+    _: &Handlebars,
+    ctx: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    if let Some(v) = variable(h, ctx) {
+        return Ok(out.write(&v)?);
+    }
+    let format = h.param(0).and_then(|p| p.value().as_str()).unwrap_or("%Y-%m-%d");
+    out.write(&format_date(today(), format))?;
+    Ok(())
+}
+
+/// `{{git-user-name}}`: `git config user.name`.
+fn git_user_name(
+    _: &Helper,
+    r: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let name = Command::new("git")
+        .args(["config", "user.name"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .filter(|n| !n.is_empty());
+    match name {
+        Some(n) => out.write(&n)?,
+        None if r.strict_mode() => {
+            return Err(RenderError::new("git-user-name: git config user.name is not set"))
+        }
+        None => {}
+    }
+    Ok(())
+}
+
+/// `{{env "NAME"}}` / `{{env "NAME" "fallback"}}`.
+fn env(
+    h: &Helper,
+    r: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let name = h
+        .param(0)
+        .and_then(|p| p.value().as_str())
+        .ok_or_else(|| RenderError::new("env: expected a variable name, e.g. {{env \"USER\"}}"))?;
+    let fallback = h.param(1).and_then(|p| p.value().as_str());
+    match (std::env::var(name).ok(), fallback) {
+        (Some(v), _) => out.write(&v)?,
+        (None, Some(f)) => out.write(f)?,
+        (None, None) if r.strict_mode() => {
+            return Err(RenderError::new(format!("env: {} is not set", name)))
+        }
+        (None, None) => {}
+    }
+    Ok(())
+}
+
+/// Context variable overriding a parameterless helper.
+fn variable(h: &Helper, ctx: &Context) -> Option<String> {
+    if !h.params().is_empty() {
+        return None;
+    }
+    match ctx.data().get(h.name())? {
+        serde_json::Value::String(s) => Some(s.clone()),
+        v => Some(v.to_string()),
+    }
+}
+
+/// Today's UTC date as `(year, month, day)`.
+fn today() -> (i64, u32, u32) {
+    civil_from_days(now().div_euclid(86_400))
+}
+
+/// Seconds since the epoch: `SOURCE_DATE_EPOCH` when set, else the clock.
+pub(crate) fn now() -> i64 {
+    std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|s| s.trim().parse::<i64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0)
+        })
+}
+
+/// Proleptic Gregorian date for days since 1970-01-01 (Howard Hinnant's
+/// `civil_from_days`).
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+fn format_date((year, month, day): (i64, u32, u32), format: &str) -> String {
+    let mut out = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => out.push_str(&year.to_string()),
+            Some('m') => out.push_str(&format!("{:02}", month)),
+            Some('d') => out.push_str(&format!("{:02}", day)),
+            Some('%') => out.push('%'),
+            Some(other) => {
+                out.push('%');
+                out.push(other);
+            }
+            None => out.push('%'),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn case_helpers_and_dates() {
+        let mut hb = Handlebars::new();
+        register(&mut hb);
+        let ctx = serde_json::json!({"project-name": "my-cool app"});
+        let render = |t: &str| hb.render_template(t, &ctx).unwrap();
+        assert_eq!(render("{{snake_case project-name}}"), "my_cool_app");
+        assert_eq!(render("{{kebab_case project-name}}"), "my-cool-app");
+        assert_eq!(render("{{pascal_case project-name}}"), "MyCoolApp");
+        assert_eq!(render("{{camel_case project-name}}"), "myCoolApp");
+        assert_eq!(render("{{screaming_snake_case project-name}}"), "MY_COOL_APP");
+        assert_eq!(render("{{env \"COSMOS_SURELY_UNSET_VAR\" \"x\"}}"), "x");
+        assert_eq!(render("{{kebab-case project-name}}"), "my-cool-app");
+        assert_eq!(render("{{PascalCase project-name}}"), "MyCoolApp");
+        assert_eq!(render("{{SCREAMING_SNAKE project-name}}"), "MY_COOL_APP");
+
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(20_742), (2026, 10, 16));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(format_date((2026, 3, 7), "%d.%m.%Y %%"), "07.03.2026 %");
+
+        let ctx = serde_json::json!({"year": 1999});
+        assert_eq!(hb.render_template("{{year}}", &ctx).unwrap(), "1999");
+    }
+
+    #[test]
+    fn license_header_checks_ids_in_strict_mode() {
+        let mut hb = Handlebars::new();
+        register(&mut hb);
+        let ctx = serde_json::json!({"license": "MIT OR Apache-2.0", "typo": "Apache2"});
+        assert_eq!(
+            hb.render_template("// {{license license}}", &ctx).unwrap(),
+            "// SPDX-License-Identifier: MIT OR Apache-2.0"
+        );
+        assert_eq!(
+            hb.render_template("{{license \"(Apache-2.0 WITH LLVM-exception)\"}}", &ctx).unwrap(),
+            "SPDX-License-Identifier: (Apache-2.0 WITH LLVM-exception)"
+        );
+        assert_eq!(hb.render_template("{{license}}", &ctx).unwrap(), "MIT OR Apache-2.0");
+        assert!(hb.render_template("{{license typo}}", &ctx).is_ok());
+        hb.set_strict_mode(true);
+        let err = hb.render_template("{{license typo}}", &ctx).unwrap_err();
+        assert!(err.to_string().contains("unknown SPDX license id 'Apache2'"), "{}", err);
+    }
+}
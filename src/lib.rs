@@ -1,4 +1,26 @@
 //! Library for the project template.
+//!
+//! Besides the example [`add`] function this crate hosts the `cosmos` tool as a
+//! library, so plan transitions, template generation and validation can be
+//! driven from other Rust tools and build scripts:
+//!
+//! - [`template`]: resolve and render project templates
+//! - [`generate`]: `cosmos generate` orchestration: selection, sync plan and transactional apply
+//! - [`vars`]: template variables from files, the environment and existing projects
+//! - [`workspace`]: workspace member sub-templates and `[workspace] members`
+//! - [`helpers`]: Handlebars helpers available to templates
+//! - [`component`]: component templates and file patches for `cosmos add`
+//! - [`update`]: `.cosmos/template.lock` and three-way template updates
+//! - [`transaction`]: staged writes with backups and rollback
+//! - [`archive`]: `.tar.gz` / `.zip` packing of generated projects
+//! - [`git`]: dirty-worktree check, `git init` and the initial commit
+//! - [`exclude`]: `.cosmosignore` and `protected` rules
+//! - [`verify`]: configurable verification steps and their JSON / JUnit reports
+//! - [`plan`]: `plan/` task lifecycle (`Plan::open(root)?.transition(id, Status::Working)`)
+//! - [`project`]: `project.toml` checks and GitHub Actions outputs
+//! - [`validate`]: repository validation and auto-fixes
+//! - [`repo`]: repository root discovery
+//! - [`error`]: [`error::CosmosError`] and the CLI exit-code contract
 
 /// Adds two numbers (example function with docs and a testable unit)
 ///
@@ -12,6 +34,24 @@ pub fn add(a: i32, b: i32) -> i32 {
     a + b
 }
 
+pub mod archive;
+pub mod component;
+pub mod error;
+pub mod exclude;
+pub mod generate;
+pub mod git;
+pub mod helpers;
+pub mod plan;
+pub mod project;
+pub mod repo;
+pub mod template;
+pub mod transaction;
+pub mod update;
+pub mod validate;
+pub mod vars;
+pub mod verify;
+pub mod workspace;
+
 // Expose feature-gated modules for binaries/tests
 #[cfg(any(test, feature = "llm"))]
 pub mod llm;
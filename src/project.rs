@@ -0,0 +1,347 @@
+//! `project.toml` manifest: consistency checks against `Cargo.toml` and
+//! GitHub Actions outputs.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::error::CosmosError;
+use crate::validate::Issue;
+
+/// Errors and warnings found in `project.toml`.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct ProjectValidationReport {
+    pub errors: Vec<Issue>,
+    pub warnings: Vec<Issue>,
+}
+
+impl ProjectValidationReport {
+    /// Errors always block; warnings only block in strict mode.
+    pub fn has_blocking_issues(&self, strict: bool) -> bool {
+        !self.errors.is_empty() || (strict && !self.warnings.is_empty())
+    }
+}
+
+/// Whether `s` contains a handlebars-style placeholder such as `{{project-name}}`.
+pub fn is_template_placeholder(s: &str) -> bool {
+    // Heuristic: treat handlebars-style placeholders as template values.
+    // This repo is itself a template, so we must not fail drift checks on placeholders.
+    s.contains("{{") && s.contains("}}")
+}
+
+fn is_concrete_value(s: &str) -> bool {
+    let trimmed = s.trim();
+    !trimmed.is_empty() && !is_template_placeholder(trimmed)
+}
+
+pub fn read_toml_value(path: &Path) -> Result<toml::Value> {
+    let s = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    toml::from_str(&s).with_context(|| format!("parsing {}", path.display()))
+}
+
+/// GitHub Actions outputs derived from `project.toml`, in a stable order.
+pub fn gha_outputs(repo_root: &Path) -> Result<Vec<(String, String)>> {
+    let path = repo_root.join("project.toml");
+    if !path.exists() {
+        return Err(
+            CosmosError::NotFound(format!("project.toml not found at {}", path.display())).into()
+        );
+    }
+
+    let data = read_toml_value(&path)?;
+
+    let project = data.get("project").and_then(|v| v.as_table());
+    let ci = data.get("ci").and_then(|v| v.as_table());
+    let artifact = data.get("artifact").and_then(|v| v.as_table());
+    let docker = data.get("docker").and_then(|v| v.as_table());
+
+    let project_type = project
+        .and_then(|t| t.get("type"))
+        .and_then(|v| v.as_str())
+        .unwrap_or("library")
+        .to_string();
+
+    let run_build = ci.and_then(|t| t.get("run_build")).and_then(|v| v.as_bool()).unwrap_or(true);
+    let run_tests = ci.and_then(|t| t.get("run_tests")).and_then(|v| v.as_bool()).unwrap_or(true);
+    let run_security =
+        ci.and_then(|t| t.get("run_security")).and_then(|v| v.as_bool()).unwrap_or(true);
+    let run_docs = ci.and_then(|t| t.get("run_docs")).and_then(|v| v.as_bool()).unwrap_or(true);
+
+    let quick_gate_precommit = ci
+        .and_then(|t| t.get("quick_gate"))
+        .and_then(|v| v.as_array())
+        .map(|arr| arr.iter().any(|x| x.as_str() == Some("pre-commit")))
+        .unwrap_or(false);
+
+    let outputs = artifact
+        .and_then(|t| t.get("outputs"))
+        .and_then(|v| v.as_array())
+        .map(|arr| arr.iter().filter_map(|x| x.as_str().map(|s| s.to_string())).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let outputs_list = outputs.join(",");
+    let outputs_contains_docker = outputs.iter().any(|s| s == "docker");
+
+    let docker_enabled =
+        docker.and_then(|t| t.get("enabled")).and_then(|v| v.as_bool()).unwrap_or(false);
+    let docker_image =
+        docker.and_then(|t| t.get("image")).and_then(|v| v.as_str()).unwrap_or("").to_string();
+
+    let project_name =
+        project.and_then(|t| t.get("name")).and_then(|v| v.as_str()).unwrap_or("").to_string();
+    let project_version =
+        project.and_then(|t| t.get("version")).and_then(|v| v.as_str()).unwrap_or("").to_string();
+
+    let b = |v: bool| if v { "true" } else { "false" };
+    Ok(vec![
+        ("project_type".to_string(), project_type),
+        ("run_build".to_string(), b(run_build).to_string()),
+        ("run_tests".to_string(), b(run_tests).to_string()),
+        ("run_security".to_string(), b(run_security).to_string()),
+        ("run_docs".to_string(), b(run_docs).to_string()),
+        ("quick_gate_precommit".to_string(), b(quick_gate_precommit).to_string()),
+        ("outputs_list".to_string(), outputs_list),
+        ("outputs_contains_docker".to_string(), b(outputs_contains_docker).to_string()),
+        ("docker_enabled".to_string(), b(docker_enabled).to_string()),
+        ("docker_image".to_string(), docker_image),
+        ("project_name".to_string(), project_name),
+        ("project_version".to_string(), project_version),
+    ])
+}
+
+/// Check `project.toml` for schema, drift against `Cargo.toml` and artifact
+/// settings. Pure function over already-parsed manifests.
+pub fn collect_project_validation_issues(
+    repo_root: &Path,
+    project_toml: &toml::Value,
+    cargo_toml: &toml::Value,
+) -> ProjectValidationReport {
+    let mut report = ProjectValidationReport::default();
+
+    let cosmos = project_toml.get("cosmos").and_then(|v| v.as_table());
+    let schema_version = cosmos.and_then(|t| t.get("schema_version")).and_then(|v| v.as_integer());
+    if schema_version != Some(1) {
+        report.errors.push(Issue::new(
+            "project.schema-version",
+            "project.toml: expected [cosmos].schema_version = 1",
+        ));
+    }
+
+    let project = project_toml.get("project").and_then(|v| v.as_table());
+    let build = project_toml.get("build").and_then(|v| v.as_table());
+    let artifact = project_toml.get("artifact").and_then(|v| v.as_table());
+    let docker = project_toml.get("docker").and_then(|v| v.as_table());
+
+    let project_name = project.and_then(|t| t.get("name")).and_then(|v| v.as_str()).unwrap_or("");
+    let project_version =
+        project.and_then(|t| t.get("version")).and_then(|v| v.as_str()).unwrap_or("");
+
+    let cargo_pkg = cargo_toml.get("package").and_then(|v| v.as_table());
+    let cargo_name = cargo_pkg.and_then(|t| t.get("name")).and_then(|v| v.as_str()).unwrap_or("");
+    let cargo_version =
+        cargo_pkg.and_then(|t| t.get("version")).and_then(|v| v.as_str()).unwrap_or("");
+
+    // Drift checks: enable only when the manifest looks like a concrete repo (not a template).
+    // We key off project.name because template repos commonly use handlebars placeholders there.
+    let drift_checks_enabled = is_concrete_value(project_name);
+
+    if drift_checks_enabled && !cargo_name.is_empty() && project_name != cargo_name {
+        report.errors.push(Issue::new(
+            "project.name-drift",
+            format!(
+                "project.toml: [project].name '{}' does not match Cargo.toml [package].name '{}'",
+                project_name, cargo_name
+            ),
+        ));
+    }
+    if drift_checks_enabled
+        && is_concrete_value(project_version)
+        && !cargo_version.is_empty()
+        && project_version != cargo_version
+    {
+        report.errors.push(Issue::new(
+            "project.version-drift",
+            format!(
+                "project.toml: [project].version '{}' does not match Cargo.toml [package].version '{}'",
+                project_version, cargo_version
+            ),
+        ));
+    }
+
+    let outputs: Vec<String> = artifact
+        .and_then(|t| t.get("outputs"))
+        .and_then(|v| v.as_array())
+        .map(|arr| arr.iter().filter_map(|x| x.as_str().map(|s| s.to_string())).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let allowed_outputs = ["docker", "binary", "crate", "wasm", "deb", "rpm"];
+    for o in &outputs {
+        if !allowed_outputs.contains(&o.as_str()) {
+            report.errors.push(Issue::new(
+                "project.unknown-output",
+                format!("project.toml: [artifact].outputs contains unknown value '{}'", o),
+            ));
+        }
+    }
+
+    let outputs_contains_docker = outputs.iter().any(|s| s == "docker");
+    let outputs_contains_binary = outputs.iter().any(|s| s == "binary");
+
+    let docker_enabled =
+        docker.and_then(|t| t.get("enabled")).and_then(|v| v.as_bool()).unwrap_or(false);
+    let docker_image = docker.and_then(|t| t.get("image")).and_then(|v| v.as_str()).unwrap_or("");
+
+    if docker_enabled {
+        if !outputs_contains_docker {
+            report.errors.push(Issue::new(
+                "project.docker-output-missing",
+                "project.toml: [docker].enabled=true requires [artifact].outputs to include 'docker'",
+            ));
+        }
+        if docker_image.trim().is_empty() {
+            report.errors.push(Issue::new(
+                "project.docker-image-empty",
+                "project.toml: [docker].image must be non-empty when docker is enabled",
+            ));
+        } else if drift_checks_enabled && is_template_placeholder(docker_image) {
+            report.warnings.push(Issue::new(
+                "project.docker-image-placeholder",
+                "project.toml: [docker].image appears to be a template placeholder",
+            ));
+        }
+    } else if outputs_contains_docker {
+        report.warnings.push(Issue::new(
+            "project.docker-disabled",
+            "project.toml: [artifact].outputs contains 'docker' but [docker].enabled is false",
+        ));
+    }
+
+    if outputs_contains_binary {
+        let build_bins: Vec<String> = build
+            .and_then(|t| t.get("bins"))
+            .and_then(|v| v.as_array())
+            .map(|arr| {
+                arr.iter().filter_map(|x| x.as_str().map(|s| s.to_string())).collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        let cargo_bins_defined = cargo_toml
+            .get("bin")
+            .and_then(|v| v.as_array())
+            .map(|a| !a.is_empty())
+            .unwrap_or(false);
+        let default_main_exists = repo_root.join("src/main.rs").exists();
+
+        if build_bins.is_empty() && !cargo_bins_defined && !default_main_exists {
+            report.errors.push(Issue::new(
+                "project.binary-missing",
+                "project.toml: [artifact].outputs includes 'binary' but no binaries were found (set [build].bins, define [[bin]] in Cargo.toml, or provide src/main.rs)",
+            ));
+        }
+    }
+
+    report
+}
+
+/// Read `project.toml` and `Cargo.toml` under `repo_root` and validate them.
+/// The caller decides whether the report is blocking (see
+/// [`ProjectValidationReport::has_blocking_issues`]).
+pub fn validate_project_manifest(repo_root: &Path) -> Result<ProjectValidationReport> {
+    let project_path = repo_root.join("project.toml");
+    let cargo_path = repo_root.join("Cargo.toml");
+
+    if !project_path.exists() {
+        return Err(CosmosError::NotFound(format!(
+            "project.toml not found at {}",
+            project_path.display()
+        ))
+        .into());
+    }
+    if !cargo_path.exists() {
+        return Err(CosmosError::NotFound(format!(
+            "Cargo.toml not found at {}",
+            cargo_path.display()
+        ))
+        .into());
+    }
+
+    let project_toml = read_toml_value(&project_path)?;
+    let cargo_toml = read_toml_value(&cargo_path)?;
+
+    Ok(collect_project_validation_issues(repo_root, &project_toml, &cargo_toml))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn project_validate_collects_drift_errors_for_concrete_values() {
+        let td = tempdir().unwrap();
+        let root = td.path();
+
+        let project_toml: toml::Value = toml::from_str(
+            r#"[cosmos]
+schema_version = 1
+
+[project]
+name = "myapp"
+version = "1.0.0"
+
+[artifact]
+outputs = ["binary"]
+"#,
+        )
+        .unwrap();
+        let cargo_toml: toml::Value = toml::from_str(
+            r#"[package]
+name = "other"
+version = "2.0.0"
+edition = "2021"
+"#,
+        )
+        .unwrap();
+
+        let report = collect_project_validation_issues(root, &project_toml, &cargo_toml);
+        assert!(!report.errors.is_empty());
+        assert!(report.errors.iter().any(|e| e.id == "project.name-drift"));
+        assert!(report.errors.iter().any(|e| e.message.contains("[project].version")));
+    }
+
+    #[test]
+    fn project_validate_skips_drift_for_placeholders() {
+        let td = tempdir().unwrap();
+        let root = td.path();
+
+        let project_toml: toml::Value = toml::from_str(
+            r#"[cosmos]
+schema_version = 1
+
+[project]
+name = "{{project-name}}"
+version = "{{version}}"
+
+[artifact]
+outputs = ["binary"]
+"#,
+        )
+        .unwrap();
+        let cargo_toml: toml::Value = toml::from_str(
+            r#"[package]
+name = "rust-repo-template"
+version = "0.2.0"
+edition = "2021"
+"#,
+        )
+        .unwrap();
+
+        let report = collect_project_validation_issues(root, &project_toml, &cargo_toml);
+        assert!(report
+            .errors
+            .iter()
+            .all(|e| !e.message.contains("[project].name")
+                && !e.message.contains("[project].version")));
+    }
+}
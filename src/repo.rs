@@ -0,0 +1,98 @@
+//! Repository root discovery.
+//!
+//! `cosmos` can run from anywhere inside a checkout: the root is the nearest
+//! ancestor of the working directory that looks like a repository, unless an
+//! explicit directory is given (`--repo` / `COSMOS_REPO`). Inside a git
+//! checkout that is the checkout itself, even when a nested folder (such as
+//! `templates/default/`) carries its own `project.toml` or `plan/`.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+
+use crate::error::CosmosError;
+
+/// What a [`ROOT_MARKERS`] entry has to be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkerKind {
+    File,
+    Dir,
+    /// File or directory (`.git` is a file in worktrees and submodules)
+    Any,
+}
+
+/// Entries that mark a repository root, strongest first.
+pub const ROOT_MARKERS: &[(&str, MarkerKind)] =
+    &[(".git", MarkerKind::Any), ("project.toml", MarkerKind::File), ("plan", MarkerKind::Dir)];
+
+fn has_marker(dir: &Path, name: &str, kind: MarkerKind) -> bool {
+    let p = dir.join(name);
+    match kind {
+        MarkerKind::File => p.is_file(),
+        MarkerKind::Dir => p.is_dir(),
+        MarkerKind::Any => p.exists(),
+    }
+}
+
+/// Ancestor of `start` (including `start`) marking the repository root: the
+/// nearest one holding the first of [`ROOT_MARKERS`] found on the way up, so
+/// a checkout's `.git` wins over a nested `project.toml` or `plan/`.
+pub fn find_root(start: &Path) -> Option<PathBuf> {
+    ROOT_MARKERS
+        .iter()
+        .find_map(|&(name, kind)| start.ancestors().find(|d| has_marker(d, name, kind)))
+        .map(Path::to_path_buf)
+}
+
+/// Resolve the repository root: an explicit directory is used as-is,
+/// otherwise the root is discovered from `cwd`, falling back to `cwd` itself.
+pub fn resolve_root(explicit: Option<&Path>, cwd: &Path) -> Result<PathBuf> {
+    if let Some(dir) = explicit {
+        let dir = cwd.join(dir);
+        if !dir.is_dir() {
+            return Err(CosmosError::NotFound(format!(
+                "repository directory not found: {}",
+                dir.display()
+            ))
+            .into());
+        }
+        return Ok(dir);
+    }
+    Ok(find_root(cwd).unwrap_or_else(|| cwd.to_path_buf()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn find_root_walks_up_to_nearest_marker() {
+        let td = tempdir().unwrap();
+        let root = td.path().join("repo");
+        fs::create_dir_all(root.join("plan")).unwrap();
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        fs::write(root.join("src/plan"), "not a dir").unwrap();
+
+        assert_eq!(find_root(&root.join("src/nested")), Some(root.clone()));
+        assert_eq!(resolve_root(None, &root.join("src")).unwrap(), root);
+        assert_eq!(resolve_root(Some(Path::new("src")), &root).unwrap(), root.join("src"));
+        assert!(resolve_root(Some(Path::new("missing")), &root).is_err());
+    }
+
+    #[test]
+    fn find_root_prefers_the_checkout_over_nested_markers() {
+        let td = tempdir().unwrap();
+        let root = td.path().join("repo");
+        let nested = root.join("templates/default");
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::create_dir_all(nested.join("plan")).unwrap();
+        fs::write(nested.join("project.toml"), "[project]\n").unwrap();
+
+        assert_eq!(find_root(&nested), Some(root.clone()));
+        // without git the nearest marker still counts
+        fs::remove_dir(root.join(".git")).unwrap();
+        assert_eq!(find_root(&nested.join("plan")), Some(nested));
+    }
+}
@@ -0,0 +1,2052 @@
+//! Template resolution, rendering and destination sync for `cosmos generate`.
+//!
+//! A template is a folder (`templates/<name>/`) whose files are rendered with
+//! Handlebars into a destination directory, optionally described by a sibling
+//! manifest (`templates/<name>.toml`). Folders are looked up in `--template-dir`,
+//! the `COSMOS_TEMPLATE_PATH` roots, the repository, the binary itself and next
+//! to the executable (see [`Template::locate`]); a manifest may `extends` another
+//! template and share Handlebars partials. When no folder can be found the
+//! caller falls back to copying repository paths selected by the manifest
+//! categories ([`expand_patterns`] / [`copy_paths_to`]).
+//!
+//! ```no_run
+//! use rust_repo_template::template::{build_context, write_files, Renderer, Template};
+//!
+//! let tpl = Template::resolve(".".as_ref(), "default")?.expect("template exists");
+//! let files = tpl.files();
+//! let renderer = Renderer::new(build_context(Some("demo".into()), Vec::new()));
+//! write_files(&tpl, &files, &renderer, "out".as_ref(), false)?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use glob::glob;
+use include_dir::{include_dir, Dir};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+
+use crate::component::{PatchSpec, COMPONENTS_DIR};
+use crate::error::CosmosError;
+use crate::exclude::{self, Rule, Rules};
+use crate::transaction::Transaction;
+use crate::verify::VerifyConfig;
+use crate::workspace::MemberSpec;
+
+/// Templates embedded in the binary so installed users can generate without a
+/// local template repo.
+pub static EMBEDDED_TEMPLATES: Dir = include_dir!("templates");
+
+// `EMBEDDED_MODES` / `EMBEDDED_SYMLINKS`, generated by build.rs
+include!(concat!(env!("OUT_DIR"), "/embedded_meta.rs"));
+
+/// `templates/<name>.toml`: category name -> glob patterns, plus the
+/// variables the template declares.
+#[derive(Debug, Default, Deserialize)]
+pub struct TemplateManifest {
+    pub name: Option<String>,
+    /// Template version, recorded in `.cosmos/template.lock`
+    #[serde(default)]
+    pub version: Option<String>,
+    /// One-line summary shown by `cosmos template list`
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Parent template whose files, partials and manifest entries are inherited
+    #[serde(default)]
+    pub extends: Option<String>,
+    /// Globs of inherited files left out of this template
+    #[serde(default)]
+    pub remove: Vec<String>,
+    #[serde(default)]
+    pub categories: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    pub variables: BTreeMap<String, VariableSpec>,
+    /// Globs of files copied byte-for-byte, without rendering
+    #[serde(default)]
+    pub raw: Vec<String>,
+    /// Files only generated when a condition holds
+    #[serde(default)]
+    pub conditional: Vec<ConditionalRule>,
+    /// Edits to existing project files (component templates, see [`crate::component`])
+    #[serde(default)]
+    pub patch: Vec<PatchSpec>,
+    /// Sub-templates rendered into folders of the project (see [`crate::workspace`])
+    #[serde(default)]
+    pub members: Vec<MemberSpec>,
+    /// Destination paths (gitignore syntax) never deleted or overwritten,
+    /// on top of the destination's `.cosmosignore` (see [`crate::exclude`])
+    #[serde(default)]
+    pub protected: Vec<String>,
+    /// `generate --verify` steps, unless the project's `project.toml` has its own
+    #[serde(default)]
+    pub verify: Option<VerifyConfig>,
+}
+
+/// `[[conditional]]` manifest entry: `paths` (globs) are only generated
+/// when `when` (see [`eval_condition`]) holds.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ConditionalRule {
+    pub paths: Vec<String>,
+    pub when: String,
+}
+
+/// Value type of a declared template variable.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VarType {
+    #[default]
+    String,
+    Bool,
+    Integer,
+}
+
+/// One entry of the manifest `[variables]` table.
+///
+/// ```toml
+/// [variables.project-name]
+/// description = "Crate name"
+/// regex = "^[a-z][a-z0-9_-]*$"
+///
+/// [variables.bin-name]
+/// default = "{{project-name}}"   # derived from another variable
+/// ```
+///
+/// A variable without `default` is required.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct VariableSpec {
+    #[serde(default, rename = "type")]
+    pub kind: VarType,
+    pub description: Option<String>,
+    /// Default value; strings are rendered against the other variables
+    pub default: Option<toml::Value>,
+    /// Regular expression the whole value must match
+    pub regex: Option<String>,
+    /// Allowed values
+    #[serde(default)]
+    pub choices: Vec<String>,
+}
+
+impl VariableSpec {
+    /// Parse and validate a raw value for variable `name`.
+    pub fn parse(&self, name: &str, raw: &str) -> Result<serde_json::Value> {
+        let invalid = |why: String| -> anyhow::Error {
+            CosmosError::Usage(format!("invalid value '{}' for variable '{}': {}", raw, name, why))
+                .into()
+        };
+        if !self.choices.is_empty() && !self.choices.iter().any(|c| c == raw) {
+            return Err(invalid(format!("expected one of {}", self.choices.join(", "))));
+        }
+        if let Some(re) = &self.regex {
+            let re = regex::Regex::new(&format!("^(?:{})$", re))
+                .with_context(|| format!("invalid regex for variable '{}'", name))?;
+            if !re.is_match(raw) {
+                return Err(invalid(format!("must match /{}/", re.as_str())));
+            }
+        }
+        match self.kind {
+            VarType::String => Ok(serde_json::Value::String(raw.to_string())),
+            VarType::Bool => match raw.to_ascii_lowercase().as_str() {
+                "true" | "yes" | "y" | "1" => Ok(serde_json::Value::Bool(true)),
+                "false" | "no" | "n" | "0" => Ok(serde_json::Value::Bool(false)),
+                _ => Err(invalid("expected true or false".to_string())),
+            },
+            VarType::Integer => raw
+                .parse::<i64>()
+                .map(serde_json::Value::from)
+                .map_err(|_| invalid("expected an integer".to_string())),
+        }
+    }
+
+    /// The default rendered against the variables resolved so far. `None`
+    /// when there is no default; `Some(Err)` when it refers to a variable
+    /// that is not resolved yet.
+    fn default_value(
+        &self,
+        ctx: &serde_json::Map<String, serde_json::Value>,
+    ) -> Option<Result<String>> {
+        let raw = match self.default.as_ref()? {
+            toml::Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        if !raw.contains("{{") {
+            return Some(Ok(raw));
+        }
+        let mut hb = handlebars::Handlebars::new();
+        hb.register_escape_fn(handlebars::no_escape);
+        hb.set_strict_mode(true);
+        Some(hb.render_template(&raw, ctx).map_err(anyhow::Error::from))
+    }
+}
+
+/// A variable the caller should ask the user for.
+pub struct VariablePrompt<'a> {
+    pub name: &'a str,
+    pub spec: &'a VariableSpec,
+    /// Default offered to the user (already rendered)
+    pub default: Option<String>,
+}
+
+/// Callback asking the user for a variable value.
+pub type PromptFn<'a> = dyn FnMut(&VariablePrompt) -> Result<String> + 'a;
+
+/// Resolve declared `variables` into `ctx`, which already holds the values
+/// given on the command line (those are validated and converted to the
+/// declared type). Missing values come from `prompt` when given, otherwise
+/// from the default; a missing variable without default is an error.
+///
+/// Derived defaults are resolved once the variables they refer to are known.
+pub fn resolve_variables(
+    variables: &BTreeMap<String, VariableSpec>,
+    ctx: &mut serde_json::Map<String, serde_json::Value>,
+    mut prompt: Option<&mut PromptFn>,
+) -> Result<()> {
+    let mut pending: Vec<(&String, &VariableSpec)> = Vec::new();
+    for (name, spec) in variables {
+        match ctx.get(name) {
+            Some(serde_json::Value::String(raw)) => {
+                let v = spec.parse(name, raw)?;
+                ctx.insert(name.clone(), v);
+            }
+            Some(_) => {}
+            None => pending.push((name, spec)),
+        }
+    }
+
+    let mut missing = Vec::new();
+    while !pending.is_empty() {
+        let mut deferred = Vec::new();
+        for (name, spec) in pending.iter().copied() {
+            let default = match spec.default_value(ctx) {
+                Some(Ok(d)) => Some(d),
+                Some(Err(_)) => {
+                    deferred.push((name, spec));
+                    continue;
+                }
+                None => None,
+            };
+            let raw = match prompt.as_mut() {
+                Some(ask) => ask(&VariablePrompt { name, spec, default })?,
+                None => match default {
+                    Some(d) => d,
+                    None => {
+                        missing.push(name.as_str());
+                        continue;
+                    }
+                },
+            };
+            let v = spec.parse(name, &raw)?;
+            ctx.insert(name.clone(), v);
+        }
+        if deferred.len() == pending.len() {
+            // nothing resolved in this round: defaults refer to missing, undeclared or
+            // cyclic variables; only blame the latter when nothing else is missing
+            if missing.is_empty() {
+                missing.extend(deferred.iter().map(|(n, _)| n.as_str()));
+            }
+            break;
+        }
+        pending = deferred;
+    }
+
+    if !missing.is_empty() {
+        return Err(CosmosError::Usage(format!(
+            "missing required template variables: {} (pass --var <name>=<value>)",
+            missing.join(", ")
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// Load `templates/<name>.toml` from the repository, if present.
+pub fn load_manifest(repo_root: &Path, template_name: &str) -> Result<Option<TemplateManifest>> {
+    let path = repo_root.join("templates").join(format!("{}.toml", template_name));
+    read_manifest(&path)
+}
+
+fn read_manifest(path: &Path) -> Result<Option<TemplateManifest>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let s = fs::read_to_string(path).with_context(|| format!("reading manifest {:?}", path))?;
+    parse_manifest(&s).map(Some)
+}
+
+fn parse_manifest(s: &str) -> Result<TemplateManifest> {
+    toml::from_str(s).context("parsing manifest")
+}
+
+impl TemplateManifest {
+    /// Layer this (child) manifest over `parent`: categories and variables
+    /// are overridden by name, raw globs, conditional rules, patches and
+    /// members accumulate.
+    fn inherit(self, parent: TemplateManifest) -> TemplateManifest {
+        let mut categories = parent.categories;
+        categories.extend(self.categories);
+        let mut variables = parent.variables;
+        variables.extend(self.variables);
+        let mut raw = parent.raw;
+        raw.extend(self.raw);
+        let mut conditional = parent.conditional;
+        conditional.extend(self.conditional);
+        let mut patch = parent.patch;
+        patch.extend(self.patch);
+        let mut members = parent.members;
+        members.extend(self.members);
+        let mut protected = parent.protected;
+        protected.extend(self.protected);
+        TemplateManifest {
+            name: self.name.or(parent.name),
+            version: self.version.or(parent.version),
+            description: self.description.or(parent.description),
+            extends: self.extends,
+            remove: self.remove,
+            categories,
+            variables,
+            raw,
+            conditional,
+            patch,
+            members,
+            protected,
+            verify: self.verify.or(parent.verify),
+        }
+    }
+}
+
+/// Environment variable holding extra template roots (`PATH`-style list).
+pub const TEMPLATE_PATH_ENV: &str = "COSMOS_TEMPLATE_PATH";
+
+/// Template roots from `COSMOS_TEMPLATE_PATH`, in order. Each root holds
+/// `<name>/` folders and optional `<name>.toml` manifests.
+pub fn template_search_path() -> Vec<PathBuf> {
+    std::env::var_os(TEMPLATE_PATH_ENV)
+        .map(|v| std::env::split_paths(&v).filter(|p| !p.as_os_str().is_empty()).collect())
+        .unwrap_or_default()
+}
+
+/// Categories used when no manifest is available: reasonable defaults for
+/// this repo template.
+pub fn default_categories() -> HashMap<String, Vec<String>> {
+    let mut map = HashMap::new();
+    map.insert(
+        "basis".to_string(),
+        vec!["Cargo.toml".into(), "README.md".into(), "LICENSE".into(), "CONTRIBUTING.md".into()],
+    );
+    map.insert("docs".to_string(), vec!["docs/**".into()]);
+    map.insert("ci".to_string(), vec![".github/**".into()]);
+    map.insert("tests".to_string(), vec!["tests/**".into()]);
+    map.insert("examples".to_string(), vec!["examples/**".into()]);
+    map.insert("scripts".to_string(), vec!["scripts/**".into()]);
+    map.insert("plan".to_string(), vec!["plan/**".into()]);
+    map
+}
+
+/// Glob patterns selected by `category` (`all` selects every category).
+/// Returns `None` for an unknown category.
+pub fn category_patterns(
+    categories: &HashMap<String, Vec<String>>,
+    category: &str,
+) -> Option<Vec<String>> {
+    if category == "all" {
+        Some(categories.values().flatten().cloned().collect())
+    } else {
+        categories.get(category).cloned()
+    }
+}
+
+/// Whether any manifest glob in `patterns` matches `rel`.
+fn matches_any(patterns: &[String], rel: &Path) -> bool {
+    let opts = glob::MatchOptions {
+        case_sensitive: true,
+        require_literal_separator: true,
+        require_literal_leading_dot: false,
+    };
+    patterns
+        .iter()
+        .any(|pat| glob::Pattern::new(pat).map(|p| p.matches_path_with(rel, opts)).unwrap_or(false))
+}
+
+/// Categories (sorted) whose globs match `rel`, a path relative to the
+/// template or repository root.
+pub fn file_categories(categories: &HashMap<String, Vec<String>>, rel: &Path) -> Vec<String> {
+    let mut out: Vec<String> = categories
+        .iter()
+        .filter(|(_, pats)| matches_any(pats, rel))
+        .map(|(name, _)| name.clone())
+        .collect();
+    out.sort();
+    out
+}
+
+/// Flag files matching the manifest `raw` globs so they are copied
+/// byte-for-byte (binary files are detected automatically).
+pub fn mark_raw(files: &mut [TemplateFile], raw: &[String]) {
+    for f in files {
+        f.raw = matches_any(raw, &f.rel);
+    }
+}
+
+/// Heuristic binary check: NUL bytes or invalid UTF-8.
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes.contains(&0) || std::str::from_utf8(bytes).is_err()
+}
+
+/// `--category` / `--exclude-category` selection. Including `all` selects
+/// every file, uncategorized ones too; exclusions always win.
+#[derive(Debug, Clone)]
+pub struct CategoryFilter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl CategoryFilter {
+    pub fn new(include: Vec<String>, exclude: Vec<String>) -> CategoryFilter {
+        CategoryFilter { include, exclude }
+    }
+
+    /// Reject category names the manifest does not define.
+    pub fn check(&self, categories: &HashMap<String, Vec<String>>) -> Result<()> {
+        let names = self.include.iter().filter(|c| c.as_str() != "all").chain(&self.exclude);
+        for c in names {
+            if !categories.contains_key(c) {
+                return Err(CosmosError::Usage(format!("Unknown category '{}'", c)).into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a file in `file_cats` is part of the selection.
+    pub fn selects(&self, file_cats: &[String]) -> bool {
+        if file_cats.iter().any(|c| self.exclude.contains(c)) {
+            return false;
+        }
+        self.include.iter().any(|c| c == "all" || file_cats.contains(c))
+    }
+
+    /// Glob patterns of the included categories (pattern-based generation).
+    pub fn patterns(&self, categories: &HashMap<String, Vec<String>>) -> Vec<String> {
+        let mut out: Vec<String> = self
+            .include
+            .iter()
+            .filter_map(|c| category_patterns(categories, c))
+            .flatten()
+            .collect();
+        out.sort();
+        out.dedup();
+        out
+    }
+}
+
+/// Evaluate a file condition against the template context.
+///
+/// Conditions are `||`-separated alternatives of `&&`-separated terms; a term
+/// is `name` (truthy), `!name`, `name == value` or `name != value`, where
+/// `value` may be quoted. Missing variables are falsy / empty.
+pub fn eval_condition(
+    expr: &str,
+    ctx: &serde_json::Map<String, serde_json::Value>,
+) -> Result<bool> {
+    let mut any = false;
+    for alt in expr.split("||") {
+        let mut all = true;
+        for term in alt.split("&&") {
+            all &= eval_term(term.trim(), ctx)
+                .with_context(|| format!("invalid condition '{}'", expr.trim()))?;
+        }
+        any |= all;
+    }
+    Ok(any)
+}
+
+fn eval_term(term: &str, ctx: &serde_json::Map<String, serde_json::Value>) -> Result<bool> {
+    let value_text = |name: &str| match ctx.get(name.trim()) {
+        Some(serde_json::Value::String(s)) => s.clone(),
+        Some(serde_json::Value::Null) | None => String::new(),
+        Some(v) => v.to_string(),
+    };
+    let literal = |s: &str| s.trim().trim_matches(|c| c == '"' || c == '\'').to_string();
+    let is_name = |s: &str| {
+        !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    };
+
+    if let Some((lhs, rhs)) = term.split_once("!=") {
+        return Ok(value_text(lhs) != literal(rhs));
+    }
+    if let Some((lhs, rhs)) = term.split_once("==") {
+        return Ok(value_text(lhs) == literal(rhs));
+    }
+    if let Some(name) = term.strip_prefix('!') {
+        return eval_term(name.trim(), ctx).map(|b| !b);
+    }
+    if !is_name(term) {
+        return Err(CosmosError::Render(format!("unexpected term '{}'", term)).into());
+    }
+    Ok(match ctx.get(term) {
+        None | Some(serde_json::Value::Null) => false,
+        Some(serde_json::Value::Bool(b)) => *b,
+        Some(serde_json::Value::String(s)) => !s.is_empty() && s != "false" && s != "0",
+        Some(serde_json::Value::Number(n)) => n.as_f64() != Some(0.0),
+        Some(_) => true,
+    })
+}
+
+/// Marker file whose content is the condition for its whole directory.
+pub const CONDITION_MARKER: &str = ".cosmos-if";
+
+/// Split a `{{!-- cosmos-if: <condition> --}}` first line off file contents.
+pub fn front_matter_condition(text: &str) -> Option<(&str, &str)> {
+    let (first, rest) = text.split_once('\n').unwrap_or((text, ""));
+    let cond = first
+        .trim()
+        .strip_prefix("{{!--")?
+        .strip_suffix("--}}")?
+        .trim()
+        .strip_prefix("cosmos-if:")?;
+    Some((cond.trim(), rest))
+}
+
+/// A template file left out because its condition does not hold.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExcludedFile {
+    pub path: PathBuf,
+    pub when: String,
+}
+
+/// Drop files whose condition is false: manifest `[[conditional]]` rules,
+/// `.cosmos-if` markers in the file's directories and a front-matter line
+/// (see [`front_matter_condition`]). Markers themselves are never generated.
+pub fn filter_conditional(
+    files: Vec<TemplateFile>,
+    rules: &[ConditionalRule],
+    ctx: &serde_json::Map<String, serde_json::Value>,
+) -> Result<(Vec<TemplateFile>, Vec<ExcludedFile>)> {
+    let mut kept = Vec::new();
+    let mut excluded = Vec::new();
+    for f in files {
+        if f.rel.file_name() == Some(std::ffi::OsStr::new(CONDITION_MARKER)) {
+            continue;
+        }
+        let mut conditions: Vec<String> = rules
+            .iter()
+            .filter(|r| matches_any(&r.paths, &f.rel))
+            .map(|r| r.when.clone())
+            .collect();
+        // `.cosmos-if` in any directory between the template root and the file
+        let mut dir = f.src.parent();
+        for _ in 1..f.rel.components().count() {
+            let Some(d) = dir else { break };
+            let marker = d.join(CONDITION_MARKER);
+            if marker.is_file() {
+                let cond =
+                    fs::read_to_string(&marker).with_context(|| format!("reading {:?}", marker))?;
+                conditions.push(cond.trim().to_string());
+            }
+            dir = d.parent();
+        }
+        if !f.raw && !f.src.is_symlink() {
+            if let Ok(text) = fs::read_to_string(&f.src) {
+                if let Some((cond, _)) = front_matter_condition(&text) {
+                    conditions.push(cond.to_string());
+                }
+            }
+        }
+
+        let mut failed = None;
+        for cond in conditions {
+            if !eval_condition(&cond, ctx)? {
+                failed = Some(cond);
+                break;
+            }
+        }
+        match failed {
+            Some(when) => excluded.push(ExcludedFile { path: f.rel, when }),
+            None => kept.push(f),
+        }
+    }
+    Ok((kept, excluded))
+}
+
+/// Keep the files selected by `filter`, recording each file's categories.
+pub fn filter_by_category(
+    files: Vec<TemplateFile>,
+    categories: &HashMap<String, Vec<String>>,
+    filter: &CategoryFilter,
+) -> Vec<TemplateFile> {
+    files
+        .into_iter()
+        .map(|mut f| {
+            f.categories = file_categories(categories, &f.rel);
+            f
+        })
+        .filter(|f| filter.selects(&f.categories))
+        .collect()
+}
+
+/// Where a resolved template folder comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TemplateSource {
+    /// Folder given explicitly with `--template-dir`
+    Dir,
+    /// `templates/<name>/` in the repository
+    Repo,
+    /// `<root>/<name>/` from `COSMOS_TEMPLATE_PATH`
+    SearchPath,
+    /// Copy embedded in the binary
+    Embedded,
+    /// `templates/<name>/` next to the executable
+    ExeRelative,
+}
+
+/// Folder inside a template holding Handlebars partials (see
+/// [`Template::partials`]); its files are not generated.
+pub const PARTIALS_DIR: &str = "partials";
+
+/// A template folder ready to be rendered.
+#[derive(Debug)]
+pub struct Template {
+    pub name: String,
+    /// Folder containing the template files
+    pub root: PathBuf,
+    pub source: TemplateSource,
+    /// Template named by the manifest's `extends`, resolved by [`Template::locate`]
+    pub parent: Option<Box<Template>>,
+    // Keeps extracted embedded templates alive.
+    _extracted: Option<TempDir>,
+}
+
+/// A template file a `.cosmosignore` rule leaves out.
+#[derive(Debug, Clone, Serialize)]
+pub struct IgnoredFile {
+    /// Path relative to the template root
+    pub path: PathBuf,
+    pub rule: Rule,
+}
+
+/// A file inside a template folder.
+#[derive(Debug, Clone)]
+pub struct TemplateFile {
+    /// Absolute path of the template file
+    pub src: PathBuf,
+    /// Path relative to the template root (before rendering)
+    pub rel: PathBuf,
+    /// Manifest categories the file belongs to (see [`filter_by_category`])
+    pub categories: Vec<String>,
+    /// Copy without rendering (see [`mark_raw`])
+    pub raw: bool,
+}
+
+impl Template {
+    /// Resolve a template folder with the default search path
+    /// ([`template_search_path`]); see [`Template::locate`].
+    pub fn resolve(repo_root: &Path, name: &str) -> Result<Option<Template>> {
+        Template::locate(repo_root, name, None, &template_search_path())
+    }
+
+    /// Locate a template folder: an explicit `template_dir` (which must
+    /// exist), then `<root>/<name>/` for each `search_path` root, then repo
+    /// `templates/<name>/`, then the embedded templates, then exe-relative
+    /// `templates/<name>/`. Returns `None` when no folder exists.
+    ///
+    /// A manifest `extends = "<parent>"` is resolved the same way, preferring
+    /// a `<parent>/` folder next to the template (or the embedded parent of an
+    /// embedded template).
+    pub fn locate(
+        repo_root: &Path,
+        name: &str,
+        template_dir: Option<&Path>,
+        search_path: &[PathBuf],
+    ) -> Result<Option<Template>> {
+        match Template::find(repo_root, name, template_dir, search_path)? {
+            Some(t) => t.with_parents(repo_root, search_path, &mut Vec::new()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn find(
+        repo_root: &Path,
+        name: &str,
+        template_dir: Option<&Path>,
+        search_path: &[PathBuf],
+    ) -> Result<Option<Template>> {
+        if let Some(dir) = template_dir {
+            if !dir.is_dir() {
+                return Err(CosmosError::NotFound(format!(
+                    "template directory not found: {}",
+                    dir.display()
+                ))
+                .into());
+            }
+            return Ok(Some(Template::from_dir(name, dir.to_path_buf(), TemplateSource::Dir)));
+        }
+        // the explicit search path wins over whatever checkout we run from
+        for root in search_path {
+            let dir = root.join(name);
+            if dir.is_dir() {
+                return Ok(Some(Template::from_dir(name, dir, TemplateSource::SearchPath)));
+            }
+        }
+        let repo_dir = repo_root.join("templates").join(name);
+        if repo_dir.is_dir() {
+            return Ok(Some(Template::from_dir(name, repo_dir, TemplateSource::Repo)));
+        }
+        if let Some(d) = EMBEDDED_TEMPLATES.get_dir(name) {
+            // extract embedded template into tempdir
+            let td = tempfile::tempdir().context("creating temp dir for embedded template")?;
+            extract_embedded(d, td.path(), name).context("extracting embedded template files")?;
+            return Ok(Some(Template {
+                name: name.to_string(),
+                root: td.path().to_path_buf(),
+                source: TemplateSource::Embedded,
+                parent: None,
+                _extracted: Some(td),
+            }));
+        }
+        // try executable-relative templates/ (useful for installed binaries)
+        if let Some(parent) = std::env::current_exe().ok().as_deref().and_then(Path::parent) {
+            let exe_t = parent.join("templates").join(name);
+            if exe_t.is_dir() {
+                return Ok(Some(Template::from_dir(name, exe_t, TemplateSource::ExeRelative)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Use an arbitrary folder as template.
+    pub fn from_dir(name: &str, root: PathBuf, source: TemplateSource) -> Template {
+        Template { name: name.to_string(), root, source, parent: None, _extracted: None }
+    }
+
+    /// Resolve the `extends` chain; `seen` holds the templates already on it.
+    fn with_parents(
+        mut self,
+        repo_root: &Path,
+        search_path: &[PathBuf],
+        seen: &mut Vec<String>,
+    ) -> Result<Template> {
+        let key = match self.source {
+            TemplateSource::Embedded => format!("embedded:{}", self.name),
+            _ => {
+                self.root.canonicalize().unwrap_or_else(|_| self.root.clone()).display().to_string()
+            }
+        };
+        if seen.contains(&key) {
+            return Err(CosmosError::Render(format!(
+                "template '{}' extends itself (directly or through its parents)",
+                self.name
+            ))
+            .into());
+        }
+        seen.push(key);
+        let Some(parent_name) = self.own_manifest()?.and_then(|m| m.extends) else {
+            return Ok(self);
+        };
+        let parent = self.find_parent(&parent_name, repo_root, search_path)?.ok_or_else(|| {
+            CosmosError::NotFound(format!(
+                "template '{}' extends '{}', which was not found",
+                self.name, parent_name
+            ))
+        })?;
+        self.parent = Some(Box::new(parent.with_parents(repo_root, search_path, seen)?));
+        Ok(self)
+    }
+
+    /// Locate a template this one refers to (a workspace member, see
+    /// [`crate::workspace`]) the way `extends` is resolved.
+    pub fn locate_related(
+        &self,
+        name: &str,
+        repo_root: &Path,
+        search_path: &[PathBuf],
+    ) -> Result<Option<Template>> {
+        match self.find_parent(name, repo_root, search_path)? {
+            Some(t) => t.with_parents(repo_root, search_path, &mut Vec::new()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn find_parent(
+        &self,
+        name: &str,
+        repo_root: &Path,
+        search_path: &[PathBuf],
+    ) -> Result<Option<Template>> {
+        if self.source == TemplateSource::Embedded {
+            if EMBEDDED_TEMPLATES.get_dir(name).is_some() {
+                return Template::find(Path::new(""), name, None, &[]);
+            }
+        } else if let Some(dir) = self.root.parent().map(|p| p.join(name)) {
+            if dir.is_dir() && dir != self.root {
+                return Ok(Some(Template::from_dir(name, dir, self.source)));
+            }
+        }
+        Template::find(repo_root, name, None, search_path)
+    }
+
+    /// The template's manifest layered over its parents' (see `extends`).
+    pub fn manifest(&self) -> Result<Option<TemplateManifest>> {
+        let own = self.own_manifest()?;
+        let Some(parent) = &self.parent else {
+            return Ok(own);
+        };
+        let inherited = parent.manifest()?.unwrap_or_default();
+        Ok(Some(own.unwrap_or_default().inherit(inherited)))
+    }
+
+    /// The template's own manifest: `<name>.toml` next to the folder (or
+    /// inside the embedded tree for embedded templates).
+    fn own_manifest(&self) -> Result<Option<TemplateManifest>> {
+        self.manifest_source()?.as_deref().map(parse_manifest).transpose()
+    }
+
+    /// Text of the template's own manifest, if it has one.
+    pub fn manifest_source(&self) -> Result<Option<String>> {
+        if self.source == TemplateSource::Embedded {
+            return Ok(embedded_manifest_source(&self.name));
+        }
+        // `<folder>.toml`, also for nested names such as `components/<name>`
+        let file_name =
+            self.root.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        match self.root.parent() {
+            Some(parent) if !file_name.is_empty() => {
+                let path = parent.join(format!("{}.toml", file_name));
+                if !path.exists() {
+                    return Ok(None);
+                }
+                fs::read_to_string(&path)
+                    .with_context(|| format!("reading manifest {:?}", path))
+                    .map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Copy the template folder (including `partials/`, modes and symlinks)
+    /// to `dest` and its own manifest to `<dest>.toml`, so the copy can be
+    /// used with `--template-dir` or from a `COSMOS_TEMPLATE_PATH` root.
+    /// Inherited files stay with the parent. Returns the copied paths.
+    pub fn export(&self, dest: &Path) -> Result<Vec<PathBuf>> {
+        let mut copied = Vec::new();
+        for entry in walkdir::WalkDir::new(&self.root).min_depth(1) {
+            let entry = entry?;
+            let rel = entry.path().strip_prefix(&self.root).unwrap().to_path_buf();
+            let target = dest.join(&rel);
+            if entry.path_is_symlink() {
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                let link = fs::read_link(entry.path())?;
+                make_symlink(&link, &target, || Ok(fs::copy(entry.path(), &target).map(|_| ())?))?;
+                copied.push(rel);
+            } else if entry.file_type().is_dir() {
+                fs::create_dir_all(&target)?;
+            } else {
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::copy(entry.path(), &target)
+                    .with_context(|| format!("copying {:?} to {:?}", entry.path(), target))?;
+                copy_mode(&entry.metadata()?, &target)?;
+                copied.push(rel);
+            }
+        }
+        if let Some(manifest) = self.manifest_source()? {
+            let mut path = dest.as_os_str().to_os_string();
+            path.push(".toml");
+            fs::write(&path, manifest).with_context(|| format!("writing {:?}", path))?;
+        }
+        Ok(copied)
+    }
+
+    /// Rendered destination path of `f`, without an accidental leading
+    /// template directory segment.
+    pub fn dest_rel(&self, f: &TemplateFile, renderer: &Renderer) -> PathBuf {
+        strip_template_prefix(&renderer.render_path(&f.rel), &self.name)
+    }
+
+    /// All files in the template folder, with relative paths normalized to
+    /// drop an accidental leading template component (e.g. `default/...`).
+    /// Files inherited from the parent are included unless the manifest's
+    /// `remove` globs drop them or the template has a file at the same path.
+    pub fn files(&self) -> Vec<TemplateFile> {
+        self.split_files().0
+    }
+
+    /// Files the template's `.cosmosignore` (or a parent's) leaves out, with
+    /// the matching rule. Fails on invalid patterns.
+    pub fn ignored(&self) -> Result<Vec<IgnoredFile>> {
+        self.ignore_rules()?;
+        let mut parent = self.parent.as_ref();
+        while let Some(p) = parent {
+            p.ignore_rules()?;
+            parent = p.parent.as_ref();
+        }
+        Ok(self.split_files().1)
+    }
+
+    /// The patterns of `.cosmosignore` in the template root.
+    pub fn ignore_rules(&self) -> Result<Rules> {
+        let mut rules = Rules::default();
+        rules.add_file(&self.root.join(exclude::IGNORE_FILE))?;
+        Ok(rules)
+    }
+
+    /// `(files, ignored files)`: own files override inherited ones, and this
+    /// template's `remove` globs and `.cosmosignore` apply to both.
+    fn split_files(&self) -> (Vec<TemplateFile>, Vec<IgnoredFile>) {
+        // invalid patterns are reported by `ignored`
+        let rules = self.ignore_rules().unwrap_or_default();
+        let (mut files, mut ignored) = self.own_files(&rules);
+        let Some(parent) = &self.parent else {
+            return (files, ignored);
+        };
+        let removed = self.own_manifest().ok().flatten().map(|m| m.remove).unwrap_or_default();
+        let overridden: HashSet<PathBuf> = files.iter().map(|f| f.rel.clone()).collect();
+        let (inherited, parent_ignored) = parent.split_files();
+        let mut kept = Vec::new();
+        for f in inherited {
+            if overridden.contains(&f.rel) || matches_any(&removed, &f.rel) {
+                continue;
+            }
+            match rules.matched(&f.rel, false) {
+                Some(rule) => ignored.push(IgnoredFile { path: f.rel, rule }),
+                None => kept.push(f),
+            }
+        }
+        kept.append(&mut files);
+        ignored.extend(parent_ignored.into_iter().filter(|i| !overridden.contains(&i.path)));
+        (kept, ignored)
+    }
+
+    fn own_files(&self, rules: &Rules) -> (Vec<TemplateFile>, Vec<IgnoredFile>) {
+        let mut files = Vec::new();
+        let mut ignored = Vec::new();
+        for entry in walkdir::WalkDir::new(&self.root).into_iter().filter_map(|e| e.ok()) {
+            // symlinks are kept as links, whatever they point to
+            if entry.file_type().is_file() || entry.path_is_symlink() {
+                let p = entry.path().to_path_buf();
+                let rel = p.strip_prefix(&self.root).unwrap().to_path_buf();
+                let rel = strip_template_prefix(&rel, &self.name);
+                if rel.starts_with(PARTIALS_DIR) || rel.as_os_str() == exclude::IGNORE_FILE {
+                    continue;
+                }
+                match rules.matched(&rel, false) {
+                    Some(rule) => ignored.push(IgnoredFile { path: rel, rule }),
+                    None => {
+                        files.push(TemplateFile { src: p, rel, categories: Vec::new(), raw: false })
+                    }
+                }
+            }
+        }
+        (files, ignored)
+    }
+
+    /// Files under `partials/` (the parents' first), as `(partial name, file)`.
+    /// A partial is named by its path inside `partials/` without the last
+    /// extension: `partials/ci/rust-steps.yml` is `{{> ci/rust-steps}}`.
+    pub fn partial_files(&self) -> Vec<(String, PathBuf)> {
+        let mut out = self.parent.as_ref().map(|p| p.partial_files()).unwrap_or_default();
+        let dir = self.root.join(PARTIALS_DIR);
+        for entry in walkdir::WalkDir::new(&dir).into_iter().filter_map(|e| e.ok()) {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let rel = entry.path().strip_prefix(&dir).unwrap().with_extension("");
+            out.push((rel.to_string_lossy().replace('\\', "/"), entry.path().to_path_buf()));
+        }
+        out
+    }
+
+    /// Partial name -> source; a template's partials override its parents'.
+    pub fn partials(&self) -> Result<BTreeMap<String, String>> {
+        self.partial_files()
+            .into_iter()
+            .map(|(name, path)| {
+                let text = fs::read_to_string(&path)
+                    .with_context(|| format!("reading partial {:?}", path))?;
+                Ok((name, text))
+            })
+            .collect()
+    }
+
+    /// Content hash of the template files and partials (see [`content_hash`]).
+    pub fn content_hash(&self) -> Result<String> {
+        let mut files = self.files();
+        files.extend(self.partial_files().into_iter().map(|(name, src)| TemplateFile {
+            src,
+            rel: Path::new(PARTIALS_DIR).join(name),
+            categories: Vec::new(),
+            raw: false,
+        }));
+        content_hash(&files)
+    }
+}
+
+fn embedded_manifest_source(name: &str) -> Option<String> {
+    EMBEDDED_TEMPLATES
+        .get_file(format!("{}.toml", name))
+        .and_then(|f| f.contents_utf8())
+        .map(str::to_string)
+}
+
+/// A template found by [`list_templates`].
+#[derive(Debug, Clone, Serialize)]
+pub struct TemplateInfo {
+    pub name: String,
+    pub source: TemplateSource,
+    /// Template folder (`None` for embedded templates)
+    pub path: Option<PathBuf>,
+    pub version: Option<String>,
+    pub description: Option<String>,
+    pub extends: Option<String>,
+    /// Whether [`Template::locate`] picks this copy; `false` when a location
+    /// searched earlier has a template of the same name
+    pub active: bool,
+}
+
+/// Every template available from the `search_path` roots, the repository
+/// `templates/`, the embedded copy and exe-relative `templates/`, in
+/// lookup order within each name.
+pub fn list_templates(repo_root: &Path, search_path: &[PathBuf]) -> Result<Vec<TemplateInfo>> {
+    list_in(repo_root, search_path, Path::new(""))
+}
+
+/// Every component (`components/<name>/` in the template roots), like
+/// [`list_templates`].
+pub fn list_components(repo_root: &Path, search_path: &[PathBuf]) -> Result<Vec<TemplateInfo>> {
+    list_in(repo_root, search_path, Path::new(COMPONENTS_DIR))
+}
+
+/// Templates in the `sub` folder of every template root.
+fn list_in(repo_root: &Path, search_path: &[PathBuf], sub: &Path) -> Result<Vec<TemplateInfo>> {
+    let mut found = Vec::new();
+    for root in search_path {
+        scan_templates(&root.join(sub), TemplateSource::SearchPath, &mut found)?;
+    }
+    scan_templates(&repo_root.join("templates").join(sub), TemplateSource::Repo, &mut found)?;
+    let embedded = if sub.as_os_str().is_empty() {
+        Some(&EMBEDDED_TEMPLATES)
+    } else {
+        EMBEDDED_TEMPLATES.get_dir(sub)
+    };
+    for d in embedded.into_iter().flat_map(|d| d.dirs()) {
+        let key = embedded_key(d.path());
+        if key == COMPONENTS_DIR {
+            continue;
+        }
+        let name = d.path().file_name().unwrap_or_default().to_string_lossy().to_string();
+        let manifest = embedded_manifest_source(&key)
+            .as_deref()
+            .map(parse_manifest)
+            .transpose()
+            .with_context(|| format!("embedded template '{}'", key))?;
+        found.push(template_info(name, TemplateSource::Embedded, None, manifest));
+    }
+    if let Some(parent) = std::env::current_exe().ok().as_deref().and_then(Path::parent) {
+        let root = parent.join("templates").join(sub);
+        scan_templates(&root, TemplateSource::ExeRelative, &mut found)?;
+    }
+
+    let mut seen = HashSet::new();
+    for t in found.iter_mut() {
+        t.active = seen.insert(t.name.clone());
+    }
+    // stable: keeps lookup order within a name
+    found.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(found)
+}
+
+/// Template folders directly under `root`.
+fn scan_templates(
+    root: &Path,
+    source: TemplateSource,
+    found: &mut Vec<TemplateInfo>,
+) -> Result<()> {
+    let Ok(entries) = fs::read_dir(root) else {
+        return Ok(());
+    };
+    // the components folder of a template root holds components, not a template
+    let mut dirs: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_dir() && p.file_name() != Some(std::ffi::OsStr::new(COMPONENTS_DIR)))
+        .collect();
+    dirs.sort();
+    for dir in dirs {
+        let name = dir.file_name().unwrap().to_string_lossy().to_string();
+        let manifest = read_manifest(&root.join(format!("{}.toml", name)))
+            .with_context(|| format!("template '{}' in {}", name, root.display()))?;
+        found.push(template_info(name, source, Some(dir), manifest));
+    }
+    Ok(())
+}
+
+fn template_info(
+    name: String,
+    source: TemplateSource,
+    path: Option<PathBuf>,
+    manifest: Option<TemplateManifest>,
+) -> TemplateInfo {
+    let m = manifest.unwrap_or_default();
+    TemplateInfo {
+        name,
+        source,
+        path,
+        version: m.version,
+        description: m.description,
+        extends: m.extends,
+        active: false,
+    }
+}
+
+/// Value used for a variable by `cosmos template check`: its default, else
+/// the first choice or a type-appropriate placeholder.
+pub fn sample_value(p: &VariablePrompt) -> String {
+    if let Some(d) = &p.default {
+        return d.clone();
+    }
+    if let Some(c) = p.spec.choices.first() {
+        return c.clone();
+    }
+    match p.spec.kind {
+        VarType::Bool => "false".to_string(),
+        VarType::Integer => "0".to_string(),
+        VarType::String => "sample".to_string(),
+    }
+}
+
+/// Sanity checks on a rendered project in `dir`: every `*.toml` and `*.json`
+/// file must parse, and `project.toml` must agree with `Cargo.toml`.
+pub fn check_output(dir: &Path) -> Result<Vec<RenderIssue>> {
+    let mut issues = Vec::new();
+    let issue = |rel: &Path, message: String| RenderIssue {
+        file: rel.to_path_buf(),
+        line: None,
+        column: None,
+        message,
+    };
+    for entry in walkdir::WalkDir::new(dir).into_iter().filter_map(|e| e.ok()) {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let rel = entry.path().strip_prefix(dir).unwrap();
+        let ext = rel.extension().and_then(|e| e.to_str());
+        if !matches!(ext, Some("toml") | Some("json")) {
+            continue;
+        }
+        let text = fs::read_to_string(entry.path())
+            .with_context(|| format!("reading {:?}", entry.path()))?;
+        let err = if ext == Some("toml") {
+            toml::from_str::<toml::Value>(&text).err().map(|e| e.message().to_string())
+        } else {
+            serde_json::from_str::<serde_json::Value>(&text).err().map(|e| e.to_string())
+        };
+        if let Some(e) = err {
+            issues.push(issue(rel, format!("invalid {}: {}", ext.unwrap_or_default(), e)));
+        }
+    }
+    if dir.join("project.toml").exists() && issues.is_empty() {
+        let report = crate::project::validate_project_manifest(dir)?;
+        for i in report.errors {
+            issues.push(issue(Path::new("project.toml"), format!("{} ({})", i.message, i.id)));
+        }
+    }
+    Ok(issues)
+}
+
+fn strip_template_prefix(rel: &Path, template: &str) -> PathBuf {
+    match rel.strip_prefix(template) {
+        Ok(stripped) if !stripped.as_os_str().is_empty() => stripped.to_path_buf(),
+        _ => rel.to_path_buf(),
+    }
+}
+
+fn extract_embedded(d: &Dir, base: &Path, template: &str) -> Result<()> {
+    for file in d.files() {
+        // remove a leading template dir segment if present (e.g., 'default/...')
+        let rel = strip_template_prefix(file.path(), template);
+        let dest = base.join(rel);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if let Some(target) = embedded_symlink(file.path()) {
+            make_symlink(Path::new(target), &dest, || Ok(fs::write(&dest, file.contents())?))?;
+            continue;
+        }
+        fs::write(&dest, file.contents())?;
+        if let Some(mode) = embedded_mode(file.path()) {
+            set_mode(&dest, mode)?;
+        }
+    }
+    for sd in d.dirs() {
+        if let Some(target) = embedded_symlink(sd.path()) {
+            let dest = base.join(strip_template_prefix(sd.path(), template));
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            make_symlink(Path::new(target), &dest, || extract_embedded(sd, base, template))?;
+            continue;
+        }
+        extract_embedded(sd, base, template)?;
+    }
+    Ok(())
+}
+
+fn embedded_key(p: &Path) -> String {
+    p.to_string_lossy().replace('\\', "/")
+}
+
+fn embedded_mode(p: &Path) -> Option<u32> {
+    let key = embedded_key(p);
+    EMBEDDED_MODES.iter().find(|(path, _)| *path == key).map(|(_, mode)| *mode)
+}
+
+fn embedded_symlink(p: &Path) -> Option<&'static str> {
+    let key = embedded_key(p);
+    EMBEDDED_SYMLINKS.iter().find(|(path, _)| *path == key).map(|(_, target)| *target)
+}
+
+/// Create `link` pointing at `target`; where symlinks are unavailable run
+/// `fallback` (copy the contents) instead.
+#[cfg(unix)]
+fn make_symlink(target: &Path, link: &Path, _fallback: impl FnOnce() -> Result<()>) -> Result<()> {
+    std::os::unix::fs::symlink(target, link)
+        .with_context(|| format!("creating symlink {:?} -> {:?}", link, target))
+}
+
+#[cfg(not(unix))]
+fn make_symlink(_target: &Path, _link: &Path, fallback: impl FnOnce() -> Result<()>) -> Result<()> {
+    fallback()
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+        .with_context(|| format!("setting permissions on {:?}", path))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) -> Result<()> {
+    Ok(())
+}
+
+/// Give `dest` the permissions of `src`, keeping it writable by the owner so
+/// later syncs can overwrite it.
+#[cfg(unix)]
+fn copy_mode(src: &fs::Metadata, dest: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    set_mode(dest, (src.permissions().mode() & 0o7777) | 0o200)
+}
+
+#[cfg(not(unix))]
+fn copy_mode(_src: &fs::Metadata, _dest: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// Build the Handlebars context from `--project-name` and `--var k=v` pairs.
+pub fn build_context(
+    project_name: Option<String>,
+    vars: Vec<(String, String)>,
+) -> serde_json::Map<String, serde_json::Value> {
+    let mut ctx = serde_json::Map::new();
+    if let Some(n) = project_name {
+        ctx.insert("project-name".to_string(), serde_json::Value::String(n));
+    }
+    for (k, v) in vars {
+        ctx.insert(k, serde_json::Value::String(v));
+    }
+    ctx
+}
+
+/// Renders template paths and contents against a context.
+///
+/// GitHub Actions expressions (`${{ ... }}`) are never interpreted by
+/// Handlebars, so workflows can be templated too.
+pub struct Renderer {
+    hb: handlebars::Handlebars<'static>,
+    ctx: serde_json::Map<String, serde_json::Value>,
+}
+
+impl Renderer {
+    pub fn new(ctx: serde_json::Map<String, serde_json::Value>) -> Renderer {
+        let mut hb = handlebars::Handlebars::new();
+        // generated files are source code and config, not HTML
+        hb.register_escape_fn(handlebars::no_escape);
+        crate::helpers::register(&mut hb);
+        Renderer { hb, ctx }
+    }
+
+    /// Register Handlebars partials (see [`Template::partials`]).
+    pub fn partials(mut self, partials: &BTreeMap<String, String>) -> Result<Renderer> {
+        for (name, text) in partials {
+            self.hb.register_partial(name, escape_github_expressions(text)).map_err(|e| {
+                CosmosError::Render(format!(
+                    "partial '{}' ({}:{}): {}",
+                    name,
+                    e.line_no.unwrap_or(0),
+                    e.column_no.unwrap_or(0),
+                    e.reason()
+                ))
+            })?;
+        }
+        Ok(self)
+    }
+
+    /// Enable Handlebars strict mode: unknown variables become errors.
+    pub fn strict(mut self, strict: bool) -> Renderer {
+        self.hb.set_strict_mode(strict);
+        self
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.hb.strict_mode()
+    }
+
+    /// Variables templates are rendered with.
+    pub fn context(&self) -> &serde_json::Map<String, serde_json::Value> {
+        &self.ctx
+    }
+
+    /// Render a relative path; falls back to the raw path on error.
+    pub fn render_path(&self, rel: &Path) -> PathBuf {
+        match self.try_render(&rel.to_string_lossy(), rel) {
+            Ok(s) => PathBuf::from(s),
+            Err(_) => rel.to_path_buf(),
+        }
+    }
+
+    /// Render file contents; falls back to the raw text on error.
+    pub fn render_text(&self, text: &str) -> String {
+        match self.try_render(text, Path::new("")) {
+            Ok(s) => s,
+            Err(_) => text.to_string(),
+        }
+    }
+
+    /// Render `text` (the path or contents of `file`), reporting where it fails.
+    pub fn try_render(&self, text: &str, file: &Path) -> std::result::Result<String, RenderIssue> {
+        let text = escape_github_expressions(text);
+        self.hb.render_template(&text, &self.ctx).map_err(|e| {
+            let mut issue = RenderIssue {
+                file: file.to_path_buf(),
+                line: e.line_no,
+                column: e.column_no,
+                message: e.desc.clone(),
+            };
+            // syntax errors only carry their position on the underlying TemplateError
+            if let Err(te) = handlebars::Template::compile(&text) {
+                issue.line = te.line_no;
+                issue.column = te.column_no;
+                issue.message = te.reason().to_string();
+            }
+            issue
+        })
+    }
+}
+
+/// Escape `${{` so Handlebars leaves GitHub Actions expressions alone.
+fn escape_github_expressions(text: &str) -> Cow<'_, str> {
+    if text.contains("${{") {
+        Cow::Owned(text.replace("${{", "$\\{{"))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// A template error found by [`check_render`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RenderIssue {
+    /// Template-relative path of the offending file
+    pub file: PathBuf,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl std::fmt::Display for RenderIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.file.display())?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+            if let Some(col) = self.column {
+                write!(f, ":{}", col)?;
+            }
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Render the path and contents of every file without writing anything and
+/// collect all errors. Use a [`Renderer::strict`] renderer to also catch
+/// unknown variables.
+pub fn check_render(files: &[TemplateFile], renderer: &Renderer) -> Result<Vec<RenderIssue>> {
+    let mut issues = Vec::new();
+    for f in files {
+        if let Err(issue) = renderer.try_render(&f.rel.to_string_lossy(), &f.rel) {
+            issues.push(issue);
+        }
+        if f.raw || f.src.is_symlink() {
+            continue;
+        }
+        let bytes =
+            fs::read(&f.src).with_context(|| format!("reading template file {:?}", f.src))?;
+        if is_binary(&bytes) {
+            continue;
+        }
+        if let Err(issue) = renderer.try_render(&String::from_utf8_lossy(&bytes), &f.rel) {
+            issues.push(issue);
+        }
+    }
+    Ok(issues)
+}
+
+/// Whether a destination path must never be deleted by a sync (VCS data,
+/// third-party dirs, lockfiles; see [`exclude::BUILTIN_PROTECTED`]).
+pub fn is_protected_path(p: &Path) -> bool {
+    let mut rules = Rules::default();
+    rules.add_patterns("built-in", exclude::BUILTIN_PROTECTED).is_ok()
+        && rules.matched(p, false).is_some()
+}
+
+/// Differences between the desired template files and an existing destination.
+#[derive(Debug, Default)]
+pub struct SyncPlan {
+    /// Files missing from the destination
+    pub missing: Vec<PathBuf>,
+    /// Differing files that will be overwritten (`force`)
+    pub modified: Vec<PathBuf>,
+    /// Differing files left untouched (no `force`)
+    pub skipped_overwrite: Vec<PathBuf>,
+    /// Destination files not in the template that may be deleted
+    pub extra: Vec<PathBuf>,
+    /// Destination files kept as they are: extra files protected from
+    /// deletion and differing files protected from being overwritten
+    pub protected: Vec<PathBuf>,
+    /// The rule protecting each `protected` path
+    pub rules: BTreeMap<PathBuf, Rule>,
+}
+
+impl SyncPlan {
+    /// Whether the destination needs confirmation before writing. Protected
+    /// files are never touched, so they alone need none.
+    pub fn has_changes(&self) -> bool {
+        !self.missing.is_empty() || !self.modified.is_empty() || !self.extra.is_empty()
+    }
+
+    /// Flat list of every planned path and its action, sorted by path.
+    pub fn entries(&self) -> Vec<PlannedFile> {
+        let groups = [
+            (&self.missing, FileAction::Add),
+            (&self.modified, FileAction::Overwrite),
+            (&self.skipped_overwrite, FileAction::Skip),
+            (&self.extra, FileAction::Delete),
+            (&self.protected, FileAction::Protected),
+        ];
+        let mut out: Vec<PlannedFile> = groups
+            .into_iter()
+            .flat_map(|(paths, action)| {
+                paths.iter().map(move |p| PlannedFile {
+                    path: p.clone(),
+                    action,
+                    rule: self.rules.get(p).cloned(),
+                })
+            })
+            .collect();
+        out.sort_by(|a, b| a.path.cmp(&b.path));
+        out
+    }
+}
+
+/// What generation does with a destination path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileAction {
+    /// New file
+    Add,
+    /// Differing file replaced (`force`)
+    Overwrite,
+    /// Differing file left untouched
+    Skip,
+    /// Extra file removed from the destination
+    Delete,
+    /// Existing file kept because it is protected
+    Protected,
+}
+
+/// One entry of [`SyncPlan::entries`].
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedFile {
+    pub path: PathBuf,
+    pub action: FileAction,
+    /// Rule behind a `protected` action
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule: Option<Rule>,
+}
+
+/// Compare template files against the current contents of `dest`.
+pub fn plan_sync(files: &[TemplateFile], dest: &Path, force: bool) -> Result<SyncPlan> {
+    plan_sync_rendered(&raw_outputs(files)?, dest, force)
+}
+
+/// Files as they are, for verbatim copies: `(rel, contents or link)`.
+pub fn raw_outputs(files: &[TemplateFile]) -> Result<Vec<(PathBuf, RenderedFile)>> {
+    files
+        .iter()
+        .map(|f| {
+            let out = if f.src.is_symlink() {
+                RenderedFile::Symlink(fs::read_link(&f.src)?)
+            } else {
+                RenderedFile::Contents(
+                    fs::read(&f.src).with_context(|| format!("reading {:?}", f.src))?,
+                )
+            };
+            Ok((f.rel.clone(), out))
+        })
+        .collect()
+}
+
+/// [`plan_sync`] for already rendered output (see [`render_all`]), so that
+/// destination files are compared with what would actually be written.
+/// Protection comes from the destination's `.cosmosignore` and the built-in
+/// rules, see [`plan_sync_protected`].
+pub fn plan_sync_rendered(
+    files: &[(PathBuf, RenderedFile)],
+    dest: &Path,
+    force: bool,
+) -> Result<SyncPlan> {
+    plan_sync_protected(files, dest, force, &Rules::destination(dest, &[])?)
+}
+
+/// [`plan_sync_rendered`] with explicit protection rules (see
+/// [`Rules::destination`]): matching extra files are never deleted and
+/// matching differing files never overwritten, even with `force`.
+pub fn plan_sync_protected(
+    files: &[(PathBuf, RenderedFile)],
+    dest: &Path,
+    force: bool,
+    protect: &Rules,
+) -> Result<SyncPlan> {
+    let mut plan = SyncPlan::default();
+    if !dest.exists() {
+        plan.missing = files.iter().map(|(rel, _)| rel.clone()).collect();
+        plan.missing.sort();
+        return Ok(plan);
+    }
+
+    // gather current files under dest
+    let mut current: HashSet<PathBuf> = HashSet::new();
+    for entry in walkdir::WalkDir::new(dest).into_iter().filter_map(|e| e.ok()) {
+        let p = entry.path();
+        if entry.file_type().is_file() || entry.path_is_symlink() {
+            if let Ok(rel) = p.strip_prefix(dest) {
+                // cosmos bookkeeping (lock file, base snapshot, side-by-side
+                // copies) is not project content
+                if rel.starts_with(crate::update::STATE_DIR)
+                    || rel.to_string_lossy().ends_with(crate::update::SIDE_BY_SIDE_SUFFIX)
+                {
+                    continue;
+                }
+                current.insert(rel.to_path_buf());
+            }
+        }
+    }
+    let desired: HashSet<PathBuf> = files.iter().map(|(rel, _)| rel.clone()).collect();
+
+    plan.missing = desired.difference(&current).cloned().collect();
+    for e in current.difference(&desired) {
+        match protect.matched(e, false) {
+            Some(rule) => {
+                plan.protected.push(e.clone());
+                plan.rules.insert(e.clone(), rule);
+            }
+            None => plan.extra.push(e.clone()),
+        }
+    }
+
+    // modified: intersection where contents differ
+    for (rel, out) in files.iter().filter(|(rel, _)| current.contains(rel)) {
+        if differs(out, &dest.join(rel)) {
+            if let Some(rule) = protect.matched(rel, false) {
+                plan.protected.push(rel.clone());
+                plan.rules.insert(rel.clone(), rule);
+            } else if force {
+                plan.modified.push(rel.clone());
+            } else {
+                plan.skipped_overwrite.push(rel.clone());
+            }
+        }
+    }
+
+    plan.missing.sort();
+    plan.modified.sort();
+    plan.skipped_overwrite.sort();
+    plan.extra.sort();
+    plan.protected.sort();
+    Ok(plan)
+}
+
+/// Whether writing `out` would change `dst`.
+pub fn differs(out: &RenderedFile, dst: &Path) -> bool {
+    match out {
+        RenderedFile::Symlink(target) => fs::read_link(dst).ok().as_ref() != Some(target),
+        RenderedFile::Contents(_) if dst.is_symlink() => true,
+        RenderedFile::Contents(bytes) => matches!(fs::read(dst), Ok(db) if &db != bytes),
+    }
+}
+
+/// Difference between rendered output and the destination, see
+/// [`diff_rendered`].
+#[derive(Debug, Clone, Serialize)]
+pub struct FileDiff {
+    pub path: PathBuf,
+    /// The destination has no such file yet
+    pub new: bool,
+    pub insertions: usize,
+    pub deletions: usize,
+    /// Binary contents; no line diff
+    pub binary: bool,
+    /// Unified diff (`a/` = destination, `b/` = rendered output)
+    pub diff: String,
+}
+
+/// Unified diffs of every rendered file that is new or differs from its
+/// destination counterpart, sorted by path.
+pub fn diff_rendered(files: &[(PathBuf, RenderedFile)], dest: &Path) -> Result<Vec<FileDiff>> {
+    use similar::{ChangeTag, TextDiff};
+
+    let mut out = Vec::new();
+    for (rel, rendered) in files {
+        let dst = dest.join(rel);
+        let exists = dst.symlink_metadata().is_ok();
+        if exists && !differs(rendered, &dst) {
+            continue;
+        }
+        let old: Vec<u8> = match fs::read_link(&dst) {
+            Ok(target) => format!("-> {}\n", target.display()).into_bytes(),
+            Err(_) if exists => fs::read(&dst).with_context(|| format!("reading {:?}", dst))?,
+            Err(_) => Vec::new(),
+        };
+        let new: Vec<u8> = match rendered {
+            RenderedFile::Symlink(target) => format!("-> {}\n", target.display()).into_bytes(),
+            RenderedFile::Contents(bytes) => bytes.clone(),
+        };
+        let path = rel.to_string_lossy().replace('\\', "/");
+        let old_name = if exists { format!("a/{}", path) } else { "/dev/null".to_string() };
+        let mut d = FileDiff {
+            path: rel.clone(),
+            new: !exists,
+            insertions: 0,
+            deletions: 0,
+            binary: false,
+            diff: String::new(),
+        };
+        if is_binary(&old) || is_binary(&new) {
+            d.binary = true;
+            d.diff = format!("Binary files {} and b/{} differ\n", old_name, path);
+            out.push(d);
+            continue;
+        }
+        let (old, new) = (String::from_utf8_lossy(&old), String::from_utf8_lossy(&new));
+        let text = TextDiff::from_lines(&*old, &*new);
+        for change in text.iter_all_changes() {
+            match change.tag() {
+                ChangeTag::Insert => d.insertions += 1,
+                ChangeTag::Delete => d.deletions += 1,
+                ChangeTag::Equal => {}
+            }
+        }
+        d.diff = text.unified_diff().header(&old_name, &format!("b/{}", path)).to_string();
+        out.push(d);
+    }
+    out.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(out)
+}
+
+/// Delete `paths` (relative to `dest`) if they exist.
+pub fn delete_paths(dest: &Path, paths: &[PathBuf]) -> Result<()> {
+    for e in paths {
+        let p = dest.join(e);
+        if p.symlink_metadata().is_ok() {
+            fs::remove_file(&p).with_context(|| format!("deleting extra file {:?}", p))?;
+        }
+    }
+    Ok(())
+}
+
+/// Files written and skipped by [`write_files`].
+#[derive(Debug, Default)]
+pub struct WriteReport {
+    pub written: Vec<PathBuf>,
+    /// Existing destination files left alone because `force` was not set
+    pub skipped: Vec<PathBuf>,
+}
+
+/// Rendered output of one template file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenderedFile {
+    Contents(Vec<u8>),
+    Symlink(PathBuf),
+}
+
+/// Render one file in memory: text is rendered (without its front-matter
+/// line), raw and binary files are returned byte-for-byte, symlinks as links.
+pub fn render_file(f: &TemplateFile, renderer: &Renderer) -> Result<RenderedFile> {
+    if f.src.is_symlink() {
+        return Ok(RenderedFile::Symlink(fs::read_link(&f.src)?));
+    }
+    let sbytes = fs::read(&f.src).with_context(|| format!("reading template file {:?}", f.src))?;
+    if f.raw || is_binary(&sbytes) {
+        return Ok(RenderedFile::Contents(sbytes));
+    }
+    let text = String::from_utf8_lossy(&sbytes);
+    let body = front_matter_condition(&text).map_or(&*text, |(_, rest)| rest);
+    Ok(RenderedFile::Contents(renderer.render_text(body).into_bytes()))
+}
+
+/// Destination path of every file and its rendered output.
+pub fn render_all(
+    template: &Template,
+    files: &[TemplateFile],
+    renderer: &Renderer,
+) -> Result<Vec<(PathBuf, RenderedFile)>> {
+    files.iter().map(|f| Ok((template.dest_rel(f, renderer), render_file(f, renderer)?))).collect()
+}
+
+/// Content hash of a template (`sha256:<hex>` over every path and file).
+pub fn content_hash(files: &[TemplateFile]) -> Result<String> {
+    use sha2::{Digest, Sha256};
+    let mut sorted: Vec<&TemplateFile> = files.iter().collect();
+    sorted.sort_by(|a, b| a.rel.cmp(&b.rel));
+    let mut hasher = Sha256::new();
+    for f in sorted {
+        hasher.update(f.rel.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        if f.src.is_symlink() {
+            hasher.update(fs::read_link(&f.src)?.to_string_lossy().as_bytes());
+        } else {
+            hasher.update(fs::read(&f.src).with_context(|| format!("reading {:?}", f.src))?);
+        }
+        hasher.update([0]);
+    }
+    Ok(format!("sha256:{:x}", hasher.finalize()))
+}
+
+/// Render every template file into `dest`. Existing files are only
+/// overwritten with `force`.
+pub fn write_files(
+    template: &Template,
+    files: &[TemplateFile],
+    renderer: &Renderer,
+    dest: &Path,
+    force: bool,
+) -> Result<WriteReport> {
+    write_files_into(template, files, renderer, dest, dest, force)
+}
+
+/// Like [`write_files`], but stage the output in `tx` instead of writing to
+/// the destination directly.
+pub fn stage_files(
+    template: &Template,
+    files: &[TemplateFile],
+    renderer: &Renderer,
+    tx: &Transaction,
+    force: bool,
+) -> Result<WriteReport> {
+    write_files_into(template, files, renderer, tx.dest(), tx.root(), force)
+}
+
+/// Like [`stage_files`], for a template rendered into the `prefix` folder
+/// of the destination (a workspace member).
+pub fn stage_files_at(
+    template: &Template,
+    files: &[TemplateFile],
+    renderer: &Renderer,
+    tx: &Transaction,
+    prefix: &Path,
+    force: bool,
+) -> Result<WriteReport> {
+    write_files_into(
+        template,
+        files,
+        renderer,
+        &tx.dest().join(prefix),
+        &tx.root().join(prefix),
+        force,
+    )
+}
+
+/// Write into `out`, skipping files that already exist in `dest`.
+fn write_files_into(
+    template: &Template,
+    files: &[TemplateFile],
+    renderer: &Renderer,
+    dest: &Path,
+    out: &Path,
+    force: bool,
+) -> Result<WriteReport> {
+    let mut report = WriteReport::default();
+    for f in files {
+        let dest_rel = template.dest_rel(f, renderer);
+        let exists = dest.join(&dest_rel).symlink_metadata().is_ok();
+        if exists && !force {
+            report.skipped.push(dest.join(&dest_rel));
+            continue;
+        }
+        let destpath = out.join(&dest_rel);
+        if let Some(parent) = destpath.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let exists = destpath.symlink_metadata().is_ok();
+
+        match render_file(f, renderer)? {
+            RenderedFile::Symlink(target) => {
+                if exists {
+                    fs::remove_file(&destpath)
+                        .with_context(|| format!("replacing {:?} with a symlink", destpath))?;
+                }
+                make_symlink(&target, &destpath, || Ok(fs::copy(&f.src, &destpath).map(|_| ())?))?;
+            }
+            RenderedFile::Contents(contents) => {
+                fs::write(&destpath, contents)
+                    .with_context(|| format!("writing file {:?}", destpath))?;
+                copy_mode(&fs::metadata(&f.src)?, &destpath)?;
+            }
+        }
+        report.written.push(dest_rel);
+    }
+    Ok(report)
+}
+
+/// If files were accidentally written under a leading template subdir (e.g.
+/// `./default/*`), move them up to the destination root. Returns whether
+/// anything was moved.
+pub fn flatten_nested(dest: &Path, template: &str) -> Result<bool> {
+    let nested: Vec<PathBuf> = walkdir::WalkDir::new(dest.join(template))
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_file())
+        .map(|e| e.path().to_path_buf())
+        .collect();
+    if nested.is_empty() {
+        return Ok(false);
+    }
+    for p in nested {
+        let rel = p.strip_prefix(dest.join(template)).unwrap();
+        let target = dest.join(rel);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(&p, &target).or_else(|_| {
+            // fallback to copy+remove
+            fs::copy(&p, &target).and_then(|_| fs::remove_file(&p))
+        })?;
+    }
+    // try to remove the now-empty template dir
+    let _ = fs::remove_dir_all(dest.join(template));
+    Ok(true)
+}
+
+/// Expand manifest glob patterns relative to `repo_root`, skipping `.git`,
+/// `target` and what the repository's `.cosmosignore` lists.
+pub fn expand_patterns(repo_root: &Path, patterns: &[String]) -> Result<Vec<PathBuf>> {
+    let mut ignore = Rules::default();
+    ignore.add_file(&repo_root.join(exclude::IGNORE_FILE))?;
+    let mut matches = Vec::new();
+    for pat in patterns {
+        // Patterns are relative to repo root
+        let full = repo_root.join(pat);
+        let pattern = full.to_string_lossy().to_string();
+        for p in (glob(&pattern).context("globbing pattern")?).flatten() {
+            // skip .git and target directories
+            if p.components().any(|c| c.as_os_str() == ".git" || c.as_os_str() == "target") {
+                continue;
+            }
+            let rel = p.strip_prefix(repo_root).unwrap_or(&p);
+            if ignore.matched(rel, p.is_dir()).is_some() {
+                continue;
+            }
+            matches.push(p);
+        }
+    }
+    matches.sort();
+    matches.dedup();
+    Ok(matches)
+}
+
+/// Template files for pattern-based generation: matched repo files relative
+/// to `repo_root`.
+pub fn pattern_files(repo_root: &Path, matches: &[PathBuf]) -> Vec<TemplateFile> {
+    matches
+        .iter()
+        .filter(|p| p.is_file())
+        .filter_map(|p| {
+            p.strip_prefix(repo_root).ok().map(|rel| TemplateFile {
+                src: p.clone(),
+                rel: rel.to_path_buf(),
+                categories: Vec::new(),
+                raw: false,
+            })
+        })
+        .collect()
+}
+
+/// Copy matched repo paths verbatim into `dest`. Returns the existing files
+/// skipped because `force` was not set.
+pub fn copy_paths_to(
+    repo_root: &Path,
+    paths: &[PathBuf],
+    dest: &Path,
+    force: bool,
+) -> Result<Vec<PathBuf>> {
+    copy_paths_into(repo_root, paths, dest, dest, force)
+}
+
+/// Like [`copy_paths_to`], but stage the copies in `tx`.
+pub fn stage_paths(
+    repo_root: &Path,
+    paths: &[PathBuf],
+    tx: &Transaction,
+    force: bool,
+) -> Result<Vec<PathBuf>> {
+    copy_paths_into(repo_root, paths, tx.dest(), tx.root(), force)
+}
+
+/// Copy into `out`, skipping files that already exist in `dest`.
+fn copy_paths_into(
+    repo_root: &Path,
+    paths: &[PathBuf],
+    dest: &Path,
+    out: &Path,
+    force: bool,
+) -> Result<Vec<PathBuf>> {
+    let mut skipped = Vec::new();
+    for p in paths {
+        let rel = p.strip_prefix(repo_root).unwrap_or(p);
+        if !p.is_dir() && dest.join(rel).exists() && !force {
+            skipped.push(dest.join(rel));
+            continue;
+        }
+        let destpath = out.join(rel);
+        if let Some(parent) = destpath.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if p.is_dir() {
+            // create dir marker
+            fs::create_dir_all(&destpath)?;
+        } else {
+            fs::copy(p, &destpath).with_context(|| format!("copy {:?} to {:?}", p, destpath))?;
+        }
+    }
+    Ok(skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_categories_match_manifest_globs() {
+        let cats = default_categories();
+        assert_eq!(file_categories(&cats, Path::new("docs/guide/intro.md")), vec!["docs"]);
+        assert_eq!(file_categories(&cats, Path::new(".github/workflows/ci.yml")), vec!["ci"]);
+        assert_eq!(file_categories(&cats, Path::new("Cargo.toml")), vec!["basis"]);
+        assert!(file_categories(&cats, Path::new("src/lib.rs")).is_empty());
+
+        let only_docs = CategoryFilter::new(vec!["docs".into()], Vec::new());
+        assert!(only_docs.selects(&["docs".to_string()]));
+        assert!(!only_docs.selects(&[]));
+        let all_but_plan = CategoryFilter::new(vec!["all".into()], vec!["plan".into()]);
+        assert!(all_but_plan.selects(&[]));
+        assert!(!all_but_plan.selects(&["plan".to_string()]));
+        assert!(CategoryFilter::new(vec!["nope".into()], Vec::new()).check(&cats).is_err());
+    }
+
+    #[test]
+    fn resolve_variables_derives_defaults_and_validates() {
+        let manifest = parse_manifest(
+            r#"
+[categories]
+[variables.bin-name]
+default = "{{project-name}}-cli"
+[variables.project-name]
+regex = "[a-z][a-z-]*"
+[variables.docker]
+type = "bool"
+default = false
+[variables.license]
+choices = ["MIT", "Apache-2.0"]
+default = "MIT"
+"#,
+        )
+        .unwrap();
+        let vars = &manifest.variables;
+
+        let mut ctx = build_context(Some("demo".into()), vec![("docker".into(), "yes".into())]);
+        resolve_variables(vars, &mut ctx, None).unwrap();
+        assert_eq!(ctx["bin-name"], "demo-cli");
+        assert_eq!(ctx["docker"], true);
+        assert_eq!(ctx["license"], "MIT");
+
+        let mut ctx = build_context(None, Vec::new());
+        let err = resolve_variables(vars, &mut ctx, None).unwrap_err();
+        assert!(err.to_string().contains("variables: project-name ("), "{}", err);
+
+        let mut ctx = build_context(Some("Demo".into()), Vec::new());
+        assert!(resolve_variables(vars, &mut ctx, None).is_err());
+        let mut ctx = build_context(Some("demo".into()), vec![("license".into(), "GPL".into())]);
+        assert!(resolve_variables(vars, &mut ctx, None).is_err());
+
+        // prompts are asked with the derived default
+        let mut asked = Vec::new();
+        let mut ctx = build_context(None, Vec::new());
+        let mut ask = |p: &VariablePrompt| -> Result<String> {
+            asked.push((p.name.to_string(), p.default.clone()));
+            Ok(p.default.clone().unwrap_or_else(|| "prompted".to_string()))
+        };
+        resolve_variables(vars, &mut ctx, Some(&mut ask)).unwrap();
+        assert_eq!(ctx["bin-name"], "prompted-cli");
+        assert!(asked.contains(&("bin-name".to_string(), Some("prompted-cli".to_string()))));
+    }
+
+    #[test]
+    fn strict_renderer_reports_positions_and_keeps_github_expressions() {
+        let renderer = Renderer::new(build_context(Some("demo".into()), Vec::new())).strict(true);
+        let text = "name: {{project-name}}\nif: ${{ github.ref == 'main' || false }}\n";
+        assert_eq!(
+            renderer.try_render(text, Path::new("ci.yml")).unwrap(),
+            "name: demo\nif: ${{ github.ref == 'main' || false }}\n"
+        );
+
+        let issue = renderer.try_render("ok\n{{nope}}\n", Path::new("a.md")).unwrap_err();
+        assert_eq!((issue.line, issue.column), (Some(2), Some(1)));
+        assert_eq!(issue.to_string(), "a.md:2:1: Variable \"nope\" not found in strict mode.");
+        assert!(renderer.try_render("{{#if x}}", Path::new("b.md")).is_err());
+    }
+
+    #[test]
+    fn conditions_and_front_matter() {
+        let ctx = build_context(
+            Some("demo".into()),
+            vec![("kind".into(), "service".into()), ("docker".into(), "false".into())],
+        );
+        assert!(eval_condition("kind == service", &ctx).unwrap());
+        assert!(eval_condition("kind == \"service\" && !docker", &ctx).unwrap());
+        assert!(!eval_condition("docker", &ctx).unwrap());
+        assert!(eval_condition("docker || kind != library", &ctx).unwrap());
+        assert!(!eval_condition("missing", &ctx).unwrap());
+        assert!(eval_condition("kind ==", &ctx).is_ok());
+        assert!(eval_condition("a b", &ctx).is_err());
+
+        assert_eq!(
+            front_matter_condition("{{!-- cosmos-if: docker --}}\nbody\n"),
+            Some(("docker", "body\n"))
+        );
+        assert_eq!(front_matter_condition("{{!-- note --}}\nbody\n"), None);
+    }
+}
@@ -0,0 +1,346 @@
+//! Transactional writes into a destination directory.
+//!
+//! Output is staged in a temporary directory next to the destination and only
+//! moved into place once everything rendered and wrote successfully. Before
+//! anything is touched, every file that will be replaced or deleted is copied
+//! to `.cosmos/backup/<id>/`, together with a manifest of the files added, so
+//! [`rollback`] can restore the previous state.
+//!
+//! ```no_run
+//! use rust_repo_template::transaction::Transaction;
+//!
+//! let dest = std::path::Path::new("my-project");
+//! let mut tx = Transaction::begin(dest)?;
+//! std::fs::write(tx.root().join("README.md"), "# new\n")?;
+//! tx.delete("OLD.md");
+//! let commit = tx.commit()?;
+//! println!("backup: {:?}", commit.backup);
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+
+use crate::error::CosmosError;
+
+/// Backups of replaced and deleted files, one folder per commit.
+pub const BACKUP_DIR: &str = ".cosmos/backup";
+/// Manifest inside each backup folder.
+const MANIFEST: &str = ".cosmos-backup.toml";
+
+/// Staged changes to `dest`; dropping it without [`commit`](Self::commit)
+/// discards them.
+pub struct Transaction {
+    dest: PathBuf,
+    staging: TempDir,
+    deletes: Vec<PathBuf>,
+}
+
+/// What a commit changed, relative to the destination.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Commit {
+    /// Backup id (`.cosmos/backup/<id>/`), `None` when nothing changed
+    #[serde(skip)]
+    pub backup: Option<String>,
+    #[serde(default)]
+    pub added: Vec<PathBuf>,
+    #[serde(default)]
+    pub replaced: Vec<PathBuf>,
+    #[serde(default)]
+    pub deleted: Vec<PathBuf>,
+}
+
+impl Commit {
+    /// Replaced or deleted project files, ignoring cosmos bookkeeping.
+    pub fn backed_up(&self) -> usize {
+        self.replaced.iter().chain(&self.deleted).filter(|p| !p.starts_with(".cosmos")).count()
+    }
+}
+
+impl Transaction {
+    /// Start a transaction, creating `dest` if needed.
+    pub fn begin(dest: &Path) -> Result<Self> {
+        fs::create_dir_all(dest).with_context(|| format!("creating {:?}", dest))?;
+        let dest = dest.canonicalize().with_context(|| format!("resolving {:?}", dest))?;
+        // stage next to the destination so the final renames stay on one
+        // filesystem; fall back to the state dir when the parent is read-only
+        let staging = dest
+            .parent()
+            .and_then(|p| tempfile::Builder::new().prefix(".cosmos-staging-").tempdir_in(p).ok());
+        let staging = match staging {
+            Some(s) => s,
+            None => {
+                let state = dest.join(".cosmos");
+                fs::create_dir_all(&state)?;
+                tempfile::Builder::new()
+                    .prefix("staging-")
+                    .tempdir_in(&state)
+                    .context("creating staging directory")?
+            }
+        };
+        Ok(Transaction { dest, staging, deletes: Vec::new() })
+    }
+
+    /// Staging root: write files here at their destination-relative paths.
+    pub fn root(&self) -> &Path {
+        self.staging.path()
+    }
+
+    /// Destination the transaction commits to.
+    pub fn dest(&self) -> &Path {
+        &self.dest
+    }
+
+    /// Delete `rel` on commit (ignored when it does not exist).
+    pub fn delete(&mut self, rel: impl Into<PathBuf>) {
+        self.deletes.push(rel.into());
+    }
+
+    /// Back up everything about to change, then move staged files into place
+    /// and apply the deletions. If moving fails halfway, the destination is
+    /// restored from the backup.
+    pub fn commit(self) -> Result<Commit> {
+        let mut commit = Commit::default();
+        let mut staged = Vec::new();
+        for e in walkdir::WalkDir::new(self.root()).min_depth(1).sort_by_file_name() {
+            let e = e?;
+            if e.file_type().is_dir() {
+                continue;
+            }
+            let rel = e.path().strip_prefix(self.root()).unwrap_or(e.path()).to_path_buf();
+            let target = self.dest.join(&rel);
+            if target.is_dir() && !target.is_symlink() {
+                bail!("cannot replace directory {:?} with a file", target);
+            }
+            if target.symlink_metadata().is_err() {
+                commit.added.push(rel.clone());
+            } else if !same_entry(e.path(), &target) {
+                commit.replaced.push(rel.clone());
+            } else {
+                continue;
+            }
+            staged.push(rel);
+        }
+        for rel in &self.deletes {
+            let target = self.dest.join(rel);
+            if target.symlink_metadata().is_ok() && !target.is_dir() {
+                commit.deleted.push(rel.clone());
+            }
+        }
+        if staged.is_empty() && commit.deleted.is_empty() {
+            return Ok(commit);
+        }
+
+        let id = new_backup_id(&self.dest);
+        let backup = self.dest.join(BACKUP_DIR).join(&id);
+        let backed_up = (|| -> Result<()> {
+            fs::create_dir_all(&backup).with_context(|| format!("creating {:?}", backup))?;
+            for rel in commit.replaced.iter().chain(&commit.deleted) {
+                copy_entry(&self.dest.join(rel), &backup.join(rel))?;
+            }
+            let manifest =
+                toml::to_string_pretty(&commit).context("serializing backup manifest")?;
+            Ok(fs::write(backup.join(MANIFEST), manifest)?)
+        })();
+        if let Err(e) = backed_up {
+            let _ = fs::remove_dir_all(&backup);
+            return Err(e.context("backing up files; nothing was changed"));
+        }
+        commit.backup = Some(id);
+
+        let applied = (|| -> Result<()> {
+            for rel in &staged {
+                move_entry(&self.root().join(rel), &self.dest.join(rel))?;
+            }
+            for rel in &commit.deleted {
+                let p = self.dest.join(rel);
+                fs::remove_file(&p).with_context(|| format!("deleting {:?}", p))?;
+            }
+            Ok(())
+        })();
+        if let Err(e) = applied {
+            let _ = restore(&self.dest, &backup, &commit);
+            return Err(e.context(format!(
+                "applying changes to {:?} failed; restored the previous files",
+                self.dest
+            )));
+        }
+        Ok(commit)
+    }
+}
+
+/// Backup ids in `dest`, oldest first.
+pub fn backups(dest: &Path) -> Result<Vec<String>> {
+    let dir = dest.join(BACKUP_DIR);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut ids: Vec<String> = fs::read_dir(&dir)?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().join(MANIFEST).exists())
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .collect();
+    ids.sort();
+    Ok(ids)
+}
+
+/// Undo the commit recorded as backup `id` (or `latest`): replaced and
+/// deleted files are restored, added files removed. The rollback is itself
+/// a transaction, so it gets a backup of its own.
+pub fn rollback(dest: &Path, id: &str) -> Result<Commit> {
+    let ids = backups(dest)?;
+    let id = match id {
+        "latest" => ids.last().cloned(),
+        _ => ids.iter().find(|i| *i == id).cloned(),
+    }
+    .ok_or_else(|| {
+        let available = if ids.is_empty() { "none".to_string() } else { ids.join(", ") };
+        CosmosError::NotFound(format!(
+            "backup '{}' not found in {} (available: {})",
+            id,
+            dest.join(BACKUP_DIR).display(),
+            available
+        ))
+    })?;
+    let backup = dest.join(BACKUP_DIR).join(&id);
+    let s = fs::read_to_string(backup.join(MANIFEST))?;
+    let recorded: Commit = toml::from_str(&s).with_context(|| format!("parsing {:?}", backup))?;
+
+    let mut tx = Transaction::begin(dest)?;
+    for rel in recorded.replaced.iter().chain(&recorded.deleted) {
+        copy_entry(&backup.join(rel), &tx.root().join(rel))?;
+    }
+    for rel in recorded.added {
+        tx.delete(rel);
+    }
+    tx.commit()
+}
+
+/// Put back the backed-up files and drop the added ones.
+fn restore(dest: &Path, backup: &Path, commit: &Commit) -> Result<()> {
+    for rel in &commit.added {
+        let _ = fs::remove_file(dest.join(rel));
+    }
+    for rel in commit.replaced.iter().chain(&commit.deleted) {
+        copy_entry(&backup.join(rel), &dest.join(rel))?;
+    }
+    Ok(())
+}
+
+/// Timestamp id (`YYYYMMDD-HHMMSS`, UTC) not yet used in `dest`.
+fn new_backup_id(dest: &Path) -> String {
+    let secs =
+        SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    let (y, m, d) = crate::helpers::civil_from_days(secs.div_euclid(86_400));
+    let t = secs.rem_euclid(86_400);
+    let ts = format!("{:04}{:02}{:02}-{:02}{:02}{:02}", y, m, d, t / 3600, t % 3600 / 60, t % 60);
+    let mut id = ts.clone();
+    let mut n = 1;
+    while dest.join(BACKUP_DIR).join(&id).exists() {
+        n += 1;
+        id = format!("{}-{}", ts, n);
+    }
+    id
+}
+
+/// Whether two paths hold the same file contents or symlink target.
+fn same_entry(a: &Path, b: &Path) -> bool {
+    match (fs::read_link(a), fs::read_link(b)) {
+        (Ok(x), Ok(y)) => x == y,
+        (Err(_), Err(_)) => {
+            matches!((fs::read(a), fs::read(b)), (Ok(x), Ok(y)) if x == y) && same_mode(a, b)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(unix)]
+fn same_mode(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = |p: &Path| fs::metadata(p).map(|m| m.permissions().mode()).ok();
+    mode(a) == mode(b)
+}
+
+#[cfg(not(unix))]
+fn same_mode(_a: &Path, _b: &Path) -> bool {
+    true
+}
+
+/// Copy a file (with its permissions) or recreate a symlink at `to`.
+fn copy_entry(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if to.symlink_metadata().is_ok() {
+        fs::remove_file(to).with_context(|| format!("replacing {:?}", to))?;
+    }
+    match fs::read_link(from) {
+        #[cfg(unix)]
+        Ok(target) => std::os::unix::fs::symlink(&target, to)
+            .with_context(|| format!("creating symlink {:?} -> {:?}", to, target)),
+        _ => fs::copy(from, to)
+            .map(|_| ())
+            .with_context(|| format!("copying {:?} to {:?}", from, to)),
+    }
+}
+
+/// Move a staged entry into place, replacing what is there.
+fn move_entry(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    // rename cannot replace on every platform or cross filesystems
+    copy_entry(from, to)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commit_backs_up_and_rollback_restores() {
+        let td = tempfile::tempdir().unwrap();
+        let dest = td.path().join("out");
+        fs::create_dir_all(&dest).unwrap();
+        fs::write(dest.join("keep.md"), "same\n").unwrap();
+        fs::write(dest.join("edit.md"), "old\n").unwrap();
+        fs::write(dest.join("gone.md"), "bye\n").unwrap();
+
+        let mut tx = Transaction::begin(&dest).unwrap();
+        fs::write(tx.root().join("keep.md"), "same\n").unwrap();
+        fs::write(tx.root().join("edit.md"), "new\n").unwrap();
+        fs::create_dir_all(tx.root().join("docs")).unwrap();
+        fs::write(tx.root().join("docs/add.md"), "add\n").unwrap();
+        tx.delete("gone.md");
+        tx.delete("never-existed.md");
+        let commit = tx.commit().unwrap();
+        assert_eq!(commit.added, vec![PathBuf::from("docs/add.md")]);
+        assert_eq!(commit.replaced, vec![PathBuf::from("edit.md")]);
+        assert_eq!(commit.deleted, vec![PathBuf::from("gone.md")]);
+        assert_eq!(fs::read_to_string(dest.join("edit.md")).unwrap(), "new\n");
+        assert!(!dest.join("gone.md").exists());
+        let id = commit.backup.unwrap();
+        let backup = dest.join(BACKUP_DIR).join(&id);
+        assert_eq!(fs::read_to_string(backup.join("edit.md")).unwrap(), "old\n");
+        assert!(!backup.join("keep.md").exists());
+
+        rollback(&dest, &id).unwrap();
+        assert_eq!(fs::read_to_string(dest.join("edit.md")).unwrap(), "old\n");
+        assert_eq!(fs::read_to_string(dest.join("gone.md")).unwrap(), "bye\n");
+        assert!(!dest.join("docs/add.md").exists());
+        assert_eq!(backups(&dest).unwrap().len(), 2);
+
+        // nothing to change, nothing backed up
+        let tx = Transaction::begin(&dest).unwrap();
+        assert!(tx.commit().unwrap().backup.is_none());
+        assert!(rollback(&dest, "19700101-000000").is_err());
+    }
+}
@@ -0,0 +1,621 @@
+//! Template lock file and `cosmos update`.
+//!
+//! `generate --apply` records which template, version and variables produced
+//! a project in `.cosmos/template.lock`, plus a snapshot of every rendered
+//! file under `.cosmos/base/`. `cosmos update` re-renders the template and
+//! merges the changes into the project with a three-way merge: snapshot
+//! (what the template produced last time) vs new render vs current file.
+//! [`apply_update`] stages the result in a [`Transaction`], so an update is
+//! backed up and can be rolled back like `generate`.
+//!
+//! ```no_run
+//! use rust_repo_template::exclude::Rules;
+//! use rust_repo_template::update::{plan_update, TemplateLock};
+//!
+//! let dest = std::path::Path::new("my-project");
+//! let lock = TemplateLock::read(dest)?.expect("generated by cosmos");
+//! println!("generated from {} ({})", lock.template.name, lock.template.hash);
+//! # let rendered = Vec::new();
+//! let mut plan = plan_update(dest, &rendered, false)?;
+//! plan.protect(&Rules::destination(dest, &[])?);
+//! plan.resolve(&lock.resolutions);
+//! println!("{} conflicts", plan.conflicts());
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use similar::{capture_diff_slices, Algorithm, DiffOp, TextDiff};
+
+use crate::exclude::{Rule, Rules};
+use crate::template::{is_binary, RenderedFile, TemplateSource};
+use crate::transaction::Transaction;
+
+/// Directory (relative to the project) holding cosmos bookkeeping.
+pub const STATE_DIR: &str = ".cosmos";
+/// Lock file recording how the project was generated.
+pub const LOCK_FILE: &str = ".cosmos/template.lock";
+/// Snapshot of the files as last rendered by the template.
+pub const BASE_DIR: &str = ".cosmos/base";
+/// Suffix of the side-by-side copies written by interactive `generate`.
+pub const SIDE_BY_SIDE_SUFFIX: &str = ".cosmos-new";
+
+/// `.cosmos/template.lock`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateLock {
+    pub template: LockedTemplate,
+    /// `--category` / `--exclude-category` used at generation time
+    #[serde(default)]
+    pub categories: Vec<String>,
+    #[serde(default)]
+    pub exclude_categories: Vec<String>,
+    /// Variables the template was rendered with
+    #[serde(default)]
+    pub variables: BTreeMap<String, toml::Value>,
+    /// Rendered path -> `sha256:<hex>` of the rendered contents
+    #[serde(default)]
+    pub files: BTreeMap<String, String>,
+    /// Answers given in interactive `generate`, by destination path
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub resolutions: BTreeMap<String, Resolution>,
+}
+
+/// What to do with a destination file that differs from the template (or
+/// is not part of it).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Choice {
+    /// Leave the destination file as it is
+    Keep,
+    /// Replace it with the template output
+    Overwrite,
+    /// Keep it and write the template output to `<file>.cosmos-new`
+    SideBySide,
+    /// Delete it (files not in the template)
+    Delete,
+}
+
+impl Choice {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Choice::Keep => "keep",
+            Choice::Overwrite => "overwrite",
+            Choice::SideBySide => "side_by_side",
+            Choice::Delete => "delete",
+        }
+    }
+}
+
+impl fmt::Display for Choice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A remembered [`Choice`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Resolution {
+    pub choice: Choice,
+    /// Hash of the template output the choice was made for; the question is
+    /// asked again once the output changes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template_hash: Option<String>,
+}
+
+impl Resolution {
+    /// The remembered choice, if it still applies to output hashed `hash`.
+    pub fn applies_to(&self, hash: Option<&str>) -> Option<Choice> {
+        (self.template_hash.as_deref() == hash).then_some(self.choice)
+    }
+}
+
+/// Which template produced the project.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockedTemplate {
+    pub name: String,
+    pub source: TemplateSource,
+    /// Template folder for `--template-dir` templates
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    /// Manifest `version`, if the template declares one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// [`crate::template::content_hash`] of the template
+    pub hash: String,
+}
+
+impl TemplateLock {
+    /// Read `<dest>/.cosmos/template.lock`, if present.
+    pub fn read(dest: &Path) -> Result<Option<TemplateLock>> {
+        let path = dest.join(LOCK_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let s = fs::read_to_string(&path).with_context(|| format!("reading {:?}", path))?;
+        toml::from_str(&s).with_context(|| format!("parsing {:?}", path)).map(Some)
+    }
+
+    /// Variables as a template context.
+    pub fn context(&self) -> serde_json::Map<String, serde_json::Value> {
+        self.variables
+            .iter()
+            .filter_map(|(k, v)| serde_json::to_value(v).ok().map(|v| (k.clone(), v)))
+            .collect()
+    }
+
+    /// Record the template context (values that cannot be stored in TOML
+    /// are skipped).
+    pub fn set_context(&mut self, ctx: &serde_json::Map<String, serde_json::Value>) {
+        self.variables = ctx
+            .iter()
+            .filter_map(|(k, v)| toml::Value::try_from(v).ok().map(|v| (k.clone(), v)))
+            .collect();
+    }
+}
+
+/// `sha256:<hex>` of `bytes`, as recorded in the lock.
+pub fn sha256(bytes: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+    format!("sha256:{:x}", Sha256::digest(bytes))
+}
+
+/// Write the lock file and replace the base snapshot with `rendered`.
+pub fn record_generation(
+    dest: &Path,
+    lock: &mut TemplateLock,
+    rendered: &[(PathBuf, RenderedFile)],
+) -> Result<()> {
+    let base = dest.join(BASE_DIR);
+    if base.exists() {
+        fs::remove_dir_all(&base).with_context(|| format!("clearing {:?}", base))?;
+    }
+    lock.files.clear();
+    for (rel, out) in rendered {
+        // symlinks are recreated from the template and need no snapshot
+        if let RenderedFile::Contents(bytes) = out {
+            let p = base.join(rel);
+            if let Some(parent) = p.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&p, bytes).with_context(|| format!("writing {:?}", p))?;
+            lock.files.insert(rel.to_string_lossy().replace('\\', "/"), sha256(bytes));
+        }
+    }
+    let path = dest.join(LOCK_FILE);
+    fs::create_dir_all(dest.join(STATE_DIR))?;
+    let s = toml::to_string_pretty(&*lock).context("serializing template lock")?;
+    fs::write(&path, s).with_context(|| format!("writing {:?}", path))
+}
+
+/// [`record_generation`] staged in `tx`, so the lock and snapshot are only
+/// replaced (and backed up) together with the generated files.
+pub fn stage_generation(
+    tx: &mut Transaction,
+    lock: &mut TemplateLock,
+    rendered: &[(PathBuf, RenderedFile)],
+) -> Result<()> {
+    record_generation(tx.root(), lock, rendered)?;
+    let base = tx.dest().join(BASE_DIR);
+    let stale: Vec<PathBuf> = walkdir::WalkDir::new(&base)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| !e.file_type().is_dir())
+        .filter_map(|e| e.path().strip_prefix(tx.dest()).ok().map(Path::to_path_buf))
+        .filter(|rel| !tx.root().join(rel).exists())
+        .collect();
+    for rel in stale {
+        tx.delete(rel);
+    }
+    Ok(())
+}
+
+/// Line-based three-way merge of `current` and `new` against `base`.
+/// Returns the merged text and whether it contains conflict markers.
+pub fn merge3(base: &str, current: &str, new: &str) -> (String, bool) {
+    let b: Vec<&str> = base.split_inclusive('\n').collect();
+    let c: Vec<&str> = current.split_inclusive('\n').collect();
+    let n: Vec<&str> = new.split_inclusive('\n').collect();
+    let mc = line_matches(&b, &c);
+    let mn = line_matches(&b, &n);
+
+    let mut out = String::new();
+    let mut conflict = false;
+    let (mut i, mut j, mut k) = (0, 0, 0);
+    while i < b.len() || j < c.len() || k < n.len() {
+        if i < b.len() && mc[i] == Some(j) && mn[i] == Some(k) {
+            out.push_str(b[i]);
+            i += 1;
+            j += 1;
+            k += 1;
+            continue;
+        }
+        // next base line kept by both sides
+        let (i2, j2, k2) = (i..b.len())
+            .find_map(|x| match (mc[x], mn[x]) {
+                (Some(y), Some(z)) if y >= j && z >= k => Some((x, y, z)),
+                _ => None,
+            })
+            .unwrap_or((b.len(), c.len(), n.len()));
+        let (bc, cc, nc) = (&b[i..i2], &c[j..j2], &n[k..k2]);
+        if cc == bc || cc == nc {
+            nc.iter().for_each(|l| out.push_str(l));
+        } else if nc == bc {
+            cc.iter().for_each(|l| out.push_str(l));
+        } else {
+            conflict = true;
+            out.push_str("<<<<<<< current\n");
+            push_lines(&mut out, cc);
+            out.push_str("=======\n");
+            push_lines(&mut out, nc);
+            out.push_str(">>>>>>> template\n");
+        }
+        (i, j, k) = (i2, j2, k2);
+    }
+    (out, conflict)
+}
+
+/// For every line of `base`, the index of the matching line in `other`.
+fn line_matches(base: &[&str], other: &[&str]) -> Vec<Option<usize>> {
+    let mut m = vec![None; base.len()];
+    for op in capture_diff_slices(Algorithm::Myers, base, other) {
+        if let DiffOp::Equal { old_index, new_index, len } = op {
+            for d in 0..len {
+                m[old_index + d] = Some(new_index + d);
+            }
+        }
+    }
+    m
+}
+
+fn push_lines(out: &mut String, lines: &[&str]) {
+    for l in lines {
+        out.push_str(l);
+    }
+    if !out.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+/// What `cosmos update` does with one file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateAction {
+    /// New template file
+    Added,
+    /// Unmodified locally; replaced by the new render
+    Updated,
+    /// Changed on both sides, merged cleanly
+    Merged,
+    /// Changed on both sides with overlapping edits
+    Conflict,
+    /// Removed from the template and unmodified locally
+    Removed,
+    /// Removed from the template but modified locally, or changed on both
+    /// sides with a remembered [`Choice::Keep`], so kept
+    Kept,
+    /// Changed on both sides with a remembered [`Choice::SideBySide`]; the
+    /// new render goes to `<file>.cosmos-new`
+    SideBySide,
+    /// Deleted locally; the template change is not re-applied
+    DeletedLocally,
+    /// Would change, but a protection rule keeps the project's copy
+    Protected,
+}
+
+impl UpdateAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UpdateAction::Added => "added",
+            UpdateAction::Updated => "updated",
+            UpdateAction::Merged => "merged",
+            UpdateAction::Conflict => "conflict",
+            UpdateAction::Removed => "removed",
+            UpdateAction::Kept => "kept",
+            UpdateAction::SideBySide => "side_by_side",
+            UpdateAction::DeletedLocally => "deleted_locally",
+            UpdateAction::Protected => "protected",
+        }
+    }
+}
+
+impl fmt::Display for UpdateAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// One file touched by an update.
+#[derive(Debug, Clone, Serialize)]
+pub struct UpdateEntry {
+    pub path: PathBuf,
+    pub action: UpdateAction,
+    /// `.rej` file written for a conflict in reject mode, or the
+    /// `.cosmos-new` copy of a side-by-side entry
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reject: Option<PathBuf>,
+    /// Rule that protected the file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule: Option<Rule>,
+    /// Remembered interactive answer that decided the action
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<Choice>,
+    #[serde(skip)]
+    write: Option<RenderedFile>,
+    #[serde(skip)]
+    reject_text: Option<String>,
+    /// New render of a file changed on both sides, for [`UpdatePlan::resolve`]
+    #[serde(skip)]
+    new: Option<RenderedFile>,
+}
+
+/// Result of [`plan_update`]; nothing is written until [`apply_update`].
+#[derive(Debug, Default)]
+pub struct UpdatePlan {
+    /// Touched files, sorted by path (unchanged files are not listed)
+    pub entries: Vec<UpdateEntry>,
+}
+
+impl UpdatePlan {
+    pub fn conflicts(&self) -> usize {
+        self.entries.iter().filter(|e| e.action == UpdateAction::Conflict).count()
+    }
+
+    /// Turn changes to existing files that match `rules` (see
+    /// [`Rules::destination`]) into [`UpdateAction::Protected`] entries that
+    /// write nothing.
+    pub fn protect(&mut self, rules: &Rules) {
+        for e in &mut self.entries {
+            if !matches!(
+                e.action,
+                UpdateAction::Updated
+                    | UpdateAction::Merged
+                    | UpdateAction::Conflict
+                    | UpdateAction::Removed
+            ) {
+                continue;
+            }
+            if let Some(rule) = rules.matched(&e.path, false) {
+                e.action = UpdateAction::Protected;
+                e.rule = Some(rule);
+                e.reject = None;
+                e.write = None;
+                e.reject_text = None;
+            }
+        }
+    }
+
+    /// Apply the answers remembered from interactive `generate` (see
+    /// [`TemplateLock::resolutions`]) to files changed on both sides: `keep`
+    /// leaves the project's copy alone and `side_by_side` writes the new
+    /// render to `<file>.cosmos-new` instead of merging. The answers hold
+    /// until they are changed with `generate -i`, even when the template
+    /// output has moved on since.
+    pub fn resolve(&mut self, resolutions: &BTreeMap<String, Resolution>) {
+        for e in &mut self.entries {
+            if !matches!(e.action, UpdateAction::Merged | UpdateAction::Conflict) {
+                continue;
+            }
+            let key = e.path.to_string_lossy().replace('\\', "/");
+            match resolutions.get(&key).map(|r| r.choice) {
+                Some(Choice::Keep) => {
+                    e.action = UpdateAction::Kept;
+                    e.reject = None;
+                    e.write = None;
+                }
+                Some(Choice::SideBySide) => {
+                    // the copy is the plain new render, not the merge
+                    let Some(new) = e.new.take() else { continue };
+                    let mut copy = e.path.clone().into_os_string();
+                    copy.push(SIDE_BY_SIDE_SUFFIX);
+                    e.action = UpdateAction::SideBySide;
+                    e.reject = Some(PathBuf::from(copy));
+                    e.write = Some(new);
+                }
+                _ => continue,
+            }
+            e.resolution = resolutions.get(&key).map(|r| r.choice);
+            e.reject_text = None;
+        }
+    }
+}
+
+/// Compare the new render against the base snapshot and the project files.
+/// With `reject`, conflicting files are left untouched and the template
+/// change is written to `<file>.rej` instead of conflict markers.
+pub fn plan_update(
+    dest: &Path,
+    rendered: &[(PathBuf, RenderedFile)],
+    reject: bool,
+) -> Result<UpdatePlan> {
+    let base_dir = dest.join(BASE_DIR);
+    let read = |p: PathBuf| -> Result<Option<Vec<u8>>> {
+        match p.symlink_metadata() {
+            Ok(m) if m.is_file() => {
+                Ok(Some(fs::read(&p).with_context(|| format!("reading {:?}", p))?))
+            }
+            Ok(_) => Ok(None),
+            Err(_) => Ok(None),
+        }
+    };
+    let entry = |path: &Path, action, write| UpdateEntry {
+        path: path.to_path_buf(),
+        action,
+        reject: None,
+        rule: None,
+        resolution: None,
+        write,
+        reject_text: None,
+        new: None,
+    };
+
+    let mut plan = UpdatePlan::default();
+    let mut seen = HashSet::new();
+    for (rel, out) in rendered {
+        seen.insert(rel.clone());
+        let current_path = dest.join(rel);
+        let new = match out {
+            RenderedFile::Symlink(_) => {
+                if current_path.symlink_metadata().is_err() {
+                    plan.entries.push(entry(rel, UpdateAction::Added, Some(out.clone())));
+                }
+                continue;
+            }
+            RenderedFile::Contents(bytes) => bytes,
+        };
+        let base = read(base_dir.join(rel))?;
+        let current = read(current_path.clone())?;
+        let current = match current {
+            Some(c) => c,
+            None if current_path.symlink_metadata().is_ok() => continue,
+            None => {
+                match base {
+                    None => plan.entries.push(entry(rel, UpdateAction::Added, Some(out.clone()))),
+                    Some(b) if &b != new => {
+                        plan.entries.push(entry(rel, UpdateAction::DeletedLocally, None))
+                    }
+                    Some(_) => {}
+                }
+                continue;
+            }
+        };
+        let base = base.unwrap_or_default();
+        if &current == new || &base == new {
+            continue;
+        }
+        if current == base {
+            plan.entries.push(entry(rel, UpdateAction::Updated, Some(out.clone())));
+            continue;
+        }
+        if is_binary(&base) || is_binary(&current) || is_binary(new) {
+            // binary files cannot be merged: keep the project's copy and
+            // store the new version next to it
+            let mut e = entry(rel, UpdateAction::Conflict, Some(out.clone()));
+            e.reject = Some(reject_path(rel));
+            e.new = Some(out.clone());
+            plan.entries.push(e);
+            continue;
+        }
+        let (b, c, n) = (
+            String::from_utf8_lossy(&base),
+            String::from_utf8_lossy(&current),
+            String::from_utf8_lossy(new),
+        );
+        let (merged, conflict) = merge3(&b, &c, &n);
+        let mut e = if !conflict {
+            let merged = RenderedFile::Contents(merged.into_bytes());
+            entry(rel, UpdateAction::Merged, Some(merged))
+        } else if reject {
+            let diff = TextDiff::from_lines(&*b, &*n)
+                .unified_diff()
+                .header(&format!("a/{}", rel.display()), &format!("b/{}", rel.display()))
+                .to_string();
+            let mut e = entry(rel, UpdateAction::Conflict, None);
+            e.reject = Some(reject_path(rel));
+            e.reject_text = Some(diff);
+            e
+        } else {
+            let merged = RenderedFile::Contents(merged.into_bytes());
+            entry(rel, UpdateAction::Conflict, Some(merged))
+        };
+        e.new = Some(out.clone());
+        plan.entries.push(e);
+    }
+
+    // files the template no longer produces
+    if base_dir.is_dir() {
+        for e in walkdir::WalkDir::new(&base_dir).into_iter().filter_map(|e| e.ok()) {
+            if !e.file_type().is_file() {
+                continue;
+            }
+            let rel = e.path().strip_prefix(&base_dir).unwrap().to_path_buf();
+            if seen.contains(&rel) {
+                continue;
+            }
+            if let Some(current) = read(dest.join(&rel))? {
+                let base = fs::read(e.path())?;
+                let action =
+                    if current == base { UpdateAction::Removed } else { UpdateAction::Kept };
+                plan.entries.push(entry(&rel, action, None));
+            }
+        }
+    }
+
+    plan.entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(plan)
+}
+
+fn reject_path(rel: &Path) -> PathBuf {
+    let mut s = rel.as_os_str().to_os_string();
+    s.push(".rej");
+    PathBuf::from(s)
+}
+
+/// Stage the planned changes in `tx`; nothing reaches the project until
+/// the transaction commits, which backs up every replaced or removed file.
+pub fn apply_update(tx: &mut Transaction, plan: &UpdatePlan) -> Result<()> {
+    for e in &plan.entries {
+        if e.action == UpdateAction::Removed {
+            tx.delete(&e.path);
+            continue;
+        }
+        let target = match &e.reject {
+            Some(rej) => tx.root().join(rej),
+            None => tx.root().join(&e.path),
+        };
+        let contents = match (&e.reject, &e.reject_text, &e.write) {
+            (Some(_), Some(text), _) => Some(RenderedFile::Contents(text.clone().into_bytes())),
+            (_, _, Some(out)) => Some(out.clone()),
+            _ => None,
+        };
+        let Some(out) = contents else { continue };
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("creating {:?}", parent))?;
+        }
+        match out {
+            RenderedFile::Contents(bytes) => {
+                fs::write(&target, bytes).with_context(|| format!("writing {:?}", target))?
+            }
+            RenderedFile::Symlink(link) => {
+                // staged in an empty tree, so this never hits an existing
+                // path; the commit replaces whatever is in the project
+                #[cfg(unix)]
+                std::os::unix::fs::symlink(&link, &target)
+                    .with_context(|| format!("creating symlink {:?}", target))?;
+                #[cfg(not(unix))]
+                let _ = link;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge3_combines_disjoint_edits_and_marks_overlaps() {
+        let base = "a\nb\nc\nd\n";
+        let (m, conflict) = merge3(base, "a\nB\nc\nd\n", "a\nb\nc\nD\n");
+        assert_eq!((m.as_str(), conflict), ("a\nB\nc\nD\n", false));
+
+        let (m, conflict) = merge3(base, "a\nmine\nc\nd\n", "a\ntheirs\nc\nd\n");
+        assert!(conflict);
+        assert_eq!(m, "a\n<<<<<<< current\nmine\n=======\ntheirs\n>>>>>>> template\nc\nd\n");
+
+        // identical edits on both sides are not a conflict
+        assert_eq!(merge3(base, "a\nx\nc\nd\n", "a\nx\nc\nd\n"), ("a\nx\nc\nd\n".into(), false));
+        // appended lines on one side, edit on the other
+        assert_eq!(
+            merge3("a\nb\nc\n", "a\nb\nc\nlocal\n", "A\nb\nc\n"),
+            ("A\nb\nc\nlocal\n".into(), false)
+        );
+        // adjacent edits conflict, as in diff3
+        assert!(merge3("a\n", "a\nlocal\n", "A\n").1);
+    }
+}
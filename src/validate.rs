@@ -0,0 +1,327 @@
+//! Repository validation (`cosmos validate`) and auto-fixes.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::plan::{read_todo, validate_plan, write_todo};
+use crate::project::validate_project_manifest;
+
+/// A validation finding with a stable rule id such as `plan.task-file-missing`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Issue {
+    pub id: &'static str,
+    pub message: String,
+    /// Plan task the issue is about, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task: Option<String>,
+}
+
+impl Issue {
+    pub fn new(id: &'static str, message: impl Into<String>) -> Issue {
+        Issue { id, message: message.into(), task: None }
+    }
+
+    pub fn for_task(mut self, task: &str) -> Issue {
+        self.task = Some(task.to_string());
+        self
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Outcome of [`validate_repo`].
+#[derive(Debug, Default, Serialize)]
+pub struct RepoValidation {
+    /// Blocking issues
+    pub errors: Vec<Issue>,
+    /// Informational issues
+    pub warnings: Vec<Issue>,
+    /// Fixes applied when validation ran with `fix = true`
+    pub fixes: Vec<String>,
+}
+
+/// Validate repository structure, plan and `project.toml`. With `fix`, try
+/// [`auto_fix_repo`] first and report what is left afterwards.
+pub fn validate_repo(repo_root: &Path, _level: &str, fix: bool) -> Result<RepoValidation> {
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+
+    // required basis files
+    for b in &["Cargo.toml", "README.md", "LICENSE", "CONTRIBUTING.md"] {
+        if !repo_root.join(b).exists() {
+            errors.push(Issue::new("repo.missing-file", format!("Missing required file: {}", b)));
+        }
+    }
+
+    // docs
+    if !repo_root.join("docs").exists() {
+        warnings.push(Issue::new("repo.docs-missing", "docs/ missing"));
+    }
+
+    // workflows
+    let wf = repo_root.join(".github/workflows");
+    if !wf.exists() {
+        warnings.push(Issue::new("repo.workflows-missing", ".github/workflows missing"));
+    } else {
+        let mut found = false;
+        for ent in
+            wf.read_dir().with_context(|| format!("reading workflows dir {:?}", wf))?.flatten()
+        {
+            let p = ent.path();
+            if p.extension()
+                .and_then(|s| s.to_str())
+                .map(|s| s == "yml" || s == "yaml")
+                .unwrap_or(false)
+            {
+                found = true;
+                break;
+            }
+        }
+        if !found {
+            warnings.push(Issue::new(
+                "repo.no-workflow-files",
+                "No workflow yml files found under .github/workflows",
+            ));
+        }
+    }
+
+    // scripts
+    if !repo_root.join("scripts").exists() {
+        warnings.push(Issue::new("repo.scripts-missing", "scripts/ missing"));
+    } else if !repo_root.join("scripts/validate_plan.py").exists() {
+        warnings
+            .push(Issue::new("repo.plan-validator-missing", "scripts/validate_plan.py missing"));
+    }
+
+    // plan checks
+    errors.extend(validate_plan(repo_root)?);
+
+    // project.toml consistency checks (blocking on errors)
+    if repo_root.join("project.toml").exists() {
+        let report = validate_project_manifest(repo_root).context("validating project.toml")?;
+        errors.extend(report.errors);
+        warnings.extend(report.warnings);
+    }
+
+    // AI heuristics
+    warnings.extend(check_ai_heuristics(repo_root));
+
+    // attempt auto-fixes if requested and we found issues
+    if fix && (!errors.is_empty() || !warnings.is_empty()) {
+        let fixes = auto_fix_repo(repo_root)?;
+        // Re-run validations to update lists
+        let mut after = validate_repo(repo_root, _level, false)?;
+        after.fixes = fixes;
+        return Ok(after);
+    }
+
+    Ok(RepoValidation { errors, warnings, fixes: Vec::new() })
+}
+
+fn check_ai_heuristics(repo_root: &Path) -> Vec<Issue> {
+    let mut warnings = Vec::new();
+    if !repo_root.join(".github/copilot-instructions.md").exists()
+        && !repo_root.join(".github/ai").exists()
+    {
+        warnings.push(Issue::new(
+            "ai.guidelines-missing",
+            "No AI guidelines or .github/copilot-instructions.md found",
+        ));
+    }
+    let readme = fs::read_to_string(repo_root.join("README.md")).unwrap_or_default();
+    if !readme.to_lowercase().contains("ai") {
+        warnings.push(Issue::new(
+            "ai.readme-guidance-missing",
+            "README doesn't mention AI collaboration guidance",
+        ));
+    }
+    warnings
+}
+
+/// Apply safe automatic fixes (missing CONTRIBUTING.md, legacy plan statuses,
+/// broken `task_file` references, unarchived finished tasks). Returns a
+/// description of every fix applied.
+pub fn auto_fix_repo(repo_root: &Path) -> Result<Vec<String>> {
+    let mut fixes = Vec::new();
+
+    // 1) Ensure CONTRIBUTING.md exists: copy from docs/contributing.md if present
+    if !repo_root.join("CONTRIBUTING.md").exists() {
+        let candidates = ["docs/contributing.md", "CONTRIBUTING.md", ".github/CONTRIBUTING.md"];
+        for c in candidates {
+            let p = repo_root.join(c);
+            if p.exists() {
+                fs::copy(&p, repo_root.join("CONTRIBUTING.md"))?;
+                fixes.push(format!("Copied {} -> CONTRIBUTING.md", c));
+                break;
+            }
+        }
+    }
+
+    // 2) Fix plan todo entries
+    let mut plan = read_todo(repo_root)?;
+    let mut changed = false;
+    for t in plan.task.iter_mut() {
+        // normalize status
+        if let Some(st) = &t.status {
+            if st == "open" {
+                t.status = Some("pending_review".to_string());
+                fixes.push(format!("Updated status for {}: open -> pending_review", t.id));
+                changed = true;
+            } else if st == "done" {
+                t.status = Some("finished".to_string());
+                fixes.push(format!("Updated status for {}: done -> finished", t.id));
+                changed = true;
+            }
+        }
+
+        // ensure task_file points to existing file
+        if let Some(tf) = t.task_file.clone() {
+            let p = repo_root.join("plan").join(&tf);
+            if !p.exists() {
+                // try to normalize patterns like tasks/0001-template.md -> tasks/0001/task.md
+                if tf.contains("-template") {
+                    // try to extract id from tf or use t.id
+                    let id = t.id.clone();
+                    let new_tf = format!("tasks/{}/task.md", id);
+                    let newp = repo_root.join("plan").join(&new_tf);
+                    if newp.exists() {
+                        t.task_file = Some(new_tf.clone());
+                        fixes.push(format!("Fixed task_file for {}: {} -> {}", t.id, tf, new_tf));
+                        changed = true;
+                        continue;
+                    }
+                    // try to copy from template if available
+                    let templ = repo_root
+                        .join("templates")
+                        .join("default")
+                        .join("plan")
+                        .join("tasks")
+                        .join(&id)
+                        .join("task.md");
+                    if templ.exists() {
+                        fs::create_dir_all(newp.parent().unwrap())?;
+                        fs::copy(&templ, &newp)?;
+                        t.task_file = Some(new_tf.clone());
+                        fixes.push(format!(
+                            "Copied template task {} to {}",
+                            templ.display(),
+                            newp.display()
+                        ));
+                        changed = true;
+                        continue;
+                    }
+                }
+
+                // try tasks/<id>/task.md
+                let new_tf = format!("tasks/{}/task.md", t.id);
+                let newp = repo_root.join("plan").join(&new_tf);
+                if newp.exists() {
+                    t.task_file = Some(new_tf.clone());
+                    fixes.push(format!("Fixed task_file for {}: {} -> {}", t.id, tf, new_tf));
+                    changed = true;
+                    continue;
+                }
+
+                // as last resort, create placeholder
+                fs::create_dir_all(newp.parent().unwrap())?;
+                fs::write(&newp, format!("title: \"Task {}\"\n\nAutomatically created.", t.id))?;
+                t.task_file = Some(new_tf.clone());
+                fixes.push(format!("Created placeholder task file for {}", t.id));
+                changed = true;
+            }
+        } else {
+            // missing task_file: create one under tasks/{id}/task.md
+            let new_tf = format!("tasks/{}/task.md", t.id);
+            let newp = repo_root.join("plan").join(&new_tf);
+            if !newp.exists() {
+                fs::create_dir_all(newp.parent().unwrap())?;
+                fs::write(&newp, format!("title: \"Task {}\"\n\nAutomatically created.", t.id))?;
+                fixes.push(format!("Created task file {}", new_tf));
+            } else {
+                fixes.push(format!("Found existing task file {}", new_tf));
+            }
+            t.task_file = Some(new_tf);
+            changed = true;
+        }
+
+        // if finished, ensure task_file in archive
+        if let Some(st) = &t.status {
+            if st == "finished" {
+                if let Some(tf) = t.task_file.clone() {
+                    if !tf.starts_with("archive/") {
+                        let src = repo_root.join("plan").join(&tf);
+                        if src.exists() {
+                            let dst_dir = repo_root.join("plan").join("archive").join(&t.id);
+                            fs::create_dir_all(&dst_dir)?;
+                            let dst = dst_dir.join("task.md");
+                            fs::rename(&src, &dst).or_else(|_| {
+                                fs::copy(&src, &dst)?;
+                                fs::remove_file(&src)
+                            })?;
+                            t.task_file = Some(format!("archive/{}/task.md", t.id));
+                            fixes.push(format!("Moved {} to archive/{}", tf, t.id));
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if changed {
+        write_todo(repo_root, &plan)?;
+    }
+
+    Ok(fixes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_auto_fix_repo_creates_contributing_and_fixes_plan() -> Result<()> {
+        let td = tempdir()?;
+        let root = td.path();
+
+        // create docs/contributing.md
+        fs::create_dir_all(root.join("docs"))?;
+        fs::write(root.join("docs").join("contributing.md"), "contrib")?;
+
+        // create plan dir and task file exists
+        fs::create_dir_all(root.join("plan").join("tasks").join("0001"))?;
+        fs::write(root.join("plan").join("tasks").join("0001").join("task.md"), "title: x")?;
+
+        // write todo.toml with open status and wrong task_file
+        let todo = r#"[meta]
+owner = "maintainers"
+created = "2025-12-18T12:00:00Z"
+
+[[task]]
+id = "0001"
+title = "Template init"
+status = "open"
+assignee = "maintainer"
+labels = ["template"]
+task_file = "tasks/0001-template.md"
+"#;
+        fs::create_dir_all(root.join("plan"))?;
+        fs::write(root.join("plan").join("todo.toml"), todo)?;
+
+        let _fixes = auto_fix_repo(root)?;
+        assert!(root.join("CONTRIBUTING.md").exists());
+        let plan = read_todo(root)?;
+        assert_eq!(plan.task[0].status.as_deref(), Some("pending_review"));
+        assert_eq!(plan.task[0].task_file.as_deref(), Some("tasks/0001/task.md"));
+        Ok(())
+    }
+}
@@ -0,0 +1,214 @@
+//! Sources of template variables besides `--var`: variables files,
+//! `COSMOS_VAR_<NAME>` environment variables and the metadata of an
+//! existing project.
+//!
+//! `cosmos generate` merges them in this order, later sources winning:
+//!
+//! 1. the destination's template lock, `Cargo.toml` and `project.toml`
+//!    ([`from_project`])
+//! 2. `--vars-file` files, in the order given ([`from_file`])
+//! 3. `COSMOS_VAR_<NAME>` environment variables ([`from_env`])
+//! 4. `--project-name` and `--var key=value`
+//!
+//! Variables still missing afterwards fall back to the manifest defaults
+//! (or a prompt), see [`crate::template::resolve_variables`].
+
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use serde_json::{Map, Value};
+
+use crate::error::CosmosError;
+use crate::update::TemplateLock;
+
+/// Prefix of environment variables that set template variables.
+pub const ENV_PREFIX: &str = "COSMOS_VAR_";
+
+/// Read a variables file: a table of `name = value` in TOML (`.toml`), JSON
+/// (`.json`) or YAML (`.yaml`/`.yml`).
+///
+/// Scalars become strings so declared variables are validated like `--var`
+/// values; arrays and tables are passed to the templates as they are.
+pub fn from_file(path: &Path) -> Result<Map<String, Value>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("reading variables file {:?}", path))?;
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+    let value = match ext {
+        "toml" => {
+            let v: toml::Value =
+                toml::from_str(&text).with_context(|| format!("parsing {:?}", path))?;
+            serde_json::to_value(v)?
+        }
+        "json" => serde_json::from_str(&text).with_context(|| format!("parsing {:?}", path))?,
+        "yaml" | "yml" => {
+            serde_yaml::from_str(&text).with_context(|| format!("parsing {:?}", path))?
+        }
+        _ => {
+            return Err(CosmosError::Usage(format!(
+                "unsupported variables file {:?}: use .toml, .json or .yaml",
+                path
+            ))
+            .into())
+        }
+    };
+    let Value::Object(map) = value else {
+        bail!("{:?} must contain a table of variables", path);
+    };
+    Ok(map.into_iter().map(|(k, v)| (k, stringify(v))).collect())
+}
+
+/// Template variables set through `COSMOS_VAR_<NAME>` in `env`.
+///
+/// `<NAME>` matches a declared variable when it equals the variable's name
+/// upper-cased with `-` replaced by `_` (`COSMOS_VAR_PROJECT_NAME` sets
+/// `project-name`); other names are lower-cased with `_` turned into `-`.
+pub fn from_env<I>(env: I, declared: &[&str]) -> Map<String, Value>
+where
+    I: IntoIterator<Item = (String, String)>,
+{
+    let mut vars = Map::new();
+    for (key, value) in env {
+        let Some(name) = key.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        if name.is_empty() {
+            continue;
+        }
+        let name = declared
+            .iter()
+            .find(|d| d.to_uppercase().replace('-', "_") == name)
+            .map(|d| d.to_string())
+            .unwrap_or_else(|| name.to_lowercase().replace('_', "-"));
+        vars.insert(name, Value::String(value));
+    }
+    vars
+}
+
+/// Defaults taken from an existing project in `dest`: the variables
+/// recorded in its `.cosmos/template.lock`, overridden by `project-name`,
+/// `version`, `repository` and `license` from `[package]` (or
+/// `[workspace.package]`) in `Cargo.toml`, overridden by `[project]` in
+/// `project.toml` (where `type` sets `project-type`) and its
+/// `[docker].enabled` (`docker`). Values that are still template
+/// placeholders are ignored, as are files that are missing or do not parse
+/// (they are about to be regenerated).
+pub fn from_project(dest: &Path) -> Map<String, Value> {
+    let mut vars = TemplateLock::read(dest).ok().flatten().map(|l| l.context()).unwrap_or_default();
+    vars.extend(metadata(&dest.join("Cargo.toml"), &["package", "workspace.package"]));
+    let project = dest.join("project.toml");
+    vars.extend(metadata(&project, &["project"]));
+    if let Some(doc) = read_toml(&project) {
+        if let Some(s) =
+            doc.get("project").and_then(|p| p.get("type")).and_then(toml::Value::as_str)
+        {
+            if !s.is_empty() && !s.contains("{{") {
+                vars.insert("project-type".to_string(), Value::String(s.into()));
+            }
+        }
+        if let Some(b) =
+            doc.get("docker").and_then(|d| d.get("enabled")).and_then(toml::Value::as_bool)
+        {
+            vars.insert("docker".to_string(), Value::String(b.to_string()));
+        }
+    }
+    vars
+}
+
+fn read_toml(path: &Path) -> Option<toml::Value> {
+    let text = std::fs::read_to_string(path).ok()?;
+    toml::from_str(&text).ok()
+}
+
+/// Project metadata from the first of `tables` in `path` that has any.
+fn metadata(path: &Path, tables: &[&str]) -> Map<String, Value> {
+    const KEYS: [(&str, &str); 4] = [
+        ("name", "project-name"),
+        ("version", "version"),
+        ("repository", "repository"),
+        ("license", "license"),
+    ];
+    let mut vars = Map::new();
+    let Some(doc) = read_toml(path) else {
+        return vars;
+    };
+    for table in tables {
+        let Some(t) = table.split('.').try_fold(&doc, |v, k| v.get(k)) else {
+            continue;
+        };
+        for (key, var) in KEYS {
+            match t.get(key).and_then(toml::Value::as_str) {
+                Some(s) if !s.is_empty() && !s.contains("{{") => {
+                    vars.insert(var.to_string(), Value::String(s.into()));
+                }
+                _ => {}
+            }
+        }
+        if !vars.is_empty() {
+            break;
+        }
+    }
+    vars
+}
+
+fn stringify(v: Value) -> Value {
+    match v {
+        Value::Bool(b) => Value::String(b.to_string()),
+        Value::Number(n) => Value::String(n.to_string()),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_names_map_to_declared_variables() {
+        let env = vec![
+            ("COSMOS_VAR_PROJECT_NAME".to_string(), "acme".to_string()),
+            ("COSMOS_VAR_SNAKE_CASE".to_string(), "x".to_string()),
+            ("COSMOS_VAR_OTHER_THING".to_string(), "y".to_string()),
+            ("PATH".to_string(), "/bin".to_string()),
+        ];
+        let vars = from_env(env, &["project-name", "snake_case"]);
+        assert_eq!(vars["project-name"], "acme");
+        assert_eq!(vars["snake_case"], "x");
+        assert_eq!(vars["other-thing"], "y");
+        assert_eq!(vars.len(), 3);
+    }
+
+    #[test]
+    fn project_type_and_docker_come_from_project_toml() {
+        let td = tempfile::tempdir().unwrap();
+        std::fs::write(
+            td.path().join("project.toml"),
+            "[project]\nname = \"acme\"\ntype = \"library\"\n\n[docker]\nenabled = false\n",
+        )
+        .unwrap();
+        let vars = from_project(td.path());
+        assert_eq!(vars["project-name"], "acme");
+        assert_eq!(vars["project-type"], "library");
+        assert_eq!(vars["docker"], "false");
+    }
+
+    #[test]
+    fn yaml_files_keep_nested_values() {
+        let td = tempfile::tempdir().unwrap();
+        let path = td.path().join("vars.yaml");
+        std::fs::write(
+            &path,
+            "# vars\nproject-name: acme\nauthor: \"Jane: Doe\"\ndocker: false # no image\nversion: 2.0\nfeatures:\n  - cli\n  - serde\nci: {provider: github}\n",
+        )
+        .unwrap();
+        let v = from_file(&path).unwrap();
+        assert_eq!(v["project-name"], "acme");
+        assert_eq!(v["author"], "Jane: Doe");
+        assert_eq!(v["docker"], "false");
+        assert_eq!(v["version"], "2.0");
+        assert_eq!(v["features"], serde_json::json!(["cli", "serde"]));
+        assert_eq!(v["ci"]["provider"], "github");
+
+        std::fs::write(&path, "- not\n- a table\n").unwrap();
+        assert!(from_file(&path).is_err());
+    }
+}
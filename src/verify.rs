@@ -0,0 +1,753 @@
+//! Verification of a generated project (`generate --verify`) or any
+//! checkout (`cosmos verify`).
+//!
+//! The steps come from the `[verify]` table of the project's `project.toml`,
+//! else from the template manifest, else [`default_steps`] (fmt, clippy and
+//! test over the workspace):
+//!
+//! ```toml
+//! [verify]
+//! steps = [
+//!   { name = "fmt", command = ["cargo", "fmt", "--all", "--", "--check"] },
+//!   { name = "test", command = "cargo test --workspace", timeout = 600, env = { RUST_BACKTRACE = "1" } },
+//!   { name = "audit", command = ["cargo", "audit"], allow_failure = true, parallel = true },
+//!   { name = "docs", command = "mkdocs build --strict", paths = ["docs/**", "mkdocs.yml"] },
+//! ]
+//! ```
+//!
+//! `parallel` steps run alongside the others; `paths` limits a step to
+//! changes of matching files when [`VerifyOptions::changed`] is set.
+//!
+//! Each `generate --verify` run leaves a JSON and a JUnit report under
+//! [`REPORT_DIR`].
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::error::CosmosError;
+
+/// Folder of the generated project that receives the reports.
+pub const REPORT_DIR: &str = ".cosmos/reports";
+/// JSON report, inside [`REPORT_DIR`].
+pub const JSON_REPORT: &str = "verify.json";
+/// JUnit XML report, inside [`REPORT_DIR`].
+pub const JUNIT_REPORT: &str = "verify.xml";
+
+/// `[verify]` table of `project.toml` or a template manifest.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct VerifyConfig {
+    #[serde(default)]
+    pub steps: Vec<StepSpec>,
+}
+
+/// One configured verification step.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct StepSpec {
+    pub name: String,
+    pub command: StepCommand,
+    /// Seconds before the step is killed and reported as failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// Extra environment variables
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// A failure is reported but does not fail the verification
+    #[serde(default)]
+    pub allow_failure: bool,
+    /// Safe to run alongside other steps (it does not build or lock `target/`)
+    #[serde(default)]
+    pub parallel: bool,
+    /// Globs of the files the step checks; with [`VerifyOptions::changed`]
+    /// it is skipped when none of them changed. Empty: always runs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<String>,
+}
+
+/// Program and arguments, or a command line run by the shell.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum StepCommand {
+    Args(Vec<String>),
+    Shell(String),
+}
+
+impl fmt::Display for StepCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StepCommand::Args(args) => f.write_str(&args.join(" ")),
+            StepCommand::Shell(line) => f.write_str(line),
+        }
+    }
+}
+
+impl StepSpec {
+    fn cargo(name: &str, args: &[&str], paths: &[&str]) -> StepSpec {
+        let command = std::iter::once("cargo").chain(args.iter().copied()).map(String::from);
+        StepSpec {
+            name: name.to_string(),
+            command: StepCommand::Args(command.collect()),
+            timeout: None,
+            env: BTreeMap::new(),
+            allow_failure: false,
+            parallel: false,
+            paths: paths.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    /// Whether one of `changed` matches [`StepSpec::paths`].
+    pub fn is_affected(&self, changed: &[PathBuf]) -> bool {
+        if self.paths.is_empty() {
+            return true;
+        }
+        let globs: Vec<glob::Pattern> =
+            self.paths.iter().filter_map(|p| glob::Pattern::new(p).ok()).collect();
+        changed.iter().any(|c| globs.iter().any(|g| g.matches_path(c)))
+    }
+}
+
+/// Files the built-in build steps depend on.
+const RUST_SOURCES: &[&str] = &["**/*.rs", "**/Cargo.toml", "Cargo.lock", "rust-toolchain*"];
+
+/// `cargo fmt --check`, `cargo clippy -D warnings` and `cargo test`, over
+/// every workspace member with the default features (projects whose
+/// features cannot all be enabled together would fail `--all-features`;
+/// declare `[verify]` steps to lint other feature sets).
+pub fn default_steps() -> Vec<StepSpec> {
+    let mut fmt =
+        StepSpec::cargo("fmt", &["fmt", "--all", "--", "--check"], &["**/*.rs", "*rustfmt.toml"]);
+    // formatting never builds, so it can run next to clippy and the tests
+    fmt.parallel = true;
+    let clippy_sources: Vec<&str> = RUST_SOURCES.iter().copied().chain(["clippy.toml"]).collect();
+    vec![
+        fmt,
+        StepSpec::cargo(
+            "clippy",
+            &["clippy", "--workspace", "--all-targets", "--", "-D", "warnings"],
+            &clippy_sources,
+        ),
+        StepSpec::cargo("test", &["test", "--workspace", "--quiet"], RUST_SOURCES),
+    ]
+}
+
+/// [`default_steps`] adjusted by the `[clippy]` table of `dir`'s
+/// `project.toml`: the `allow` lints are passed to clippy with `-A`, and an
+/// `msrv` adds an `msrv` step building with that toolchain
+/// (`cargo +<msrv> check`).
+pub fn project_default_steps(dir: &Path) -> Result<Vec<StepSpec>> {
+    let mut steps = default_steps();
+    let path = dir.join("project.toml");
+    if !path.is_file() {
+        return Ok(steps);
+    }
+    let doc = crate::project::read_toml_value(&path)?;
+    let Some(clippy) = doc.get("clippy") else {
+        return Ok(steps);
+    };
+    let allow: Vec<&str> = clippy
+        .get("allow")
+        .and_then(|v| v.as_array())
+        .map(|a| a.iter().filter_map(|v| v.as_str()).collect())
+        .unwrap_or_default();
+    if let Some(StepCommand::Args(args)) =
+        steps.iter_mut().find(|s| s.name == "clippy").map(|s| &mut s.command)
+    {
+        for lint in allow {
+            args.extend(["-A".to_string(), lint.to_string()]);
+        }
+    }
+    let msrv = clippy.get("msrv").and_then(|v| v.as_str()).map(str::trim);
+    if let Some(msrv) =
+        msrv.filter(|m| !m.is_empty() && !crate::project::is_template_placeholder(m))
+    {
+        let toolchain = format!("+{}", msrv);
+        let args = [toolchain.as_str(), "check", "--workspace", "--all-targets"];
+        // before clippy and the tests: a Cargo.lock written by a newer cargo
+        // may use a format the older toolchain cannot read
+        steps.insert(1, StepSpec::cargo("msrv", &args, RUST_SOURCES));
+    }
+    Ok(steps)
+}
+
+/// Where the steps of a run come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepSource {
+    Project,
+    Manifest,
+    BuiltIn,
+}
+
+impl fmt::Display for StepSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            StepSource::Project => "project.toml",
+            StepSource::Manifest => "the template manifest",
+            StepSource::BuiltIn => "the built-in fmt/clippy/test",
+        })
+    }
+}
+
+/// The steps for the project in `dir`: its `project.toml` `[verify]`, else
+/// the template manifest's, else [`project_default_steps`].
+pub fn resolve_steps(
+    dir: &Path,
+    manifest: Option<&VerifyConfig>,
+) -> Result<(Vec<StepSpec>, StepSource)> {
+    if let Some(steps) = project_steps(dir)? {
+        return Ok((steps, StepSource::Project));
+    }
+    match manifest {
+        Some(m) if !m.steps.is_empty() => Ok((m.steps.clone(), StepSource::Manifest)),
+        _ => Ok((project_default_steps(dir)?, StepSource::BuiltIn)),
+    }
+}
+
+/// The `[verify]` steps of `dir`'s `project.toml`, if it declares any.
+pub fn project_steps(dir: &Path) -> Result<Option<Vec<StepSpec>>> {
+    let path = dir.join("project.toml");
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let doc = crate::project::read_toml_value(&path)?;
+    let Some(table) = doc.get("verify") else {
+        return Ok(None);
+    };
+    let config: VerifyConfig = table
+        .clone()
+        .try_into()
+        .with_context(|| format!("parsing [verify] in {}", path.display()))?;
+    Ok(Some(config.steps).filter(|s| !s.is_empty()))
+}
+
+/// How to run the steps.
+#[derive(Debug, Clone, Default)]
+pub struct VerifyOptions {
+    /// Set `CARGO_NET_OFFLINE=true`, so cargo never touches the network
+    pub offline: bool,
+    /// Changed files (relative to the project): steps whose
+    /// [`StepSpec::paths`] match none of them are skipped
+    pub changed: Option<Vec<PathBuf>>,
+}
+
+/// Result of one verification step.
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifyStep {
+    pub name: String,
+    pub command: String,
+    pub ok: bool,
+    /// Not run: none of its files changed
+    pub skipped: bool,
+    /// The step may fail without failing the verification
+    pub allow_failure: bool,
+    pub timed_out: bool,
+    pub duration_ms: u64,
+    /// Combined stdout/stderr
+    pub output: String,
+}
+
+impl VerifyStep {
+    /// Failed, and the failure counts.
+    pub fn blocks(&self) -> bool {
+        !self.ok && !self.allow_failure
+    }
+
+    /// `OK`, `SKIPPED`, `FAILED`, `FAILED (allowed)` or `TIMED OUT`.
+    pub fn status(&self) -> &'static str {
+        if self.skipped {
+            return "SKIPPED";
+        }
+        match (self.ok, self.timed_out, self.allow_failure) {
+            (true, _, _) => "OK",
+            (false, true, false) => "TIMED OUT",
+            (false, true, true) => "TIMED OUT (allowed)",
+            (false, false, true) => "FAILED (allowed)",
+            (false, false, false) => "FAILED",
+        }
+    }
+
+    /// The last [`FAILED_OUTPUT_LINES`] lines of a failed step's output,
+    /// indented for a summary.
+    pub fn failed_output(&self) -> Vec<String> {
+        if self.ok {
+            return Vec::new();
+        }
+        let lines: Vec<&str> = self.output.trim_end().lines().collect();
+        let shown = &lines[lines.len().saturating_sub(FAILED_OUTPUT_LINES)..];
+        let mut out = Vec::new();
+        if shown.len() < lines.len() {
+            out.push(format!("   | ... ({} more lines)", lines.len() - shown.len()));
+        }
+        out.extend(shown.iter().map(|line| format!("   | {}", line)));
+        out
+    }
+}
+
+/// Lines of a failed step's output shown in a summary; the report has all.
+pub const FAILED_OUTPUT_LINES: usize = 20;
+
+/// Whether no step blocks.
+pub fn passed(steps: &[VerifyStep]) -> bool {
+    !steps.iter().any(VerifyStep::blocks)
+}
+
+/// Fail with [`CosmosError::VerificationFailed`] when a step blocks.
+pub fn check(steps: &[VerifyStep]) -> Result<()> {
+    if passed(steps) {
+        return Ok(());
+    }
+    Err(CosmosError::VerificationFailed("Verification checks failed".to_string()).into())
+}
+
+/// `generate --verify`: run the steps for the project in `dir` (see
+/// [`resolve_steps`]), passing the progress and summary lines to `say`.
+pub fn verify_project(
+    dir: &Path,
+    manifest: Option<&VerifyConfig>,
+    opts: &VerifyOptions,
+    say: &dyn Fn(&str),
+) -> Result<Vec<VerifyStep>> {
+    let (specs, source) = resolve_steps(dir, manifest)?;
+    say(&format!(
+        "Running {} verification steps from {} in {}",
+        specs.len(),
+        source,
+        dir.display()
+    ));
+    let steps = run_verification(dir, &specs, opts)
+        .map_err(|e| CosmosError::VerificationError(format!("Verification error: {:#}", e)))?;
+    say("Verification summary:");
+    for s in &steps {
+        say(&format!(" - {}: {} ({:.1}s)", s.name, s.status(), s.duration_ms as f64 / 1000.0));
+        for line in s.failed_output() {
+            say(&line);
+        }
+    }
+    if passed(&steps) {
+        say("Verification checks passed");
+    }
+    Ok(steps)
+}
+
+/// `cosmos verify` options.
+#[derive(Debug, Clone, Default)]
+pub struct CheckoutOptions {
+    /// Run only these steps (`--only`)
+    pub only: Vec<String>,
+    /// Skip steps whose files did not change since this revision
+    pub changed_since: Option<String>,
+    pub offline: bool,
+    /// Also write the reports into this folder
+    pub report_dir: Option<PathBuf>,
+}
+
+/// A [`verify_checkout`] run.
+#[derive(Debug, Clone)]
+pub struct CheckoutRun {
+    pub source: StepSource,
+    /// Files changed since [`CheckoutOptions::changed_since`]
+    pub changed: Option<Vec<PathBuf>>,
+    pub steps: Vec<VerifyStep>,
+    /// Reports written into [`CheckoutOptions::report_dir`]
+    pub reports: Vec<PathBuf>,
+}
+
+/// `cosmos verify`: the `[verify]` steps of `root`'s project.toml, else the
+/// built-in ones with its `[clippy]` settings. What is about to run is
+/// passed to `say`.
+pub fn verify_checkout(
+    root: &Path,
+    opts: &CheckoutOptions,
+    say: &dyn Fn(&str),
+) -> Result<CheckoutRun> {
+    let (mut specs, source) = resolve_steps(root, None)?;
+    if !opts.only.is_empty() {
+        let names: Vec<&str> = specs.iter().map(|s| s.name.as_str()).collect();
+        if let Some(unknown) = opts.only.iter().find(|o| !names.contains(&o.as_str())) {
+            return Err(CosmosError::Usage(format!(
+                "unknown verification step '{}' (available: {})",
+                unknown,
+                names.join(", ")
+            ))
+            .into());
+        }
+        specs.retain(|s| opts.only.contains(&s.name));
+    }
+    let changed = opts
+        .changed_since
+        .as_deref()
+        .map(|rev| crate::git::changed_files(root, rev))
+        .transpose()?;
+    say(&format!(
+        "Running {} verification steps from {} in {}",
+        specs.len(),
+        source,
+        root.display()
+    ));
+    if let (Some(rev), Some(files)) = (&opts.changed_since, &changed) {
+        say(&format!("{} files changed since {}", files.len(), rev));
+    }
+    let run_opts = VerifyOptions { offline: opts.offline, changed: changed.clone() };
+    let steps = run_verification(root, &specs, &run_opts)
+        .map_err(|e| CosmosError::VerificationError(format!("Verification error: {:#}", e)))?;
+    let reports = match &opts.report_dir {
+        Some(dir) => write_reports_to(dir, &steps)?,
+        None => Vec::new(),
+    };
+    Ok(CheckoutRun { source, changed, steps, reports })
+}
+
+/// Run `steps` in `dir`: the [`StepSpec::parallel`] ones on their own
+/// threads, the rest one after the other. Results keep the order of
+/// `steps`. A step that cannot be started (program not found) counts as
+/// failed.
+pub fn run_verification(
+    dir: &Path,
+    steps: &[StepSpec],
+    opts: &VerifyOptions,
+) -> Result<Vec<VerifyStep>> {
+    if let Some(empty) =
+        steps.iter().find(|s| matches!(&s.command, StepCommand::Args(a) if a.is_empty()))
+    {
+        anyhow::bail!("verification step '{}' has an empty command", empty.name);
+    }
+    let mut results: Vec<Option<VerifyStep>> = vec![None; steps.len()];
+    std::thread::scope(|scope| {
+        let parallel: Vec<_> = steps
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.parallel)
+            .map(|(i, spec)| (i, scope.spawn(move || run_step(dir, spec, opts))))
+            .collect();
+        for (i, spec) in steps.iter().enumerate().filter(|(_, s)| !s.parallel) {
+            results[i] = Some(run_step(dir, spec, opts));
+        }
+        for (i, handle) in parallel {
+            results[i] = handle.join().ok();
+        }
+    });
+    Ok(results
+        .into_iter()
+        .zip(steps)
+        .map(|(r, spec)| r.unwrap_or_else(|| failed(spec, "the step panicked".to_string())))
+        .collect())
+}
+
+fn failed(spec: &StepSpec, output: String) -> VerifyStep {
+    VerifyStep {
+        name: spec.name.clone(),
+        command: spec.command.to_string(),
+        ok: false,
+        skipped: false,
+        allow_failure: spec.allow_failure,
+        timed_out: false,
+        duration_ms: 0,
+        output,
+    }
+}
+
+fn run_step(dir: &Path, spec: &StepSpec, opts: &VerifyOptions) -> VerifyStep {
+    if opts.changed.as_deref().is_some_and(|changed| !spec.is_affected(changed)) {
+        return VerifyStep { ok: true, skipped: true, ..failed(spec, String::new()) };
+    }
+    let mut cmd = match &spec.command {
+        StepCommand::Args(args) => {
+            let mut cmd = Command::new(&args[0]);
+            cmd.args(&args[1..]);
+            cmd
+        }
+        StepCommand::Shell(line) => shell(line),
+    };
+    cmd.current_dir(dir).envs(&spec.env);
+    if opts.offline {
+        cmd.env("CARGO_NET_OFFLINE", "true");
+    }
+    let start = Instant::now();
+    let (ok, timed_out, output) = match run_with_timeout(cmd, spec.timeout) {
+        Ok(r) => r,
+        Err(e) => (false, false, format!("{:#}", e)),
+    };
+    VerifyStep {
+        ok,
+        timed_out,
+        duration_ms: start.elapsed().as_millis() as u64,
+        ..failed(spec, output)
+    }
+}
+
+#[cfg(unix)]
+fn shell(line: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", line]);
+    cmd
+}
+
+#[cfg(not(unix))]
+fn shell(line: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.args(["/C", line]);
+    cmd
+}
+
+/// Run `cmd`, killing it after `timeout` seconds; returns success, whether
+/// it timed out, and the combined output.
+fn run_with_timeout(mut cmd: Command, timeout: Option<u64>) -> Result<(bool, bool, String)> {
+    // a timed step gets its own process group, so the whole tree (e.g. the
+    // test binaries under `cargo test`) can be killed
+    #[cfg(unix)]
+    if timeout.is_some() {
+        use std::os::unix::process::CommandExt;
+        cmd.process_group(0);
+    }
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("running {:?}", cmd.get_program()))?;
+    // drain both pipes while waiting, so a chatty step cannot block
+    let drain = |pipe: Option<Box<dyn Read + Send>>| {
+        std::thread::spawn(move || {
+            let mut buf = Vec::new();
+            if let Some(mut p) = pipe {
+                let _ = p.read_to_end(&mut buf);
+            }
+            buf
+        })
+    };
+    let stdout = drain(child.stdout.take().map(|p| Box::new(p) as Box<dyn Read + Send>));
+    let stderr = drain(child.stderr.take().map(|p| Box::new(p) as Box<dyn Read + Send>));
+
+    let deadline = timeout.map(|s| Instant::now() + Duration::from_secs(s));
+    let mut timed_out = false;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            kill_tree(&mut child);
+            timed_out = true;
+            break child.wait()?;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    };
+    let mut output = String::from_utf8_lossy(&stdout.join().unwrap_or_default()).into_owned();
+    output.push_str(&String::from_utf8_lossy(&stderr.join().unwrap_or_default()));
+    if let (true, Some(secs)) = (timed_out, timeout) {
+        output.push_str(&format!("\nkilled after the {}s timeout\n", secs));
+    }
+    Ok((status.success() && !timed_out, timed_out, output))
+}
+
+#[cfg(unix)]
+fn kill_tree(child: &mut std::process::Child) {
+    let group = format!("-{}", child.id());
+    let killed = Command::new("kill").args(["-KILL", "--", &group]).status();
+    if !killed.is_ok_and(|s| s.success()) {
+        let _ = child.kill();
+    }
+}
+
+#[cfg(not(unix))]
+fn kill_tree(child: &mut std::process::Child) {
+    let _ = child.kill();
+}
+
+/// Write the JSON and JUnit reports into `dir`'s [`REPORT_DIR`]; returns
+/// their paths.
+pub fn write_reports(dir: &Path, steps: &[VerifyStep]) -> Result<Vec<PathBuf>> {
+    write_reports_to(&dir.join(REPORT_DIR), steps)
+}
+
+/// Write [`JSON_REPORT`] and [`JUNIT_REPORT`] into the `reports` folder.
+pub fn write_reports_to(reports: &Path, steps: &[VerifyStep]) -> Result<Vec<PathBuf>> {
+    let reports = reports.to_path_buf();
+    fs::create_dir_all(&reports).with_context(|| format!("creating {:?}", reports))?;
+    let json = serde_json::json!({ "passed": passed(steps), "steps": steps });
+    let files = [
+        (reports.join(JSON_REPORT), serde_json::to_string_pretty(&json)? + "\n"),
+        (reports.join(JUNIT_REPORT), junit(steps)),
+    ];
+    for (path, text) in &files {
+        fs::write(path, text).with_context(|| format!("writing {:?}", path))?;
+    }
+    Ok(files.into_iter().map(|(p, _)| p).collect())
+}
+
+/// JUnit XML: one test case per step; allowed failures and skipped steps
+/// are reported as skipped so CI dashboards stay green.
+fn junit(steps: &[VerifyStep]) -> String {
+    let secs = |ms: u64| format!("{:.3}", ms as f64 / 1000.0);
+    let total: u64 = steps.iter().map(|s| s.duration_ms).sum();
+    let failures = steps.iter().filter(|s| s.blocks()).count();
+    let skipped = steps.iter().filter(|s| s.skipped || (!s.ok && s.allow_failure)).count();
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str(&format!(
+        "<testsuites>\n  <testsuite name=\"cosmos verify\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{}\">\n",
+        steps.len(),
+        failures,
+        skipped,
+        secs(total)
+    ));
+    for s in steps {
+        out.push_str(&format!(
+            "    <testcase name=\"{}\" classname=\"verify\" time=\"{}\">\n",
+            xml_escape(&s.name),
+            secs(s.duration_ms)
+        ));
+        if s.skipped {
+            out.push_str("      <skipped message=\"no matching changes\"/>\n");
+        } else if !s.ok {
+            let tag = if s.allow_failure { "skipped" } else { "failure" };
+            out.push_str(&format!(
+                "      <{} message=\"{}: {}\"/>\n",
+                tag,
+                s.status(),
+                xml_escape(&s.command)
+            ));
+        }
+        out.push_str(&format!("      <system-out>{}</system-out>\n", xml_escape(&s.output)));
+        out.push_str("    </testcase>\n");
+    }
+    out.push_str("  </testsuite>\n</testsuites>\n");
+    out
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            // control characters are not allowed in XML 1.0
+            c if c.is_control() && !matches!(c, '\n' | '\r' | '\t') => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn steps_honor_env_timeout_and_allow_failure() {
+        let td = tempfile::tempdir().unwrap();
+        let steps: VerifyConfig = toml::from_str(
+            r#"
+            steps = [
+              { name = "env", command = "test \"$GREETING\" = hi", env = { GREETING = "hi" } },
+              { name = "slow", command = "sleep 5; echo done", timeout = 1 },
+              { name = "lint", command = "echo '<warn>' && exit 3", allow_failure = true },
+            ]
+            "#,
+        )
+        .unwrap();
+        let opts = VerifyOptions { offline: true, ..VerifyOptions::default() };
+        let results = run_verification(td.path(), &steps.steps, &opts).unwrap();
+        let status: Vec<&str> = results.iter().map(VerifyStep::status).collect();
+        assert_eq!(status, ["OK", "TIMED OUT", "FAILED (allowed)"]);
+        assert!(results[1].duration_ms < 4000);
+        assert!(!passed(&results));
+        assert!(passed(&results[2..]));
+
+        let paths = write_reports(td.path(), &results).unwrap();
+        assert_eq!(paths.len(), 2);
+        let xml = fs::read_to_string(td.path().join(REPORT_DIR).join(JUNIT_REPORT)).unwrap();
+        assert!(xml.contains("tests=\"3\" failures=\"1\" skipped=\"1\""), "{}", xml);
+        assert!(xml.contains("&lt;warn&gt;"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn parallel_steps_run_alongside_and_unchanged_steps_skip() {
+        let td = tempfile::tempdir().unwrap();
+        let steps: VerifyConfig = toml::from_str(
+            r#"
+            steps = [
+              { name = "waits", command = "for i in $(seq 50); do test -f ready && exit 0; sleep 0.1; done; exit 1" },
+              { name = "signals", command = "sleep 0.3 && touch ready", parallel = true },
+              { name = "docs", command = "exit 1", paths = ["docs/**"] },
+            ]
+            "#,
+        )
+        .unwrap();
+        let opts = VerifyOptions {
+            changed: Some(vec![PathBuf::from("src/lib.rs")]),
+            ..VerifyOptions::default()
+        };
+        let results = run_verification(td.path(), &steps.steps, &opts).unwrap();
+        let status: Vec<&str> = results.iter().map(VerifyStep::status).collect();
+        assert_eq!(status, ["OK", "OK", "SKIPPED"]);
+
+        let opts = VerifyOptions {
+            changed: Some(vec![PathBuf::from("docs/index.md")]),
+            ..VerifyOptions::default()
+        };
+        let results = run_verification(td.path(), &steps.steps[2..], &opts).unwrap();
+        assert_eq!(results[0].status(), "FAILED");
+    }
+
+    #[test]
+    fn project_clippy_table_adjusts_the_default_steps() {
+        let td = tempfile::tempdir().unwrap();
+        fs::write(
+            td.path().join("project.toml"),
+            "[clippy]\nmsrv = \"1.70.0\"\nallow = [\"clippy::module_name_repetitions\"]\n",
+        )
+        .unwrap();
+        let steps = project_default_steps(td.path()).unwrap();
+        let commands: Vec<String> = steps.iter().map(|s| s.command.to_string()).collect();
+        assert_eq!(commands[1], "cargo +1.70.0 check --workspace --all-targets");
+        assert!(commands[2].ends_with("-D warnings -A clippy::module_name_repetitions"));
+        assert!(steps[0].parallel && !steps[1].parallel);
+    }
+
+    #[test]
+    fn project_steps_win_over_the_manifest() {
+        let td = tempfile::tempdir().unwrap();
+        let manifest = VerifyConfig { steps: vec![StepSpec::cargo("check", &["check"], &[])] };
+        let (steps, source) = resolve_steps(td.path(), Some(&manifest)).unwrap();
+        assert_eq!((steps[0].name.as_str(), source), ("check", StepSource::Manifest));
+
+        fs::write(
+            td.path().join("project.toml"),
+            "[verify]\nsteps = [{ name = \"build\", command = [\"cargo\", \"build\"] }]\n",
+        )
+        .unwrap();
+        let (steps, source) = resolve_steps(td.path(), Some(&manifest)).unwrap();
+        assert_eq!((steps[0].name.as_str(), source), ("build", StepSource::Project));
+
+        let (steps, source) = resolve_steps(Path::new("/nonexistent"), None).unwrap();
+        assert_eq!((steps.len(), source), (3, StepSource::BuiltIn));
+
+        // without [verify] the built-in steps still pick up [clippy]
+        fs::write(td.path().join("project.toml"), "[clippy]\nmsrv = \"1.70.0\"\n").unwrap();
+        let (steps, source) = resolve_steps(td.path(), None).unwrap();
+        assert_eq!((steps.len(), source), (4, StepSource::BuiltIn));
+    }
+
+    #[test]
+    fn misspelled_step_keys_are_rejected() {
+        let td = tempfile::tempdir().unwrap();
+        fs::write(
+            td.path().join("project.toml"),
+            "[verify]\nsteps = [{ name = \"slow\", command = \"sleep 60\", timout = 30 }]\n",
+        )
+        .unwrap();
+        let err = project_steps(td.path()).unwrap_err();
+        assert!(format!("{:#}", err).contains("unknown field `timout`"), "{:#}", err);
+    }
+}
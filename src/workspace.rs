@@ -0,0 +1,273 @@
+//! Workspace (multi-crate) templates: a template's manifest may list
+//! `[[members]]`, sub-templates rendered into a folder of the project.
+//!
+//! ```toml
+//! [[members]]                  # one crate
+//! template = "lib-crate"
+//! path = "crates/{{name}}"
+//! vars = { name = "core" }
+//!
+//! [[members]]                  # one crate per entry of the `bins` variable
+//! template = "bin-crate"
+//! path = "crates/{{name}}"
+//! each = "bins"                # a list, or a comma-separated string
+//! ```
+//!
+//! A member is rendered with the parent's variables plus its own `vars`
+//! (and `name` for `each`); variables its manifest declares fall back to
+//! their defaults. `cosmos generate` adds every member path to
+//! `[workspace] members` of the root `Cargo.toml` (see [`set_members`]).
+
+use std::collections::BTreeSet;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use toml_edit::{Array, Document, Item, Table};
+
+use crate::error::CosmosError;
+use crate::template::{
+    self, eval_condition, RenderedFile, Renderer, Template, TemplateFile, WriteReport,
+};
+use crate::transaction::Transaction;
+
+/// `[[members]]` manifest entry.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MemberSpec {
+    /// Template rendered for the member, looked up like `--template`
+    pub template: String,
+    /// Folder of the member in the project (rendered)
+    pub path: String,
+    /// Extra variables for the member; strings are rendered
+    #[serde(default)]
+    pub vars: toml::value::Table,
+    /// Variable holding a list of names: one member per name, as `name`
+    #[serde(default)]
+    pub each: Option<String>,
+    /// Only generate the member when this condition holds
+    #[serde(default)]
+    pub when: Option<String>,
+}
+
+/// A member template ready to be rendered under [`Member::path`].
+pub struct Member {
+    /// Project-relative folder of the member
+    pub path: PathBuf,
+    pub template: Template,
+    pub renderer: Renderer,
+    /// Selected member files (raw files marked, conditions applied)
+    pub files: Vec<TemplateFile>,
+}
+
+impl Member {
+    /// Project-relative path of a member file.
+    pub fn dest_rel(&self, f: &TemplateFile) -> PathBuf {
+        self.path.join(self.template.dest_rel(f, &self.renderer))
+    }
+
+    /// Render every file, with project-relative paths.
+    pub fn render(&self) -> Result<Vec<(PathBuf, RenderedFile)>> {
+        self.files
+            .iter()
+            .map(|f| Ok((self.dest_rel(f), template::render_file(f, &self.renderer)?)))
+            .collect()
+    }
+
+    /// Stage the member files in `tx` (only those in `only`, when given).
+    pub fn stage(
+        &self,
+        tx: &Transaction,
+        only: Option<&BTreeSet<PathBuf>>,
+        force: bool,
+    ) -> Result<WriteReport> {
+        let files: Vec<TemplateFile> = self
+            .files
+            .iter()
+            .filter(|f| only.map_or(true, |o| o.contains(&self.dest_rel(f))))
+            .cloned()
+            .collect();
+        let mut report = template::stage_files_at(
+            &self.template,
+            &files,
+            &self.renderer,
+            tx,
+            &self.path,
+            force,
+        )?;
+        for rel in &mut report.written {
+            *rel = self.path.join(&*rel);
+        }
+        Ok(report)
+    }
+}
+
+/// Resolve `specs` of template `parent` against its `renderer`: locate each
+/// member template (next to `parent` first, see [`Template::locate_related`]),
+/// build its variables and select its files.
+pub fn resolve(
+    specs: &[MemberSpec],
+    parent: &Template,
+    renderer: &Renderer,
+    repo_root: &Path,
+    search_path: &[PathBuf],
+) -> Result<Vec<Member>> {
+    let mut members: Vec<Member> = Vec::new();
+    for spec in specs {
+        if let Some(cond) = &spec.when {
+            if !eval_condition(cond, renderer.context())? {
+                continue;
+            }
+        }
+        let names = match &spec.each {
+            Some(var) => each_names(var, renderer.context().get(var))?,
+            None => vec![None],
+        };
+        for name in names {
+            let member = resolve_one(spec, name, parent, renderer, repo_root, search_path)
+                .with_context(|| format!("workspace member '{}'", spec.template))?;
+            if members.iter().any(|m| m.path == member.path) {
+                bail!("two workspace members use the path {}", member.path.display());
+            }
+            members.push(member);
+        }
+    }
+    Ok(members)
+}
+
+fn each_names(var: &str, value: Option<&Value>) -> Result<Vec<Option<String>>> {
+    let names: Vec<String> = match value {
+        Some(Value::String(s)) => s.split(',').map(str::trim).map(str::to_string).collect(),
+        Some(Value::Array(items)) => items
+            .iter()
+            .map(|v| match v {
+                Value::String(s) => s.trim().to_string(),
+                other => other.to_string(),
+            })
+            .collect(),
+        Some(other) => bail!("`each = \"{}\"`: expected a list, got {}", var, other),
+        None => bail!("`each = \"{}\"`: the variable is not set", var),
+    };
+    Ok(names.into_iter().filter(|n| !n.is_empty()).map(Some).collect())
+}
+
+fn resolve_one(
+    spec: &MemberSpec,
+    name: Option<String>,
+    parent_template: &Template,
+    parent: &Renderer,
+    repo_root: &Path,
+    search_path: &[PathBuf],
+) -> Result<Member> {
+    let template = parent_template
+        .locate_related(&spec.template, repo_root, search_path)?
+        .ok_or_else(|| CosmosError::NotFound(format!("template '{}' not found", spec.template)))?;
+    let manifest = template.manifest()?.unwrap_or_default();
+
+    let mut ctx = parent.context().clone();
+    if let Some(name) = name {
+        ctx.insert("name".to_string(), Value::String(name));
+    }
+    for (key, value) in &spec.vars {
+        let value = match value {
+            toml::Value::String(s) => Value::String(Renderer::new(ctx.clone()).render_text(s)),
+            other => serde_json::to_value(other)?,
+        };
+        ctx.insert(key.clone(), value);
+    }
+    template::resolve_variables(&manifest.variables, &mut ctx, None)?;
+    let renderer = Renderer::new(ctx).partials(&template.partials()?)?.strict(parent.is_strict());
+
+    let path = PathBuf::from(renderer.render_text(&spec.path));
+    if path.as_os_str().is_empty() || !path.components().all(|c| matches!(c, Component::Normal(_)))
+    {
+        return Err(CosmosError::Usage(format!(
+            "member path {:?} must be a relative folder inside the project",
+            path
+        ))
+        .into());
+    }
+
+    let mut files = template.files();
+    template::mark_raw(&mut files, &manifest.raw);
+    let (files, _) =
+        template::filter_conditional(files, &manifest.conditional, renderer.context())?;
+    Ok(Member { path, template, renderer, files })
+}
+
+/// Add `paths` to `[workspace] members` of the `Cargo.toml` in `current`,
+/// keeping the entries already there. Returns `None` when nothing changes.
+pub fn set_members(current: &str, paths: &[PathBuf]) -> Result<Option<String>> {
+    let mut doc: Document = current.parse().context("parsing Cargo.toml")?;
+    let workspace = doc
+        .entry("workspace")
+        .or_insert_with(|| Item::Table(Table::new()))
+        .as_table_mut()
+        .context("Cargo.toml: `workspace` is not a table")?;
+    let members = workspace
+        .entry("members")
+        .or_insert_with(|| Item::Value(Array::new().into()))
+        .as_array_mut()
+        .context("Cargo.toml: `workspace.members` is not an array")?;
+
+    // one entry per line, unless the list is already written inline
+    let multiline = members.is_empty()
+        || members.iter().any(|v| {
+            v.decor().prefix().is_some_and(|p| p.as_str().is_some_and(|s| s.contains('\n')))
+        });
+    let mut changed = false;
+    for path in paths {
+        let path = path.to_string_lossy().replace('\\', "/");
+        if members.iter().any(|v| v.as_str() == Some(path.as_str())) {
+            continue;
+        }
+        members.push(path);
+        changed = true;
+    }
+    if !changed {
+        return Ok(None);
+    }
+    if multiline {
+        for v in members.iter_mut() {
+            v.decor_mut().set_prefix("\n    ");
+            v.decor_mut().set_suffix("");
+        }
+        members.set_trailing("\n");
+        members.set_trailing_comma(true);
+    }
+    Ok(Some(doc.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn members_are_added_once_and_keep_the_layout() {
+        let paths = [PathBuf::from("crates/core"), PathBuf::from("crates/cli")];
+        let empty = "[workspace]\nresolver = \"2\"\nmembers = []\n";
+        let out = set_members(empty, &paths).unwrap().unwrap();
+        assert_eq!(
+            out,
+            "[workspace]\nresolver = \"2\"\nmembers = [\n    \"crates/core\",\n    \"crates/cli\",\n]\n"
+        );
+        assert!(set_members(&out, &paths).unwrap().is_none());
+
+        let inline = "[workspace]\nmembers = [\"tools\"]\n";
+        let out = set_members(inline, &paths[..1]).unwrap().unwrap();
+        assert_eq!(out, "[workspace]\nmembers = [\"tools\", \"crates/core\"]\n");
+
+        let package = "[package]\nname = \"app\"\n";
+        let out = set_members(package, &paths[..1]).unwrap().unwrap();
+        assert!(out.contains("[workspace]\nmembers = [\n    \"crates/core\",\n]\n"), "{}", out);
+    }
+
+    #[test]
+    fn each_accepts_lists_and_comma_separated_strings() {
+        let names = each_names("bins", Some(&Value::String("cli, server,".into()))).unwrap();
+        assert_eq!(names, vec![Some("cli".to_string()), Some("server".to_string())]);
+        let list = serde_json::json!(["core"]);
+        assert_eq!(each_names("libs", Some(&list)).unwrap(), vec![Some("core".to_string())]);
+        assert!(each_names("libs", None).is_err());
+    }
+}
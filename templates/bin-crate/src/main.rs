@@ -0,0 +1,3 @@
+fn main() {
+    println!("{{crate-name}} {}", env!("CARGO_PKG_VERSION"));
+}
@@ -0,0 +1,26 @@
+#!/usr/bin/env python3
+"""{{hook}} plan hook.
+Provide a unified entrypoint: `def run(ctx: dict) -> dict`.
+When executed as a script it reads a JSON `ctx` from stdin and prints a JSON `res` to stdout.
+Exiting with nonzero indicates failure and will block the transition.
+"""
+import json
+import sys
+
+
+def run(ctx: dict) -> dict:
+    # implement checks here, e.g. ensure reports exist
+    task_id = ctx.get('task_id')
+    repo_root = ctx.get('repo_root')
+    # return {'ok': True, 'message': 'ok'} or {'ok': False, 'message': 'explain'}
+    return {'ok': True, 'message': 'ok'}
+
+
+if __name__ == "__main__":
+    try:
+        data = json.load(sys.stdin)
+    except Exception:
+        data = {}
+    res = run(data)
+    print(json.dumps(res))
+    sys.exit(0 if res.get('ok', False) else 1)
@@ -0,0 +1,4 @@
+#[test]
+fn {{name}}_works() {
+    assert_eq!(2 + 2, 4);
+}
@@ -0,0 +1,12 @@
+#!/usr/bin/env sh
+# Install the git hooks in .githooks by pointing core.hooksPath at it.
+# `cosmos generate --git-init` runs this once; run it again after a fresh clone.
+set -eu
+
+ROOT=$(git rev-parse --show-toplevel 2>/dev/null || echo ".")
+cd "$ROOT"
+
+git config core.hooksPath .githooks
+chmod +x .githooks/* 2>/dev/null || true
+echo "git hooks installed (core.hooksPath = .githooks); undo with: git config --unset core.hooksPath"
+echo "Bypass them for a single commit with: git commit --no-verify"
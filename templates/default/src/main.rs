@@ -0,0 +1,3 @@
+fn main() {
+    println!("{{project-name}} {}", env!("CARGO_PKG_VERSION"));
+}
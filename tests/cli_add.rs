@@ -0,0 +1,80 @@
+use predicates::prelude::*;
+use std::fs;
+use tempfile::tempdir;
+
+const CARGO: &str = "[package]\nname = \"demo\"\nversion = \"0.1.0\"\n";
+
+#[test]
+fn add_lists_the_builtin_components() {
+    let td = tempdir().unwrap();
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).arg("add");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("bin"))
+        .stdout(predicate::str::contains("docs-page"))
+        .stdout(predicate::str::contains("plan-hook"));
+}
+
+#[test]
+fn add_bin_creates_the_file_and_patches_cargo_toml() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    fs::write(td.path().join("Cargo.toml"), CARGO)?;
+
+    // dry run by default
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args(["add", "bin", "--var", "name=tool"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("create src/bin/tool.rs"))
+        .stdout(predicate::str::contains("Dry run"));
+    assert!(!td.path().join("src/bin/tool.rs").exists());
+    assert_eq!(fs::read_to_string(td.path().join("Cargo.toml"))?, CARGO);
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args(["add", "bin", "--var", "name=tool", "--apply"]);
+    cmd.assert().success().stdout(predicate::str::contains("Component added"));
+    assert!(td.path().join("src/bin/tool.rs").is_file());
+    let cargo = fs::read_to_string(td.path().join("Cargo.toml"))?;
+    assert!(cargo.starts_with(CARGO), "{}", cargo);
+    assert!(cargo.contains("[[bin]]\nname = \"tool\"\npath = \"src/bin/tool.rs\"\n"), "{}", cargo);
+
+    // adding the same component again changes nothing
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args(["add", "bin", "--var", "name=tool", "--apply"]);
+    cmd.assert().success().stdout(predicate::str::contains("already present"));
+    assert_eq!(fs::read_to_string(td.path().join("Cargo.toml"))?, cargo);
+    Ok(())
+}
+
+#[test]
+fn add_docs_page_extends_mkdocs_nav() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    fs::write(td.path().join("mkdocs.yml"), "site_name: demo\nnav:\n  - Home: index.md\n")?;
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["add", "docs-page", "--var", "name=deploy", "--var", "title=Deploy"])
+        .args(["--var", "section=Guides", "--apply"]);
+    cmd.assert().success();
+    assert!(td.path().join("docs/deploy.md").is_file());
+    assert_eq!(
+        fs::read_to_string(td.path().join("mkdocs.yml"))?,
+        "site_name: demo\nnav:\n  - Home: index.md\n  - Guides:\n      - Deploy: deploy.md\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn add_refuses_to_overwrite_without_force() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    fs::write(td.path().join("Cargo.toml"), CARGO)?;
+    fs::create_dir_all(td.path().join("src/bin"))?;
+    fs::write(td.path().join("src/bin/tool.rs"), "fn main() {}\n")?;
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args(["add", "bin", "--var", "name=tool", "--apply"]);
+    cmd.assert().code(12).stderr(predicate::str::contains("--force"));
+    assert_eq!(fs::read_to_string(td.path().join("Cargo.toml"))?, CARGO);
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use predicates::prelude::*;
+use std::fs;
+use tempfile::tempdir;
+
+fn cosmos(dir: &std::path::Path) -> assert_cmd::Command {
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(dir);
+    cmd
+}
+
+#[test]
+fn exit_codes_distinguish_error_kinds() {
+    let td = tempdir().unwrap();
+    let dir = td.path();
+    fs::create_dir_all(dir.join("plan/tasks")).unwrap();
+
+    // task not found
+    cosmos(dir)
+        .args(["plan", "show", "--id", "0042"])
+        .assert()
+        .code(8)
+        .stderr(predicate::str::contains("error[task_not_found]"));
+
+    cosmos(dir)
+        .args(["plan", "create", "--kind", "feature", "--title", "T", "--content", "no criteria"])
+        .assert()
+        .success();
+
+    // built-in check rejects the review
+    cosmos(dir)
+        .args(["plan", "review", "--id", "0001", "--decision", "accept"])
+        .assert()
+        .code(10)
+        .stderr(predicate::str::contains("error[check_failed]"));
+
+    // task is pending_review, not finished
+    cosmos(dir)
+        .args(["plan", "reopen", "--id", "0001"])
+        .assert()
+        .code(9)
+        .stderr(predicate::str::contains("error[invalid_transition]"))
+        .stderr(predicate::str::contains("Hint:"));
+
+    // a failing pre hook rejects the transition
+    fs::write(dir.join("plan/tasks/0001/task.md"), "Acceptance criteria:\n- ok\n").unwrap();
+    fs::create_dir_all(dir.join("scripts/plan-hooks")).unwrap();
+    fs::write(
+        dir.join("scripts/plan-hooks/pre_review_accept.py"),
+        "import sys\nprint('nope')\nsys.exit(1)\n",
+    )
+    .unwrap();
+    cosmos(dir)
+        .args(["plan", "review", "--id", "0001", "--decision", "accept"])
+        .assert()
+        .code(11)
+        .stderr(predicate::str::contains("error[hook_rejected]"));
+
+    // invalid argument value
+    cosmos(dir)
+        .args(["plan", "review", "--id", "0001", "--decision", "maybe"])
+        .assert()
+        .code(6)
+        .stderr(predicate::str::contains("error[usage]"));
+
+    // clap parse errors share the usage code
+    cosmos(dir).args(["plan", "bogus"]).assert().code(6);
+
+    // missing manifest
+    cosmos(dir).args(["project", "validate"]).assert().code(7);
+}
@@ -0,0 +1,103 @@
+use std::fs;
+
+use serde_json::Value;
+use tempfile::tempdir;
+
+fn stdout_json(out: &[u8]) -> Value {
+    serde_json::from_slice(out).expect("stdout is a JSON document")
+}
+
+#[test]
+fn plan_list_and_show_emit_json_and_ndjson() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    let dir = td.path();
+    fs::create_dir_all(dir.join("plan/tasks/0001"))?;
+    fs::write(
+        dir.join("plan/todo.toml"),
+        r#"[[task]]
+id = "0001"
+kind = "feature"
+title = "First"
+status = "pending_review"
+assignee = "alice"
+task_file = "tasks/0001/task.md"
+
+[[task]]
+id = "0002"
+title = "Second"
+status = "queued"
+"#,
+    )?;
+    fs::write(dir.join("plan/tasks/0001/task.md"), "body")?;
+
+    let out = assert_cmd::cargo::cargo_bin_cmd!("cosmos")
+        .current_dir(dir)
+        .args(["--format", "json", "plan", "list"])
+        .output()?;
+    assert!(out.status.success());
+    let doc = stdout_json(&out.stdout);
+    let tasks = doc["tasks"].as_array().unwrap();
+    assert_eq!(tasks.len(), 2);
+    assert_eq!(tasks[0]["assignee"], "alice");
+    assert_eq!(tasks[0]["task_file"], "tasks/0001/task.md");
+
+    let out = assert_cmd::cargo::cargo_bin_cmd!("cosmos")
+        .current_dir(dir)
+        .args(["plan", "list", "--format", "ndjson"])
+        .output()?;
+    let lines: Vec<Value> =
+        String::from_utf8(out.stdout)?.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[1]["id"], "0002");
+
+    let out = assert_cmd::cargo::cargo_bin_cmd!("cosmos")
+        .current_dir(dir)
+        .args(["--format", "json", "plan", "show", "--id", "0001"])
+        .output()?;
+    let doc = stdout_json(&out.stdout);
+    assert_eq!(doc["task"]["kind"], "feature");
+    assert_eq!(doc["content"], "body");
+    Ok(())
+}
+
+#[test]
+fn validate_json_reports_issue_ids_and_errors_as_json() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    let dir = td.path();
+
+    let out = assert_cmd::cargo::cargo_bin_cmd!("cosmos")
+        .current_dir(dir)
+        .args(["--format", "json", "validate"])
+        .output()?;
+    assert_eq!(out.status.code(), Some(2));
+    let doc = stdout_json(&out.stdout);
+    let errors = doc["errors"].as_array().unwrap();
+    assert!(errors.iter().any(|e| e["id"] == "repo.missing-file"));
+    let err: Value = serde_json::from_slice(&out.stderr)?;
+    assert_eq!(err["error"]["kind"], "validation_failed");
+    assert_eq!(err["error"]["exit_code"], 2);
+    Ok(())
+}
+
+#[test]
+fn generate_json_lists_file_actions() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    let dest = td.path().join("out");
+    fs::create_dir_all(&dest)?;
+    fs::write(dest.join("Cargo.toml"), "stale")?;
+    fs::write(dest.join("EXTRA.txt"), "extra")?;
+
+    let out = assert_cmd::cargo::cargo_bin_cmd!("cosmos")
+        .args(["generate", "--format", "json", "--out-dir"])
+        .arg(&dest)
+        .output()?;
+    assert!(out.status.success());
+    let doc = stdout_json(&out.stdout);
+    assert_eq!(doc["applied"], false);
+    let files = doc["files"].as_array().unwrap();
+    let action = |p: &str| files.iter().find(|f| f["path"] == p).map(|f| f["action"].clone());
+    assert_eq!(action("Cargo.toml"), Some("skip".into()));
+    assert_eq!(action("EXTRA.txt"), Some("delete".into()));
+    assert_eq!(action("README.md"), Some("add".into()));
+    Ok(())
+}
@@ -0,0 +1,158 @@
+use predicates::prelude::*;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use tempfile::tempdir;
+
+/// `tpl` template with a rendered README and an executable script.
+fn template(root: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    fs::create_dir_all(root.join("tpl/scripts"))?;
+    fs::write(root.join("tpl/README.md"), "# {{project-name}}\n")?;
+    fs::write(root.join("tpl/scripts/run.sh"), "#!/bin/sh\necho {{project-name}}\n")?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let script = root.join("tpl/scripts/run.sh");
+        fs::set_permissions(script, fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
+
+/// Path, mode and contents of every regular file in a `.tar.gz`.
+fn tar_gz_files(bytes: &[u8]) -> Vec<(String, u32, String)> {
+    let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(bytes));
+    let mut files = Vec::new();
+    for e in tar.entries().unwrap() {
+        let mut e = e.unwrap();
+        let path = e.path().unwrap().to_string_lossy().into_owned();
+        let mode = e.header().mode().unwrap();
+        let mut contents = String::new();
+        e.read_to_string(&mut contents).unwrap();
+        files.push((path, mode, contents));
+    }
+    files
+}
+
+#[test]
+fn tar_gz_archive_keeps_contents_and_modes() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    template(td.path())?;
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["generate", "--template-dir", "tpl", "--project-name", "acme"])
+        .args(["--output-archive", "acme.tar.gz", "--apply"]);
+    cmd.assert().success().stdout(predicate::str::contains("into acme.tar.gz (tar.gz)"));
+    assert!(!td.path().join("out").exists());
+
+    let files = tar_gz_files(&fs::read(td.path().join("acme.tar.gz"))?);
+    assert!(files.contains(&("README.md".into(), 0o644, "# acme\n".into())), "{:?}", files);
+    let script = files.iter().find(|f| f.0 == "scripts/run.sh").unwrap();
+    assert_eq!(script.2, "#!/bin/sh\necho acme\n");
+    #[cfg(unix)]
+    assert_eq!(script.1, 0o755);
+    // the lock travels with the project, so `cosmos update` works once unpacked
+    assert!(files.iter().any(|f| f.0 == ".cosmos/template.lock"));
+    Ok(())
+}
+
+#[test]
+fn zip_archive_and_stdout_stream() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    template(td.path())?;
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["generate", "--template-dir", "tpl", "--project-name", "acme"])
+        .args(["--output-archive", "acme.zip", "--apply"]);
+    cmd.assert().success();
+    let mut zip = zip::ZipArchive::new(fs::File::open(td.path().join("acme.zip"))?)?;
+    let mut readme = String::new();
+    zip.by_name("README.md")?.read_to_string(&mut readme)?;
+    assert_eq!(readme, "# acme\n");
+    #[cfg(unix)]
+    assert_eq!(zip.by_name("scripts/run.sh")?.unix_mode().unwrap() & 0o777, 0o755);
+
+    // `-` streams the archive; messages go to stderr
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["generate", "--template-dir", "tpl", "--project-name", "acme"])
+        .args(["--output-archive", "-", "--apply"]);
+    let out =
+        cmd.assert().success().stderr(predicate::str::contains("into stdout")).get_output().clone();
+    let files = tar_gz_files(&out.stdout);
+    assert!(files.iter().any(|f| f.0 == "README.md" && f.2 == "# acme\n"));
+    Ok(())
+}
+
+#[test]
+fn dry_run_lists_every_packed_entry() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    template(td.path())?;
+    let generate = || {
+        let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+        cmd.current_dir(td.path())
+            .args(["generate", "--template-dir", "tpl", "--project-name", "acme"])
+            .args(["--output-archive", "acme.tar.gz"]);
+        cmd
+    };
+
+    let out = generate().args(["--format", "json"]).assert().success().get_output().clone();
+    let doc: serde_json::Value = serde_json::from_slice(&out.stdout)?;
+    let mut planned: Vec<String> = doc["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|f| f["path"].as_str().unwrap().to_string())
+        .collect();
+    planned.sort();
+    // the lock and the base snapshot are listed along with the rendered files
+    assert!(planned.contains(&".cosmos/template.lock".to_string()));
+    assert!(planned.contains(&".cosmos/base/README.md".to_string()));
+
+    generate()
+        .arg("--apply")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("Packed {} files", planned.len())));
+    let mut packed: Vec<String> =
+        tar_gz_files(&fs::read(td.path().join("acme.tar.gz"))?).into_iter().map(|f| f.0).collect();
+    packed.sort();
+    assert_eq!(planned, packed);
+    Ok(())
+}
+
+#[test]
+fn dry_run_and_strict_mode_write_no_archive() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    template(td.path())?;
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["generate", "--template-dir", "tpl", "--project-name", "acme"])
+        .args(["--output-archive", "acme.tar.gz"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("scripts/run.sh"))
+        .stdout(predicate::str::contains("Dry run (no archive written)"));
+    assert!(!td.path().join("acme.tar.gz").exists());
+
+    fs::write(td.path().join("tpl/README.md"), "# {{project-name}} by {{author}}\n")?;
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["generate", "--template-dir", "tpl", "--project-name", "acme", "--strict"])
+        .args(["--output-archive", "acme.tar.gz", "--apply"]);
+    cmd.assert().failure().stderr(predicate::str::contains("author"));
+    assert!(!td.path().join("acme.tar.gz").exists());
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args([
+        "generate",
+        "--template-dir",
+        "tpl",
+        "--output-archive",
+        "acme.rar",
+    ]);
+    cmd.assert().failure().stderr(predicate::str::contains("--archive-format"));
+    Ok(())
+}
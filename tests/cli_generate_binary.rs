@@ -0,0 +1,74 @@
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn generate_copies_binary_and_raw_files_verbatim() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    let tpl = td.path().join("tpl");
+    fs::create_dir_all(tpl.join("assets"))?;
+    fs::create_dir_all(tpl.join("data"))?;
+    let png: Vec<u8> = vec![0x89, b'P', b'N', b'G', 0, 0xff, b'{', b'{', b'x', b'}', b'}', 0xfe];
+    fs::write(tpl.join("assets/logo.png"), &png)?;
+    fs::write(tpl.join("data/sample.txt"), "keep {{project-name}} literally\n")?;
+    fs::write(tpl.join("README.md"), "# {{project-name}}\n")?;
+    fs::write(td.path().join("tpl.toml"), "raw = [\"data/*.txt\"]\n\n[categories]\n")?;
+    let out = td.path().join("out");
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["generate", "--template-dir", "tpl", "--project-name", "acme", "--strict"])
+        .args(["--apply", "-o"])
+        .arg(&out);
+    cmd.assert().success();
+    assert_eq!(fs::read(out.join("assets/logo.png"))?, png);
+    assert_eq!(
+        fs::read_to_string(out.join("data/sample.txt"))?,
+        "keep {{project-name}} literally\n"
+    );
+    assert_eq!(fs::read_to_string(out.join("README.md"))?, "# acme\n");
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn generate_preserves_modes_and_symlinks() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    let td = tempdir()?;
+    let tpl = td.path().join("tpl");
+    fs::create_dir_all(tpl.join("scripts"))?;
+    fs::write(tpl.join("scripts/install.sh"), "#!/bin/sh\necho {{project-name}}\n")?;
+    fs::set_permissions(tpl.join("scripts/install.sh"), fs::Permissions::from_mode(0o755))?;
+    fs::write(tpl.join("README.md"), "# {{project-name}}\n")?;
+    symlink("README.md", tpl.join("INDEX.md"))?;
+    fs::write(td.path().join("tpl.toml"), "[categories]\n")?;
+    let out = td.path().join("out");
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["generate", "--template-dir", "tpl", "--project-name", "acme", "--apply", "-o"])
+        .arg(&out);
+    cmd.assert().success();
+    let script = out.join("scripts/install.sh");
+    assert_eq!(fs::metadata(&script)?.permissions().mode() & 0o777, 0o755);
+    assert_eq!(fs::read_to_string(&script)?, "#!/bin/sh\necho acme\n");
+    assert_eq!(fs::read_link(out.join("INDEX.md"))?, std::path::Path::new("README.md"));
+
+    // an unchanged symlink is not reported as modified on the next sync
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["--format", "json", "generate", "--template-dir", "tpl", "--project-name", "acme"])
+        .arg("-o")
+        .arg(&out);
+    let output = cmd.assert().success().get_output().stdout.clone();
+    assert!(!String::from_utf8(output)?.contains("\"path\": \"INDEX.md\""));
+
+    // embedded templates keep the executable bit of hook scripts
+    let out = td.path().join("embedded");
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args(["generate", "--apply", "-o"]).arg(&out);
+    cmd.assert().success();
+    let hook = fs::metadata(out.join(".githooks/pre-commit"))?;
+    assert_eq!(hook.permissions().mode() & 0o111, 0o111);
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use predicates::prelude::*;
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn generate_template_folder_filters_by_category() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    let out = td.path().join("out");
+
+    // dry run reports the categories of each file
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.args(["generate", "--template", "default", "--category", "ci"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(".github/workflows/ci.yml [ci]"))
+        .stdout(predicate::str::contains(" - README.md").not());
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.args(["generate", "--template", "default", "-c", "ci", "-c", "plan", "--apply"])
+        .arg("--out-dir")
+        .arg(&out);
+    cmd.assert().success();
+    assert!(out.join(".github/workflows/ci.yml").exists());
+    assert!(out.join("plan/todo.toml").exists());
+    assert!(!out.join("README.md").exists());
+    assert!(!out.join("docs").exists());
+
+    // files outside the slice still count as extras and need --allow-delete
+    fs::write(out.join("NOTES.md"), "mine")?;
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.args(["generate", "--template", "default", "--category", "ci", "--apply", "--yes"])
+        .arg("--out-dir")
+        .arg(&out);
+    cmd.assert().code(6).stderr(predicate::str::contains("--allow-delete"));
+    assert!(out.join("NOTES.md").exists());
+    assert!(out.join("plan/todo.toml").exists());
+    Ok(())
+}
+
+#[test]
+fn generate_excludes_categories() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    let out = td.path().join("out");
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.args(["generate", "--template", "default", "--exclude-category", "docs,plan", "--apply"])
+        .arg("--out-dir")
+        .arg(&out);
+    cmd.assert().success();
+    assert!(out.join("README.md").exists());
+    assert!(out.join("src").exists());
+    assert!(!out.join("docs").exists());
+    assert!(!out.join("plan").exists());
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.args(["generate", "--template", "default", "--exclude-category", "nope"]);
+    cmd.assert().code(6).stderr(predicate::str::contains("Unknown category 'nope'"));
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use predicates::prelude::*;
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn generate_honors_manifest_markers_and_front_matter() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    let tpl = td.path().join("tpl");
+    fs::create_dir_all(tpl.join("deploy/k8s"))?;
+    fs::write(tpl.join("Dockerfile"), "FROM scratch\n")?;
+    fs::write(tpl.join("deploy/.cosmos-if"), "kind == \"service\"\n")?;
+    fs::write(tpl.join("deploy/k8s/app.yaml"), "name: {{project-name}}\n")?;
+    fs::write(
+        tpl.join("CLI.md"),
+        "{{!-- cosmos-if: kind != service --}}\n# {{project-name}} CLI\n",
+    )?;
+    fs::write(tpl.join("README.md"), "# {{project-name}}\n")?;
+    fs::write(
+        td.path().join("tpl.toml"),
+        r#"[categories]
+
+[variables.kind]
+choices = ["library", "service"]
+default = "library"
+
+[variables.docker]
+type = "bool"
+default = false
+
+[[conditional]]
+paths = ["Dockerfile"]
+when = "docker && kind == service"
+"#,
+    )?;
+
+    let lib = td.path().join("lib");
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["generate", "--template-dir", "tpl", "--project-name", "acme", "--apply", "-o"])
+        .arg(&lib);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Dockerfile (skipped: docker && kind == service)"))
+        .stdout(predicate::str::contains("deploy/k8s/app.yaml (skipped: kind == \"service\")"));
+    assert!(!lib.join("Dockerfile").exists());
+    assert!(!lib.join("deploy").exists());
+    assert_eq!(fs::read_to_string(lib.join("CLI.md"))?, "# acme CLI\n");
+
+    let svc = td.path().join("svc");
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["generate", "--template-dir", "tpl", "--project-name", "acme"])
+        .args(["--var", "kind=service", "--var", "docker=true", "--apply", "-o"])
+        .arg(&svc);
+    cmd.assert().success();
+    assert!(svc.join("Dockerfile").exists());
+    assert_eq!(fs::read_to_string(svc.join("deploy/k8s/app.yaml"))?, "name: acme\n");
+    assert!(!svc.join("deploy/.cosmos-if").exists());
+    assert!(!svc.join("CLI.md").exists());
+    Ok(())
+}
+
+#[test]
+fn default_template_variants_follow_project_type() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+
+    let lib = td.path().join("lib");
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.args(["generate", "--var", "project-type=library", "--apply", "-o"]).arg(&lib);
+    cmd.assert().success();
+    assert!(lib.join("src/lib.rs").exists());
+    assert!(!lib.join("src/main.rs").exists());
+    assert!(!lib.join("Dockerfile").exists());
+    assert!(fs::read_to_string(lib.join("project.toml"))?.contains("type = \"library\""));
+
+    let svc = td.path().join("svc");
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.args(["generate", "--project-name", "api", "--apply", "-o"]).arg(&svc);
+    cmd.assert().success();
+    assert!(svc.join("src/main.rs").exists());
+    assert!(fs::read_to_string(svc.join("Dockerfile"))?.contains("--bin api"));
+    Ok(())
+}
+
+#[test]
+fn a_generated_library_regenerates_without_changes() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    let lib = td.path().join("lib");
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.args(["generate", "--project-name", "acme", "--var", "project-type=library"])
+        .args(["--apply", "-o"])
+        .arg(&lib);
+    cmd.assert().success();
+
+    // no --var: the project type comes from project.toml
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.args(["generate", "--format", "json", "-o"]).arg(&lib);
+    let out = cmd.assert().success().get_output().stdout.clone();
+    let doc: serde_json::Value = serde_json::from_slice(&out)?;
+    assert_eq!(doc["variables"]["project-type"], "library");
+    assert_eq!(doc["files"], serde_json::json!([]), "nothing to add or change");
+    Ok(())
+}
+
+#[test]
+fn malformed_condition_is_a_template_error() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    fs::create_dir_all(td.path().join("tpl"))?;
+    fs::write(td.path().join("tpl/Dockerfile"), "FROM scratch\n")?;
+    fs::write(
+        td.path().join("tpl.toml"),
+        "[categories]\n\n[[conditional]]\npaths = [\"Dockerfile\"]\nwhen = \"docker && (kind)\"\n",
+    )?;
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args([
+        "generate",
+        "--template-dir",
+        "tpl",
+        "--project-name",
+        "acme",
+    ]);
+    cmd.assert()
+        .code(15)
+        .stderr(predicate::str::contains("error[render_failed]"))
+        .stderr(predicate::str::contains("unexpected term '(kind)'"));
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use predicates::prelude::*;
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn diff_compares_rendered_output_with_destination() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    let tpl = td.path().join("tpl");
+    fs::create_dir_all(&tpl)?;
+    fs::write(tpl.join("README.md"), "# {{project-name}}\n\nintro\n")?;
+    fs::write(tpl.join("LICENSE"), "MIT\n")?;
+    fs::write(tpl.join("NEW.md"), "new\n")?;
+    fs::write(td.path().join("tpl.toml"), "[categories]\n")?;
+
+    let out = td.path().join("out");
+    fs::create_dir_all(&out)?;
+    // README is up to date once rendered; LICENSE drifted; NEW.md is missing
+    fs::write(out.join("README.md"), "# acme\n\nintro\n")?;
+    fs::write(out.join("LICENSE"), "Apache-2.0\n")?;
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["generate", "--template-dir", "tpl", "--project-name", "acme"])
+        .args(["--diff", "--diff-stat", "-o"])
+        .arg(&out);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("--- a/LICENSE\n+++ b/LICENSE\n"))
+        .stdout(predicate::str::contains("-Apache-2.0\n+MIT\n"))
+        .stdout(predicate::str::contains("--- /dev/null\n+++ b/NEW.md\n"))
+        .stdout(predicate::str::contains("README.md").count(1))
+        .stdout(predicate::str::contains(" LICENSE | 2 +-\n"))
+        .stdout(predicate::str::contains(" NEW.md  | 1 +\n"))
+        .stdout(predicate::str::contains("2 files changed, 2 insertions(+), 1 deletion(-)"))
+        .stdout(predicate::str::contains("Dry run"));
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["--format", "json", "generate", "--template-dir", "tpl"])
+        .args(["--project-name", "acme", "--diff-stat", "-o"])
+        .arg(&out);
+    let stdout = cmd.assert().success().get_output().stdout.clone();
+    let doc: serde_json::Value = serde_json::from_slice(&stdout)?;
+    let diffs = doc["diffs"].as_array().unwrap();
+    assert_eq!(diffs.len(), 2);
+    assert_eq!(diffs[0]["path"], "LICENSE");
+    assert_eq!(diffs[1]["new"], true);
+    // the rendered README matches, so it is not reported as a pending overwrite
+    let readme = doc["files"].as_array().unwrap().iter().find(|f| f["path"] == "README.md");
+    assert!(readme.is_none(), "{:?}", readme);
+    Ok(())
+}
@@ -0,0 +1,87 @@
+use predicates::prelude::*;
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn child_template_inherits_overrides_and_removes_files() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    let base = td.path().join("base");
+    fs::create_dir_all(base.join("partials/ci"))?;
+    fs::create_dir_all(base.join("docs"))?;
+    fs::write(base.join("partials/license-header.txt"), "// (c) {{author}}\n")?;
+    fs::write(base.join("partials/ci/steps.yml"), "- run: cargo test ${{ matrix.flags }}\n")?;
+    fs::write(base.join("README.md"), "# base\n")?;
+    fs::write(base.join("ci.yml"), "steps:\n{{> ci/steps}}")?;
+    fs::write(base.join("docs/old.md"), "old\n")?;
+    fs::write(base.join("docs/keep.md"), "keep\n")?;
+    fs::write(
+        td.path().join("base.toml"),
+        "version = \"1.4.0\"\n\n[categories]\ndocs = [\"docs/**\"]\n\n[variables.author]\ndefault = \"base\"\n",
+    )?;
+
+    let child = td.path().join("child");
+    fs::create_dir_all(child.join("partials"))?;
+    fs::write(child.join("partials/license-header.txt"), "// (c) {{author}}, child\n")?;
+    fs::write(child.join("README.md"), "# {{project-name}}\n")?;
+    fs::write(child.join("lib.rs"), "{{> license-header}}pub fn f() {}\n")?;
+    fs::write(
+        td.path().join("child.toml"),
+        "extends = \"base\"\nremove = [\"docs/old.md\"]\n\n[variables.author]\ndefault = \"acme\"\n",
+    )?;
+
+    let out = td.path().join("out");
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["generate", "--template-dir", "child", "--project-name", "demo"])
+        .args(["--strict", "--apply", "-o"])
+        .arg(&out);
+    cmd.assert().success().stdout(predicate::str::contains("docs/keep.md [docs]"));
+    assert_eq!(fs::read_to_string(out.join("README.md"))?, "# demo\n");
+    assert_eq!(fs::read_to_string(out.join("lib.rs"))?, "// (c) acme, child\npub fn f() {}\n");
+    assert_eq!(
+        fs::read_to_string(out.join("ci.yml"))?,
+        "steps:\n- run: cargo test ${{ matrix.flags }}\n"
+    );
+    assert!(out.join("docs/keep.md").exists());
+    assert!(!out.join("docs/old.md").exists());
+    assert!(!out.join("partials").exists());
+    // a child without its own version reports the parent's
+    let lock = fs::read_to_string(out.join(".cosmos/template.lock"))?;
+    assert!(lock.contains("version = \"1.4.0\""), "{}", lock);
+    Ok(())
+}
+
+#[test]
+fn template_dir_can_extend_the_embedded_default() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    let child = td.path().join("child");
+    fs::create_dir_all(&child)?;
+    fs::write(child.join("README.md"), "# {{project-name}} (child)\n")?;
+    fs::write(td.path().join("child.toml"), "extends = \"default\"\nremove = [\"docs/**\"]\n")?;
+
+    let out = td.path().join("out");
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["generate", "--template-dir", "child", "--project-name", "demo"])
+        .args(["--var", "year=2026", "--apply", "-o"])
+        .arg(&out);
+    cmd.assert().success();
+    assert_eq!(fs::read_to_string(out.join("README.md"))?, "# demo (child)\n");
+    assert!(out.join(".github/workflows").is_dir());
+    assert!(out.join("Cargo.toml").exists());
+    assert!(!out.join("docs").exists());
+    Ok(())
+}
+
+#[test]
+fn inheritance_cycles_are_rejected() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    for (name, parent) in [("a", "b"), ("b", "a")] {
+        fs::create_dir_all(td.path().join(name))?;
+        fs::write(td.path().join(format!("{}.toml", name)), format!("extends = \"{}\"\n", parent))?;
+    }
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args(["generate", "--template-dir", "a"]);
+    cmd.assert().code(15).stderr(predicate::str::contains("extends itself"));
+    Ok(())
+}
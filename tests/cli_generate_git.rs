@@ -0,0 +1,92 @@
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::tempdir;
+
+/// `tpl` template with a README, a pre-commit hook and a declared `author`.
+fn template(root: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    fs::create_dir_all(root.join("tpl/.githooks"))?;
+    fs::write(root.join("tpl/README.md"), "# {{project-name}}\n")?;
+    fs::write(root.join("tpl/.githooks/pre-commit"), "#!/bin/sh\nexit 1\n")?;
+    fs::write(
+        root.join("tpl.toml"),
+        "[variables.author]\ndefault = \"The {{project-name}} authors\"\n",
+    )?;
+    Ok(())
+}
+
+fn git(dir: &Path, args: &[&str]) -> String {
+    let out = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&out.stderr));
+    String::from_utf8_lossy(&out.stdout).trim().to_string()
+}
+
+#[test]
+fn git_commit_initializes_installs_hooks_and_commits() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    template(td.path())?;
+    let out = td.path().join("out");
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["generate", "--template-dir", "tpl", "--project-name", "acme"])
+        .args(["--var", "author-email=dev@acme.test", "--apply", "--yes"])
+        .args(["--git-commit", "Initial commit", "--git-branch", "trunk", "-o"])
+        .arg(&out);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Initialized a git repository"))
+        .stdout(predicate::str::contains("Installed git hooks: set core.hooksPath to .githooks"));
+
+    assert_eq!(git(&out, &["symbolic-ref", "--short", "HEAD"]), "trunk");
+    assert_eq!(
+        git(&out, &["log", "--format=%an <%ae> %s"]),
+        "The acme authors <dev@acme.test> Initial commit"
+    );
+    assert_eq!(git(&out, &["config", "core.hooksPath"]), ".githooks");
+    assert!(git(&out, &["ls-files"]).contains("README.md"));
+    assert_eq!(git(&out, &["status", "--porcelain"]), "");
+    Ok(())
+}
+
+#[test]
+fn dirty_worktree_is_refused_unless_allowed() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    template(td.path())?;
+    let out = td.path().join("out");
+    fs::create_dir_all(&out)?;
+    fs::write(out.join("README.md"), "# acme\n")?;
+    git(&out, &["init", "--quiet"]);
+    git(&out, &["add", "--all"]);
+    git(&out, &["commit", "--quiet", "-m", "init"]);
+    fs::write(out.join("README.md"), "# acme, edited\n")?;
+
+    let generate = || {
+        let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+        cmd.current_dir(td.path())
+            .args(["generate", "--template-dir", "tpl", "--project-name", "acme"])
+            .args(["--yes", "--force", "-o"])
+            .arg(&out);
+        cmd
+    };
+    // dry runs are fine
+    generate().assert().success();
+    generate()
+        .arg("--apply")
+        .assert()
+        .code(17)
+        .stderr(predicate::str::contains("uncommitted changes"))
+        .stderr(predicate::str::contains("README.md"));
+    assert_eq!(fs::read_to_string(out.join("README.md"))?, "# acme, edited\n");
+
+    generate().args(["--apply", "--allow-dirty"]).assert().success();
+    assert_eq!(fs::read_to_string(out.join("README.md"))?, "# acme\n");
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn helpers_render_in_paths_and_contents() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    let tpl = td.path().join("tpl");
+    fs::create_dir_all(tpl.join("src"))?;
+    fs::write(
+        tpl.join("src/{{snake_case project-name}}.rs"),
+        "pub struct {{pascal_case project-name}};\npub const NAME: &str = \"{{screaming_snake_case project-name}}\";\n",
+    )?;
+    fs::write(
+        tpl.join("NOTICE"),
+        "image: {{kebab_case project-name}}\n(c) {{year}} {{env \"COSMOS_TEST_OWNER\" \"nobody\"}} {{date \"%d.%m.%Y\"}}\n",
+    )?;
+    fs::write(
+        tpl.join("{{kebab-case project-name}}.txt"),
+        "{{PascalCase project-name}} / {{SCREAMING_SNAKE project-name}}\n{{license \"MIT OR Apache-2.0\"}}\n",
+    )?;
+    fs::write(td.path().join("tpl.toml"), "[categories]\n")?;
+
+    let out = td.path().join("out");
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .env("SOURCE_DATE_EPOCH", "1792195200")
+        .env("COSMOS_TEST_OWNER", "Acme Inc")
+        .args(["generate", "--template-dir", "tpl", "--project-name", "Data Loader"])
+        .args(["--strict", "--apply", "-o"])
+        .arg(&out);
+    cmd.assert().success();
+    assert_eq!(
+        fs::read_to_string(out.join("src/data_loader.rs"))?,
+        "pub struct DataLoader;\npub const NAME: &str = \"DATA_LOADER\";\n"
+    );
+    assert_eq!(
+        fs::read_to_string(out.join("NOTICE"))?,
+        "image: data-loader\n(c) 2026 Acme Inc 17.10.2026\n"
+    );
+    assert_eq!(
+        fs::read_to_string(out.join("data-loader.txt"))?,
+        "DataLoader / DATA_LOADER\nSPDX-License-Identifier: MIT OR Apache-2.0\n"
+    );
+    Ok(())
+}
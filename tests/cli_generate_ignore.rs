@@ -0,0 +1,119 @@
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+/// `tpl` template with a README, a config file and ignored drafts.
+fn template(root: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    fs::create_dir_all(root.join("tpl/drafts"))?;
+    fs::write(root.join("tpl/README.md"), "# {{project-name}}\n")?;
+    fs::write(root.join("tpl/config.toml"), "name = \"{{project-name}}\"\n")?;
+    fs::write(root.join("tpl/notes.local"), "scratch\n")?;
+    fs::write(root.join("tpl/drafts/intro.md"), "draft\n")?;
+    fs::write(root.join("tpl/.cosmosignore"), "# work in progress\n*.local\ndrafts/\n")?;
+    Ok(())
+}
+
+fn generate(root: &Path, out: &Path) -> assert_cmd::Command {
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(root)
+        .args(["generate", "--template-dir", "tpl", "--project-name", "acme", "-o"])
+        .arg(out);
+    cmd
+}
+
+#[test]
+fn template_cosmosignore_leaves_files_out() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    template(td.path())?;
+    let out = td.path().join("out");
+
+    generate(td.path(), &out)
+        .args(["--apply", "--yes"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("notes.local (ignored: .cosmosignore:2: *.local)"))
+        .stdout(predicate::str::contains("drafts/intro.md (ignored: .cosmosignore:3: drafts/)"));
+    assert!(out.join("README.md").is_file());
+    assert!(!out.join("notes.local").exists());
+    assert!(!out.join("drafts").exists());
+    assert!(!out.join(".cosmosignore").exists());
+    Ok(())
+}
+
+#[test]
+fn destination_rules_keep_files_even_with_force() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    template(td.path())?;
+    fs::write(td.path().join("tpl.toml"), "protected = [\"secrets/**\"]\n")?;
+    let out = td.path().join("out");
+    fs::create_dir_all(out.join("secrets"))?;
+    fs::write(out.join(".cosmosignore"), "config.toml\n")?;
+    fs::write(out.join("config.toml"), "name = \"customized\"\n")?;
+    fs::write(out.join("secrets/key.pem"), "KEY\n")?;
+    fs::write(out.join("stale.txt"), "old\n")?;
+
+    generate(td.path(), &out)
+        .args(["--apply", "--yes", "--force", "--allow-delete"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("(protected) config.toml [.cosmosignore:1: config.toml]"))
+        .stdout(predicate::str::contains("(protected) secrets/key.pem [protected: secrets/**]"))
+        .stdout(predicate::str::contains("(protected) .cosmosignore"));
+    assert_eq!(fs::read_to_string(out.join("config.toml"))?, "name = \"customized\"\n");
+    assert_eq!(fs::read_to_string(out.join("secrets/key.pem"))?, "KEY\n");
+    assert_eq!(fs::read_to_string(out.join("README.md"))?, "# acme\n");
+    assert!(!out.join("stale.txt").exists());
+
+    // the JSON plan names the rule
+    let output = generate(td.path(), &out).args(["--format", "json"]).output()?;
+    let doc: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    let files = doc["files"].as_array().unwrap();
+    let config = files.iter().find(|e| e["path"] == "config.toml").unwrap();
+    assert_eq!(config["action"], "protected");
+    assert_eq!(config["rule"]["source"], ".cosmosignore:1");
+    Ok(())
+}
+
+#[test]
+fn protected_only_differences_need_no_confirmation() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    template(td.path())?;
+    let out = td.path().join("out");
+    generate(td.path(), &out).args(["--apply", "--yes"]).assert().success();
+
+    fs::write(out.join(".cosmosignore"), "config.toml\n")?;
+    fs::write(out.join("config.toml"), "name = \"customized\"\n")?;
+    // no --yes and nothing on stdin: a prompt would abort
+    generate(td.path(), &out)
+        .arg("--apply")
+        .write_stdin("")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Apply these fixes?").not())
+        .stdout(predicate::str::contains("Template files written to"));
+    assert_eq!(fs::read_to_string(out.join("config.toml"))?, "name = \"customized\"\n");
+    Ok(())
+}
+
+#[test]
+fn update_keeps_protected_files() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    template(td.path())?;
+    let out = td.path().join("out");
+    generate(td.path(), &out).args(["--apply", "--yes"]).assert().success();
+
+    fs::write(out.join(".cosmosignore"), "config.toml\n")?;
+    fs::write(td.path().join("tpl/config.toml"), "name = \"{{project-name}}\"\nedition = 2\n")?;
+    fs::write(td.path().join("tpl/README.md"), "# {{project-name}}\n\nUpdated.\n")?;
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args(["update", "--template-dir", "tpl", "--apply", "-o"]).arg(&out);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("config.toml: protected [.cosmosignore:1: config.toml]"))
+        .stdout(predicate::str::contains("README.md: updated"));
+    assert_eq!(fs::read_to_string(out.join("config.toml"))?, "name = \"acme\"\n");
+    assert_eq!(fs::read_to_string(out.join("README.md"))?, "# acme\n\nUpdated.\n");
+    Ok(())
+}
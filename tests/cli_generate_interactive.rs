@@ -0,0 +1,205 @@
+use predicates::prelude::*;
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn interactive_answers_apply_per_file_and_are_remembered() -> Result<(), Box<dyn std::error::Error>>
+{
+    let td = tempdir()?;
+    let tpl = td.path().join("tpl");
+    fs::create_dir_all(&tpl)?;
+    fs::write(tpl.join("LICENSE"), "MIT\n")?;
+    fs::write(tpl.join("README.md"), "# {{project-name}}\n")?;
+    fs::write(tpl.join("ci.yml"), "steps: [test]\n")?;
+    fs::write(td.path().join("tpl.toml"), "[categories]\n")?;
+
+    let out = td.path().join("out");
+    fs::create_dir_all(&out)?;
+    fs::write(out.join("LICENSE"), "Apache-2.0\n")?;
+    fs::write(out.join("README.md"), "# mine\n")?;
+    fs::write(out.join("ci.yml"), "steps: [lint]\n")?;
+    fs::write(out.join("NOTES.md"), "notes\n")?;
+    fs::write(out.join("old.txt"), "old\n")?;
+
+    let generate = || {
+        let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+        cmd.current_dir(td.path())
+            .env("EDITOR", "true")
+            .args(["generate", "--template-dir", "tpl", "--project-name", "acme"])
+            .args(["--apply", "--allow-delete", "--interactive", "-o"])
+            .arg(&out);
+        cmd
+    };
+
+    // LICENSE: diff then overwrite; README: keep; ci.yml: side-by-side;
+    // NOTES.md: keep; old.txt: delete
+    generate()
+        .write_stdin("d\no\nk\nn\nk\nd\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("-Apache-2.0\n+MIT\n"));
+    assert_eq!(fs::read_to_string(out.join("LICENSE"))?, "MIT\n");
+    assert_eq!(fs::read_to_string(out.join("README.md"))?, "# mine\n");
+    assert_eq!(fs::read_to_string(out.join("ci.yml"))?, "steps: [lint]\n");
+    assert_eq!(fs::read_to_string(out.join("ci.yml.cosmos-new"))?, "steps: [test]\n");
+    assert!(out.join("NOTES.md").exists());
+    assert!(!out.join("old.txt").exists());
+
+    let lock = fs::read_to_string(out.join(".cosmos/template.lock"))?;
+    let lock: toml::Value = toml::from_str(&lock)?;
+    assert_eq!(lock["resolutions"]["README.md"]["choice"].as_str(), Some("keep"));
+    assert_eq!(lock["resolutions"]["ci.yml"]["choice"].as_str(), Some("side_by_side"));
+    assert_eq!(lock["resolutions"]["NOTES.md"]["choice"].as_str(), Some("keep"));
+
+    // nothing left to ask: remembered answers are reused without reading stdin
+    generate()
+        .write_stdin("")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("README.md: keep (remembered)"))
+        .stdout(predicate::str::contains("NOTES.md: keep (remembered)"));
+
+    // a changed template output invalidates the remembered answer
+    fs::write(tpl.join("README.md"), "# {{project-name}}\n\nnew intro\n")?;
+    generate().write_stdin("q\n").assert().success().stdout(predicate::str::contains("Aborted"));
+    assert_eq!(fs::read_to_string(out.join("README.md"))?, "# mine\n");
+    Ok(())
+}
+
+#[test]
+fn side_by_side_copies_of_nested_files_are_written() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    let tpl = td.path().join("tpl");
+    fs::create_dir_all(tpl.join(".github/workflows"))?;
+    fs::write(tpl.join(".github/workflows/ci.yml"), "steps: [test]\n")?;
+    fs::write(td.path().join("tpl.toml"), "[categories]\n")?;
+
+    let out = td.path().join("out");
+    fs::create_dir_all(out.join(".github/workflows"))?;
+    fs::write(out.join(".github/workflows/ci.yml"), "steps: [lint]\n")?;
+
+    assert_cmd::cargo::cargo_bin_cmd!("cosmos")
+        .current_dir(td.path())
+        .args(["generate", "--template-dir", "tpl", "--project-name", "acme"])
+        .args(["--apply", "--interactive", "-o"])
+        .arg(&out)
+        .write_stdin("n\n")
+        .assert()
+        .success();
+    let workflows = out.join(".github/workflows");
+    assert_eq!(fs::read_to_string(workflows.join("ci.yml"))?, "steps: [lint]\n");
+    assert_eq!(fs::read_to_string(workflows.join("ci.yml.cosmos-new"))?, "steps: [test]\n");
+    Ok(())
+}
+
+#[test]
+fn interactive_merge_runs_the_editor_on_merged_file() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    let tpl = td.path().join("tpl");
+    fs::create_dir_all(&tpl)?;
+    fs::write(tpl.join("notes.txt"), "a\nb\nc\n")?;
+    fs::write(td.path().join("tpl.toml"), "[categories]\n")?;
+
+    let out = td.path().join("out");
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["generate", "--template-dir", "tpl", "--apply", "-o"])
+        .arg(&out);
+    cmd.assert().success();
+
+    // local edit at the top, template edit at the bottom
+    fs::write(out.join("notes.txt"), "local\nb\nc\n")?;
+    fs::write(tpl.join("notes.txt"), "a\nb\nupstream\n")?;
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .env_remove("VISUAL")
+        .env("EDITOR", "true")
+        .args(["generate", "--template-dir", "tpl", "--apply", "-i", "-o"])
+        .arg(&out)
+        .write_stdin("m\n");
+    cmd.assert().success().stdout(predicate::str::contains("Backed up 1 replaced"));
+    assert_eq!(fs::read_to_string(out.join("notes.txt"))?, "local\nb\nupstream\n");
+    Ok(())
+}
+
+#[test]
+fn interactive_merge_is_applied_only_when_the_run_completes(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    let tpl = td.path().join("tpl");
+    fs::create_dir_all(&tpl)?;
+    fs::write(tpl.join("a.txt"), "a\nb\nc\n")?;
+    fs::write(tpl.join("b.txt"), "one\n")?;
+    fs::write(td.path().join("tpl.toml"), "[categories]\n")?;
+
+    let out = td.path().join("out");
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["generate", "--template-dir", "tpl", "--apply", "-o"])
+        .arg(&out);
+    cmd.assert().success();
+
+    fs::write(out.join("a.txt"), "local\nb\nc\n")?;
+    fs::write(out.join("b.txt"), "mine\n")?;
+    fs::write(tpl.join("a.txt"), "a\nb\nupstream\n")?;
+    let generate = |editor: &str| {
+        let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+        cmd.current_dir(td.path())
+            .env_remove("VISUAL")
+            .env("EDITOR", editor)
+            .args(["generate", "--template-dir", "tpl", "--apply", "-i", "-o"])
+            .arg(&out);
+        cmd
+    };
+
+    // quitting on a later file drops the merge
+    generate("true")
+        .write_stdin("m\nq\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Aborted by user; no changes applied."));
+    assert_eq!(fs::read_to_string(out.join("a.txt"))?, "local\nb\nc\n");
+
+    // a failing editor leaves the file as it was
+    generate("false").write_stdin("m\n").assert().failure();
+    assert_eq!(fs::read_to_string(out.join("a.txt"))?, "local\nb\nc\n");
+    Ok(())
+}
+
+#[test]
+fn update_honors_remembered_keep_and_side_by_side() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    let tpl = td.path().join("tpl");
+    fs::create_dir_all(&tpl)?;
+    fs::write(tpl.join("README.md"), "# {{project-name}}\n")?;
+    fs::write(tpl.join("ci.yml"), "steps: [test]\n")?;
+    fs::write(td.path().join("tpl.toml"), "[categories]\n")?;
+
+    let out = td.path().join("out");
+    fs::create_dir_all(&out)?;
+    fs::write(out.join("README.md"), "# mine\n")?;
+    fs::write(out.join("ci.yml"), "steps: [lint]\n")?;
+
+    // README.md: keep; ci.yml: side-by-side
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["generate", "--template-dir", "tpl", "--project-name", "acme"])
+        .args(["--apply", "--interactive", "-o"])
+        .arg(&out)
+        .write_stdin("k\nn\n")
+        .assert()
+        .success();
+    fs::remove_file(out.join("ci.yml.cosmos-new"))?;
+
+    fs::write(tpl.join("README.md"), "# {{project-name}}\n\nUpdated.\n")?;
+    fs::write(tpl.join("ci.yml"), "steps: [test, doc]\n")?;
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args(["update", "--apply", "-o"]).arg(&out);
+    cmd.assert().success().stdout(predicate::str::contains("README.md: kept (remembered)")).stdout(
+        predicate::str::contains("ci.yml: side_by_side (see ci.yml.cosmos-new, remembered)"),
+    );
+    assert_eq!(fs::read_to_string(out.join("README.md"))?, "# mine\n");
+    assert_eq!(fs::read_to_string(out.join("ci.yml"))?, "steps: [lint]\n");
+    assert_eq!(fs::read_to_string(out.join("ci.yml.cosmos-new"))?, "steps: [test, doc]\n");
+    Ok(())
+}
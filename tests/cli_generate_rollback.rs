@@ -0,0 +1,102 @@
+use predicates::prelude::*;
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn apply_backs_up_replaced_files_and_rollback_restores_them(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    let tpl = td.path().join("tpl");
+    fs::create_dir_all(&tpl)?;
+    fs::write(tpl.join("LICENSE"), "MIT\n")?;
+    fs::write(tpl.join("README.md"), "# {{project-name}}\n")?;
+    fs::write(td.path().join("tpl.toml"), "[categories]\n")?;
+
+    let out = td.path().join("out");
+    fs::create_dir_all(&out)?;
+    fs::write(out.join("LICENSE"), "Apache-2.0\n")?;
+    fs::write(out.join("extra.txt"), "mine\n")?;
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["generate", "--template-dir", "tpl", "--project-name", "acme"])
+        .args(["--apply", "--yes", "--force", "--allow-delete", "-o"])
+        .arg(&out);
+    cmd.assert().success().stdout(predicate::str::contains(
+        "Backed up 2 replaced/deleted file(s) to .cosmos/backup/",
+    ));
+    assert_eq!(fs::read_to_string(out.join("LICENSE"))?, "MIT\n");
+    assert!(!out.join("extra.txt").exists());
+    // staging happens next to the destination and is cleaned up
+    let leftovers: Vec<_> = fs::read_dir(td.path())?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name().to_string_lossy().starts_with(".cosmos-staging"))
+        .collect();
+    assert!(leftovers.is_empty());
+
+    let backups: Vec<_> = fs::read_dir(out.join(".cosmos/backup"))?.collect::<Result<_, _>>()?;
+    assert_eq!(backups.len(), 1);
+    let backup = backups[0].path();
+    assert_eq!(fs::read_to_string(backup.join("LICENSE"))?, "Apache-2.0\n");
+    assert_eq!(fs::read_to_string(backup.join("extra.txt"))?, "mine\n");
+
+    let id = backups[0].file_name().to_string_lossy().into_owned();
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args(["generate", "--rollback", &id, "-o"]).arg(&out);
+    cmd.assert().success().stdout(predicate::str::contains(format!("Rolled back {}", id)));
+    assert_eq!(fs::read_to_string(out.join("LICENSE"))?, "Apache-2.0\n");
+    assert_eq!(fs::read_to_string(out.join("extra.txt"))?, "mine\n");
+    assert!(!out.join("README.md").exists());
+    assert!(!out.join(".cosmos/template.lock").exists());
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args(["generate", "--rollback", "nope", "-o"]).arg(&out);
+    cmd.assert().code(7).stderr(predicate::str::contains(id));
+    Ok(())
+}
+
+#[test]
+fn failed_commit_leaves_destination_untouched() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    let tpl = td.path().join("tpl");
+    fs::create_dir_all(&tpl)?;
+    fs::write(tpl.join("LICENSE"), "MIT\n")?;
+    fs::write(tpl.join("README.md"), "# new\n")?;
+    fs::create_dir_all(tpl.join("zz"))?;
+    fs::write(tpl.join("zz/new.txt"), "needs a folder where the project has a file\n")?;
+    fs::write(td.path().join("tpl.toml"), "[categories]\n")?;
+
+    let out = td.path().join("out");
+    fs::create_dir_all(&out)?;
+    fs::write(out.join("zz"), "keep\n")?;
+    fs::write(out.join("LICENSE"), "Apache-2.0\n")?;
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args([
+            "generate",
+            "--template-dir",
+            "tpl",
+            "--apply",
+            "--yes",
+            "--force",
+            "--allow-delete",
+            "-o",
+        ])
+        .arg(&out);
+    cmd.assert().failure().stderr(predicate::str::contains("restored the previous files"));
+    assert_eq!(fs::read_to_string(out.join("LICENSE"))?, "Apache-2.0\n");
+    assert!(!out.join("README.md").exists());
+    assert!(!out.join(".cosmos/template.lock").exists());
+    assert_eq!(fs::read_to_string(out.join("zz"))?, "keep\n");
+    // the directory-over-file case is refused before anything is backed up
+    fs::remove_file(out.join("zz"))?;
+    fs::create_dir_all(out.join("README.md"))?;
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["generate", "--template-dir", "tpl", "--apply", "--yes", "--force", "-o"])
+        .arg(&out);
+    cmd.assert().failure().stderr(predicate::str::contains("cannot replace directory"));
+    assert_eq!(fs::read_to_string(out.join("LICENSE"))?, "Apache-2.0\n");
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use predicates::prelude::*;
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn strict_generate_reports_render_errors_before_writing() -> Result<(), Box<dyn std::error::Error>>
+{
+    let td = tempdir()?;
+    let tpl = td.path().join("tpl");
+    fs::create_dir_all(tpl.join(".github/workflows"))?;
+    fs::write(tpl.join("README.md"), "# {{project-nmae}}\n")?;
+    fs::write(tpl.join("docs.md"), "intro\n{{#if project-name}}\nunclosed\n")?;
+    fs::write(
+        tpl.join(".github/workflows/ci.yml"),
+        "name: {{project-name}}\ntoken: ${{ secrets.GITHUB_TOKEN }}\n",
+    )?;
+    fs::write(td.path().join("tpl.toml"), "[categories]\n")?;
+    let out = td.path().join("out");
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["generate", "--template-dir", "tpl", "--project-name", "acme", "--strict"])
+        .args(["--apply", "-o"])
+        .arg(&out);
+    cmd.assert()
+        .code(15)
+        .stderr(predicate::str::contains("2 template render error(s)"))
+        .stderr(predicate::str::contains("README.md:1:3: Variable \"project-nmae\" not found"))
+        .stderr(predicate::str::contains("docs.md:4:1"));
+    assert!(!out.exists());
+
+    // non-strict keeps the lenient behaviour
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["generate", "--template-dir", "tpl", "--project-name", "acme", "--apply", "-o"])
+        .arg(&out);
+    cmd.assert().success();
+    assert_eq!(fs::read_to_string(out.join("README.md"))?, "# \n");
+
+    // GitHub expressions are left alone in both modes
+    fs::write(tpl.join("README.md"), "# {{project-name}}\n")?;
+    fs::remove_file(tpl.join("docs.md"))?;
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["generate", "--template-dir", "tpl", "--project-name", "acme", "--strict"])
+        .args(["--apply", "--yes", "--allow-delete", "--force", "-o"])
+        .arg(&out);
+    cmd.assert().success();
+    assert_eq!(
+        fs::read_to_string(out.join(".github/workflows/ci.yml"))?,
+        "name: acme\ntoken: ${{ secrets.GITHUB_TOKEN }}\n"
+    );
+    Ok(())
+}
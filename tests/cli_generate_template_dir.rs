@@ -0,0 +1,101 @@
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+fn write_template(root: &Path, name: &str, marker: &str) -> std::io::Result<()> {
+    let dir = root.join(name);
+    fs::create_dir_all(dir.join("docs"))?;
+    fs::write(dir.join("README.md"), format!("# {{{{project-name}}}} from {}\n", marker))?;
+    fs::write(dir.join("docs/guide.md"), "guide\n")?;
+    fs::write(root.join(format!("{}.toml", name)), "[categories]\nbasis = [\"README.md\"]\n")?;
+    Ok(())
+}
+
+#[test]
+fn generate_uses_explicit_template_dir() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    let company = td.path().join("company-templates");
+    write_template(&company, "service", "company")?;
+    let out = td.path().join("out");
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["generate", "--template", "service", "--template-dir"])
+        .arg(company.join("service"))
+        .args(["--apply", "--project-name", "acme", "--out-dir"])
+        .arg(&out);
+    cmd.assert().success().stdout(predicate::str::contains(format!(
+        "Template 'service' matched 2 files ({}):",
+        company.join("service").display()
+    )));
+    assert_eq!(fs::read_to_string(out.join("README.md"))?, "# acme from company\n");
+    assert!(out.join("docs/guide.md").exists());
+
+    // the sibling manifest drives category validation
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["generate", "--template", "service", "--category", "ci", "--template-dir"])
+        .arg(company.join("service"));
+    cmd.assert().code(6).stderr(predicate::str::contains("Unknown category 'ci'"));
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args(["generate", "--template-dir", "missing"]);
+    cmd.assert().code(7).stderr(predicate::str::contains("template directory not found"));
+    Ok(())
+}
+
+#[test]
+fn generate_searches_cosmos_template_path_roots_in_order() -> Result<(), Box<dyn std::error::Error>>
+{
+    let td = tempdir()?;
+    let first = td.path().join("first");
+    let second = td.path().join("second");
+    fs::create_dir_all(&first)?;
+    write_template(&second, "service", "second")?;
+    write_template(&td.path().join("third"), "service", "third")?;
+    let search = std::env::join_paths([&first, &second, &td.path().join("third")])?;
+    let out = td.path().join("out");
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .env("COSMOS_TEMPLATE_PATH", &search)
+        .args(["--format", "json", "generate", "--template", "service", "--apply", "--out-dir"])
+        .arg(&out);
+    cmd.assert().success().stdout(predicate::str::contains("\"source\": \"search_path\""));
+    assert_eq!(fs::read_to_string(out.join("README.md"))?, "#  from second\n");
+    Ok(())
+}
+
+#[test]
+fn cosmos_template_path_wins_over_repository_templates() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    write_template(&td.path().join("templates"), "service", "repo")?;
+    let company = td.path().join("company");
+    write_template(&company, "service", "company")?;
+    let out = td.path().join("out");
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .env("COSMOS_TEMPLATE_PATH", &company)
+        .args(["--format", "json", "generate", "--template", "service", "--apply", "--out-dir"])
+        .arg(&out);
+    let stdout = cmd.assert().success().get_output().stdout.clone();
+    let doc: serde_json::Value = serde_json::from_slice(&stdout)?;
+    assert_eq!(doc["source"], "search_path");
+    assert_eq!(doc["template_path"], company.join("service").display().to_string());
+    assert_eq!(fs::read_to_string(out.join("README.md"))?, "#  from company\n");
+
+    // without the search path the checkout's own template is used
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).env_remove("COSMOS_TEMPLATE_PATH").args([
+        "generate",
+        "--template",
+        "service",
+    ]);
+    cmd.assert().success().stdout(predicate::str::contains(format!(
+        "Template 'service' matched 2 files ({}):",
+        td.path().join("templates/service").display()
+    )));
+    Ok(())
+}
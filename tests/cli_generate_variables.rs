@@ -0,0 +1,94 @@
+use predicates::prelude::*;
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn generate_resolves_declared_variables() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    let tpl = td.path().join("tpl");
+    fs::create_dir_all(&tpl)?;
+    fs::write(tpl.join("README.md"), "{{project-name}} ships {{bin-name}} ({{edition}})\n")?;
+    fs::write(
+        td.path().join("tpl.toml"),
+        r#"[categories]
+basis = ["README.md"]
+
+[variables.project-name]
+description = "Crate name"
+regex = "[a-z][a-z0-9-]*"
+
+[variables.bin-name]
+default = "{{project-name}}-cli"
+
+[variables.edition]
+choices = ["2018", "2021"]
+default = "2021"
+"#,
+    )?;
+    let out = td.path().join("out");
+
+    // required variable missing in non-interactive mode
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["generate", "--template-dir", "tpl", "--apply", "-o"])
+        .arg(&out);
+    cmd.assert()
+        .code(6)
+        .stderr(predicate::str::contains("missing required template variables: project-name"));
+    assert!(!out.exists());
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args([
+        "generate",
+        "--template-dir",
+        "tpl",
+        "--project-name",
+        "Bad Name",
+    ]);
+    cmd.assert().code(6).stderr(predicate::str::contains("invalid value 'Bad Name'"));
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args(["generate", "--template-dir", "tpl", "--var", "edition=2015"]);
+    cmd.assert().code(6).stderr(predicate::str::contains("expected one of 2018, 2021"));
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["generate", "--template-dir", "tpl", "--project-name", "acme", "--apply", "-o"])
+        .arg(&out);
+    cmd.assert().success();
+    assert_eq!(fs::read_to_string(out.join("README.md"))?, "acme ships acme-cli (2021)\n");
+    Ok(())
+}
+
+#[test]
+fn default_template_fills_bin_name_and_author() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    let out = td.path().join("out");
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.args(["--format", "json", "generate", "--project-name", "acme", "--apply", "-o"]).arg(&out);
+    cmd.assert().success().stdout(predicate::str::contains("\"bin-name\": \"acme\""));
+    let project = fs::read_to_string(out.join("project.toml"))?;
+    assert!(project.contains("bins = [\"acme\"]"), "{}", project);
+    assert!(fs::read_to_string(out.join("LICENSE"))?.contains("The acme authors"));
+    Ok(())
+}
+
+#[test]
+fn variables_are_rendered_without_html_escaping() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    let out = td.path().join("out");
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["generate", "--project-name", "acme", "--apply", "--yes"])
+        .args(["--var", "author=O'Brien & Co <dev@acme.test>"])
+        .args(["--var", "repository=https://example.com/acme?tab=code&lang=en", "-o"])
+        .arg(&out);
+    cmd.assert().success();
+    let license = fs::read_to_string(out.join("LICENSE"))?;
+    assert!(license.contains("O'Brien & Co <dev@acme.test>"), "{}", license);
+    let project = fs::read_to_string(out.join("project.toml"))?;
+    assert!(project.contains("https://example.com/acme?tab=code&lang=en"), "{}", project);
+    Ok(())
+}
@@ -0,0 +1,125 @@
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+const MANIFEST: &str = r#"
+[variables.project-name]
+default = "fallback"
+
+[variables.author]
+default = "nobody"
+
+[variables.license]
+default = "MIT"
+
+[variables.version]
+default = "0.1.0"
+"#;
+
+fn template(root: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let tpl = root.join("tpl");
+    fs::create_dir_all(&tpl)?;
+    fs::write(tpl.join("README.md"), "{{project-name}} {{version}} by {{author}} ({{license}})\n")?;
+    fs::write(
+        tpl.join("Cargo.toml"),
+        "[package]\nname = \"{{project-name}}\"\nversion = \"{{version}}\"\n",
+    )?;
+    fs::write(tpl.join("project.toml"), "[project]\nlicense = \"{{license}}\"\n")?;
+    fs::write(root.join("tpl.toml"), MANIFEST)?;
+    Ok(())
+}
+
+#[test]
+fn var_beats_env_beats_vars_file() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    template(td.path())?;
+    fs::write(
+        td.path().join("vars.toml"),
+        "project-name = \"from-file\"\nauthor = \"File\"\nlicense = \"Apache-2.0\"\nversion = \"1.0.0\"\n",
+    )?;
+    fs::write(td.path().join("more.yaml"), "# later files win\nversion: \"2.0.0\"\n")?;
+
+    let out = td.path().join("out");
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .env("COSMOS_VAR_AUTHOR", "Env")
+        .env("COSMOS_VAR_PROJECT_NAME", "from-env")
+        .args(["generate", "--template-dir", "tpl", "--apply", "--yes"])
+        .args(["--vars-file", "vars.toml", "--vars-file", "more.yaml"])
+        .args(["--var", "project-name=from-cli", "-o"])
+        .arg(&out);
+    cmd.assert().success();
+    assert_eq!(fs::read_to_string(out.join("README.md"))?, "from-cli 2.0.0 by Env (Apache-2.0)\n");
+    Ok(())
+}
+
+#[test]
+fn regenerating_reads_defaults_from_the_existing_project() -> Result<(), Box<dyn std::error::Error>>
+{
+    let td = tempdir()?;
+    template(td.path())?;
+    let out = td.path().join("out");
+    fs::create_dir_all(&out)?;
+    fs::write(
+        out.join("Cargo.toml"),
+        "[package]\nname = \"acme\"\nversion = \"3.1.0\"\nlicense = \"MIT\"\n",
+    )?;
+    fs::write(out.join("project.toml"), "[project]\nlicense = \"BSD-3-Clause\"\n")?;
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["generate", "--template-dir", "tpl", "--apply", "--yes", "-o"])
+        .arg(&out);
+    cmd.assert().success();
+    assert_eq!(fs::read_to_string(out.join("README.md"))?, "acme 3.1.0 by nobody (BSD-3-Clause)\n");
+    Ok(())
+}
+
+#[test]
+fn regenerating_a_non_default_project_needs_no_arguments() -> Result<(), Box<dyn std::error::Error>>
+{
+    let td = tempdir()?;
+    let out = td.path().join("app");
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.args(["generate", "--project-name", "tool", "--var", "project-type=application"])
+        .args(["--var", "docker=true", "--var", "author=Jane Doe", "--var", "license=Apache-2.0"])
+        .args(["--apply", "-o"])
+        .arg(&out);
+    cmd.assert().success();
+    assert!(out.join("Dockerfile").exists());
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.args(["generate", "--format", "json", "-o"]).arg(&out);
+    let stdout = cmd.assert().success().get_output().stdout.clone();
+    let doc: serde_json::Value = serde_json::from_slice(&stdout)?;
+    let vars = &doc["variables"];
+    assert_eq!(vars["project-type"], "application");
+    assert_eq!(vars["docker"], true);
+    assert_eq!(vars["author"], "Jane Doe");
+    assert_eq!(vars["license"], "Apache-2.0");
+    assert_eq!(doc["files"], serde_json::json!([]), "nothing to add or change");
+    Ok(())
+}
+
+#[test]
+fn json_vars_file_values_are_validated() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    template(td.path())?;
+    fs::write(
+        td.path().join("tpl.toml"),
+        format!("{}\n[variables.docker]\ntype = \"bool\"\ndefault = \"false\"\n", MANIFEST),
+    )?;
+    fs::write(td.path().join("vars.json"), r#"{"docker": "maybe"}"#)?;
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args(["generate", "--template-dir", "tpl"]);
+    cmd.args(["--vars-file", "vars.json"]);
+    cmd.assert().failure().stderr(predicates::str::contains("docker"));
+
+    fs::write(td.path().join("vars.json"), r#"{"docker": true}"#)?;
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args(["generate", "--template-dir", "tpl"]);
+    cmd.args(["--vars-file", "vars.json"]);
+    cmd.assert().success();
+    Ok(())
+}
@@ -0,0 +1,93 @@
+#![cfg(unix)]
+
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+/// `tpl` template whose manifest declares shell verification steps.
+fn template(root: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    fs::create_dir_all(root.join("tpl"))?;
+    fs::write(root.join("tpl/README.md"), "# {{project-name}}\n")?;
+    fs::write(
+        root.join("tpl.toml"),
+        r#"
+[verify]
+steps = [
+  { name = "readme", command = ["grep", "-q", "acme", "README.md"] },
+  { name = "offline", command = "test \"$CARGO_NET_OFFLINE\" = true", env = { LANG = "C" } },
+  { name = "lint", command = "echo 'style <warning>' && exit 1", allow_failure = true },
+]
+"#,
+    )?;
+    Ok(())
+}
+
+fn generate(root: &Path, out: &Path) -> assert_cmd::Command {
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(root)
+        .args(["generate", "--template-dir", "tpl", "--project-name", "acme"])
+        .args(["--apply", "--yes", "--verify", "-o"])
+        .arg(out);
+    cmd
+}
+
+#[test]
+fn manifest_steps_run_and_write_reports() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    template(td.path())?;
+    let out = td.path().join("out");
+
+    generate(td.path(), &out)
+        .arg("--offline")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Running 3 verification steps from the template manifest"))
+        .stdout(predicate::str::contains(" - readme: OK"))
+        .stdout(predicate::str::contains(" - offline: OK"))
+        .stdout(predicate::str::contains(" - lint: FAILED (allowed)"))
+        .stdout(predicate::str::contains("   | style <warning>"))
+        .stdout(predicate::str::contains("Verification checks passed"));
+
+    let report: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(out.join(".cosmos/reports/verify.json"))?)?;
+    assert_eq!(report["passed"], true);
+    assert_eq!(report["steps"].as_array().unwrap().len(), 3);
+    assert_eq!(report["steps"][2]["allow_failure"], true);
+    let junit = fs::read_to_string(out.join(".cosmos/reports/verify.xml"))?;
+    assert!(junit.contains("tests=\"3\" failures=\"0\" skipped=\"1\""), "{}", junit);
+
+    // without --offline the step sees no CARGO_NET_OFFLINE and fails
+    generate(td.path(), &out)
+        .env_remove("CARGO_NET_OFFLINE")
+        .assert()
+        .code(4)
+        .stdout(predicate::str::contains(" - offline: FAILED"));
+    let junit = fs::read_to_string(out.join(".cosmos/reports/verify.xml"))?;
+    assert!(junit.contains("failures=\"1\""), "{}", junit);
+    Ok(())
+}
+
+#[test]
+fn project_toml_steps_override_the_manifest() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    template(td.path())?;
+    fs::write(
+        td.path().join("tpl/project.toml"),
+        "[project]\nname = \"{{project-name}}\"\n\n[verify]\nsteps = [{ name = \"slow\", command = \"sleep 5\", timeout = 1 }]\n",
+    )?;
+    let out = td.path().join("out");
+
+    generate(td.path(), &out)
+        .assert()
+        .code(4)
+        .stdout(predicate::str::contains("Running 1 verification steps from project.toml"))
+        .stdout(predicate::str::contains(" - slow: TIMED OUT"));
+    let report = fs::read_to_string(out.join(".cosmos/reports/verify.json"))?;
+    assert!(report.contains("\"timed_out\": true"));
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args(["generate", "--template-dir", "tpl", "--offline"]);
+    cmd.assert().failure().stderr(predicate::str::contains("--verify"));
+    Ok(())
+}
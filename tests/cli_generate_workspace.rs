@@ -0,0 +1,104 @@
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+/// `ws` template with a root manifest and one `krate` member per `crates` entry.
+fn workspace_template(root: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    fs::create_dir_all(root.join("ws"))?;
+    fs::write(root.join("ws/Cargo.toml"), "[workspace]\nresolver = \"2\"\nmembers = []\n")?;
+    fs::write(root.join("ws/README.md"), "# {{project-name}}\n")?;
+    fs::write(
+        root.join("ws.toml"),
+        r#"
+[categories]
+crates = ["crates/**"]
+
+[variables.crates]
+default = "core,cli"
+
+[[members]]
+template = "krate"
+path = "crates/{{name}}"
+each = "crates"
+
+[[members]]
+template = "krate"
+path = "tools/xtask"
+vars = { name = "xtask" }
+"#,
+    )?;
+    fs::create_dir_all(root.join("krate/src"))?;
+    fs::write(
+        root.join("krate/Cargo.toml"),
+        "[package]\nname = \"{{crate-name}}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+    )?;
+    fs::write(root.join("krate/src/lib.rs"), "//! {{name}} of {{project-name}}\n")?;
+    fs::write(
+        root.join("krate.toml"),
+        "[variables.crate-name]\ndefault = \"{{project-name}}-{{name}}\"\n",
+    )?;
+    Ok(())
+}
+
+#[test]
+fn members_are_rendered_and_listed_in_the_workspace() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    workspace_template(td.path())?;
+    let out = td.path().join("out");
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["generate", "--template-dir", "ws", "--project-name", "acme"])
+        .args(["--apply", "--yes", "-o"])
+        .arg(&out);
+    cmd.assert().success().stdout(predicate::str::contains("crates/core/src/lib.rs [crates]"));
+    assert_eq!(
+        fs::read_to_string(out.join("Cargo.toml"))?,
+        "[workspace]\nresolver = \"2\"\nmembers = [\n    \"crates/core\",\n    \"crates/cli\",\n    \"tools/xtask\",\n]\n"
+    );
+    assert!(fs::read_to_string(out.join("crates/cli/Cargo.toml"))?.contains("name = \"acme-cli\""));
+    assert_eq!(fs::read_to_string(out.join("tools/xtask/src/lib.rs"))?, "//! xtask of acme\n");
+
+    // a new member is added to the existing (protected) root manifest
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["generate", "--template-dir", "ws", "--project-name", "acme"])
+        .args(["--var", "crates=core,cli,server", "--apply", "--yes", "-o"])
+        .arg(&out);
+    cmd.assert().success().stdout(predicate::str::contains(
+        "Added the new workspace members to the existing Cargo.toml",
+    ));
+    let cargo = fs::read_to_string(out.join("Cargo.toml"))?;
+    assert!(cargo.contains("    \"tools/xtask\",\n    \"crates/server\",\n]"), "{}", cargo);
+    assert!(out.join("crates/server/src/lib.rs").is_file());
+    Ok(())
+}
+
+#[test]
+fn category_selection_applies_to_member_files() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    workspace_template(td.path())?;
+    let out = td.path().join("out");
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path())
+        .args(["generate", "--template-dir", "ws", "--project-name", "acme"])
+        .args(["--exclude-category", "crates", "--apply", "--yes", "-o"])
+        .arg(&out);
+    cmd.assert().success();
+    assert!(out.join("README.md").is_file());
+    assert!(!out.join("crates").exists());
+    assert!(out.join("tools/xtask/src/lib.rs").is_file());
+    assert!(fs::read_to_string(out.join("Cargo.toml"))?
+        .ends_with("members = [\n    \"tools/xtask\",\n]\n"));
+    Ok(())
+}
+
+#[test]
+fn workspace_template_checks_with_its_members() {
+    let td = tempdir().unwrap();
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args(["template", "check", "workspace"]);
+    cmd.assert().success().stdout(predicate::str::contains("Template check passed"));
+}
@@ -0,0 +1,39 @@
+use predicates::prelude::*;
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn plan_list_discovers_repo_root_from_subdir_and_honors_overrides(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    let repo = td.path().join("repo");
+    fs::create_dir_all(repo.join("plan/tasks"))?;
+    fs::create_dir_all(repo.join("src/deep"))?;
+    fs::write(
+        repo.join("plan/todo.toml"),
+        "[[task]]\nid = \"0001\"\ntitle = \"Found me\"\nstatus = \"queued\"\n",
+    )?;
+
+    // from a nested directory
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(repo.join("src/deep")).args(["plan", "list"]);
+    cmd.assert().success().stdout(predicate::str::contains("Found me"));
+
+    // explicit --repo from outside the checkout
+    let other = td.path().join("elsewhere");
+    fs::create_dir_all(&other)?;
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(&other).arg("--repo").arg(&repo).args(["plan", "list"]);
+    cmd.assert().success().stdout(predicate::str::contains("Tasks (1)"));
+
+    // COSMOS_REPO environment override
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(&other).env("COSMOS_REPO", &repo).args(["plan", "list"]);
+    cmd.assert().success().stdout(predicate::str::contains("Found me"));
+
+    // a missing --repo directory is reported as not found
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(&other).args(["--repo", "nope", "plan", "list"]);
+    cmd.assert().code(7);
+    Ok(())
+}
@@ -0,0 +1,131 @@
+use predicates::prelude::*;
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn template_list_shows_sources_and_shadowing() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    let root = td.path().join("tpls");
+    fs::create_dir_all(root.join("default"))?;
+    fs::create_dir_all(root.join("svc"))?;
+    fs::write(root.join("svc.toml"), "version = \"2.1.0\"\ndescription = \"Web service\"\n")?;
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).env("COSMOS_TEMPLATE_PATH", &root);
+    cmd.args(["--format", "json", "template", "list"]);
+    let out = cmd.assert().success().get_output().stdout.clone();
+    let doc: serde_json::Value = serde_json::from_slice(&out)?;
+    let list: Vec<(String, String, bool)> = doc["templates"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| {
+            let s = |k: &str| t[k].as_str().unwrap_or_default().to_string();
+            (s("name"), s("source"), t["active"].as_bool().unwrap())
+        })
+        .collect();
+    assert!(list.contains(&("default".into(), "search_path".into(), true)), "{:?}", list);
+    assert!(list.contains(&("default".into(), "embedded".into(), false)), "{:?}", list);
+    assert!(list.contains(&("svc".into(), "search_path".into(), true)), "{:?}", list);
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).env("COSMOS_TEMPLATE_PATH", &root).args(["template", "list"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("2.1.0"))
+        .stdout(predicate::str::contains("Web service"))
+        .stdout(predicate::str::contains("(shadowed)"));
+    Ok(())
+}
+
+#[test]
+fn template_show_lists_variables_and_file_tree() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    fs::create_dir_all(td.path().join("tpl/docs"))?;
+    fs::write(td.path().join("tpl/docs/intro.md"), "# {{project-name}}\n")?;
+    fs::write(
+        td.path().join("tpl.toml"),
+        "[categories]\ndocs = [\"docs/**\"]\n\n[variables.project-name]\ndescription = \"Crate name\"\n",
+    )?;
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args(["template", "show", "--template-dir", "tpl"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("  docs: docs/**"))
+        .stdout(predicate::str::contains("  project-name (string) [required] - Crate name"))
+        .stdout(predicate::str::contains("  docs/\n    intro.md\n"));
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args([
+        "--format",
+        "json",
+        "template",
+        "show",
+        "--template-dir",
+        "tpl",
+    ]);
+    let out = cmd.assert().success().get_output().stdout.clone();
+    let doc: serde_json::Value = serde_json::from_slice(&out)?;
+    assert_eq!(doc["files"][0]["path"], "docs/intro.md");
+    assert_eq!(doc["files"][0]["categories"], serde_json::json!(["docs"]));
+    assert_eq!(doc["variables"]["project-name"]["type"], "string");
+    Ok(())
+}
+
+#[test]
+fn template_export_materializes_the_embedded_copy() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    let dir = td.path().join("mine/default");
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args(["template", "export", "default"]).arg(&dir);
+    cmd.assert().success().stdout(predicate::str::contains("Exported template 'default'"));
+    assert!(dir.join(".github/workflows/ci.yml").exists());
+    assert!(td.path().join("mine/default.toml").exists());
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(dir.join(".githooks/pre-commit"))?.permissions().mode();
+        assert!(mode & 0o111 != 0, "{:o}", mode);
+    }
+
+    // the exported copy checks out, and a second export does not clobber it
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args(["template", "check", "--template-dir"]).arg(&dir);
+    cmd.assert().success().stdout(predicate::str::contains("Template check passed"));
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args(["template", "export", "default"]).arg(&dir);
+    cmd.assert().code(12).stderr(predicate::str::contains("--force"));
+    Ok(())
+}
+
+#[test]
+fn template_check_reports_render_and_output_problems() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    fs::create_dir_all(td.path().join("tpl"))?;
+    fs::write(td.path().join("tpl/Cargo.toml"), "[package]\nname = {{project-name}}\n")?;
+    fs::write(td.path().join("tpl/README.md"), "# {{project-name}}\n")?;
+    fs::write(
+        td.path().join("tpl.toml"),
+        "[categories]\n\n[variables.project-name]\n\n[variables.kind]\nchoices = [\"lib\", \"bin\"]\n",
+    )?;
+
+    // required variables get samples; unquoted TOML value is caught
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args(["template", "check", "--template-dir", "tpl"]);
+    cmd.assert()
+        .code(2)
+        .stdout(predicate::str::contains("project-name = \"sample\""))
+        .stdout(predicate::str::contains("kind = \"lib\""))
+        .stdout(predicate::str::contains(" - Cargo.toml: invalid toml"));
+
+    fs::write(td.path().join("tpl/Cargo.toml"), "[package]\nname = \"{{project-nmae}}\"\n")?;
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args(["template", "check", "--template-dir", "tpl"]);
+    cmd.assert()
+        .code(2)
+        .stdout(predicate::str::contains("Cargo.toml:2:9: Variable \"project-nmae\""));
+    Ok(())
+}
@@ -0,0 +1,134 @@
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+fn write_template(root: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let tpl = root.join("tpl");
+    fs::create_dir_all(&tpl)?;
+    fs::write(tpl.join("README.md"), "# {{project-name}}\n\nintro\n\nusage\n")?;
+    fs::write(tpl.join("LICENSE"), "MIT\n")?;
+    fs::write(tpl.join("OLD.md"), "old\n")?;
+    fs::write(
+        root.join("tpl.toml"),
+        "version = \"1.0.0\"\n\n[categories]\n\n[variables.project-name]\ndefault = \"demo\"\n",
+    )?;
+    Ok(())
+}
+
+fn generate(root: &Path, out: &Path) {
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(root)
+        .args(["generate", "--template-dir", "tpl", "--project-name", "acme", "--apply", "-o"])
+        .arg(out);
+    cmd.assert().success();
+}
+
+#[test]
+fn generate_records_lock_and_update_merges_template_changes(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    write_template(td.path())?;
+    let proj = td.path().join("proj");
+    generate(td.path(), &proj);
+
+    let lock = fs::read_to_string(proj.join(".cosmos/template.lock"))?;
+    assert!(lock.contains("version = \"1.0.0\""), "{}", lock);
+    assert!(lock.contains("project-name = \"acme\""), "{}", lock);
+    assert!(lock.contains("hash = \"sha256:"), "{}", lock);
+    assert!(proj.join(".cosmos/base/README.md").exists());
+
+    // template changes one line, the project another; LICENSE conflicts
+    let tpl = td.path().join("tpl");
+    fs::write(tpl.join("README.md"), "# {{project-name}}\n\nintro\n\nusage: run it\n")?;
+    fs::write(tpl.join("LICENSE"), "Apache-2.0\n")?;
+    fs::write(tpl.join("NEW.md"), "new\n")?;
+    fs::remove_file(tpl.join("OLD.md"))?;
+    fs::write(proj.join("README.md"), "# acme\n\nour intro\n\nusage\n")?;
+    fs::write(proj.join("LICENSE"), "Proprietary\n")?;
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args(["update", "-o"]).arg(&proj);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(" - README.md: merged"))
+        .stdout(predicate::str::contains(" - LICENSE: conflict"))
+        .stdout(predicate::str::contains(" - NEW.md: added"))
+        .stdout(predicate::str::contains(" - OLD.md: removed"))
+        .stdout(predicate::str::contains("Dry run"));
+    assert!(proj.join("OLD.md").exists());
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args(["update", "--apply", "-o"]).arg(&proj);
+    cmd.assert().code(16).stderr(predicate::str::contains("1 file(s) with conflicts"));
+    assert_eq!(
+        fs::read_to_string(proj.join("README.md"))?,
+        "# acme\n\nour intro\n\nusage: run it\n"
+    );
+    assert_eq!(
+        fs::read_to_string(proj.join("LICENSE"))?,
+        "<<<<<<< current\nProprietary\n=======\nApache-2.0\n>>>>>>> template\n"
+    );
+    assert_eq!(fs::read_to_string(proj.join("NEW.md"))?, "new\n");
+    assert!(!proj.join("OLD.md").exists());
+
+    // the lock now points at the new render: nothing left to do
+    fs::write(proj.join("LICENSE"), "Apache-2.0\n")?;
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args(["--format", "json", "update", "-o"]).arg(&proj);
+    let out = cmd.assert().success().get_output().stdout.clone();
+    let doc: serde_json::Value = serde_json::from_slice(&out)?;
+    assert_eq!(doc["files"], serde_json::json!([]));
+    assert_eq!(doc["from_hash"], doc["to_hash"]);
+    Ok(())
+}
+
+#[test]
+fn update_reject_writes_rej_files_and_requires_lock() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    write_template(td.path())?;
+    let proj = td.path().join("proj");
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args(["update", "-o"]).arg(&proj);
+    cmd.assert().code(7).stderr(predicate::str::contains("template.lock not found"));
+
+    generate(td.path(), &proj);
+    fs::write(td.path().join("tpl/LICENSE"), "Apache-2.0\n")?;
+    fs::write(proj.join("LICENSE"), "Proprietary\n")?;
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args(["update", "--reject", "--apply", "-o"]).arg(&proj);
+    cmd.assert().code(16).stdout(predicate::str::contains("LICENSE: conflict (see LICENSE.rej)"));
+    assert_eq!(fs::read_to_string(proj.join("LICENSE"))?, "Proprietary\n");
+    let rej = fs::read_to_string(proj.join("LICENSE.rej"))?;
+    assert!(rej.contains("-MIT\n+Apache-2.0\n"), "{}", rej);
+    Ok(())
+}
+
+#[test]
+fn update_apply_is_backed_up_and_can_be_rolled_back() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    write_template(td.path())?;
+    let proj = td.path().join("proj");
+    generate(td.path(), &proj);
+
+    let tpl = td.path().join("tpl");
+    fs::write(tpl.join("README.md"), "# {{project-name}}\n\nintro\n\nusage: run it\n")?;
+    fs::remove_file(tpl.join("OLD.md"))?;
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args(["update", "--apply", "-o"]).arg(&proj);
+    cmd.assert().success().stdout(predicate::str::contains("Backed up 2 replaced/deleted file(s)"));
+    assert!(!proj.join("OLD.md").exists());
+
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args(["generate", "--rollback", "latest", "-o"]).arg(&proj);
+    cmd.assert().success();
+    assert_eq!(fs::read_to_string(proj.join("README.md"))?, "# acme\n\nintro\n\nusage\n");
+    assert_eq!(fs::read_to_string(proj.join("OLD.md"))?, "old\n");
+    // the lock went back too, so the update is pending again
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(td.path()).args(["update", "-o"]).arg(&proj);
+    cmd.assert().success().stdout(predicate::str::contains(" - README.md: updated"));
+    Ok(())
+}
@@ -0,0 +1,110 @@
+#![cfg(unix)]
+
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::tempdir;
+
+/// Repository with `[verify]` steps: a check of the sources, one of the
+/// docs and an allowed-to-fail lint.
+fn repo(root: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    fs::create_dir_all(root.join("src"))?;
+    fs::create_dir_all(root.join("docs"))?;
+    fs::write(root.join("src/lib.rs"), "pub fn hello() {}\n")?;
+    fs::write(root.join("docs/index.md"), "# Docs\n")?;
+    fs::write(
+        root.join("project.toml"),
+        r#"[project]
+name = "acme"
+
+[verify]
+steps = [
+  { name = "src", command = "grep -q hello src/lib.rs", paths = ["src/**"] },
+  { name = "docs", command = "grep -q '^# ' docs/index.md", paths = ["docs/**"] },
+  { name = "lint", command = "echo 'style nit' && exit 1", allow_failure = true, parallel = true },
+]
+"#,
+    )?;
+    for args in [&["init", "--quiet"][..], &["add", "--all"], &["commit", "--quiet", "-m", "init"]]
+    {
+        let ok = Command::new("git")
+            .arg("-C")
+            .arg(root)
+            .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .status()?
+            .success();
+        assert!(ok, "git {:?}", args);
+    }
+    Ok(())
+}
+
+fn verify(root: &Path) -> assert_cmd::Command {
+    let mut cmd = assert_cmd::cargo::cargo_bin_cmd!("cosmos");
+    cmd.current_dir(root).arg("verify");
+    cmd
+}
+
+#[test]
+fn verify_prints_a_table_and_writes_reports() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    repo(td.path())?;
+
+    verify(td.path())
+        .args(["--report-dir", "reports"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Running 3 verification steps from project.toml"))
+        .stdout(predicate::str::is_match(r"STEP\s+STATUS\s+TIME\s+COMMAND")?)
+        .stdout(predicate::str::is_match(r"lint\s+FAILED \(allowed\)")?)
+        .stdout(predicate::str::contains("   | style nit"))
+        .stdout(predicate::str::contains("2 passed, 0 failed, 1 allowed to fail, 0 skipped"));
+    let junit = fs::read_to_string(td.path().join("reports/verify.xml"))?;
+    assert!(junit.contains("tests=\"3\" failures=\"0\" skipped=\"1\""), "{}", junit);
+
+    fs::write(td.path().join("docs/index.md"), "no heading\n")?;
+    let out = verify(td.path()).args(["--format", "json", "--only", "docs,lint"]).output()?;
+    assert_eq!(out.status.code(), Some(4));
+    let doc: serde_json::Value = serde_json::from_slice(&out.stdout)?;
+    assert_eq!(doc["passed"], false);
+    let names: Vec<&str> =
+        doc["steps"].as_array().unwrap().iter().map(|s| s["name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["docs", "lint"]);
+
+    verify(td.path()).args(["--only", "fmt"]).assert().failure().stderr(predicate::str::contains(
+        "unknown verification step 'fmt' (available: src, docs, lint)",
+    ));
+    Ok(())
+}
+
+#[test]
+fn changed_since_skips_untouched_steps() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    repo(td.path())?;
+
+    verify(td.path())
+        .args(["--changed-since", "HEAD"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("0 files changed since HEAD"))
+        .stdout(predicate::str::is_match(r"src\s+SKIPPED")?)
+        .stdout(predicate::str::is_match(r"docs\s+SKIPPED")?);
+
+    // an untracked file counts as a change
+    fs::write(td.path().join("docs/guide.md"), "# Guide\n")?;
+    let out = verify(td.path()).args(["--changed-since", "HEAD", "--format", "ndjson"]).output()?;
+    assert!(out.status.success());
+    let steps: Vec<serde_json::Value> =
+        String::from_utf8(out.stdout)?.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    assert_eq!(steps[0]["skipped"], true);
+    assert_eq!(steps[1]["skipped"], false);
+    assert_eq!(steps[1]["ok"], true);
+
+    verify(td.path())
+        .args(["--changed-since", "no-such-rev"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("is not a commit"));
+    Ok(())
+}
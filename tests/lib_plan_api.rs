@@ -0,0 +1,35 @@
+use std::fs;
+
+use rust_repo_template::error::{CosmosError, ErrorKind};
+use rust_repo_template::plan::{NewTask, Plan, Status};
+use tempfile::tempdir;
+
+#[test]
+fn plan_api_creates_and_transitions_tasks() -> Result<(), Box<dyn std::error::Error>> {
+    let td = tempdir()?;
+    let dir = td.path();
+    fs::create_dir_all(dir.join("plan/tasks"))?;
+
+    let mut plan = Plan::open(dir)?;
+    let id = plan.create(NewTask {
+        kind: "feature".to_string(),
+        title: "Lib".to_string(),
+        content: Some("Acceptance criteria:\n- works\n".to_string()),
+        assignee: None,
+    })?;
+    assert_eq!(id, "0001");
+
+    // starting before review is rejected by the built-in check
+    let err = plan.transition(&id, Status::Working).unwrap_err();
+    let ce = err.downcast_ref::<CosmosError>().expect("typed transition error");
+    assert_eq!(ce.kind(), ErrorKind::CheckFailed);
+    assert!(ce.hint().unwrap().contains("Hint:"));
+
+    let err = plan.transition("0042", Status::Queued).unwrap_err();
+    assert_eq!(rust_repo_template::error::error_kind(&err), ErrorKind::TaskNotFound);
+
+    plan.transition(&id, Status::Queued)?;
+    plan.transition(&id, Status::Working)?;
+    assert_eq!(plan.task(&id).unwrap().status.as_deref(), Some("working"));
+    Ok(())
+}